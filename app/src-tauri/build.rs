@@ -1,3 +1,29 @@
+include!("src/commands.rs");
+
+/// `&["name", ...]` for a list of command idents.
+macro_rules! command_names {
+    ($($name:ident),* $(,)?) => {
+        &[$(stringify!($name)),*]
+    };
+}
+
+/// Every command of the inlined `djbot-worker` plugin, from src/commands.rs.
+const COMMANDS: &[&str] = djbot_commands!(command_names);
+
+/// What `djbot-worker:default` grants: only the commands the frontend
+/// calls. Anything else (restarts, repairs, schedules, data-dir changes)
+/// needs its `allow-<command>` permission added to a capability.
+const DEFAULT_PERMISSIONS: &[&str] = &["allow-get-worker-port", "allow-get-output-dir"];
+
 fn main() {
-    tauri_build::build()
+    println!("cargo:rerun-if-changed=src/commands.rs");
+    tauri_build::try_build(tauri_build::Attributes::new().plugin(
+        "djbot-worker",
+        tauri_build::InlinedPlugin::new()
+            .commands(COMMANDS)
+            .default_permission(tauri_build::DefaultPermissionRule::Allow(
+                DEFAULT_PERMISSIONS.iter().map(|p| p.to_string()).collect(),
+            )),
+    ))
+    .expect("failed to run tauri-build")
 }
@@ -0,0 +1,139 @@
+//! Size-triggered archiving of old files in the output directory.
+//!
+//! Once `output/` grows past `max_output_dir_mb`, files older than
+//! `ARCHIVE_AGE` are packed into `{data_dir}/archive/{date}.tar.gz` and
+//! removed from `output/`.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+const ARCHIVE_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const ARCHIVE_EXT: &str = ".tar.gz";
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ArchiveInfo {
+    pub name: String,
+    pub size_bytes: u64,
+    /// Seconds since the Unix epoch.
+    pub modified_at: u64,
+}
+
+struct OutputFile {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+fn output_files(dir: &Path) -> Vec<OutputFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            meta.is_file().then(|| OutputFile {
+                path: e.path(),
+                len: meta.len(),
+                modified: meta.modified().unwrap_or_else(|_| SystemTime::now()),
+            })
+        })
+        .collect()
+}
+
+/// `{date}.tar.gz`, or `{date}-N.tar.gz` if that name is already taken.
+fn next_archive_path(archive_dir: &Path) -> PathBuf {
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let mut path = archive_dir.join(format!("{}{}", date, ARCHIVE_EXT));
+    let mut n = 2;
+    while path.exists() {
+        path = archive_dir.join(format!("{}-{}{}", date, n, ARCHIVE_EXT));
+        n += 1;
+    }
+    path
+}
+
+fn write_archive(path: &Path, files: &[&OutputFile]) -> std::io::Result<()> {
+    let encoder = GzEncoder::new(File::create(path)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for file in files {
+        let name = file.path.file_name().unwrap_or_default();
+        builder.append_path_with_name(&file.path, name)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Archive files older than seven days if `output_dir` holds more than
+/// `threshold_mb`. Returns the number of files moved into the archive.
+pub(crate) fn archive_old_output(output_dir: &Path, archive_dir: &Path, threshold_mb: u64) -> Result<usize, String> {
+    let files = output_files(output_dir);
+    let total: u64 = files.iter().map(|f| f.len).sum();
+    if total <= threshold_mb.saturating_mul(1024 * 1024) {
+        return Ok(0);
+    }
+    let cutoff = SystemTime::now() - ARCHIVE_AGE;
+    let old: Vec<&OutputFile> = files.iter().filter(|f| f.modified < cutoff).collect();
+    if old.is_empty() {
+        return Ok(0);
+    }
+
+    std::fs::create_dir_all(archive_dir).map_err(|e| format!("{}: {}", archive_dir.display(), e))?;
+    let path = next_archive_path(archive_dir);
+    // Write under a temporary name so a partial archive is never listed.
+    let tmp = path.with_extension("part");
+    if let Err(e) = write_archive(&tmp, &old) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("{}: {}", path.display(), e));
+    }
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+
+    for file in &old {
+        if let Err(e) = std::fs::remove_file(&file.path) {
+            eprintln!("[djbot] archived but could not remove {}: {}", file.path.display(), e);
+        }
+    }
+    eprintln!("[djbot] archived {} old output files to {}", old.len(), path.display());
+    Ok(old.len())
+}
+
+pub(crate) fn list_archives(archive_dir: &Path) -> Vec<ArchiveInfo> {
+    let Ok(entries) = std::fs::read_dir(archive_dir) else { return Vec::new() };
+    let mut archives: Vec<ArchiveInfo> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            let meta = e.metadata().ok()?;
+            (meta.is_file() && name.ends_with(ARCHIVE_EXT)).then(|| ArchiveInfo {
+                name,
+                size_bytes: meta.len(),
+                modified_at: meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+            })
+        })
+        .collect();
+    archives.sort_by_key(|a| a.modified_at);
+    archives
+}
+
+/// Unpack archive `name` (as returned by `list_archives`) into `dest`.
+pub(crate) fn extract_archive(archive_dir: &Path, name: &str, dest: &Path) -> Result<(), String> {
+    if !name.ends_with(ARCHIVE_EXT) || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("not an archive name: {}", name));
+    }
+    let path = archive_dir.join(name);
+    let file = File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    std::fs::create_dir_all(dest).map_err(|e| format!("{}: {}", dest.display(), e))?;
+    // `unpack` refuses entries that would land outside `dest`.
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(dest)
+        .map_err(|e| format!("failed to extract {}: {}", name, e))
+}
@@ -0,0 +1,127 @@
+//! Embedded cover art, extracted with lofty and downscaled for the library
+//! grid without going through the worker.
+//!
+//! Thumbnails live in `{data_dir}/cache/artwork/<key>.jpg`, where the key
+//! hashes the embedded image and the requested size, so every track of an
+//! album shares one file. The directory counts towards the shared
+//! `CacheBudget`. Embedded images are size-checked and decoded with limits,
+//! so a hostile or corrupt picture fails that one request instead of
+//! exhausting memory.
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use image::{ImageReader, Limits};
+use lofty::config::ParseOptions;
+use lofty::picture::PictureType;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+
+use crate::cache::CacheBudget;
+
+/// Artwork extractions/resizes running at once.
+const MAX_PARALLEL: usize = 4;
+/// Largest `max_px` a caller may ask for.
+pub(crate) const MAX_THUMBNAIL_PX: u32 = 2048;
+/// Embedded pictures larger than this are not decoded.
+const MAX_EMBEDDED_BYTES: usize = 16 * 1024 * 1024;
+const MAX_SOURCE_DIMENSION: u32 = 8192;
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum AlbumArt {
+    Cached { path: String },
+    /// The file has no embedded picture; show a placeholder.
+    NoArtwork,
+}
+
+/// Managed state for `get_album_art`.
+pub(crate) struct ArtworkCache {
+    dir: PathBuf,
+    budget: Arc<CacheBudget>,
+    permits: Arc<Semaphore>,
+}
+
+/// The front cover if tagged as such, else the first picture of any tag.
+fn embedded_picture(track: &Path) -> Result<Option<Vec<u8>>, String> {
+    let file = Probe::open(track)
+        .and_then(|p| p.options(ParseOptions::new().read_properties(false)).read())
+        .map_err(|e| format!("{}: {}", track.display(), e))?;
+    let pictures: Vec<_> = file.tags().iter().flat_map(|t| t.pictures()).collect();
+    let picture = pictures
+        .iter()
+        .find(|p| p.pic_type() == PictureType::CoverFront)
+        .or(pictures.first());
+    Ok(picture.map(|p| p.data().to_vec()))
+}
+
+fn render(data: &[u8], max_px: u32, dest: &Path) -> Result<(), String> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| format!("unreadable artwork: {}", e))?;
+    let image = if image.width() > max_px || image.height() > max_px {
+        image.thumbnail(max_px, max_px)
+    } else {
+        image
+    };
+    let tmp = dest.with_extension("part");
+    image
+        .to_rgb8()
+        .save_with_format(&tmp, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("{}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, dest).map_err(|e| format!("{}: {}", dest.display(), e))
+}
+
+impl ArtworkCache {
+    pub(crate) fn new(dir: PathBuf, budget: Arc<CacheBudget>) -> Self {
+        ArtworkCache {
+            dir,
+            budget,
+            permits: Arc::new(Semaphore::new(MAX_PARALLEL)),
+        }
+    }
+
+    /// Cover art of `track` scaled to fit `max_px` square, from the cache
+    /// when this picture was already rendered at that size.
+    pub(crate) async fn get(&self, track: PathBuf, max_px: u32) -> Result<AlbumArt, String> {
+        if max_px == 0 || max_px > MAX_THUMBNAIL_PX {
+            return Err(format!("max_px must be between 1 and {}", MAX_THUMBNAIL_PX));
+        }
+        let _permit = self.permits.acquire().await.map_err(|e| e.to_string())?;
+        let dir = self.dir.clone();
+        let budget = Arc::clone(&self.budget);
+        tauri::async_runtime::spawn_blocking(move || {
+            let Some(data) = embedded_picture(&track)? else {
+                return Ok(AlbumArt::NoArtwork);
+            };
+            if data.len() > MAX_EMBEDDED_BYTES {
+                return Err(format!("embedded artwork in {} is too large ({} bytes)", track.display(), data.len()));
+            }
+            let mut hasher = Sha256::new();
+            hasher.update(&data);
+            hasher.update(max_px.to_le_bytes());
+            let key: String = hasher.finalize()[..16].iter().map(|b| format!("{:02x}", b)).collect();
+            let dest = dir.join(format!("{}.jpg", key));
+            if dest.is_file() {
+                CacheBudget::touch(&dest);
+            } else {
+                render(&data, max_px, &dest)?;
+                budget.enforce();
+            }
+            Ok(AlbumArt::Cached { path: dest.to_string_lossy().to_string() })
+        })
+        .await
+        .map_err(|e| format!("artwork extraction failed: {}", e))?
+    }
+}
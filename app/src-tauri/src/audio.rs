@@ -0,0 +1,208 @@
+//! Output audio device enumeration (via cpal) for the worker's preview/cue
+//! playback, and the engine settings the worker reports back.
+//!
+//! cpal has no device-change notification, so a background thread re-lists
+//! the output devices every `POLL_INTERVAL`, emitting `audio-devices-changed`
+//! when the list changes and `audio-device-fallback` when the preferred
+//! device has gone away.
+//!
+//! The default output device is checked more often (`DEFAULT_POLL_INTERVAL`)
+//! so that a switch during preview playback — typically headphones being
+//! unplugged — is acted on quickly according to `on_default_device_change`,
+//! and reported as `audio-device-switched`. Only a change from one known
+//! default to another counts; a poll that can't determine the default is
+//! ignored rather than treated as a switch.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::config::{AudioBackend, AudioEngineConfig, DeviceChangeBehavior, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE, SAMPLE_RATES};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct AudioDevice {
+    /// `<host>:<name>`; what `preferred_output_device` stores.
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// Payload of `audio-device-fallback`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AudioDeviceFallback {
+    pub missing: String,
+    /// The system default the worker will end up on, if there is one.
+    pub fallback: Option<AudioDevice>,
+}
+
+/// Payload of `audio-device-switched`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AudioDeviceSwitched {
+    pub previous: String,
+    pub current: String,
+    pub behavior: DeviceChangeBehavior,
+    /// Preview playback was running when the switch happened.
+    pub was_playing: bool,
+}
+
+/// State shared between the watcher thread and the commands that tune it.
+#[derive(Clone, Default)]
+pub(crate) struct AudioWatch {
+    /// Preferred output device, checked from the next poll on.
+    pub preferred: Arc<Mutex<Option<String>>>,
+    pub on_default_change: Arc<Mutex<DeviceChangeBehavior>>,
+    /// Whether preview playback is running, as last reported by the player.
+    pub playing: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl AudioWatch {
+    pub(crate) fn new(preferred: Option<String>, on_default_change: DeviceChangeBehavior) -> Self {
+        AudioWatch {
+            preferred: Arc::new(Mutex::new(preferred)),
+            on_default_change: Arc::new(Mutex::new(on_default_change)),
+            ..Default::default()
+        }
+    }
+
+    /// End the watcher thread at its next poll.
+    pub(crate) fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Name of the default output device, if it can be determined right now.
+fn default_output_name() -> Option<String> {
+    cpal::default_host().default_output_device().and_then(|d| d.name().ok())
+}
+
+/// Values the settings screen may offer on this platform.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AudioEngineOptions {
+    pub sample_rates: Vec<u32>,
+    pub buffer_sizes: Vec<u32>,
+    pub backends: Vec<AudioBackend>,
+}
+
+pub(crate) fn engine_options() -> AudioEngineOptions {
+    AudioEngineOptions {
+        sample_rates: SAMPLE_RATES.to_vec(),
+        buffer_sizes: (MIN_BUFFER_SIZE.trailing_zeros()..=MAX_BUFFER_SIZE.trailing_zeros())
+            .map(|shift| 1 << shift)
+            .collect(),
+        backends: AudioBackend::ALL.iter().copied().filter(|b| b.is_available()).collect(),
+    }
+}
+
+/// Engine settings actually in effect, from the worker's `AUDIO:` line.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct EffectiveAudio {
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<u32>,
+    pub backend: Option<String>,
+    /// The worker can apply new settings via `POST /audio/config` without
+    /// a restart.
+    pub live_reconfig: bool,
+}
+
+impl EffectiveAudio {
+    /// Requested settings the worker didn't honour, e.g.
+    /// `backend: requested wasapi_exclusive, got wasapi_shared`.
+    pub(crate) fn mismatches(&self, requested: &AudioEngineConfig) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut check = |name: &str, wanted: Option<String>, got: Option<String>| {
+            if let Some(wanted) = wanted {
+                if got.as_deref() != Some(wanted.as_str()) {
+                    out.push(format!("{}: requested {}, got {}", name, wanted, got.as_deref().unwrap_or("unknown")));
+                }
+            }
+        };
+        check("sample_rate", requested.sample_rate.map(|r| r.to_string()), self.sample_rate.map(|r| r.to_string()));
+        check("buffer_size", requested.buffer_size.map(|s| s.to_string()), self.buffer_size.map(|s| s.to_string()));
+        check("backend", requested.backend.map(|b| b.as_str().to_string()), self.backend.clone());
+        out
+    }
+}
+
+/// Output devices of the default host, default device first.
+pub(crate) fn list_output_devices() -> Result<Vec<AudioDevice>, String> {
+    let host = cpal::default_host();
+    let host_name = host.id().name();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+    let mut devices: Vec<AudioDevice> = host
+        .output_devices()
+        .map_err(|e| e.to_string())?
+        .filter_map(|d| d.name().ok())
+        .map(|name| AudioDevice {
+            id: format!("{}:{}", host_name, name),
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect();
+    devices.sort_by_key(|d| !d.is_default);
+    Ok(devices)
+}
+
+/// Watch for hot-plug and default-device changes until `watch.stop()`.
+/// `on_switch` is called (on the watcher thread) when the default output
+/// changes while preview playback is running and the behavior is `Pause`.
+pub(crate) fn spawn_watcher(app: AppHandle, watch: AudioWatch, on_switch: impl Fn(&AppHandle) + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut known = list_output_devices().unwrap_or_default();
+        let mut default_device = default_output_name();
+        let mut reported_missing: Option<String> = None;
+        let mut last_full_poll = Instant::now();
+        while !watch.stop.load(Ordering::Relaxed) {
+            std::thread::sleep(DEFAULT_POLL_INTERVAL);
+
+            if let Some(current) = default_output_name() {
+                if let Some(previous) = default_device.replace(current.clone()).filter(|p| *p != current) {
+                    let behavior = *watch.on_default_change.lock().unwrap();
+                    let was_playing = watch.playing.load(Ordering::Relaxed);
+                    eprintln!("[djbot] default audio output changed: {} -> {}", previous, current);
+                    if was_playing && behavior == DeviceChangeBehavior::Pause {
+                        on_switch(&app);
+                    }
+                    let _ = app.emit("audio-device-switched", AudioDeviceSwitched { previous, current, behavior, was_playing });
+                }
+            }
+
+            if last_full_poll.elapsed() < POLL_INTERVAL {
+                continue;
+            }
+            last_full_poll = Instant::now();
+            let current = match list_output_devices() {
+                Ok(devices) => devices,
+                Err(e) => {
+                    eprintln!("[djbot] failed to list audio devices: {}", e);
+                    continue;
+                }
+            };
+            if current != known {
+                let _ = app.emit("audio-devices-changed", &current);
+            }
+
+            let wanted = watch.preferred.lock().unwrap().clone();
+            match wanted {
+                Some(id) if !current.iter().any(|d| d.id == id) => {
+                    if reported_missing.as_deref() != Some(id.as_str()) {
+                        eprintln!("[djbot] preferred audio device {} disappeared; falling back to default", id);
+                        let fallback = current.iter().find(|d| d.is_default).cloned();
+                        let _ = app.emit("audio-device-fallback", AudioDeviceFallback { missing: id.clone(), fallback });
+                        reported_missing = Some(id);
+                    }
+                }
+                _ => reported_missing = None,
+            }
+            known = current;
+        }
+    });
+}
@@ -0,0 +1,158 @@
+//! Append-only record of destructive and security-relevant operations in
+//! `{data_dir}/logs/audit.log`, one JSON object per line, so "what did the
+//! app do to my files, and when" has an answer.
+//!
+//! Recording is best effort: a write that fails is logged to stderr and the
+//! operation being recorded goes ahead regardless. The log rotates to
+//! `audit.log.1` once it passes `MAX_BYTES`, keeping one older generation.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::unix_now;
+
+const MAX_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AuditKind {
+    /// A file removed or moved to the trash.
+    Delete,
+    SettingsReset,
+    /// The data directory copied or moved (`migrate_data_dir`).
+    DataDirMigration,
+    /// A path refused as outside the allowed locations.
+    PathViolation,
+    /// Installed files that don't match the signed manifest.
+    IntegrityViolation,
+    /// A file handed to the platform share sheet.
+    Share,
+    /// A downloaded worker binary installed or rolled back.
+    WorkerUpdate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AuditEntry {
+    /// Unix seconds.
+    pub at: u64,
+    pub kind: AuditKind,
+    /// Command (or subsystem) that did it.
+    pub initiator: String,
+    /// File or setting concerned.
+    pub subject: Option<String>,
+    pub detail: Option<String>,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(kind: AuditKind, initiator: &str) -> Self {
+        AuditEntry {
+            at: unix_now(),
+            kind,
+            initiator: initiator.to_string(),
+            subject: None,
+            detail: None,
+            ok: true,
+            error: None,
+        }
+    }
+
+    pub(crate) fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub(crate) fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub(crate) fn failed(mut self, error: impl Into<String>) -> Self {
+        self.ok = false;
+        self.error = Some(error.into());
+        self
+    }
+
+    /// Outcome taken from the operation's result.
+    pub(crate) fn outcome<T>(self, result: &Result<T, String>) -> Self {
+        match result {
+            Ok(_) => self,
+            Err(e) => self.failed(e.clone()),
+        }
+    }
+
+    /// Copy with `home` shown as `~` in paths, for diagnostics bundles.
+    pub(crate) fn redacted(&self, home: Option<&Path>) -> Self {
+        let Some(home) = home.map(|h| h.to_string_lossy().to_string()).filter(|h| !h.is_empty()) else {
+            return self.clone();
+        };
+        let redact = |s: &Option<String>| s.as_ref().map(|s| s.replace(&home, "~"));
+        AuditEntry {
+            subject: redact(&self.subject),
+            detail: redact(&self.detail),
+            error: redact(&self.error),
+            ..self.clone()
+        }
+    }
+}
+
+/// Managed state.
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    write: Mutex<()>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(data_dir: &Path) -> Self {
+        AuditLog { path: data_dir.join("logs").join("audit.log"), write: Mutex::new(()) }
+    }
+
+    fn rotated(&self) -> PathBuf {
+        self.path.with_extension("log.1")
+    }
+
+    pub(crate) fn record(&self, entry: AuditEntry) {
+        if let Err(e) = self.append(&entry) {
+            eprintln!("[djbot] failed to write audit entry {:?}: {}", entry.kind, e);
+        }
+    }
+
+    fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let _guard = self.write.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        if std::fs::metadata(&self.path).map(|m| m.len() >= MAX_BYTES).unwrap_or(false) {
+            std::fs::rename(&self.path, self.rotated()).map_err(|e| e.to_string())?;
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(line.as_bytes()))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Entries at or after `since` (Unix seconds) and of one of `kinds`
+    /// (all kinds if `None`), oldest first. Unreadable lines are skipped.
+    pub(crate) fn read(&self, since: Option<u64>, kinds: Option<&[AuditKind]>) -> Vec<AuditEntry> {
+        let _guard = self.write.lock().unwrap();
+        [self.rotated(), self.path.clone()]
+            .iter()
+            .filter_map(|p| std::fs::read_to_string(p).ok())
+            .flat_map(|text| {
+                text.lines()
+                    .filter_map(|l| serde_json::from_str::<AuditEntry>(l).ok())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|e| since.is_none_or(|s| e.at >= s))
+            .filter(|e| kinds.is_none_or(|k| k.contains(&e.kind)))
+            .collect()
+    }
+}
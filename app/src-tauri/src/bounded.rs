@@ -0,0 +1,175 @@
+//! Ring buffers with hard entry and byte caps for worker-derived data.
+//!
+//! Anything the worker can make us accumulate goes through `BoundedBuf`, so a
+//! misbehaving worker costs at most the configured cap in memory. Oldest
+//! entries are evicted first and the evictions are counted.
+
+use std::collections::VecDeque;
+
+use serde::{Serialize, Serializer};
+
+/// Approximate heap + inline footprint of a buffered value.
+pub(crate) trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl ByteSize for String {
+    fn byte_size(&self) -> usize {
+        std::mem::size_of::<String>() + self.len()
+    }
+}
+
+/// Current size and eviction count of one buffer, for `get_buffer_stats`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct BufStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub evicted: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct BoundedBuf<T> {
+    items: VecDeque<T>,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+    evicted: u64,
+}
+
+impl<T: ByteSize> BoundedBuf<T> {
+    pub(crate) fn new(max_entries: usize, max_bytes: usize) -> Self {
+        BoundedBuf {
+            items: VecDeque::new(),
+            bytes: 0,
+            max_entries,
+            max_bytes,
+            evicted: 0,
+        }
+    }
+
+    /// Append `item`, evicting from the front until both caps hold. An item
+    /// larger than `max_bytes` on its own is dropped (and counted).
+    pub(crate) fn push(&mut self, item: T) {
+        let size = item.byte_size();
+        if size > self.max_bytes || self.max_entries == 0 {
+            self.evicted += 1;
+            return;
+        }
+        while self.items.len() >= self.max_entries || self.bytes + size > self.max_bytes {
+            let Some(old) = self.items.pop_front() else { break };
+            self.bytes -= old.byte_size();
+            self.evicted += 1;
+        }
+        self.bytes += size;
+        self.items.push_back(item);
+    }
+
+    /// Change the entry cap, evicting the oldest entries beyond it.
+    pub(crate) fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+        while self.items.len() > max_entries {
+            let Some(old) = self.items.pop_front() else { break };
+            self.bytes -= old.byte_size();
+            self.evicted += 1;
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub(crate) fn stats(&self) -> BufStats {
+        BufStats {
+            entries: self.items.len(),
+            bytes: self.bytes,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            evicted: self.evicted,
+        }
+    }
+}
+
+impl<T: ByteSize> Extend<T> for BoundedBuf<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+/// Serializes as a plain sequence, oldest first.
+impl<T: Serialize> Serialize for BoundedBuf<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held_bytes(buf: &BoundedBuf<String>) -> usize {
+        buf.iter().map(ByteSize::byte_size).sum()
+    }
+
+    #[test]
+    fn a_flood_never_exceeds_either_cap() {
+        let mut buf = BoundedBuf::new(1000, 64 * 1024);
+        for i in 0..100_000usize {
+            // Lengths from 0 to 1 KiB, so both caps take turns binding.
+            buf.push("x".repeat(i * 37 % 1024));
+            let stats = buf.stats();
+            assert!(stats.entries <= 1000);
+            assert!(stats.bytes <= 64 * 1024);
+        }
+        let stats = buf.stats();
+        assert_eq!(stats.bytes, held_bytes(&buf));
+        assert_eq!(stats.evicted, 100_000 - stats.entries as u64);
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted_first() {
+        let mut buf = BoundedBuf::new(3, usize::MAX);
+        buf.extend((0..5).map(|i| i.to_string()));
+        assert_eq!(serde_json::to_string(&buf).unwrap(), r#"["2","3","4"]"#);
+        assert_eq!(buf.stats().evicted, 2);
+    }
+
+    #[test]
+    fn byte_cap_evicts_as_many_as_needed() {
+        let entry = "a".repeat(100).byte_size();
+        let mut buf = BoundedBuf::new(100, entry * 3);
+        buf.extend((0..3).map(|_| "a".repeat(100)));
+        buf.push("b".repeat(100 + 2 * (entry - std::mem::size_of::<String>())));
+        assert_eq!(buf.stats().entries, 1);
+        assert_eq!(buf.stats().evicted, 3);
+        assert_eq!(buf.stats().bytes, held_bytes(&buf));
+    }
+
+    #[test]
+    fn oversized_item_is_dropped_and_counted() {
+        let mut buf = BoundedBuf::new(10, 100);
+        buf.push("kept".to_string());
+        buf.push("x".repeat(100));
+        assert_eq!(buf.iter().collect::<Vec<_>>(), ["kept"]);
+        assert_eq!(buf.stats().evicted, 1);
+
+        let mut none = BoundedBuf::new(0, 100);
+        none.push(String::new());
+        assert_eq!(none.stats().entries, 0);
+        assert_eq!(none.stats().evicted, 1);
+    }
+
+    #[test]
+    fn lowering_the_entry_cap_releases_bytes() {
+        let mut buf = BoundedBuf::new(10, usize::MAX);
+        buf.extend((0..10).map(|i| "y".repeat(i)));
+        buf.set_max_entries(4);
+        let stats = buf.stats();
+        assert_eq!((stats.entries, stats.evicted), (4, 6));
+        assert_eq!(stats.bytes, held_bytes(&buf));
+        assert_eq!(buf.iter().next().map(String::len), Some(6));
+    }
+}
@@ -0,0 +1,63 @@
+//! Size limit shared by the image caches under `{data_dir}/cache`
+//! (waveform thumbnails, album art).
+//!
+//! Every cache registers its directory with one `CacheBudget`; after a write
+//! the combined size is brought back under `max_image_cache_mb` by deleting
+//! the least recently used files across all of them. Hits refresh a file's
+//! mtime so it counts as recently used.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Extensions the caches write; anything else in their dirs is left alone.
+const CACHED_EXTENSIONS: [&str; 2] = ["png", "jpg"];
+
+pub(crate) struct CacheBudget {
+    dirs: Vec<PathBuf>,
+    max_bytes: u64,
+}
+
+impl CacheBudget {
+    pub(crate) fn new(dirs: Vec<PathBuf>, max_bytes: u64) -> Self {
+        CacheBudget { dirs, max_bytes }
+    }
+
+    /// Mark `path` as just used.
+    pub(crate) fn touch(path: &Path) {
+        if let Ok(file) = std::fs::File::options().write(true).open(path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+    }
+
+    /// Delete the least recently used cached files until all dirs together
+    /// fit `max_bytes`. Blocking.
+    pub(crate) fn enforce(&self) {
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = self
+            .dirs
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                let path = e.path();
+                let cached = path
+                    .extension()
+                    .is_some_and(|x| CACHED_EXTENSIONS.iter().any(|c| x == *c));
+                (meta.is_file() && cached).then(|| (path, meta.len(), meta.modified().unwrap_or(UNIX_EPOCH)))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|f| f.1).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+        files.sort_by_key(|f| f.2);
+        for (path, len, _) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
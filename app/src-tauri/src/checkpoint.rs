@@ -0,0 +1,125 @@
+//! On-disk checkpoint of the frontend's batch queue, so a batch cut short
+//! by closing the app (or a crash) can be resumed after relaunch instead of
+//! started over.
+//!
+//! The frontend saves the queue whenever it changes; analyses that come back
+//! through `forward_worker_request` also mark their files done here, so the
+//! checkpoint stays current even if the frontend dies mid-batch.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+const CHECKPOINT_FILE: &str = "queue_checkpoint.json";
+/// Bumped when the file layout changes; other versions are ignored.
+const CHECKPOINT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct QueueItem {
+    pub path: String,
+    #[serde(default)]
+    pub done: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    version: u32,
+    /// Unix time in seconds.
+    saved_at: u64,
+    items: Vec<QueueItem>,
+}
+
+/// Managed state guarding `{data_dir}/queue_checkpoint.json`.
+pub(crate) struct QueueCheckpoint {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl QueueCheckpoint {
+    pub(crate) fn new(data_dir: &Path) -> Self {
+        QueueCheckpoint { path: data_dir.join(CHECKPOINT_FILE), lock: Mutex::new(()) }
+    }
+
+    /// Replace the checkpoint with `items`. A queue with nothing left to do
+    /// removes the file.
+    pub(crate) fn save(&self, items: Vec<QueueItem>) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        self.write(items)
+    }
+
+    fn write(&self, items: Vec<QueueItem>) -> Result<(), String> {
+        if items.iter().all(|i| i.done) {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("{}: {}", self.path.display(), e)),
+                _ => Ok(()),
+            };
+        }
+        let saved_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let checkpoint = Checkpoint { version: CHECKPOINT_VERSION, saved_at, items };
+        let json = serde_json::to_vec_pretty(&checkpoint).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| format!("{}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    /// The saved queue, or `None` if there is none or it fails validation
+    /// (unreadable, another version, relative or duplicate paths).
+    pub(crate) fn load(&self) -> Option<Vec<QueueItem>> {
+        let _guard = self.lock.lock().unwrap();
+        self.read()
+    }
+
+    fn read(&self) -> Option<Vec<QueueItem>> {
+        let bytes = std::fs::read(&self.path).ok()?;
+        let checkpoint: Checkpoint = match serde_json::from_slice(&bytes) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("[djbot] ignoring unreadable queue checkpoint: {}", e);
+                return None;
+            }
+        };
+        if checkpoint.version != CHECKPOINT_VERSION {
+            eprintln!("[djbot] ignoring queue checkpoint version {}", checkpoint.version);
+            return None;
+        }
+        let mut seen = HashSet::new();
+        for item in &checkpoint.items {
+            if !Path::new(&item.path).is_absolute() || !seen.insert(item.path.as_str()) {
+                eprintln!("[djbot] ignoring queue checkpoint with bad entry {}", item.path);
+                return None;
+            }
+        }
+        Some(checkpoint.items)
+    }
+
+    /// Mark the files of an `/analyze` response that came back with a
+    /// duration as done. No-op without a checkpoint.
+    pub(crate) fn record_analysis(&self, response: &serde_json::Value) {
+        let Some(results) = response.get("results").and_then(|r| r.as_array()) else { return };
+        let analysed: HashSet<&str> = results
+            .iter()
+            .filter(|r| r.get("duration").and_then(|d| d.as_f64()).is_some_and(|d| d > 0.0))
+            .filter_map(|r| r.get("filepath").and_then(|p| p.as_str()))
+            .collect();
+        if analysed.is_empty() {
+            return;
+        }
+        let _guard = self.lock.lock().unwrap();
+        let Some(mut items) = self.read() else { return };
+        let mut changed = false;
+        for item in items.iter_mut().filter(|i| !i.done && analysed.contains(i.path.as_str())) {
+            item.done = true;
+            changed = true;
+        }
+        if changed {
+            if let Err(e) = self.write(items) {
+                eprintln!("[djbot] failed to update queue checkpoint: {}", e);
+            }
+        }
+    }
+}
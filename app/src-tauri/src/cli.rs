@@ -0,0 +1,160 @@
+//! Job subcommands on the app binary, for scripts and cron:
+//!
+//! ```text
+//! djbot export --input a.wav --input b.wav [--format mp3] [--output name]
+//! ```
+//!
+//! Worker settings given before the subcommand (or alone, to start the app
+//! normally) override config.toml and the `DJBOT_*` environment:
+//!
+//! ```text
+//! djbot [--worker-port 9000] [--restart-policy never] [--max-memory-mb 2048] [--worker-arg <arg>...] [export ...]
+//! ```
+//!
+//! With a subcommand the app starts headless (main window hidden), runs the
+//! job through the same worker requests the UI makes, prints progress to
+//! stdout and exits, non-zero on failure. The result lands in the output
+//! dir and shows up in recent outputs like any other mix.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::{self, ImportConfig, RestartPolicy, WorkerConfigOverride};
+use crate::formats::OUTPUT_FORMATS;
+use crate::intake;
+
+pub(crate) const USAGE: &str = "usage: djbot [--worker-port <port>] [--restart-policy always|on_failure|never] [--max-memory-mb <mb>] [--worker-arg <arg>]...\n       \
+                                 djbot [worker options] export --input <file> --input <file>... [--format mp3|wav|aiff|flac] [--output <name>]";
+
+/// Fewest tracks the worker will render into a mix.
+const MIN_TRACKS: usize = 2;
+
+#[derive(Debug, Clone)]
+pub(crate) enum CliCommand {
+    Export(ExportArgs),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ExportArgs {
+    pub inputs: Vec<PathBuf>,
+    /// Output extension, one of `OUTPUT_FORMATS`.
+    pub format: String,
+    /// File name without extension; a timestamped name if `None`.
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cli {
+    /// `None` to start the app normally.
+    pub command: Option<CliCommand>,
+    pub worker: WorkerConfigOverride,
+}
+
+/// Worker options and the subcommand in `args` (without the program
+/// name). Anything that isn't a known option or subcommand, such as a
+/// `djbot://` link the OS passed on the command line, is left to the app.
+pub(crate) fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+    let mut args = args.into_iter().peekable();
+    let mut cli = Cli::default();
+    while let Some(flag) = args.next_if(|a| a.starts_with("--")) {
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        let invalid = || format!("invalid {} {:?}", flag, value);
+        match flag.as_str() {
+            "--worker-port" => cli.worker.port = Some(value.parse().map_err(|_| invalid())?),
+            "--restart-policy" => cli.worker.restart_policy = Some(RestartPolicy::parse(&value).ok_or_else(invalid)?),
+            "--max-memory-mb" => {
+                cli.worker.startup_memory_warn_mb = Some(config::parse_memory_mb(&value).ok_or_else(invalid)?)
+            }
+            // Repeatable; together they replace `extra_args`.
+            "--worker-arg" => cli.worker.extra_args.get_or_insert_with(Vec::new).push(value),
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+    cli.command = match args.next().as_deref() {
+        Some("export") => Some(CliCommand::Export(parse_export(args)?)),
+        _ => None,
+    };
+    Ok(cli)
+}
+
+fn parse_export(mut args: impl Iterator<Item = String>) -> Result<ExportArgs, String> {
+    let mut export = ExportArgs { inputs: Vec::new(), format: "mp3".to_string(), output: None };
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--input" | "-i" => export.inputs.push(PathBuf::from(value()?)),
+            "--format" | "-f" => export.format = value()?.to_ascii_lowercase(),
+            "--output" | "-o" => export.output = Some(value()?),
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+    Ok(export)
+}
+
+impl ExportArgs {
+    /// The checks any import goes through (`intake::check_file`), plus the
+    /// format and output name. Returns the inputs as absolute paths.
+    pub(crate) fn validate(&self, import: &ImportConfig) -> Result<Vec<String>, String> {
+        if self.inputs.len() < MIN_TRACKS {
+            return Err(format!("a mix needs at least {} --input files", MIN_TRACKS));
+        }
+        if !OUTPUT_FORMATS.iter().any(|f| f.extension == self.format) {
+            let known: Vec<_> = OUTPUT_FORMATS.iter().map(|f| f.extension).collect();
+            return Err(format!("unknown format {}; expected one of {}", self.format, known.join(", ")));
+        }
+        if let Some(name) = &self.output {
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                return Err(format!("--output must be a plain file name, not {:?}", name));
+            }
+        }
+        self.inputs
+            .iter()
+            .map(|input| {
+                let path = std::path::absolute(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+                intake::check_file(&input.to_string_lossy(), &path, import)
+                    .map(|admitted| admitted.path.to_string_lossy().to_string())
+                    .map_err(|r| format!("{}: {}", r.path, r.detail))
+            })
+            .collect()
+    }
+
+    /// File name for the mix, with extension.
+    pub(crate) fn file_name(&self) -> String {
+        let stem = self
+            .output
+            .clone()
+            .unwrap_or_else(|| format!("club_mix_{}", chrono::Local::now().format("%Y-%m-%dT%H-%M-%S")));
+        format!("{}.{}", stem, self.format)
+    }
+}
+
+/// The `render/mix` playlist for a plan, trimming each track to where the
+/// transitions into and out of it fall (as the UI does).
+pub(crate) fn playlist(sorted_tracks: &[Value], selections: &[Value]) -> Vec<Value> {
+    let num = |v: Option<&Value>, key: &str| v.and_then(|v| v.get(key)).and_then(Value::as_f64);
+    sorted_tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let filepath = track.get("filepath").and_then(Value::as_str).unwrap_or_default();
+            let duration = num(Some(track), "duration").unwrap_or(0.0);
+            let incoming = i.checked_sub(1).and_then(|j| selections.get(j));
+            let outgoing = selections.get(i);
+            let play_end = match (num(outgoing, "a_out_time"), num(outgoing, "duration")) {
+                (Some(out), Some(len)) => (out + len).min(duration),
+                _ => duration,
+            };
+            json!({
+                "filepath": filepath,
+                "filename": filepath.rsplit(['/', '\\']).next().unwrap_or(filepath),
+                "duration": duration,
+                "bpm": num(Some(track), "bpm").unwrap_or(0.0),
+                "loudness_db": num(Some(track), "loudness_db").unwrap_or(0.0),
+                "play_start": num(incoming, "b_in_time").unwrap_or(0.0),
+                "play_end": play_end,
+            })
+        })
+        .collect()
+}
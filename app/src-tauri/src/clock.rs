@@ -0,0 +1,38 @@
+//! Detection of a system clock that is behind the output files' timestamps.
+//!
+//! Sorting outputs and job history by time assumes the clock is sane. A
+//! file whose mtime lies well in the future means the clock was changed
+//! (or is wrong now), and time-ordered views will look scrambled.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+/// Filesystem timestamps and small clock corrections stay within this.
+const TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ClockSkew {
+    /// The output file with the newest modification time.
+    pub newest_file: String,
+    /// How far that file's mtime is ahead of the current clock.
+    pub ahead_secs: u64,
+}
+
+/// Compare the newest file in `output_dir` against `SystemTime::now()`.
+pub(crate) fn check(output_dir: &Path) -> Option<ClockSkew> {
+    let newest = std::fs::read_dir(output_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((e.path(), meta.modified().ok().filter(|_| meta.is_file())?))
+        })
+        .max_by_key(|(_, modified)| *modified)?;
+    let ahead = newest.1.duration_since(SystemTime::now()).ok()?;
+    (ahead > TOLERANCE).then(|| ClockSkew {
+        newest_file: newest.0.to_string_lossy().to_string(),
+        ahead_secs: ahead.as_secs(),
+    })
+}
@@ -0,0 +1,124 @@
+// The djbot-worker plugin's commands, listed once. Not a module: build.rs
+// includes this file to generate the commands' permissions, and
+// `plugin::init` to build the invoke handler, so the two can't drift
+// apart. A `#[tauri::command]` left out here is never registered and fails
+// the build as dead code.
+
+/// Expands to `$handler![<every command>]`, e.g.
+/// `djbot_commands!(tauri::generate_handler)`.
+macro_rules! djbot_commands {
+    ($($handler:tt)+) => {
+        $($handler)+![
+            get_worker_port,
+            get_worker_status,
+            get_ffmpeg_path,
+            get_ffmpeg_version,
+            list_hwaccels,
+            set_preferred_hwaccel,
+            get_audio_backend,
+            get_system_info,
+            test_data_dir_performance,
+            wait_for_worker,
+            get_output_dir,
+            forward_worker_request,
+            set_job_timeout,
+            set_webhook,
+            get_webhook_deliveries,
+            set_ca_bundle,
+            set_download_proxy,
+            get_download_proxy_status,
+            list_output_formats,
+            set_output_bit_depth,
+            get_crash_policy,
+            set_crash_policy,
+            set_health_check_interval,
+            set_update_channel,
+            request_reset_settings,
+            reset_settings,
+            request_migrate_data_dir,
+            migrate_data_dir,
+            check_for_update,
+            set_worker_update_channel,
+            check_worker_update,
+            download_worker_update,
+            rollback_worker,
+            trim_log_buffer,
+            get_job_history,
+            export_job_history,
+            get_timed_out_jobs,
+            record_job,
+            estimate_job_duration,
+            upload_file_to_worker,
+            import_files,
+            download_from_worker,
+            pause_download,
+            resume_download,
+            cancel_download,
+            list_downloads,
+            read_audio_tags,
+            search_tracks,
+            save_queue_checkpoint,
+            resume_from_checkpoint,
+            rebuild_index,
+            get_album_art,
+            optimize_for_streaming,
+            apply_fade,
+            request_delete_output_file,
+            delete_output_file,
+            share_output,
+            get_audit_log,
+            get_waveform,
+            get_fs_allowed_paths,
+            get_output_dir_activity,
+            list_archives,
+            extract_archive,
+            confirm_config_import,
+            check_worker_signature,
+            validate_worker_binary_signature,
+            get_install_integrity,
+            attempt_repair,
+            get_worker_fd_count,
+            get_rate_limits,
+            get_worker_memory,
+            get_window_info,
+            get_binary_resolution_report,
+            collect_diagnostics,
+            check_clock_skew,
+            get_environment_report,
+            get_environment_report_text,
+            retry_worker_start,
+            acknowledge_worker_failure,
+            exit_app,
+            check_worker_binary,
+            config_changed_since_spawn,
+            restart_worker,
+            get_heartbeat_status,
+            set_auto_start_worker,
+            get_buffer_stats,
+            check_worker_updated,
+            set_media_keys_enabled,
+            set_now_playing,
+            clear_now_playing,
+            set_discord_presence_enabled,
+            set_presence_session,
+            get_presence_status,
+            list_audio_devices,
+            set_preferred_output_device,
+            set_device_change_behavior,
+            get_audio_settings,
+            set_audio_settings,
+            get_worker_log_file_path,
+            open_path,
+            stream_worker_log,
+            open_worker_log,
+            reveal_worker_log,
+            get_worker_env_snapshot,
+            get_worker_request_metrics,
+            add_schedule,
+            list_schedules,
+            remove_schedule,
+            run_schedule_now,
+            get_network_status,
+        ]
+    };
+}
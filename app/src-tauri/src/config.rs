@@ -0,0 +1,957 @@
+//! Persistent configuration: `DjbotConfig` and the worker tunables inside it.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::formats;
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct WorkerConfig {
+    /// Time allowed to establish a TCP connection to the worker.
+    pub connect_timeout_ms: u64,
+    /// Time allowed between reads of a response body. Analysis of a large
+    /// library can keep a request open for minutes, so this is generous.
+    pub read_timeout_ms: u64,
+    /// Port to ask the worker to listen on; `None` lets it pick a free one.
+    pub port: Option<u16>,
+    /// Address the worker listens on; `None` listens on all interfaces.
+    /// We always connect over 127.0.0.1, so only loopback or unspecified
+    /// addresses are accepted (see `bind_address_reachable`).
+    pub bind_address: Option<IpAddr>,
+    /// Seconds a started worker has to report its port before it is
+    /// killed and handled like any other failed start.
+    pub startup_timeout_secs: u64,
+    /// Automatic restarts in a row of a worker that never got ready, after
+    /// which `worker-failed` is reported; `None` keeps trying.
+    pub max_start_retries: Option<u32>,
+    /// Extra arguments appended after the flags we manage ourselves.
+    pub extra_args: Vec<String>,
+    /// Upper bound on file data held in memory across all concurrent
+    /// uploads/downloads to the worker.
+    pub max_transfer_buffer_bytes: u64,
+    /// Output device for preview/cue playback (an `AudioDevice::id`);
+    /// `None` uses the system default.
+    pub preferred_output_device: Option<String>,
+    /// ffmpeg `-hwaccel` method (e.g. "videotoolbox", "cuda") tried first
+    /// when decoding, here and in the worker; one of `list_hwaccels`.
+    /// `None` decodes in software.
+    pub preferred_hwaccel: Option<String>,
+    /// ffmpeg binary to use instead of searching for one; `None` prefers
+    /// a bundled copy, then PATH, then the usual install locations.
+    pub ffmpeg_path_override: Option<String>,
+    /// Wall-clock limit for a single job (analysis, render, download);
+    /// `None` means no limit.
+    pub job_timeout_secs: Option<u64>,
+    /// Sample rate, buffer size and backend for preview playback.
+    pub audio: AudioEngineConfig,
+    /// Have the worker write its own log to `{data_dir}/logs/worker.log`.
+    /// On by default in release builds, where its stderr is then no longer
+    /// echoed to ours.
+    pub worker_log_file: bool,
+    /// Sample depth for lossless mix exports (16, 24 or 32); `None` uses
+    /// the encoder default. Ignored for lossy formats.
+    pub output_bit_depth: Option<u8>,
+    /// Regexes for noisy worker stderr lines that aren't forwarded to the
+    /// log viewer (or `worker.log`). They still reach the diagnostics tail.
+    pub stderr_filter_patterns: Vec<String>,
+    /// Whether the worker is started again after it exits on its own.
+    pub restart_policy: RestartPolicy,
+    /// Seconds between `GET /health` checks of a running worker.
+    pub health_check_interval_secs: u64,
+    /// Failed health checks in a row after which the worker is restarted.
+    pub max_missed_heartbeats: u32,
+    /// Worker stderr lines kept in memory for diagnostics.
+    pub log_buffer_capacity: usize,
+    /// PEM root certificates the worker trusts for downloads, for TLS
+    /// inspecting proxies. `None` uses `SSL_CERT_FILE` if set, else the
+    /// first system bundle found.
+    pub ca_bundle: Option<PathBuf>,
+    /// Resident memory of a freshly started worker above which the user is
+    /// warned; `None` never warns.
+    pub startup_memory_warn_mb: Option<u64>,
+    /// Proxy URL for the worker's downloads, possibly with credentials.
+    /// Stored sealed (`secrets::seal`, field `DOWNLOAD_PROXY_FIELD`), never
+    /// in plain text.
+    pub download_proxy: Option<String>,
+    /// Let the worker download from private network addresses (RFC 1918,
+    /// unique local IPv6), for self-hosted media servers. Loopback and
+    /// link-local addresses are refused regardless (see `url_guard`).
+    pub allow_private_urls: bool,
+    /// Exit with status 1 when the worker can't be restarted and the UI
+    /// hasn't answered `worker-failed` within `FAILURE_EXIT_GRACE`, for
+    /// unattended machines where a supervisor should restart the app.
+    pub exit_on_unrecoverable_failure: bool,
+
+    // Resolved at startup rather than configured.
+    #[serde(skip)]
+    pub sidecar_path: PathBuf,
+    #[serde(skip)]
+    pub ffmpeg_path: Option<String>,
+    #[serde(skip)]
+    pub data_dir: PathBuf,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            connect_timeout_ms: 2_000,
+            read_timeout_ms: 10 * 60 * 1_000,
+            port: None,
+            bind_address: None,
+            startup_timeout_secs: 60,
+            max_start_retries: Some(5),
+            extra_args: Vec::new(),
+            max_transfer_buffer_bytes: 16 * 1024 * 1024,
+            preferred_output_device: None,
+            preferred_hwaccel: None,
+            ffmpeg_path_override: None,
+            job_timeout_secs: None,
+            audio: AudioEngineConfig::default(),
+            worker_log_file: !cfg!(debug_assertions),
+            output_bit_depth: None,
+            stderr_filter_patterns: Vec::new(),
+            restart_policy: RestartPolicy::default(),
+            health_check_interval_secs: 30,
+            max_missed_heartbeats: 3,
+            log_buffer_capacity: 500,
+            ca_bundle: None,
+            startup_memory_warn_mb: Some(1024),
+            download_proxy: None,
+            allow_private_urls: false,
+            exit_on_unrecoverable_failure: false,
+            sidecar_path: PathBuf::new(),
+            ffmpeg_path: None,
+            data_dir: PathBuf::new(),
+        }
+    }
+}
+
+/// Environment variables `WorkerConfigOverride::from_env` reads, for
+/// containers where editing `config.toml` is impractical.
+const ENV_PORT_HINT: &str = "DJBOT_PORT_HINT";
+/// Sets `max_start_retries`; 0 gives up after the first failed start.
+const ENV_MAX_RETRIES: &str = "DJBOT_MAX_RETRIES";
+/// Seconds, sets `startup_timeout_secs`.
+const ENV_STARTUP_TIMEOUT: &str = "DJBOT_STARTUP_TIMEOUT";
+/// An IP address for `bind_address`.
+const ENV_BIND_ADDRESS: &str = "DJBOT_BIND_ADDRESS";
+/// "always", "on_failure" or "never".
+const ENV_RESTART_POLICY: &str = "DJBOT_RESTART_POLICY";
+/// Sets `startup_memory_warn_mb`; 0 turns the warning off.
+const ENV_MAX_MEMORY_MB: &str = "DJBOT_MAX_MEMORY_MB";
+/// Whitespace-separated arguments replacing `extra_args`; empty clears them.
+const ENV_WORKER_ARGS: &str = "DJBOT_WORKER_ARGS";
+
+/// `name` parsed, `None` if unset. A value that doesn't parse is logged
+/// and treated as unset.
+fn env_var<T>(name: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    let parsed = parse(value.trim());
+    if parsed.is_none() {
+        eprintln!("[djbot] ignoring {}={:?}: not a valid value", name, value);
+    }
+    parsed
+}
+
+/// Worker settings from a source layered over `config.toml` (environment,
+/// command line). `None` leaves the value underneath alone, so a layer can
+/// set a field back to its default.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WorkerConfigOverride {
+    pub port: Option<u16>,
+    pub max_start_retries: Option<u32>,
+    pub startup_timeout_secs: Option<u64>,
+    pub bind_address: Option<IpAddr>,
+    pub restart_policy: Option<RestartPolicy>,
+    /// `Some(None)` turns the warning off.
+    pub startup_memory_warn_mb: Option<Option<u64>>,
+    /// Replaces the list underneath rather than adding to it.
+    pub extra_args: Option<Vec<String>>,
+}
+
+impl WorkerConfigOverride {
+    /// The fields that have a `DJBOT_*` variable set.
+    pub(crate) fn from_env() -> Self {
+        WorkerConfigOverride {
+            port: env_var(ENV_PORT_HINT, |v| v.parse().ok()),
+            max_start_retries: env_var(ENV_MAX_RETRIES, |v| v.parse().ok()),
+            startup_timeout_secs: env_var(ENV_STARTUP_TIMEOUT, |v| v.parse().ok().filter(|&secs| secs > 0)),
+            bind_address: env_var(ENV_BIND_ADDRESS, |v| v.parse().ok().filter(bind_address_reachable)),
+            restart_policy: env_var(ENV_RESTART_POLICY, RestartPolicy::parse),
+            startup_memory_warn_mb: env_var(ENV_MAX_MEMORY_MB, parse_memory_mb),
+            extra_args: env_var(ENV_WORKER_ARGS, |v| Some(v.split_whitespace().map(String::from).collect())),
+        }
+    }
+
+    /// `self` with every field `upper` sets replaced, for stacking the
+    /// command line over the environment.
+    pub(crate) fn layered_under(self, upper: WorkerConfigOverride) -> Self {
+        WorkerConfigOverride {
+            port: upper.port.or(self.port),
+            max_start_retries: upper.max_start_retries.or(self.max_start_retries),
+            startup_timeout_secs: upper.startup_timeout_secs.or(self.startup_timeout_secs),
+            bind_address: upper.bind_address.or(self.bind_address),
+            restart_policy: upper.restart_policy.or(self.restart_policy),
+            startup_memory_warn_mb: upper.startup_memory_warn_mb.or(self.startup_memory_warn_mb),
+            extra_args: upper.extra_args.or(self.extra_args),
+        }
+    }
+}
+
+/// Whether we can still reach a worker listening on `ip`: loopback, or
+/// unspecified (all interfaces).
+pub(crate) fn bind_address_reachable(ip: &IpAddr) -> bool {
+    ip.is_loopback() || ip.is_unspecified()
+}
+
+/// Megabytes for `startup_memory_warn_mb`, "0" for no warning.
+pub(crate) fn parse_memory_mb(value: &str) -> Option<Option<u64>> {
+    value.parse::<u64>().ok().map(|mb| (mb > 0).then_some(mb))
+}
+
+impl WorkerConfig {
+    /// Hash of the configured fields, to tell whether the running worker
+    /// was started with the current settings. The fields resolved at
+    /// startup are left out, and so is `audio`, which can change without a
+    /// restart and is tracked on its own.
+    pub(crate) fn config_hash(&self) -> u64 {
+        let configured = WorkerConfig {
+            audio: AudioEngineConfig::default(),
+            sidecar_path: PathBuf::new(),
+            ffmpeg_path: None,
+            data_dir: PathBuf::new(),
+            ..self.clone()
+        };
+        let mut hasher = DefaultHasher::new();
+        configured.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// `base` (config.toml) with every field `override_config` sets
+    /// replaced; see `WorkerConfigOverride::layered_under` for how the
+    /// environment and command line are combined first.
+    pub(crate) fn merge(mut base: WorkerConfig, override_config: WorkerConfigOverride) -> WorkerConfig {
+        if let Some(port) = override_config.port {
+            base.port = Some(port);
+        }
+        if let Some(retries) = override_config.max_start_retries {
+            base.max_start_retries = Some(retries);
+        }
+        if let Some(secs) = override_config.startup_timeout_secs {
+            base.startup_timeout_secs = secs;
+        }
+        if let Some(address) = override_config.bind_address {
+            base.bind_address = Some(address);
+        }
+        if let Some(policy) = override_config.restart_policy {
+            base.restart_policy = policy;
+        }
+        if let Some(mb) = override_config.startup_memory_warn_mb {
+            base.startup_memory_warn_mb = mb;
+        }
+        if let Some(args) = override_config.extra_args {
+            base.extra_args = args;
+        }
+        base
+    }
+}
+
+/// Audio host API the worker should open the output device with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AudioBackend {
+    WasapiExclusive,
+    WasapiShared,
+    CoreAudio,
+    Alsa,
+    Pulse,
+    Jack,
+}
+
+impl AudioBackend {
+    pub(crate) const ALL: &'static [AudioBackend] = &[
+        AudioBackend::WasapiExclusive,
+        AudioBackend::WasapiShared,
+        AudioBackend::CoreAudio,
+        AudioBackend::Alsa,
+        AudioBackend::Pulse,
+        AudioBackend::Jack,
+    ];
+
+    /// Whether the backend exists on the platform we're running on.
+    pub(crate) fn is_available(self) -> bool {
+        match self {
+            AudioBackend::WasapiExclusive | AudioBackend::WasapiShared => cfg!(target_os = "windows"),
+            AudioBackend::CoreAudio => cfg!(target_os = "macos"),
+            AudioBackend::Alsa | AudioBackend::Pulse => cfg!(target_os = "linux"),
+            AudioBackend::Jack => cfg!(any(target_os = "linux", target_os = "macos")),
+        }
+    }
+
+    /// Value passed to the worker's `--audio-backend`.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            AudioBackend::WasapiExclusive => "wasapi_exclusive",
+            AudioBackend::WasapiShared => "wasapi_shared",
+            AudioBackend::CoreAudio => "core_audio",
+            AudioBackend::Alsa => "alsa",
+            AudioBackend::Pulse => "pulse",
+            AudioBackend::Jack => "jack",
+        }
+    }
+}
+
+pub(crate) const MIN_HEALTH_CHECK_SECS: u64 = 5;
+pub(crate) const MAX_HEALTH_CHECK_SECS: u64 = 300;
+
+pub(crate) fn validate_health_check_interval(secs: u64) -> Result<(), String> {
+    if (MIN_HEALTH_CHECK_SECS..=MAX_HEALTH_CHECK_SECS).contains(&secs) {
+        Ok(())
+    } else {
+        Err(format!(
+            "health check interval must be between {} and {} seconds",
+            MIN_HEALTH_CHECK_SECS, MAX_HEALTH_CHECK_SECS
+        ))
+    }
+}
+
+pub(crate) const MIN_LOG_BUFFER_CAPACITY: usize = 10;
+pub(crate) const MAX_LOG_BUFFER_CAPACITY: usize = 10_000;
+
+pub(crate) fn validate_log_buffer_capacity(lines: usize) -> Result<(), String> {
+    if (MIN_LOG_BUFFER_CAPACITY..=MAX_LOG_BUFFER_CAPACITY).contains(&lines) {
+        Ok(())
+    } else {
+        Err(format!(
+            "log buffer capacity must be between {} and {} lines",
+            MIN_LOG_BUFFER_CAPACITY, MAX_LOG_BUFFER_CAPACITY
+        ))
+    }
+}
+
+/// When an exited worker is restarted. Exits we cause (restart, shutdown)
+/// never trigger one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RestartPolicy {
+    Always,
+    /// Only after a non-zero exit code or a signal.
+    #[default]
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    /// The `snake_case` name as written in config.toml.
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        match name {
+            "always" => Some(RestartPolicy::Always),
+            "on_failure" => Some(RestartPolicy::OnFailure),
+            "never" => Some(RestartPolicy::Never),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn should_restart(self, success: bool) -> bool {
+        match self {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !success,
+            RestartPolicy::Never => false,
+        }
+    }
+}
+
+/// What the app does when the worker exits unexpectedly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CrashPolicy {
+    /// Restart as `WorkerConfig::restart_policy` allows.
+    #[default]
+    AutoRestart,
+    /// Emit `worker-crashed` and wait for the user to restart it.
+    NotifyOnly,
+    /// Only record the crash.
+    Silent,
+}
+
+/// What preview playback does when the system default output changes
+/// (e.g. headphones unplugged).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DeviceChangeBehavior {
+    #[default]
+    Pause,
+    Continue,
+    FollowNewDevice,
+}
+
+pub(crate) const SAMPLE_RATES: &[u32] = &[44_100, 48_000, 96_000];
+/// Buffer sizes (in frames) are powers of two in this range.
+pub(crate) const MIN_BUFFER_SIZE: u32 = 64;
+pub(crate) const MAX_BUFFER_SIZE: u32 = 8192;
+
+/// Preview playback engine settings; `None` leaves the choice to the worker.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AudioEngineConfig {
+    pub sample_rate: Option<u32>,
+    /// Frames per buffer.
+    pub buffer_size: Option<u32>,
+    pub backend: Option<AudioBackend>,
+}
+
+impl AudioEngineConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(rate) = self.sample_rate {
+            if !SAMPLE_RATES.contains(&rate) {
+                return Err(format!("unsupported sample rate {} Hz", rate));
+            }
+        }
+        if let Some(size) = self.buffer_size {
+            if !size.is_power_of_two() || !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size) {
+                return Err(format!(
+                    "buffer size must be a power of two between {} and {}",
+                    MIN_BUFFER_SIZE, MAX_BUFFER_SIZE
+                ));
+            }
+        }
+        if let Some(backend) = self.backend {
+            if !backend.is_available() {
+                return Err(format!("audio backend {} is not available on this platform", backend.as_str()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How worker output is condensed before it reaches the log viewer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LogConfig {
+    /// Collapse ffmpeg `size=… time=… bitrate=…` progress updates.
+    pub coalesce_ffmpeg_progress: bool,
+    /// Collapse consecutive identical lines.
+    pub coalesce_identical: bool,
+    /// Write the coalesced form to `worker.log` instead of every line.
+    pub coalesce_log_file: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            coalesce_ffmpeg_progress: true,
+            coalesce_identical: true,
+            coalesce_log_file: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct OutputConfig {
+    /// Once the output directory grows past this, files older than a week
+    /// are moved into `archive/`.
+    pub max_output_dir_mb: u64,
+    /// Unix permission bits for the data, output and archive directories
+    /// when we create them, e.g. `0o775` for group-writable output. Ignored
+    /// on Windows.
+    pub dir_mode: u32,
+    /// Watch the output directory for writes by other programs (sync
+    /// clients, antivirus) and warn about them.
+    pub watch_external_writes: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            max_output_dir_mb: 10_000,
+            dir_mode: 0o755,
+            watch_external_writes: false,
+        }
+    }
+}
+
+impl OutputConfig {
+    pub(crate) fn create_dir(&self, path: &Path) -> std::io::Result<()> {
+        create_dir_with_mode(path, self.dir_mode)
+    }
+}
+
+/// `create_dir_all` that applies `mode` to the directory it creates. An
+/// existing directory is left untouched. `mode` is ignored on Windows.
+pub(crate) fn create_dir_with_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        if path.is_dir() {
+            return Ok(());
+        }
+        std::fs::DirBuilder::new().recursive(true).mode(mode).create(path)?;
+        // The builder's mode is filtered through the umask; set it exactly.
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+        std::fs::create_dir_all(path)
+    }
+}
+
+/// `secrets` field name of `WorkerConfig::download_proxy`.
+pub(crate) const DOWNLOAD_PROXY_FIELD: &str = "worker.download_proxy";
+
+/// A download proxy must be an http(s) or socks5 URL.
+pub(crate) fn validate_download_proxy(url: &str) -> Result<(), String> {
+    let (scheme, rest) = url.split_once("://").ok_or_else(|| format!("{} is not a URL", url))?;
+    if !matches!(scheme, "http" | "https" | "socks5") {
+        return Err(format!("unsupported proxy scheme {}; use http, https or socks5", scheme));
+    }
+    if rest.is_empty() {
+        return Err("proxy URL has no host".to_string());
+    }
+    Ok(())
+}
+
+/// Limits for caches the app keeps under `{data_dir}/cache`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct CacheConfig {
+    /// Waveform thumbnails and album art together beyond this are evicted,
+    /// least recently used first.
+    #[serde(alias = "max_waveform_cache_mb")]
+    pub max_image_cache_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { max_image_cache_mb: 256 }
+    }
+}
+
+/// Limits on files imported into the library (`import_files`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ImportConfig {
+    pub max_file_mb: u64,
+    /// Total of one `import_files` call.
+    pub max_batch_mb: u64,
+    /// Accepted file extensions, without the dot. The content must look
+    /// like audio or video as well.
+    pub extensions: Vec<String>,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        ImportConfig {
+            max_file_mb: 2 * 1024,
+            max_batch_mb: 20 * 1024,
+            extensions: ["mp3", "wav", "flac", "m4a", "aac", "ogg", "opus", "aiff", "aif", "mp4", "mov"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// `secrets` field names of `WebhookConfig::url` and `::secret`.
+pub(crate) const WEBHOOK_URL_FIELD: &str = "webhook.url";
+pub(crate) const WEBHOOK_SECRET_FIELD: &str = "webhook.secret";
+
+/// Job statuses a webhook can be filtered on.
+pub(crate) const WEBHOOK_EVENTS: &[&str] = &["succeeded", "failed", "timed_out"];
+
+/// Webhook POSTed when a job finishes (see `webhooks`). Off by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct WebhookConfig {
+    pub enabled: bool,
+    /// Sealed with `secrets` (field `WEBHOOK_URL_FIELD`); relay URLs often
+    /// carry a token.
+    pub url: Option<String>,
+    /// HMAC key for the signature header, sealed (`WEBHOOK_SECRET_FIELD`).
+    pub secret: Option<String>,
+    /// Statuses to notify about (`WEBHOOK_EVENTS`); empty means all.
+    pub events: Vec<String>,
+}
+
+/// Discord Rich Presence (off unless the user opts in).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct PresenceConfig {
+    pub enabled: bool,
+    /// Discord application id the presence is published under.
+    pub client_id: String,
+}
+
+/// Connectivity monitoring for URL jobs (see `network`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct NetworkConfig {
+    /// URL whose host is probed for connectivity. Unset, the webhook host
+    /// is probed if webhooks are on, and otherwise only the route out.
+    pub probe_url: Option<String>,
+    pub probe_interval_secs: u64,
+    /// Hold URL jobs while offline and send them when the network is
+    /// back, instead of failing them.
+    pub queue_when_offline: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig { probe_url: None, probe_interval_secs: 15, queue_when_offline: false }
+    }
+}
+
+/// The job log behind `get_job_history` and `export_job_history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct HistoryConfig {
+    /// Entries kept; the oldest are dropped beyond this.
+    pub max_entries: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig { max_entries: 10_000 }
+    }
+}
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Release channels the updater can follow.
+pub(crate) const UPDATE_CHANNELS: &[&str] = &["stable", "beta", "nightly"];
+
+pub(crate) fn validate_update_channel(channel: &str) -> Result<(), String> {
+    if UPDATE_CHANNELS.contains(&channel) {
+        Ok(())
+    } else {
+        Err(format!("unknown update channel {:?} (expected one of {})", channel, UPDATE_CHANNELS.join(", ")))
+    }
+}
+
+/// Update manifest for `channel`.
+pub(crate) fn update_manifest_url(channel: &str) -> String {
+    format!("https://releases.djbot.app/update/{}/manifest.json", channel)
+}
+
+/// Worker release manifest for `channel` (see `worker_update`).
+pub(crate) fn worker_manifest_url(channel: &str) -> String {
+    format!("https://releases.djbot.app/worker/{}/manifest.json", channel)
+}
+
+/// Everything persisted in `{data_dir}/config.toml`. Presets imported
+/// through `djbot://import` can set a few of the fields (`import::Preset`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct DjbotConfig {
+    /// Launch the worker as soon as the app starts. When off, it stays down
+    /// until `retry_worker_start`/`restart_worker` is called.
+    pub auto_start_worker: bool,
+    /// Show worker output exactly as produced (disables log coalescing).
+    pub debug_mode: bool,
+    /// Take the OS media keys / now-playing controls. Off leaves them to
+    /// the system player.
+    pub media_keys: bool,
+    /// Reaction to the default output device changing mid-playback.
+    pub on_default_device_change: DeviceChangeBehavior,
+    /// Reaction to the worker crashing.
+    pub on_worker_crash: CrashPolicy,
+    /// Release channel checked for updates: "stable", "beta" or "nightly".
+    pub update_channel: String,
+    /// Channel worker binaries are updated from, independent of the app's.
+    pub worker_update_channel: String,
+    pub worker: WorkerConfig,
+    pub logs: LogConfig,
+    pub output: OutputConfig,
+    pub cache: CacheConfig,
+    pub import: ImportConfig,
+    pub presence: PresenceConfig,
+    pub webhook: WebhookConfig,
+    pub network: NetworkConfig,
+    pub history: HistoryConfig,
+}
+
+impl Default for DjbotConfig {
+    fn default() -> Self {
+        DjbotConfig {
+            auto_start_worker: true,
+            debug_mode: false,
+            media_keys: true,
+            on_default_device_change: DeviceChangeBehavior::default(),
+            on_worker_crash: CrashPolicy::default(),
+            update_channel: "stable".to_string(),
+            worker_update_channel: "stable".to_string(),
+            worker: WorkerConfig::default(),
+            logs: LogConfig::default(),
+            output: OutputConfig::default(),
+            cache: CacheConfig::default(),
+            import: ImportConfig::default(),
+            presence: PresenceConfig::default(),
+            webhook: WebhookConfig::default(),
+            network: NetworkConfig::default(),
+            history: HistoryConfig::default(),
+        }
+    }
+}
+
+impl DjbotConfig {
+    pub(crate) fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(CONFIG_FILE)
+    }
+
+    /// Load the config, falling back to defaults when the file is missing
+    /// or unreadable (the error is logged, never fatal).
+    pub(crate) fn load(data_dir: &Path) -> Self {
+        let path = Self::path(data_dir);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(_) => return Self::default(),
+        };
+        let mut config: Self = toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("[djbot] ignoring invalid {}: {}", path.display(), e);
+            Self::default()
+        });
+        for e in config.reset_invalid() {
+            eprintln!("[djbot] {} in {}; using the default", e, path.display());
+        }
+        config
+    }
+
+    /// Refuses to write a config `validate` rejects.
+    pub(crate) fn save(&self, data_dir: &Path) -> Result<(), String> {
+        self.validate()?;
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(Self::path(data_dir), text).map_err(|e| e.to_string())
+    }
+
+    /// Check every field that has bounds or a fixed set of values.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let errors = self.clone().reset_invalid();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Put each field `validate` would reject back to its default, returning
+    /// why each one was reset.
+    fn reset_invalid(&mut self) -> Vec<String> {
+        let defaults = DjbotConfig::default();
+        let mut errors = Vec::new();
+        if let Err(e) = validate_update_channel(&self.update_channel) {
+            errors.push(e);
+            self.update_channel = defaults.update_channel;
+        }
+        if let Err(e) = validate_update_channel(&self.worker_update_channel) {
+            errors.push(e);
+            self.worker_update_channel = defaults.worker_update_channel;
+        }
+        let worker = &mut self.worker;
+        if let Err(e) = validate_health_check_interval(worker.health_check_interval_secs) {
+            errors.push(e);
+            worker.health_check_interval_secs = defaults.worker.health_check_interval_secs;
+        }
+        if let Err(e) = validate_log_buffer_capacity(worker.log_buffer_capacity) {
+            errors.push(e);
+            worker.log_buffer_capacity = defaults.worker.log_buffer_capacity;
+        }
+        if let Err(e) = worker.audio.validate() {
+            errors.push(e);
+            worker.audio = defaults.worker.audio;
+        }
+        if let Some(Err(e)) = worker.output_bit_depth.map(formats::validate_bit_depth) {
+            errors.push(e);
+            worker.output_bit_depth = defaults.worker.output_bit_depth;
+        }
+        errors
+    }
+
+    /// Dotted keys (`worker.job_timeout_secs`) whose value differs between
+    /// `self` and `other`, sorted.
+    pub(crate) fn differences(&self, other: &DjbotConfig) -> Vec<String> {
+        fn walk(prefix: &str, a: Option<&toml::Value>, b: Option<&toml::Value>, out: &mut Vec<String>) {
+            match (a, b) {
+                (Some(toml::Value::Table(a)), Some(toml::Value::Table(b))) => {
+                    let keys: std::collections::BTreeSet<_> = a.keys().chain(b.keys()).collect();
+                    for key in keys {
+                        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                        walk(&path, a.get(key), b.get(key), out);
+                    }
+                }
+                (a, b) if a != b => out.push(prefix.to_string()),
+                _ => {}
+            }
+        }
+        let mut out = Vec::new();
+        walk("", toml::Value::try_from(self).ok().as_ref(), toml::Value::try_from(other).ok().as_ref(), &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("djbot-config-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        DjbotConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn out_of_range_health_check_interval_is_refused() {
+        for secs in [0, MIN_HEALTH_CHECK_SECS - 1, MAX_HEALTH_CHECK_SECS + 1] {
+            let mut config = DjbotConfig::default();
+            config.worker.health_check_interval_secs = secs;
+            assert!(config.validate().is_err(), "{} accepted", secs);
+            assert!(config.save(&data_dir("refused")).is_err());
+        }
+        let mut config = DjbotConfig::default();
+        config.worker.health_check_interval_secs = MAX_HEALTH_CHECK_SECS;
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn out_of_range_log_buffer_capacity_is_refused() {
+        for lines in [0, MIN_LOG_BUFFER_CAPACITY - 1, MAX_LOG_BUFFER_CAPACITY + 1] {
+            let mut config = DjbotConfig::default();
+            config.worker.log_buffer_capacity = lines;
+            assert!(config.validate().is_err(), "{} accepted", lines);
+        }
+        let mut config = DjbotConfig::default();
+        config.worker.log_buffer_capacity = MIN_LOG_BUFFER_CAPACITY;
+        config.validate().unwrap();
+    }
+
+    /// Tests that set `DJBOT_*` variables hold this, the environment being
+    /// shared by the whole process.
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        let result = f();
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        result
+    }
+
+    /// File < environment < command line, combined the way `run` does.
+    fn layered(file: WorkerConfig, env: &[(&str, &str)], args: &[&str]) -> WorkerConfig {
+        let cli = crate::cli::parse(args.iter().map(|a| a.to_string())).unwrap();
+        let overrides = with_env(env, WorkerConfigOverride::from_env).layered_under(cli.worker);
+        WorkerConfig::merge(file, overrides)
+    }
+
+    fn file_config() -> WorkerConfig {
+        WorkerConfig {
+            port: Some(1000),
+            restart_policy: RestartPolicy::Never,
+            startup_memory_warn_mb: Some(512),
+            extra_args: vec!["--from-file".to_string()],
+            ..WorkerConfig::default()
+        }
+    }
+
+    #[test]
+    fn file_alone_is_kept() {
+        let merged = layered(file_config(), &[], &[]);
+        assert_eq!(merged.port, Some(1000));
+        assert_eq!(merged.restart_policy, RestartPolicy::Never);
+        assert_eq!(merged.startup_memory_warn_mb, Some(512));
+        assert_eq!(merged.extra_args, ["--from-file"]);
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let env = [
+            (ENV_RESTART_POLICY, "always"),
+            (ENV_PORT_HINT, "2000"),
+            (ENV_MAX_MEMORY_MB, "0"),
+            (ENV_WORKER_ARGS, " --from-env  --verbose "),
+        ];
+        let merged = layered(file_config(), &env, &[]);
+        assert_eq!(merged.restart_policy, RestartPolicy::Always);
+        assert_eq!(merged.port, Some(2000));
+        // An Option the environment sets back to `None`.
+        assert_eq!(merged.startup_memory_warn_mb, None);
+        assert_eq!(merged.extra_args, ["--from-env", "--verbose"]);
+    }
+
+    #[test]
+    fn command_line_overrides_the_environment() {
+        let env = [
+            (ENV_RESTART_POLICY, "always"),
+            (ENV_PORT_HINT, "2000"),
+            (ENV_MAX_MEMORY_MB, "0"),
+            (ENV_WORKER_ARGS, "--from-env"),
+        ];
+        let args = [
+            "--restart-policy", "on_failure",
+            "--worker-port", "3000",
+            "--max-memory-mb", "2048",
+            "--worker-arg", "--from-cli",
+            "--worker-arg", "--quiet",
+        ];
+        let merged = layered(file_config(), &env, &args);
+        assert_eq!(merged.restart_policy, RestartPolicy::OnFailure);
+        assert_eq!(merged.port, Some(3000));
+        assert_eq!(merged.startup_memory_warn_mb, Some(2048));
+        assert_eq!(merged.extra_args, ["--from-cli", "--quiet"]);
+
+        // Only what the command line sets wins over the environment.
+        let merged = layered(file_config(), &env, &["--worker-port", "3000"]);
+        assert_eq!(merged.restart_policy, RestartPolicy::Always);
+        assert_eq!(merged.extra_args, ["--from-env"]);
+    }
+
+    #[test]
+    fn malformed_environment_values_are_ignored() {
+        let env = [
+            (ENV_PORT_HINT, "70000"),
+            (ENV_MAX_RETRIES, "-1"),
+            (ENV_STARTUP_TIMEOUT, "0"),
+            (ENV_BIND_ADDRESS, "10.0.0.1"),
+            (ENV_RESTART_POLICY, "sometimes"),
+            (ENV_MAX_MEMORY_MB, "lots"),
+        ];
+        let from_env = with_env(&env, WorkerConfigOverride::from_env);
+        assert_eq!(from_env.port, None);
+        assert_eq!(from_env.max_start_retries, None);
+        assert_eq!(from_env.startup_timeout_secs, None);
+        assert_eq!(from_env.bind_address, None);
+        assert_eq!(from_env.restart_policy, None);
+        assert_eq!(from_env.startup_memory_warn_mb, None);
+
+        let merged = layered(file_config(), &env, &[]);
+        let file = file_config();
+        assert_eq!(merged.config_hash(), file.config_hash());
+    }
+
+    #[test]
+    fn load_resets_only_the_invalid_fields() {
+        let dir = data_dir("load");
+        std::fs::write(
+            DjbotConfig::path(&dir),
+            "update_channel = \"canary\"\n[worker]\nhealth_check_interval_secs = 1\nlog_buffer_capacity = 0\nmax_missed_heartbeats = 7\n",
+        )
+        .unwrap();
+        let config = DjbotConfig::load(&dir);
+        assert_eq!(config.update_channel, "stable");
+        assert_eq!(config.worker.health_check_interval_secs, 30);
+        assert_eq!(config.worker.log_buffer_capacity, 500);
+        assert_eq!(config.worker.max_missed_heartbeats, 7);
+    }
+}
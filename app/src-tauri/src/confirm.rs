@@ -0,0 +1,75 @@
+//! Two-phase confirmation for destructive commands.
+//!
+//! Phase one (`request_<op>`) describes what will happen and issues a
+//! single-use token bound to the operation and a hash of its exact
+//! parameters, valid for `TOKEN_TTL`. Phase two runs only with that token
+//! and the same parameters. Tokens live in memory and are dropped whenever
+//! the webview (re)loads, so a reloaded page can't replay an old one.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+const TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Phase-one answer: what will happen, and the token to go ahead.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ConfirmationRequest {
+    pub token: String,
+    pub summary: String,
+    pub expires_in_secs: u64,
+}
+
+struct Pending {
+    op: &'static str,
+    params: [u8; 32],
+    expires: Instant,
+}
+
+fn hash_params(params: &serde_json::Value) -> [u8; 32] {
+    // serde_json maps keep keys sorted, so equal parameters hash equally.
+    Sha256::digest(params.to_string().as_bytes()).into()
+}
+
+/// Managed state: outstanding tokens.
+#[derive(Default)]
+pub(crate) struct Confirmations {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Confirmations {
+    /// Issue a token for running `op` with `params`.
+    pub(crate) fn issue(&self, op: &'static str, params: &serde_json::Value, summary: String) -> Result<ConfirmationRequest, String> {
+        let mut bytes = [0u8; 16];
+        getrandom::getrandom(&mut bytes).map_err(|e| format!("no randomness for a token: {}", e))?;
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires > now);
+        pending.insert(token.clone(), Pending { op, params: hash_params(params), expires: now + TOKEN_TTL });
+        Ok(ConfirmationRequest { token, summary, expires_in_secs: TOKEN_TTL.as_secs() })
+    }
+
+    /// Use up `token` for `op` with `params`. The token is gone afterwards
+    /// whether or not it matched.
+    pub(crate) fn redeem(&self, token: Option<&str>, op: &'static str, params: &serde_json::Value) -> Result<(), String> {
+        let token = token.ok_or_else(|| format!("{} needs a confirmation token from request_{}", op, op))?;
+        let pending = self.pending.lock().unwrap().remove(token);
+        match pending {
+            None => Err("confirmation token unknown or already used".to_string()),
+            Some(p) if p.expires <= Instant::now() => Err("confirmation token expired".to_string()),
+            Some(p) if p.op != op || p.params != hash_params(params) => {
+                Err("confirmation token was issued for a different operation".to_string())
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Drop every outstanding token.
+    pub(crate) fn clear(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+}
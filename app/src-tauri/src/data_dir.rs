@@ -0,0 +1,126 @@
+//! Moving the data directory somewhere else (`migrate_data_dir`).
+//!
+//! The default data dir holds a `LOCATION_FILE` naming the one to use
+//! instead; setup follows it before reading config.toml. A migration copies
+//! everything over, points the default dir at the copy and, for a move,
+//! empties the old one except for that file.
+
+use std::path::{Path, PathBuf};
+
+/// In the default data dir: absolute path of the data dir actually in use.
+const LOCATION_FILE: &str = "data_dir_location";
+
+/// What a migration will copy.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TreeSize {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// The data dir `default_dir` points to, if it points anywhere usable.
+pub(crate) fn relocated(default_dir: &Path) -> Option<PathBuf> {
+    let text = std::fs::read_to_string(default_dir.join(LOCATION_FILE)).ok()?;
+    let target = PathBuf::from(text.trim());
+    if target.is_absolute() && target.is_dir() {
+        Some(target)
+    } else {
+        eprintln!("[djbot] ignoring {}: {} is not a directory", LOCATION_FILE, target.display());
+        None
+    }
+}
+
+/// Point `default_dir` at `target`, or back at itself.
+pub(crate) fn set_location(default_dir: &Path, target: &Path) -> Result<(), String> {
+    let file = default_dir.join(LOCATION_FILE);
+    if target == default_dir {
+        return match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        };
+    }
+    std::fs::create_dir_all(default_dir).map_err(|e| e.to_string())?;
+    std::fs::write(file, target.to_string_lossy().as_bytes()).map_err(|e| e.to_string())
+}
+
+/// A place `current` can be migrated to: absolute, outside `current` (and
+/// not containing it), and missing or an empty directory.
+pub(crate) fn check_target(current: &Path, target: &Path) -> Result<(), String> {
+    if !target.is_absolute() {
+        return Err(format!("{} is not an absolute path", target.display()));
+    }
+    if target.starts_with(current) || current.starts_with(target) {
+        return Err(format!("{} overlaps the current data directory", target.display()));
+    }
+    match std::fs::read_dir(target).map(|mut entries| entries.next().is_none()) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!("{} is not empty", target.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("{}: {}", target.display(), e)),
+    }
+}
+
+/// Files and bytes under `dir`, leaving out `LOCATION_FILE`.
+pub(crate) fn tree_size(dir: &Path) -> Result<TreeSize, String> {
+    let mut size = TreeSize::default();
+    walk(dir, &mut |_, meta| {
+        size.files += 1;
+        size.bytes += meta.len();
+        Ok(())
+    })?;
+    Ok(size)
+}
+
+/// Copy everything under `from` into `to`, which `check_target` accepted.
+/// Symlinks are skipped.
+pub(crate) fn copy_tree(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| e.to_string())?;
+    walk(from, &mut |path, _| {
+        let dest = to.join(path.strip_prefix(from).map_err(|e| e.to_string())?);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::copy(path, &dest).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(())
+    })
+}
+
+/// Remove everything in `dir` except `LOCATION_FILE`, after a move.
+pub(crate) fn clear(dir: &Path) -> Result<(), String> {
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        if entry.file_name() == LOCATION_FILE {
+            continue;
+        }
+        let path = entry.path();
+        let result = match entry.file_type() {
+            Ok(t) if t.is_dir() => std::fs::remove_dir_all(&path),
+            _ => std::fs::remove_file(&path),
+        };
+        result.map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Call `visit` for each regular file under `dir`, except a top-level
+/// `LOCATION_FILE`.
+fn walk(dir: &Path, visit: &mut dyn FnMut(&Path, &std::fs::Metadata) -> Result<(), String>) -> Result<(), String> {
+    fn inner(
+        dir: &Path,
+        top: bool,
+        visit: &mut dyn FnMut(&Path, &std::fs::Metadata) -> Result<(), String>,
+    ) -> Result<(), String> {
+        for entry in std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?.flatten() {
+            if top && entry.file_name() == LOCATION_FILE {
+                continue;
+            }
+            let path = entry.path();
+            let meta = std::fs::symlink_metadata(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if meta.is_dir() {
+                inner(&path, false, visit)?;
+            } else if meta.is_file() {
+                visit(&path, &meta)?;
+            }
+        }
+        Ok(())
+    }
+    inner(dir, true, visit)
+}
@@ -0,0 +1,210 @@
+//! Worker binary discovery with an overall time budget.
+//!
+//! A `stat` on a network share or a sleeping external disk can block for
+//! many seconds. Candidates are checked concurrently; the best confirmed hit
+//! (in priority order) wins once the answer can no longer change or the
+//! deadline passes. Checks still pending at the deadline keep running in
+//! the background and late hits are kept for diagnostics.
+//!
+//! A candidate only counts if it looks runnable: an interrupted download or
+//! extraction can leave an empty or truncated file behind, which is skipped
+//! (and reported) in favour of the next candidate.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct DiscoveryReport {
+    /// Candidates whose existence check did not finish within the budget.
+    pub timed_out: Vec<PathBuf>,
+    /// Timed-out candidates that later turned out to contain the binary.
+    pub late_hits: Vec<PathBuf>,
+    /// Candidates that exist but can't be the worker, with the reason.
+    pub unusable: Vec<UnusableBinary>,
+    /// Every candidate, in priority order.
+    pub candidates: Vec<CandidateCheck>,
+    /// The candidate chosen, if any was usable in time.
+    pub selected: Option<PathBuf>,
+    /// Time spent choosing.
+    pub elapsed_ms: u64,
+}
+
+/// Outcome of checking one candidate.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CandidateCheck {
+    pub path: PathBuf,
+    /// Something is at the path; `None` while the check is still pending.
+    pub found: Option<bool>,
+    /// It looks like a runnable worker.
+    pub executable: bool,
+}
+
+impl CandidateCheck {
+    fn record(&mut self, check: &Result<bool, String>) {
+        self.found = Some(check.is_err() || *check == Ok(true));
+        self.executable = *check == Ok(true);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UnusableBinary {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Smaller files can't be a Go worker build (those are several MB).
+const MIN_WORKER_BYTES: u64 = 1024 * 1024;
+
+/// Executable header magics for this platform.
+#[cfg(target_os = "windows")]
+const EXECUTABLE_MAGICS: &[&[u8]] = &[b"MZ"];
+#[cfg(target_os = "macos")]
+const EXECUTABLE_MAGICS: &[&[u8]] = &[b"\xcf\xfa\xed\xfe", b"\xce\xfa\xed\xfe", b"\xca\xfe\xba\xbe"];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const EXECUTABLE_MAGICS: &[&[u8]] = &[b"\x7fELF"];
+
+/// `Ok(false)` if nothing is at `path`, `Ok(true)` if it looks like a
+/// runnable worker, `Err(reason)` if a file is there but can't be one.
+pub(crate) fn check_binary(path: &Path) -> Result<bool, String> {
+    let meta = match std::fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.to_string()),
+    };
+    if !meta.is_file() {
+        return Err("not a regular file".to_string());
+    }
+    if meta.len() < MIN_WORKER_BYTES {
+        return Err(format!("only {} bytes; the install looks incomplete", meta.len()));
+    }
+    let mut header = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map_err(|e| e.to_string())?;
+    if !EXECUTABLE_MAGICS.iter().any(|m| header.starts_with(m)) {
+        return Err("not an executable for this platform".to_string());
+    }
+    Ok(true)
+}
+
+/// Managed state holding the report; updated by the background
+/// thread as timed-out checks complete.
+#[derive(Clone, Default)]
+pub(crate) struct SidecarDiscovery(pub Arc<Mutex<DiscoveryReport>>);
+
+impl SidecarDiscovery {
+    pub(crate) fn report(&self) -> DiscoveryReport {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// The highest-priority candidate known to exist, once no higher-priority
+/// check can still succeed. `None` while undecided.
+fn decided(results: &[Option<bool>]) -> Option<Option<usize>> {
+    for (i, r) in results.iter().enumerate() {
+        match r {
+            Some(true) => return Some(Some(i)),
+            Some(false) => continue,
+            None => return None,
+        }
+    }
+    Some(None)
+}
+
+/// `name` looked up in `PATH` the way a shell would (`PATHEXT` on Windows,
+/// symlinks resolved), `None` if it isn't there.
+pub(crate) fn find_in_path(name: &str) -> Option<PathBuf> {
+    which::which(name).ok()
+}
+
+/// Return the first usable path from `candidates` (in order), spending at
+/// most `budget` on checks.
+pub(crate) fn find_first_usable(candidates: &[PathBuf], budget: Duration, discovery: &SidecarDiscovery) -> Option<PathBuf> {
+    let (tx, rx) = mpsc::channel();
+    for (i, path) in candidates.iter().cloned().enumerate() {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send((i, check_binary(&path)));
+        });
+    }
+    drop(tx);
+
+    let record_unusable = |report: &Mutex<DiscoveryReport>, path: &Path, reason: String| {
+        eprintln!("[djbot] skipping unusable worker binary {}: {}", path.display(), reason);
+        report.lock().unwrap().unusable.push(UnusableBinary { path: path.to_path_buf(), reason });
+    };
+
+    let started = Instant::now();
+    let deadline = started + budget;
+    let mut results = vec![None; candidates.len()];
+    let mut checks: Vec<_> = candidates
+        .iter()
+        .map(|path| CandidateCheck { path: path.clone(), found: None, executable: false })
+        .collect();
+    let choice = loop {
+        if let Some(choice) = decided(&results) {
+            break choice;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok((i, check)) => {
+                if let Err(reason) = &check {
+                    record_unusable(&discovery.0, &candidates[i], reason.clone());
+                }
+                checks[i].record(&check);
+                results[i] = Some(check == Ok(true));
+            }
+            Err(_) => {
+                // Out of time: settle for the best confirmed hit.
+                break results.iter().position(|r| *r == Some(true));
+            }
+        }
+    };
+
+    {
+        let mut report = discovery.0.lock().unwrap();
+        report.candidates = checks;
+        report.selected = choice.map(|i| candidates[i].clone());
+        report.elapsed_ms = started.elapsed().as_millis() as u64;
+    }
+
+    let timed_out: Vec<PathBuf> = results
+        .iter()
+        .zip(candidates)
+        .filter(|(r, _)| r.is_none())
+        .map(|(_, p)| p.clone())
+        .collect();
+    if !timed_out.is_empty() {
+        for path in &timed_out {
+            eprintln!("[djbot] worker lookup timed out for {}", path.display());
+        }
+        discovery.0.lock().unwrap().timed_out = timed_out;
+        let report = Arc::clone(&discovery.0);
+        let candidates = candidates.to_vec();
+        std::thread::spawn(move || {
+            for (i, check) in rx {
+                if results[i].is_some() {
+                    continue;
+                }
+                if let Some(entry) = report.lock().unwrap().candidates.get_mut(i) {
+                    entry.record(&check);
+                }
+                match check {
+                    Ok(true) => {
+                        eprintln!("[djbot] worker binary found late at {}", candidates[i].display());
+                        report.lock().unwrap().late_hits.push(candidates[i].clone());
+                    }
+                    Ok(false) => {}
+                    Err(reason) => record_unusable(&report, &candidates[i], reason),
+                }
+            }
+        });
+    }
+
+    choice.map(|i| candidates[i].clone())
+}
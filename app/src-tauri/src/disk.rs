@@ -0,0 +1,87 @@
+//! Free disk space for the volume holding a path, and how fast it is.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// Size of the file `measure_performance` writes and reads back.
+const BENCH_BYTES: usize = 10 * 1024 * 1024;
+const BENCH_CHUNK: usize = 1024 * 1024;
+/// Small synced writes averaged into `latency_ms`.
+const LATENCY_SAMPLES: u32 = 5;
+
+/// Bytes available to unprivileged users on the filesystem containing
+/// `path`, or `None` if it can't be determined on this platform.
+#[cfg(unix)]
+pub(crate) fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out-pointer.
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Throughput of a directory's volume, for diagnostics.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct DiskPerformance {
+    pub write_mb_per_sec: f32,
+    /// Reads back what was just written, so the OS cache may flatter it.
+    pub read_mb_per_sec: f32,
+    /// Mean time to write and sync 4 KiB.
+    pub latency_ms: f32,
+}
+
+/// Write `BENCH_BYTES` to a temp file in `dir` (synced to disk), read it
+/// back, then time a few small synced writes. The files are removed
+/// whatever the outcome. Blocking.
+pub(crate) fn measure_performance(dir: &Path) -> std::io::Result<DiskPerformance> {
+    let path = dir.join(".djbot-disk-bench.tmp");
+    let result = bench(&path);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+fn bench(path: &Path) -> std::io::Result<DiskPerformance> {
+    let mb = BENCH_BYTES as f32 / (1024.0 * 1024.0);
+    let chunk = vec![0x5au8; BENCH_CHUNK];
+
+    let started = Instant::now();
+    let mut file = std::fs::File::create(path)?;
+    for _ in 0..BENCH_BYTES / BENCH_CHUNK {
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+    drop(file);
+    let write_secs = started.elapsed().as_secs_f32();
+
+    let started = Instant::now();
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; BENCH_CHUNK];
+    while file.read(&mut buf)? > 0 {}
+    let read_secs = started.elapsed().as_secs_f32();
+
+    let started = Instant::now();
+    for _ in 0..LATENCY_SAMPLES {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(&chunk[..4096])?;
+        file.sync_all()?;
+    }
+    let latency_ms = started.elapsed().as_secs_f32() * 1000.0 / LATENCY_SAMPLES as f32;
+
+    Ok(DiskPerformance {
+        write_mb_per_sec: mb / write_secs.max(f32::EPSILON),
+        read_mb_per_sec: mb / read_secs.max(f32::EPSILON),
+        latency_ms,
+    })
+}
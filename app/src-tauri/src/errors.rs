@@ -0,0 +1,167 @@
+//! Command errors the UI can translate.
+//!
+//! An `AppError` serializes as `{ code, params, fallback_message }`: `code`
+//! is a stable identifier from the registry below, `params` the dynamic
+//! values a translation interpolates, and `fallback_message` the English
+//! text shown when the UI has no translation for the code. Codes exist only
+//! as `ErrorCode` variants, so a mistyped one fails to compile, and the
+//! registry itself is checked at compile time for malformed or duplicate
+//! codes.
+//!
+//! Commands still returning `Result<_, String>` get the fallback message
+//! through `From<AppError> for String`; migrated commands take any `String`
+//! error as `internal`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::paths::PathViolation;
+
+macro_rules! error_codes {
+    ($($(#[$doc:meta])* $name:ident = $code:literal,)+) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub(crate) enum ErrorCode {
+            $($(#[$doc])* $name,)+
+        }
+
+        impl ErrorCode {
+            pub(crate) fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$name => $code,)+
+                }
+            }
+        }
+
+        const _: () = assert!(registry_valid(&[$($code),+]), "malformed or duplicate error code");
+    };
+}
+
+error_codes! {
+    /// A `String` error from code not migrated to `AppError` yet.
+    Internal = "internal",
+    AppDataDirMissing = "app.data_dir_missing",
+    /// Params: `path`.
+    FsNotFound = "fs.not_found",
+    /// Params: `path`.
+    FsOutsideScope = "fs.outside_scope",
+    /// Params: `path`, `reason`.
+    FsInvalidPath = "fs.invalid_path",
+    ToolsFfmpegMissing = "tools.ffmpeg_missing",
+    /// `auto_start_worker` is off and the worker hasn't been started.
+    WorkerNotStarted = "worker.not_started",
+    /// Params: `status`.
+    WorkerNotReady = "worker.not_ready",
+    /// Params: `detail`.
+    WorkerStartupFailed = "worker.startup_failed",
+    /// A worker job returned an error. Params: `kind`.
+    JobFailed = "job.failed",
+    /// Params: `kind`, `secs`.
+    JobTimedOut = "job.timed_out",
+}
+
+/// Codes are dot-separated lowercase `snake_case` segments, each unique.
+const fn registry_valid(codes: &[&str]) -> bool {
+    let mut i = 0;
+    while i < codes.len() {
+        if !code_valid(codes[i].as_bytes()) {
+            return false;
+        }
+        let mut j = i + 1;
+        while j < codes.len() {
+            if bytes_eq(codes[i].as_bytes(), codes[j].as_bytes()) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn code_valid(code: &[u8]) -> bool {
+    if code.is_empty() || code[0] == b'.' || code[code.len() - 1] == b'.' {
+        return false;
+    }
+    let mut i = 0;
+    while i < code.len() {
+        let c = code[i];
+        let ok = c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_' || (c == b'.' && code[i - 1] != b'.');
+        if !ok {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AppError {
+    pub code: ErrorCode,
+    pub params: BTreeMap<&'static str, Value>,
+    pub fallback_message: String,
+}
+
+impl AppError {
+    pub(crate) fn new(code: ErrorCode, fallback_message: impl Into<String>) -> Self {
+        AppError { code, params: BTreeMap::new(), fallback_message: fallback_message.into() }
+    }
+
+    pub(crate) fn param(mut self, name: &'static str, value: impl Serialize) -> Self {
+        self.params.insert(name, serde_json::to_value(value).unwrap_or(Value::Null));
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.fallback_message)
+    }
+}
+
+impl From<AppError> for String {
+    fn from(e: AppError) -> String {
+        e.fallback_message
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<PathViolation> for AppError {
+    fn from(v: PathViolation) -> Self {
+        let message = v.to_string();
+        match v {
+            PathViolation::NotFound(path) => AppError::new(ErrorCode::FsNotFound, message).param("path", path),
+            PathViolation::OutsideScope(path) => AppError::new(ErrorCode::FsOutsideScope, message).param("path", path),
+            PathViolation::Invalid { path, reason } => {
+                AppError::new(ErrorCode::FsInvalidPath, message).param("path", path).param("reason", reason)
+            }
+        }
+    }
+}
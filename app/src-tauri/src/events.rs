@@ -0,0 +1,262 @@
+//! Bounded, batched forwarding of high-frequency worker output to the webview.
+//!
+//! Reader threads push into a bounded channel; a single emitter task drains
+//! it every `FLUSH_INTERVAL` and sends one Tauri event per kind per flush.
+//! When the channel is full, new items are dropped and counted instead of
+//! blocking the reader (which would in turn block the worker's pipe).
+//! Log lines pass through `LogPipeline`, which folds repetitive runs, and
+//! are then published on a broadcast channel rather than emitted, so each
+//! `stream_worker_log` subscriber takes them at its own pace.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::bounded::BufStats;
+use crate::logs::{LogLine, LogPipeline};
+use crate::sanitize;
+
+const CHANNEL_CAPACITY: usize = 1024;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(75);
+/// Cap on the payload bytes waiting in the channel. Log lines can be up to
+/// `worker::MAX_LINE_BYTES` each, so the entry cap alone isn't enough.
+const MAX_QUEUED_BYTES: usize = 8 * 1024 * 1024;
+
+pub(crate) enum WorkerEvent {
+    /// One line of worker stderr.
+    Log(String),
+    /// `PROGRESS:<job>:<done>/<total>` from worker stdout.
+    Progress(JobProgress),
+    /// A new file appeared in the output directory.
+    OutputFile(String),
+}
+
+impl WorkerEvent {
+    /// Strings cleaned for the webview (`sanitize`).
+    fn sanitized(self) -> Self {
+        match self {
+            WorkerEvent::Log(line) => WorkerEvent::Log(sanitize::line(&line)),
+            WorkerEvent::Progress(p) => WorkerEvent::Progress(JobProgress { job: sanitize::field(&p.job), ..p }),
+            WorkerEvent::OutputFile(path) => WorkerEvent::OutputFile(sanitize::field(&path)),
+        }
+    }
+
+    fn byte_size(&self) -> usize {
+        let payload = match self {
+            WorkerEvent::Log(line) => line.len(),
+            WorkerEvent::Progress(p) => p.job.len(),
+            WorkerEvent::OutputFile(path) => path.len(),
+        };
+        std::mem::size_of::<Self>() + payload
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct JobProgress {
+    pub job: String,
+    pub done: u64,
+    pub total: u64,
+    /// "paused" or "resumed" on the update where a transfer changes state;
+    /// absent otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<&'static str>,
+}
+
+impl JobProgress {
+    /// Parse the payload after the `PROGRESS:` prefix, e.g. `analyze:3/10`.
+    pub(crate) fn parse(payload: &str) -> Option<Self> {
+        let (job, counts) = payload.trim().rsplit_once(':')?;
+        let (done, total) = counts.split_once('/')?;
+        Some(JobProgress {
+            job: job.to_string(),
+            done: done.parse().ok()?,
+            total: total.parse().ok()?,
+            state: None,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicU64,
+    dropped: AtomicU64,
+    merged: AtomicU64,
+    coalesced: AtomicU64,
+    filtered: AtomicU64,
+    emitted: AtomicU64,
+    batches: AtomicU64,
+    queued_bytes: AtomicUsize,
+}
+
+/// Counters exposed through `get_worker_request_metrics`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct EventMetrics {
+    pub events_queued: u64,
+    pub events_dropped: u64,
+    pub progress_merged: u64,
+    pub logs_coalesced: u64,
+    /// Worker lines suppressed by `stderr_filter_patterns`.
+    pub log_lines_dropped: u64,
+    pub events_emitted: u64,
+    pub batches_flushed: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct EventBus {
+    tx: mpsc::Sender<WorkerEvent>,
+    counters: Arc<Counters>,
+}
+
+impl EventBus {
+    /// Create the channel and spawn the emitter task on the Tauri runtime.
+    /// Log lines go to `log_tx`.
+    pub(crate) fn start(app: AppHandle, logs: LogPipeline, log_tx: broadcast::Sender<String>) -> Self {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let counters = Arc::new(Counters::default());
+        tauri::async_runtime::spawn(run_emitter(app, rx, logs, log_tx, Arc::clone(&counters)));
+        EventBus { tx, counters }
+    }
+
+    /// A bus without an emitter task; events go to the returned receiver.
+    #[cfg(test)]
+    pub(crate) fn detached() -> (Self, mpsc::Receiver<WorkerEvent>) {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        (EventBus { tx, counters: Arc::new(Counters::default()) }, rx)
+    }
+
+    /// Queue an event without blocking. Safe to call from std threads.
+    pub(crate) fn send(&self, event: WorkerEvent) {
+        let event = event.sanitized();
+        let size = event.byte_size();
+        let before = self.counters.queued_bytes.fetch_add(size, Ordering::Relaxed);
+        if before + size > MAX_QUEUED_BYTES {
+            self.counters.queued_bytes.fetch_sub(size, Ordering::Relaxed);
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        match self.tx.try_send(event) {
+            Ok(()) => {
+                self.counters.queued.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.counters.queued_bytes.fetch_sub(size, Ordering::Relaxed);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Count a log line that was filtered out instead of sent.
+    pub(crate) fn count_filtered_log(&self) {
+        self.counters.filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fill level of the forwarding queue; drops count as evictions.
+    pub(crate) fn queue_stats(&self) -> BufStats {
+        BufStats {
+            entries: CHANNEL_CAPACITY - self.tx.capacity(),
+            bytes: self.counters.queued_bytes.load(Ordering::Relaxed),
+            max_entries: CHANNEL_CAPACITY,
+            max_bytes: MAX_QUEUED_BYTES,
+            evicted: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> EventMetrics {
+        let c = &self.counters;
+        EventMetrics {
+            events_queued: c.queued.load(Ordering::Relaxed),
+            events_dropped: c.dropped.load(Ordering::Relaxed),
+            progress_merged: c.merged.load(Ordering::Relaxed),
+            logs_coalesced: c.coalesced.load(Ordering::Relaxed),
+            log_lines_dropped: c.filtered.load(Ordering::Relaxed),
+            events_emitted: c.emitted.load(Ordering::Relaxed),
+            batches_flushed: c.batches.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    logs: Vec<LogLine>,
+    progress: Vec<JobProgress>,
+    outputs: Vec<String>,
+    merged: u64,
+    coalesced: u64,
+}
+
+impl Batch {
+    /// Add an event, collapsing progress updates for a job already in this
+    /// batch into its latest value and folding repetitive log lines.
+    fn push(&mut self, event: WorkerEvent, logs: &mut LogPipeline) {
+        match event {
+            WorkerEvent::Log(line) => {
+                if logs.push(&mut self.logs, line) {
+                    self.coalesced += 1;
+                }
+            }
+            WorkerEvent::OutputFile(path) => self.outputs.push(path),
+            WorkerEvent::Progress(p) => {
+                if let Some(existing) = self.progress.iter_mut().find(|e| e.job == p.job) {
+                    *existing = p;
+                    self.merged += 1;
+                } else {
+                    self.progress.push(p);
+                }
+            }
+        }
+    }
+
+    fn emit(self, app: &AppHandle, log_tx: &broadcast::Sender<String>) -> u64 {
+        let mut sent = 0;
+        // Sending only fails with no subscribers, when nobody is watching.
+        for entry in &self.logs {
+            let line = match entry.repeat {
+                1 => entry.line.clone(),
+                n => format!("{} (x{})", entry.line, n),
+            };
+            if log_tx.send(line).is_ok() {
+                sent += 1;
+            }
+        }
+        if !self.progress.is_empty() && app.emit("job-progress", &self.progress).is_ok() {
+            sent += self.progress.len() as u64;
+        }
+        if !self.outputs.is_empty() && app.emit("output-file-created", &self.outputs).is_ok() {
+            sent += self.outputs.len() as u64;
+        }
+        sent
+    }
+}
+
+async fn run_emitter(
+    app: AppHandle,
+    mut rx: mpsc::Receiver<WorkerEvent>,
+    mut logs: LogPipeline,
+    log_tx: broadcast::Sender<String>,
+    counters: Arc<Counters>,
+) {
+    // Wait for the first event, give the batch a short window to fill, then
+    // drain whatever arrived. Idle periods cost nothing.
+    while let Some(first) = rx.recv().await {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+
+        let mut batch = Batch::default();
+        counters.queued_bytes.fetch_sub(first.byte_size(), Ordering::Relaxed);
+        batch.push(first, &mut logs);
+        while let Ok(event) = rx.try_recv() {
+            counters.queued_bytes.fetch_sub(event.byte_size(), Ordering::Relaxed);
+            batch.push(event, &mut logs);
+        }
+        logs.finish_batch(&batch.logs);
+
+        counters.merged.fetch_add(batch.merged, Ordering::Relaxed);
+        counters.coalesced.fetch_add(batch.coalesced, Ordering::Relaxed);
+        let sent = batch.emit(&app, &log_tx);
+        counters.emitted.fetch_add(sent, Ordering::Relaxed);
+        counters.batches.fetch_add(1, Ordering::Relaxed);
+    }
+}
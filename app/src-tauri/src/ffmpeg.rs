@@ -0,0 +1,374 @@
+//! Small helpers that shell out to the resolved ffmpeg binary.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+
+/// Parse `Duration: HH:MM:SS.xx` from ffmpeg's stderr banner.
+fn parse_duration(banner: &str) -> Option<f64> {
+    let rest = banner.split("Duration:").nth(1)?;
+    let stamp = rest.split(',').next()?.trim();
+    let mut parts = stamp.split(':');
+    let h: f64 = parts.next()?.parse().ok()?;
+    let m: f64 = parts.next()?.parse().ok()?;
+    let s: f64 = parts.next()?.parse().ok()?;
+    Some(h * 3600.0 + m * 60.0 + s)
+}
+
+/// Demuxer names from `Input #0, mov,mp4,m4a,3gp,3g2,mj2, from '...'`.
+fn parse_input_formats(banner: &str) -> Vec<String> {
+    let Some(line) = banner.lines().find(|l| l.starts_with("Input #0, ")) else { return Vec::new() };
+    let rest = &line["Input #0, ".len()..];
+    let names = rest.split(" from ").next().unwrap_or_default();
+    names.split(',').map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect()
+}
+
+/// A real video stream, ignoring embedded cover art.
+fn parse_has_video(banner: &str) -> bool {
+    banner
+        .lines()
+        .any(|l| l.contains("Stream #") && l.contains("Video:") && !l.contains("(attached pic)"))
+}
+
+/// What `ffmpeg -i` says about a file.
+pub(crate) struct ProbeInfo {
+    pub formats: Vec<String>,
+    pub duration: Option<f64>,
+    pub has_video: bool,
+}
+
+/// Run `ffmpeg -i` on `input` without decoding anything.
+pub(crate) fn probe(ffmpeg: &str, input: &str) -> Option<ProbeInfo> {
+    // ffmpeg exits non-zero without an output file; the banner is all we need.
+    let out = Command::new(ffmpeg)
+        .args(["-hide_banner", "-i", input])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .ok()?;
+    let banner = String::from_utf8_lossy(&out.stderr);
+    let formats = parse_input_formats(&banner);
+    if formats.is_empty() {
+        return None;
+    }
+    Some(ProbeInfo {
+        formats,
+        duration: parse_duration(&banner),
+        has_video: parse_has_video(&banner),
+    })
+}
+
+/// Length of `input` in seconds, read from `ffmpeg -i` without decoding.
+pub(crate) fn probe_duration(ffmpeg: &str, input: &str) -> Option<f64> {
+    probe(ffmpeg, input)?.duration
+}
+
+/// Inputs `remux_for_streaming` accepts: MP4-family files are remuxed as
+/// they are, the rest have their audio encoded to AAC.
+const STREAMABLE_INPUTS: &[&str] = &["mov", "mp4", "m4a", "wav", "flac", "mp3", "ogg", "aiff"];
+
+/// Rewrite `input` as an MP4 (video) or M4A (audio) with the index at the
+/// front (`-movflags +faststart`) so it can start playing and seek before
+/// it has fully loaded. `on_progress` receives processed and total
+/// milliseconds. Returns the path of the new file next to `input`.
+pub(crate) fn remux_for_streaming(
+    ffmpeg: &str,
+    input: &Path,
+    hwaccel: Option<&str>,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<PathBuf, String> {
+    let input_str = input.to_string_lossy();
+    let info = probe(ffmpeg, &input_str).ok_or_else(|| format!("{}: not a media file ffmpeg can read", input.display()))?;
+    if !info.formats.iter().any(|f| STREAMABLE_INPUTS.contains(&f.as_str())) {
+        return Err(format!("unsupported container {} for {}", info.formats.join(","), input.display()));
+    }
+    let copyable = info.formats.iter().any(|f| f == "mp4" || f == "mov");
+    let ext = if info.has_video { "mp4" } else { "m4a" };
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let output = input.with_file_name(format!("{}.stream.{}", stem, ext));
+    // ffmpeg can't infer the muxer from `.part`, hence `-f mp4`.
+    let tmp = output.with_extension("part");
+
+    let build = |hwaccel: Option<&str>| {
+        let mut cmd = Command::new(ffmpeg);
+        cmd.args(["-hide_banner", "-nostats", "-loglevel", "error", "-y"]);
+        // A straight copy decodes nothing, so there is nothing to accelerate.
+        if let Some(hw) = hwaccel.filter(|_| !copyable) {
+            cmd.args(["-hwaccel", hw]);
+        }
+        cmd.arg("-i").arg(input);
+        if !info.has_video {
+            cmd.arg("-vn");
+        }
+        if copyable {
+            cmd.args(["-c", "copy"]);
+        } else {
+            cmd.args(["-c:a", "aac", "-b:a", "256k"]);
+        }
+        cmd.args(["-movflags", "+faststart", "-f", "mp4"]);
+        cmd
+    };
+    let total_ms = info.duration.map(|d| (d * 1000.0) as u64).unwrap_or(0);
+    run_decoding(build, hwaccel.filter(|_| !copyable), &tmp, total_ms, &mut on_progress)?;
+
+    let written = probe(ffmpeg, &tmp.to_string_lossy());
+    if !written.is_some_and(|w| w.formats.iter().any(|f| f == "mp4")) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("ffmpeg produced an unreadable file for {}", input.display()));
+    }
+    std::fs::rename(&tmp, &output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    on_progress(total_ms, total_ms);
+    Ok(output)
+}
+
+/// Stderr of a run that failed because the `-hwaccel` device couldn't be
+/// set up, as opposed to a problem with the input.
+fn hwaccel_failed(errors: &str) -> bool {
+    const MARKERS: &[&str] = &["hwaccel", "Device creation failed", "Failed setup for format", "No device available"];
+    MARKERS.iter().any(|m| errors.contains(m))
+}
+
+/// `run_with_progress` on `build(hwaccel)`, and again on `build(None)`
+/// (software decoding) if the hardware decoder couldn't be initialised.
+fn run_decoding(
+    build: impl Fn(Option<&str>) -> Command,
+    hwaccel: Option<&str>,
+    tmp: &Path,
+    total_ms: u64,
+    on_progress: &mut impl FnMut(u64, u64),
+) -> Result<(), String> {
+    let Some(hw) = hwaccel else { return run_with_progress(build(None), tmp, total_ms, on_progress) };
+    match run_with_progress(build(Some(hw)), tmp, total_ms, on_progress) {
+        Err(e) if hwaccel_failed(&e) => {
+            eprintln!("[djbot] ffmpeg -hwaccel {} unavailable, decoding in software: {}", hw, e);
+            run_with_progress(build(None), tmp, total_ms, on_progress)
+        }
+        result => result,
+    }
+}
+
+/// Finish `cmd` with `-progress` and the output file `tmp`, run it, and
+/// report processed and total milliseconds. `tmp` is removed if ffmpeg
+/// fails.
+fn run_with_progress(
+    mut cmd: Command,
+    tmp: &Path,
+    total_ms: u64,
+    on_progress: &mut impl FnMut(u64, u64),
+) -> Result<(), String> {
+    cmd.args(["-progress", "pipe:1"])
+        .arg(tmp)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+    // Drained on its own thread so a chatty stderr can't stall the progress pipe.
+    let stderr = child.stderr.take().map(|mut err| {
+        std::thread::spawn(move || {
+            let mut text = String::new();
+            let _ = err.read_to_string(&mut text);
+            text
+        })
+    });
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            // `out_time_ms` is in microseconds too, despite its name.
+            let us = line.strip_prefix("out_time_us=").or_else(|| line.strip_prefix("out_time_ms="));
+            if let Some(done_ms) = us.and_then(|v| v.trim().parse::<u64>().ok()).map(|us| us / 1000) {
+                on_progress(done_ms, total_ms.max(done_ms));
+            }
+        }
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    let errors = stderr.and_then(|t| t.join().ok()).unwrap_or_default();
+    if !status.success() {
+        let _ = std::fs::remove_file(tmp);
+        return Err(format!("ffmpeg failed ({}): {}", status, errors.trim()));
+    }
+    Ok(())
+}
+
+/// Write a copy of `input` into `output_dir` with `fade_in` seconds faded
+/// in from silence and `fade_out` seconds faded out at the end (ffmpeg's
+/// `afade`). The fade-out start comes from the probed duration, so both
+/// together must fit in the track. Audio is re-encoded in the input's
+/// format; `on_progress` gets processed and total milliseconds.
+pub(crate) fn apply_fade(
+    ffmpeg: &str,
+    input: &Path,
+    output_dir: &Path,
+    fade_in: f64,
+    fade_out: f64,
+    hwaccel: Option<&str>,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<PathBuf, String> {
+    if !fade_in.is_finite() || !fade_out.is_finite() || fade_in < 0.0 || fade_out < 0.0 {
+        return Err("fade durations must be zero or more seconds".to_string());
+    }
+    if fade_in == 0.0 && fade_out == 0.0 {
+        return Err("nothing to fade".to_string());
+    }
+    let info = probe(ffmpeg, &input.to_string_lossy())
+        .ok_or_else(|| format!("{}: not a media file ffmpeg can read", input.display()))?;
+    let duration = info.duration.ok_or_else(|| format!("{}: unknown duration", input.display()))?;
+    if fade_in + fade_out > duration {
+        return Err(format!(
+            "fades of {:.1}s in and {:.1}s out exceed the track length of {:.1}s",
+            fade_in, fade_out, duration
+        ));
+    }
+
+    let ext = input.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let output = output_dir.join(format!("{}.faded.{}", stem, ext));
+    // Keep the real extension last so ffmpeg still picks the muxer from it.
+    let tmp = output_dir.join(format!("{}.faded.part.{}", stem, ext));
+
+    let mut filters = Vec::new();
+    if fade_in > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={}", fade_in));
+    }
+    if fade_out > 0.0 {
+        filters.push(format!("afade=t=out:st={}:d={}", duration - fade_out, fade_out));
+    }
+    let filters = filters.join(",");
+    let build = |hwaccel: Option<&str>| {
+        let mut cmd = Command::new(ffmpeg);
+        cmd.args(["-hide_banner", "-nostats", "-loglevel", "error", "-y"]);
+        if let Some(hw) = hwaccel {
+            cmd.args(["-hwaccel", hw]);
+        }
+        cmd.arg("-i").arg(input).args(["-vn", "-map_metadata", "0", "-af"]).arg(&filters);
+        cmd
+    };
+    let total_ms = (duration * 1000.0) as u64;
+    run_decoding(build, hwaccel, &tmp, total_ms, &mut on_progress)?;
+
+    std::fs::rename(&tmp, &output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    on_progress(total_ms, total_ms);
+    Ok(output)
+}
+
+/// Extract `X.Y.Z` from the first line of `ffmpeg -version`
+/// (`ffmpeg version X.Y.Z Copyright ...`).
+fn parse_version(output: &str) -> Option<String> {
+    let first = output.lines().next()?;
+    let rest = first.trim().strip_prefix("ffmpeg version ")?;
+    rest.split_whitespace().next().map(str::to_string)
+}
+
+/// Version string reported by `ffmpeg -version`.
+pub(crate) fn version(ffmpeg: &str) -> Option<String> {
+    let out = Command::new(ffmpeg)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    parse_version(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// Method names listed after `Hardware acceleration methods:` in
+/// `ffmpeg -hwaccels` output.
+fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|l| !l.starts_with("Hardware acceleration methods:"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Hardware decoding methods this ffmpeg build was compiled with, e.g.
+/// `videotoolbox` or `cuda`. A listed method can still fail to initialise
+/// on a machine without the hardware. `None` if ffmpeg couldn't be run.
+pub(crate) fn hwaccels(ffmpeg: &str) -> Option<Vec<String>> {
+    let out = Command::new(ffmpeg)
+        .args(["-hide_banner", "-hwaccels"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(parse_hwaccels(&String::from_utf8_lossy(&out.stdout)))
+}
+
+/// An ffmpeg input device format that captures audio from the host.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CaptureBackend {
+    /// Value for ffmpeg's `-f`, e.g. `avfoundation`.
+    pub format: String,
+    /// Host audio system behind it, for display.
+    pub name: String,
+    /// Arguments that make ffmpeg list this backend's input devices.
+    pub list_devices_args: Vec<String>,
+}
+
+/// Capture formats we know how to enumerate: `-f` name, host audio system,
+/// and whether devices are listed with `-sources` (else `-list_devices`).
+const CAPTURE_FORMATS: &[(&str, &str, bool)] = &[
+    ("avfoundation", "CoreAudio", false),
+    ("dshow", "DirectShow", false),
+    ("alsa", "ALSA", true),
+    ("pulse", "PulseAudio", true),
+    ("jack", "JACK", false),
+    ("oss", "OSS", false),
+];
+
+/// Names of demuxing-capable devices in `ffmpeg -devices` output, whose
+/// rows look like ` D  alsa            ALSA audio output`.
+fn parse_input_devices(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|l| l.trim() != "---")
+        .skip(1)
+        .filter_map(|l| {
+            let mut cols = l.split_whitespace();
+            let flags = cols.next()?;
+            let name = cols.next()?;
+            flags.starts_with('D').then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// A PipeWire daemon is serving this session (`pulse` then talks to it).
+fn pipewire_running() -> bool {
+    cfg!(target_os = "linux")
+        && std::env::var_os("XDG_RUNTIME_DIR")
+            .is_some_and(|dir| Path::new(&dir).join("pipewire-0").exists())
+}
+
+/// Every audio capture backend this ffmpeg build supports, in preference
+/// order. `None` if ffmpeg couldn't be run.
+pub(crate) fn capture_backends(ffmpeg: &str) -> Option<Vec<CaptureBackend>> {
+    let out = Command::new(ffmpeg)
+        .args(["-hide_banner", "-devices"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let devices = parse_input_devices(&String::from_utf8_lossy(&out.stdout));
+    let backends = CAPTURE_FORMATS
+        .iter()
+        .filter(|(format, _, _)| devices.iter().any(|d| d == format))
+        .map(|&(format, name, sources)| {
+            let name = if format == "pulse" && pipewire_running() {
+                "PipeWire (PulseAudio)"
+            } else {
+                name
+            };
+            let list_devices_args = if sources {
+                vec!["-sources".to_string(), format.to_string()]
+            } else {
+                ["-f", format, "-list_devices", "true", "-i", ""].map(str::to_string).to_vec()
+            };
+            CaptureBackend { format: format.to_string(), name: name.to_string(), list_devices_args }
+        })
+        .collect();
+    Some(backends)
+}
@@ -0,0 +1,48 @@
+//! Mix export formats the worker can write, and which sample depths each
+//! lossless one supports.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Depths `set_output_bit_depth` accepts.
+pub(crate) const BIT_DEPTHS: [u8; 3] = [16, 24, 32];
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct OutputFormat {
+    pub extension: &'static str,
+    pub lossless: bool,
+    /// Empty for lossy formats, where the depth setting doesn't apply.
+    pub bit_depths: &'static [u8],
+}
+
+pub(crate) const OUTPUT_FORMATS: &[OutputFormat] = &[
+    OutputFormat { extension: "mp3", lossless: false, bit_depths: &[] },
+    OutputFormat { extension: "wav", lossless: true, bit_depths: &[16, 24, 32] },
+    OutputFormat { extension: "aiff", lossless: true, bit_depths: &[16, 24, 32] },
+    OutputFormat { extension: "flac", lossless: true, bit_depths: &[16, 24] },
+];
+
+pub(crate) fn validate_bit_depth(bits: u8) -> Result<(), String> {
+    if BIT_DEPTHS.contains(&bits) {
+        Ok(())
+    } else {
+        Err(format!("unsupported bit depth {}; expected 16, 24 or 32", bits))
+    }
+}
+
+/// Check that a mix written to `output_path` can be encoded at `bits`.
+/// Unknown extensions are left for the worker to reject.
+pub(crate) fn check_output_path(output_path: &str, bits: Option<u8>) -> Result<(), String> {
+    let Some(bits) = bits else { return Ok(()) };
+    let ext = Path::new(output_path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match OUTPUT_FORMATS.iter().find(|f| f.extension == ext) {
+        Some(format) if format.lossless && !format.bit_depths.contains(&bits) => {
+            Err(format!("{} output does not support {}-bit samples", ext, bits))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,53 @@
+//! Grants the fs plugin access to output files as the worker creates them,
+//! so the frontend can read results through `@tauri-apps/plugin-fs` without
+//! a static scope covering the whole output directory.
+
+use std::path::Path;
+
+use tauri::{AppHandle, Listener};
+use tauri_plugin_fs::FsExt;
+
+/// Listen for `output-file-created` batches and allow each new file.
+pub(crate) fn allow_new_outputs(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen("output-file-created", move |event| {
+        let paths: Vec<String> = match serde_json::from_str(event.payload()) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("[djbot] unexpected output-file-created payload: {}", e);
+                return;
+            }
+        };
+        let scope = handle.fs_scope();
+        for path in paths {
+            if let Err(e) = scope.allow_file(&path) {
+                eprintln!("[djbot] failed to allow {} in fs scope: {}", path, e);
+            }
+        }
+    });
+}
+
+/// Allow the files already in `output_dir`, written before this session's
+/// listener was there to see them.
+pub(crate) fn allow_existing_outputs(app: &AppHandle, output_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(output_dir) else { return };
+    let scope = app.fs_scope();
+    for path in entries.flatten().map(|e| e.path()).filter(|p| p.is_file()) {
+        if let Err(e) = scope.allow_file(&path) {
+            eprintln!("[djbot] failed to allow {} in fs scope: {}", path.display(), e);
+        }
+    }
+}
+
+/// Paths and patterns currently allowed by the fs scope, sorted.
+pub(crate) fn allowed_paths(app: &AppHandle) -> Vec<String> {
+    let mut paths: Vec<String> = app
+        .fs_scope()
+        .allowed_patterns()
+        .iter()
+        .map(|p| p.as_str().to_string())
+        .collect();
+    paths.sort();
+    paths
+}
+
@@ -0,0 +1,98 @@
+//! Open file handle count of the worker process, against its soft limit.
+//!
+//! A worker leaking descriptors over a long batch eventually fails every
+//! open with "too many open files"; watching the count lets us warn first.
+
+use serde::Serialize;
+
+/// Share of the soft limit at which `near_limit` is set.
+const WARN_FRACTION: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct HandleUsage {
+    pub pid: u32,
+    pub open: u64,
+    /// `None` where the OS has no practical per-process limit (Windows) or
+    /// it couldn't be read.
+    pub soft_limit: Option<u64>,
+    pub near_limit: bool,
+}
+
+/// Handle usage of `pid`, `None` if the count can't be read.
+pub(crate) fn usage(pid: u32) -> Option<HandleUsage> {
+    let open = open_handles(pid)?;
+    let soft_limit = soft_limit(pid).filter(|l| *l > 0);
+    let near_limit = soft_limit.is_some_and(|l| open as f64 >= l as f64 * WARN_FRACTION);
+    Some(HandleUsage { pid, open, soft_limit, near_limit })
+}
+
+#[cfg(target_os = "linux")]
+fn open_handles(pid: u32) -> Option<u64> {
+    Some(std::fs::read_dir(format!("/proc/{}/fd", pid)).ok()?.count() as u64)
+}
+
+/// `Max open files` soft value from `/proc/<pid>/limits`.
+#[cfg(target_os = "linux")]
+fn soft_limit(pid: u32) -> Option<u64> {
+    let limits = std::fs::read_to_string(format!("/proc/{}/limits", pid)).ok()?;
+    let line = limits.lines().find(|l| l.starts_with("Max open files"))?;
+    line["Max open files".len()..].split_whitespace().next()?.parse().ok()
+}
+
+/// One `f<fd>` line per descriptor in `lsof -F f` output.
+#[cfg(target_os = "macos")]
+fn open_handles(pid: u32) -> Option<u64> {
+    let out = std::process::Command::new("lsof")
+        .args(["-n", "-P", "-F", "f", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    let fds = text
+        .lines()
+        .filter(|l| l.strip_prefix('f').is_some_and(|fd| fd.chars().all(|c| c.is_ascii_digit())))
+        .count();
+    Some(fds as u64)
+}
+
+/// The worker inherits our `RLIMIT_NOFILE` and doesn't change it.
+#[cfg(target_os = "macos")]
+fn soft_limit(_pid: u32) -> Option<u64> {
+    let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+    // SAFETY: `limit` is a valid out-pointer.
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+    (limit.rlim_cur != libc::RLIM_INFINITY).then_some(limit.rlim_cur)
+}
+
+#[cfg(target_os = "windows")]
+fn open_handles(pid: u32) -> Option<u64> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let script = format!("(Get-Process -Id {}).HandleCount", pid);
+    let out = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+}
+
+#[cfg(target_os = "windows")]
+fn soft_limit(_pid: u32) -> Option<u64> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn open_handles(_pid: u32) -> Option<u64> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn soft_limit(_pid: u32) -> Option<u64> {
+    None
+}
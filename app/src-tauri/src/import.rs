@@ -0,0 +1,351 @@
+//! `djbot://import?url=<base64 https url>` deep links for sharing presets.
+//!
+//! The link is validated, the preset downloaded and parsed into a `Preset`,
+//! and then held as pending until the user confirms it in the UI (via the
+//! `config-import-requested` event and `confirm_config_import`). Only the
+//! fields in `Preset` are taken from it; they are laid over the user's own
+//! config.
+
+use std::sync::Mutex;
+
+use base64::Engine;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AudioEngineConfig, DeviceChangeBehavior, DjbotConfig, LogConfig};
+use crate::url_guard;
+
+const MAX_LINK_LEN: usize = 2048;
+const MAX_PRESET_BYTES: usize = 100 * 1024;
+
+/// The only host presets may be downloaded from, along with its
+/// subdomains. Hosts where anyone can publish a file (GitHub raw and gist
+/// content, paste sites) would let any web page hand out a working link.
+const ALLOWED_DOMAIN: &str = "djbot.app";
+
+/// The settings a preset may change, in the layout of `DjbotConfig`. Paths,
+/// security switches, network endpoints and secrets are left out on
+/// purpose: anything else in the file is ignored. Unset fields keep the
+/// user's value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Preset {
+    pub on_default_device_change: Option<DeviceChangeBehavior>,
+    pub worker: WorkerPreset,
+    pub logs: Option<LogConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct WorkerPreset {
+    pub audio: Option<AudioEngineConfig>,
+    pub output_bit_depth: Option<u8>,
+    pub stderr_filter_patterns: Option<Vec<String>>,
+}
+
+impl Preset {
+    /// `config` with the fields the preset sets replaced.
+    pub(crate) fn apply_to(&self, mut config: DjbotConfig) -> DjbotConfig {
+        if let Some(behavior) = self.on_default_device_change {
+            config.on_default_device_change = behavior;
+        }
+        if let Some(audio) = &self.worker.audio {
+            config.worker.audio = audio.clone();
+        }
+        if let Some(bits) = self.worker.output_bit_depth {
+            config.worker.output_bit_depth = Some(bits);
+        }
+        if let Some(patterns) = &self.worker.stderr_filter_patterns {
+            config.worker.stderr_filter_patterns = patterns.clone();
+        }
+        if let Some(logs) = &self.logs {
+            config.logs = logs.clone();
+        }
+        config
+    }
+}
+
+/// Payload of the `config-import-requested` event.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ImportRequest {
+    pub source: String,
+    pub preset: Preset,
+    /// Dotted keys of the settings accepting it would change.
+    pub changes: Vec<String>,
+}
+
+/// The settings `preset` would change in `current`, or why the result
+/// fails `DjbotConfig::validate`.
+pub(crate) fn preview(preset: &Preset, current: &DjbotConfig) -> Result<Vec<String>, String> {
+    let applied = preset.apply_to(current.clone());
+    applied.validate().map_err(|e| format!("invalid preset: {}", e))?;
+    Ok(current.differences(&applied))
+}
+
+/// The preset waiting for user confirmation, if any.
+#[derive(Default)]
+pub(crate) struct PendingImport(pub Mutex<Option<ImportRequest>>);
+
+fn host_allowed(host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    host == ALLOWED_DOMAIN || host.strip_suffix(ALLOWED_DOMAIN).is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// Extract and validate the preset URL from a `djbot://import` link.
+pub(crate) fn parse_import_link(link: &Url) -> Result<Url, String> {
+    if link.as_str().len() > MAX_LINK_LEN {
+        return Err(format!("import link longer than {} characters", MAX_LINK_LEN));
+    }
+    if link.scheme() != "djbot" || link.host_str() != Some("import") {
+        return Err(format!("not an import link: {}", link));
+    }
+    let encoded = link
+        .query_pairs()
+        .find(|(k, _)| k == "url")
+        .map(|(_, v)| v.into_owned())
+        .ok_or("import link has no url parameter")?;
+
+    // Accept both the standard and URL-safe base64 alphabets, padded or not.
+    // A `+` left unescaped in the link comes out of the query as a space.
+    let encoded = encoded.replace(' ', "+");
+    let trimmed = encoded.trim_end_matches('=');
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(trimmed)
+        .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(trimmed))
+        .map_err(|_| "import url is not valid base64".to_string())?;
+    let decoded = String::from_utf8(bytes).map_err(|_| "import url is not valid UTF-8".to_string())?;
+
+    let url = Url::parse(&decoded).map_err(|e| format!("invalid import url: {}", e))?;
+    check_preset_url(&url)?;
+    Ok(url)
+}
+
+/// A preset (or a redirect while fetching one) must be https from an
+/// allowed host.
+fn check_preset_url(url: &Url) -> Result<(), String> {
+    if url.scheme() != "https" {
+        return Err("presets can only be imported over https".to_string());
+    }
+    url_guard::check_literal_host(url, false)?;
+    match url.host_str() {
+        Some(host) if host_allowed(host) => Ok(()),
+        Some(host) => Err(format!("presets from {} are not allowed", host)),
+        None => Err("import url has no host".to_string()),
+    }
+}
+
+/// Follow at most a few redirects, each held to `check_preset_url`, so an
+/// allowed host can't bounce the download somewhere else.
+pub(crate) fn redirect_policy() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= 5 {
+            return attempt.error("too many redirects");
+        }
+        match check_preset_url(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => attempt.error(e),
+        }
+    })
+}
+
+/// Download the preset at `url`, refusing bodies over `MAX_PRESET_BYTES`.
+pub(crate) async fn fetch_preset(client: &reqwest::Client, url: Url) -> Result<Preset, String> {
+    let mut resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("preset download failed: {}", resp.status()));
+    }
+    if resp.content_length().is_some_and(|len| len as usize > MAX_PRESET_BYTES) {
+        return Err("preset is larger than 100 KB".to_string());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await.map_err(|e| e.to_string())? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_PRESET_BYTES {
+            return Err("preset is larger than 100 KB".to_string());
+        }
+    }
+    parse_preset(&body)
+}
+
+/// Parse a preset as JSON, or as TOML if it isn't JSON.
+fn parse_preset(body: &[u8]) -> Result<Preset, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "preset is not valid UTF-8".to_string())?;
+    if text.trim_start().starts_with('{') {
+        serde_json::from_str(text).map_err(|e| format!("invalid JSON preset: {}", e))
+    } else {
+        toml::from_str(text).map_err(|e| format!("invalid TOML preset: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+
+    const PRESET_URL: &str = "https://presets.djbot.app/techno?v=>>";
+
+    fn link(encoded: &str) -> Result<Url, String> {
+        parse_import_link(&Url::parse(&format!("djbot://import?url={}", encoded)).unwrap())
+    }
+
+    /// Serve one request on a local port with `head` and then `body`.
+    fn stub_server(head: String, body: Vec<u8>) -> Url {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/preset.toml", listener.local_addr().unwrap())).unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    return;
+                }
+            }
+            // The client hangs up once it has seen enough.
+            let stream = reader.get_mut();
+            let _ = stream.write_all(head.as_bytes()).and_then(|()| stream.write_all(&body));
+        });
+        url
+    }
+
+    fn fetch(url: Url) -> Result<Preset, String> {
+        let client = reqwest::Client::builder().redirect(redirect_policy()).build().unwrap();
+        tauri::async_runtime::block_on(fetch_preset(&client, url))
+    }
+
+    #[test]
+    fn both_base64_alphabets_are_accepted_with_or_without_padding() {
+        let standard = base64::engine::general_purpose::STANDARD.encode(PRESET_URL);
+        let url_safe = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(PRESET_URL);
+        assert!(standard.contains(['+', '/']) && standard.ends_with('='));
+        let expected = Url::parse(PRESET_URL).unwrap();
+        for encoded in [
+            url_safe.clone(),
+            format!("{}==", url_safe),
+            standard.clone(),
+            standard.replace('+', "%2B").replace('/', "%2F").replace('=', "%3D"),
+            standard.trim_end_matches('=').to_string(),
+        ] {
+            assert_eq!(link(&encoded), Ok(expected.clone()), "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn malformed_links_are_refused() {
+        assert!(link("!!!").unwrap_err().contains("base64"));
+        let not_utf8 = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode([0xff, 0xfe]);
+        assert!(link(&not_utf8).unwrap_err().contains("UTF-8"));
+        assert!(parse_import_link(&Url::parse("djbot://import?other=1").unwrap()).is_err());
+        assert!(parse_import_link(&Url::parse("djbot://export?url=aHR0cHM6").unwrap()).is_err());
+        assert!(parse_import_link(&Url::parse("https://import?url=aHR0cHM6").unwrap()).is_err());
+    }
+
+    #[test]
+    fn over_length_links_are_refused() {
+        let long = format!("{}?{}", PRESET_URL, "a".repeat(MAX_LINK_LEN));
+        let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(long);
+        assert!(link(&encoded).unwrap_err().contains("longer than"));
+    }
+
+    #[test]
+    fn preset_urls_must_be_https_from_djbot_app() {
+        let check = |url: &str| check_preset_url(&Url::parse(url).unwrap());
+        assert!(check("https://presets.djbot.app/set.toml").is_ok());
+        assert!(check("http://presets.djbot.app/set.toml").unwrap_err().contains("https"));
+        assert!(check("https://raw.githubusercontent.com/x/y/main/set.toml").is_err());
+        assert!(check("https://127.0.0.1/set.toml").is_err());
+        assert!(check("https://[::1]/set.toml").is_err());
+        let http = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("http://djbot.app/set.toml");
+        assert!(link(&http).is_err());
+    }
+
+    #[test]
+    fn redirects_are_held_to_the_same_rules() {
+        for target in ["https://raw.githubusercontent.com/x/y/main/set.toml", "http://presets.djbot.app/set.toml"] {
+            let head = format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n", target);
+            let err = fetch(stub_server(head, Vec::new())).unwrap_err();
+            assert!(err.contains("redirect") || err.contains("preset"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn bodies_over_100_kb_are_refused() {
+        let at_limit = format!("# {}\n", "x".repeat(MAX_PRESET_BYTES - 3)).into_bytes();
+        assert_eq!(at_limit.len(), MAX_PRESET_BYTES);
+        let head = |len: usize| format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len);
+        assert!(fetch(stub_server(head(at_limit.len()), at_limit)).is_ok());
+
+        let over = vec![b'#'; MAX_PRESET_BYTES + 1];
+        assert!(fetch(stub_server(head(over.len()), over)).unwrap_err().contains("100 KB"));
+
+        // Without a Content-Length the body is cut off while streaming.
+        let mut chunked = Vec::new();
+        for _ in 0..4 {
+            chunked.extend_from_slice(format!("{:x}\r\n{}\r\n", 32 * 1024, "#".repeat(32 * 1024)).as_bytes());
+        }
+        chunked.extend_from_slice(b"0\r\n\r\n");
+        let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_string();
+        assert!(fetch(stub_server(head, chunked)).unwrap_err().contains("100 KB"));
+    }
+
+    #[test]
+    fn presets_failing_validation_are_refused_before_confirmation() {
+        let current = DjbotConfig::default();
+        for body in [&b"[worker.audio]\nsample_rate = 12345\n"[..], b"[worker]\noutput_bit_depth = 7\n"] {
+            let preset = parse_preset(body).unwrap();
+            assert!(preview(&preset, &current).unwrap_err().starts_with("invalid preset"));
+        }
+        let preset = parse_preset(br#"{"worker": {"output_bit_depth": 24}}"#).unwrap();
+        assert_eq!(preview(&preset, &current), Ok(vec!["worker.output_bit_depth".to_string()]));
+        assert!(parse_preset(b"worker = 3").is_err());
+        assert!(parse_preset(b"{ not json").is_err());
+    }
+
+    #[test]
+    fn only_djbot_app_and_its_subdomains_are_allowed() {
+        for host in ["djbot.app", "presets.djbot.app", "PRESETS.DJBOT.APP"] {
+            assert!(host_allowed(host), "{} refused", host);
+        }
+        for host in ["raw.githubusercontent.com", "gist.githubusercontent.com", "evildjbot.app", "djbot.app.evil.example"] {
+            assert!(!host_allowed(host), "{} allowed", host);
+        }
+    }
+
+    #[test]
+    fn preset_cannot_touch_paths_security_or_secrets() {
+        let hostile = br#"{
+            "debug_mode": true,
+            "worker": {
+                "ffmpeg_path_override": "/tmp/evil",
+                "ca_bundle": "/tmp/evil.pem",
+                "allow_private_urls": true,
+                "bind_address": "0.0.0.0",
+                "extra_args": ["--evil"],
+                "output_bit_depth": 24
+            },
+            "output": { "dir_mode": 511 },
+            "network": { "probe_url": "https://evil.example" },
+            "webhook": { "url": null, "secret": null }
+        }"#;
+        let mut mine = DjbotConfig::default();
+        mine.webhook.url = Some("sealed-url".to_string());
+        mine.webhook.secret = Some("sealed-secret".to_string());
+        mine.worker.download_proxy = Some("sealed-proxy".to_string());
+
+        let applied = parse_preset(hostile).unwrap().apply_to(mine.clone());
+        assert_eq!(applied.differences(&mine), ["worker.output_bit_depth"]);
+    }
+
+    #[test]
+    fn unset_preset_fields_keep_the_users_values() {
+        let mut mine = DjbotConfig::default();
+        mine.worker.output_bit_depth = Some(32);
+        mine.worker.stderr_filter_patterns = vec!["^noise".to_string()];
+
+        let preset = parse_preset(b"[worker.audio]\nsample_rate = 48000\n").unwrap();
+        let applied = preset.apply_to(mine.clone());
+        assert_eq!(applied.worker.audio.sample_rate, Some(48_000));
+        assert_eq!(applied.differences(&mine), ["worker.audio.sample_rate"]);
+    }
+}
@@ -0,0 +1,99 @@
+//! Checks on files offered for import before they are uploaded to the
+//! worker: size limits, an extension allowlist, and magic-byte sniffing of
+//! the first `SNIFF_BYTES` so a renamed executable or archive is refused
+//! whatever it is called. Only that prefix is read, however large the file.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config::ImportConfig;
+
+/// Bytes read from the start of a file to identify its content.
+const SNIFF_BYTES: u64 = 8 * 1024;
+const MIB: u64 = 1024 * 1024;
+
+/// Why a file was refused, for the UI to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RejectReason {
+    /// Outside the locations the app may read.
+    NotAllowed,
+    NotAFile,
+    Unreadable,
+    TooLarge,
+    /// Extension not in `import.extensions`.
+    Extension,
+    /// The content isn't a format we recognise.
+    UnrecognisedContent,
+    /// The content is recognisably something other than audio or video.
+    ContentMismatch,
+    /// Passed the checks but the worker didn't take it.
+    UploadFailed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Rejection {
+    pub path: String,
+    pub reason: RejectReason,
+    pub detail: String,
+}
+
+impl Rejection {
+    pub(crate) fn new(path: &str, reason: RejectReason, detail: impl Into<String>) -> Self {
+        Rejection { path: path.to_string(), reason, detail: detail.into() }
+    }
+}
+
+/// A file that passed `check_file`, with its size.
+pub(crate) struct Admitted {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Validate one file against `config`. `input` is what the caller named
+/// the file, for the rejection.
+pub(crate) fn check_file(input: &str, path: &Path, config: &ImportConfig) -> Result<Admitted, Rejection> {
+    let reject = |reason, detail: String| Rejection::new(input, reason, detail);
+    let meta = std::fs::metadata(path).map_err(|e| reject(RejectReason::Unreadable, e.to_string()))?;
+    if !meta.is_file() {
+        return Err(reject(RejectReason::NotAFile, "not a regular file".to_string()));
+    }
+    if meta.len() > config.max_file_mb * MIB {
+        return Err(reject(
+            RejectReason::TooLarge,
+            format!("{} MB is over the {} MB limit", meta.len() / MIB, config.max_file_mb),
+        ));
+    }
+    let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    if !config.extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(&ext)) {
+        return Err(reject(RejectReason::Extension, format!(".{} files can't be imported", ext)));
+    }
+    let mut prefix = Vec::with_capacity(SNIFF_BYTES as usize);
+    std::fs::File::open(path)
+        .and_then(|f| f.take(SNIFF_BYTES).read_to_end(&mut prefix))
+        .map_err(|e| reject(RejectReason::Unreadable, e.to_string()))?;
+    match infer::get(&prefix) {
+        None => Err(reject(RejectReason::UnrecognisedContent, "content is not a recognised audio format".to_string())),
+        Some(kind) if matches!(kind.matcher_type(), infer::MatcherType::Audio | infer::MatcherType::Video) => {
+            Ok(Admitted { path: path.to_path_buf(), size: meta.len() })
+        }
+        Some(kind) => Err(reject(RejectReason::ContentMismatch, format!("content is {}, not audio", kind.mime_type()))),
+    }
+}
+
+/// Refuse a batch whose admitted files add up to more than
+/// `import.max_batch_mb`, or more than is free at `dest`, before any of it
+/// is uploaded.
+pub(crate) fn check_batch(total: u64, config: &ImportConfig, dest: &Path) -> Result<(), String> {
+    if total > config.max_batch_mb * MIB {
+        return Err(format!("import of {} MB is over the {} MB batch limit", total / MIB, config.max_batch_mb));
+    }
+    if let Some(free) = crate::disk::free_space(dest) {
+        if total > free {
+            return Err(format!("insufficient space: import needs {} MB, {} MB free", total / MIB, free / MIB));
+        }
+    }
+    Ok(())
+}
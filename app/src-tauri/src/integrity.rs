@@ -0,0 +1,353 @@
+//! Sidecar integrity verification against a `manifest.json` in the resource dir.
+//!
+//! Manifest format:
+//!
+//! ```json
+//! {
+//!   "sidecars": { "goworker-x86_64-pc-windows-msvc.exe": "<sha256 hex>", ... },
+//!   "signature": "<ed25519 signature hex>",
+//!   "resources": { "ffmpeg/ffmpeg.exe": "<sha256 hex>", ... },
+//!   "resources_signature": "<ed25519 signature hex>"
+//! }
+//! ```
+//!
+//! `signature` covers the compact JSON encoding of `sidecars` with keys in
+//! sorted order, `resources_signature` the same encoding of `resources`
+//! (paths relative to the resource dir). When the build embeds a public key
+//! (`DJBOT_MANIFEST_PUBKEY`, hex, set at compile time) the signatures are
+//! mandatory; otherwise they are ignored and only the hashes are checked.
+//!
+//! `check_install` checks everything listed at startup; resources can be
+//! restored from `repair.tar.gz` in the resource dir by `repair`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_PUBKEY: Option<&str> = option_env!("DJBOT_MANIFEST_PUBKEY");
+/// Pristine copies of the manifest's `resources`, at the same relative paths.
+const REPAIR_ARCHIVE: &str = "repair.tar.gz";
+
+#[derive(Deserialize)]
+struct Manifest {
+    sidecars: BTreeMap<String, String>,
+    signature: Option<String>,
+    #[serde(default)]
+    resources: BTreeMap<String, String>,
+    resources_signature: Option<String>,
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Identity of a binary on disk, recorded when the worker is spawned.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct BinaryFingerprint {
+    pub sha256: String,
+    /// Modification time, seconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+pub(crate) fn fingerprint(path: &Path) -> std::io::Result<BinaryFingerprint> {
+    let modified = std::fs::metadata(path)?
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    Ok(BinaryFingerprint { sha256: sha256_file(path)?, modified })
+}
+
+/// SHA-256 of the file at `path`, lowercase hex.
+pub(crate) fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn verify_signature(entries: &BTreeMap<String, String>, signature: Option<&str>, pubkey_hex: &str) -> Result<(), String> {
+    let key_bytes: [u8; 32] = decode_hex(pubkey_hex)
+        .and_then(|b| b.try_into().ok())
+        .ok_or("embedded manifest public key is malformed")?;
+    let key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| format!("embedded manifest public key is invalid: {}", e))?;
+
+    let sig_hex = signature.ok_or("manifest is not signed")?;
+    let sig_bytes: [u8; 64] = decode_hex(sig_hex)
+        .and_then(|b| b.try_into().ok())
+        .ok_or("manifest signature is malformed")?;
+
+    let payload = serde_json::to_vec(entries).map_err(|e| e.to_string())?;
+    key.verify(&payload, &Signature::from_bytes(&sig_bytes))
+        .map_err(|_| "manifest signature does not match".to_string())
+}
+
+/// Verify `sidecar` against the manifest entry for `worker_name`.
+///
+/// Returns `Ok(false)` when there is no manifest to check against (dev builds,
+/// or bundles that predate the manifest). Any other problem — unreadable or
+/// unsigned manifest, missing entry, hash mismatch — is an error and the
+/// worker must not be started.
+pub(crate) fn verify_sidecar(resource_dir: &Path, worker_name: &str, sidecar: &Path) -> Result<bool, String> {
+    let Some(manifest) = read_manifest(resource_dir)? else { return Ok(false) };
+    if let Some(pubkey) = MANIFEST_PUBKEY {
+        verify_signature(&manifest.sidecars, manifest.signature.as_deref(), pubkey)?;
+    }
+
+    let expected = manifest
+        .sidecars
+        .get(worker_name)
+        .ok_or_else(|| format!("manifest has no entry for {}", worker_name))?;
+    let actual = sha256_file(sidecar)
+        .map_err(|e| format!("cannot hash {}: {}", sidecar.display(), e))?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(format!(
+            "{} does not match the manifest (expected sha256 {}, got {})",
+            sidecar.display(),
+            expected,
+            actual
+        ));
+    }
+    Ok(true)
+}
+
+/// The manifest in `resource_dir`, `None` if there is none.
+fn read_manifest(resource_dir: &Path) -> Result<Option<Manifest>, String> {
+    let manifest_path = resource_dir.join(MANIFEST_FILE);
+    let bytes = match std::fs::read(&manifest_path) {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot read {}: {}", manifest_path.display(), e)),
+    };
+    serde_json::from_slice(&bytes)
+        .map(Some)
+        .map_err(|e| format!("{} is malformed: {}", manifest_path.display(), e))
+}
+
+/// `resources` with both signatures checked (when a key is embedded) and
+/// entries that would escape the resource dir refused.
+fn verified_resources(manifest: &Manifest) -> Result<&BTreeMap<String, String>, String> {
+    if let Some(pubkey) = MANIFEST_PUBKEY {
+        verify_signature(&manifest.sidecars, manifest.signature.as_deref(), pubkey)?;
+        if !manifest.resources.is_empty() {
+            verify_signature(&manifest.resources, manifest.resources_signature.as_deref(), pubkey)?;
+        }
+    }
+    for name in manifest.resources.keys() {
+        if !Path::new(name).components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("manifest resource {:?} is not a plain relative path", name));
+        }
+    }
+    Ok(&manifest.resources)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FileState {
+    Ok,
+    Missing,
+    Mismatch,
+    Unreadable,
+}
+
+/// One file listed in the manifest.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct FileIntegrity {
+    /// Sidecar name or path relative to the resource dir.
+    pub name: String,
+    pub state: FileState,
+    /// `repair` can restore it from the bundled archive.
+    pub repairable: bool,
+}
+
+/// Result of checking the installed files against the manifest.
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct InstallIntegrity {
+    /// Unix time in seconds.
+    pub checked_at: u64,
+    /// False for dev builds and bundles without a manifest; nothing is
+    /// checked then.
+    pub manifest_found: bool,
+    /// The manifest itself could not be trusted; `files` is empty.
+    pub error: Option<String>,
+    pub files: Vec<FileIntegrity>,
+}
+
+impl InstallIntegrity {
+    pub(crate) fn healthy(&self) -> bool {
+        self.error.is_none() && self.files.iter().all(|f| f.state == FileState::Ok)
+    }
+}
+
+/// Managed state: the last `check_install` report, `None` until it ran.
+#[derive(Clone, Default)]
+pub(crate) struct InstallCheck(pub Arc<Mutex<Option<InstallIntegrity>>>);
+
+impl InstallCheck {
+    pub(crate) fn report(&self) -> Option<InstallIntegrity> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, report: InstallIntegrity) {
+        *self.0.lock().unwrap() = Some(report);
+    }
+}
+
+fn file_state(path: &Path, expected: &str) -> FileState {
+    match sha256_file(path) {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected.trim()) => FileState::Ok,
+        Ok(_) => FileState::Mismatch,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => FileState::Missing,
+        Err(_) => FileState::Unreadable,
+    }
+}
+
+/// Paths (relative, `/`-separated) of the files in the repair archive.
+fn archive_entries(resource_dir: &Path) -> HashSet<String> {
+    let Ok(file) = File::open(resource_dir.join(REPAIR_ARCHIVE)) else { return HashSet::new() };
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let Ok(entries) = archive.entries() else { return HashSet::new() };
+    entries
+        .flatten()
+        .filter_map(|e| e.path().ok().map(|p| p.to_string_lossy().replace('\\', "/")))
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Hash the worker binary and every manifest resource. Blocking; reads
+/// every listed file in full.
+pub(crate) fn check_install(resource_dir: &Path, worker_name: &str, sidecar: &Path) -> InstallIntegrity {
+    let mut report = InstallIntegrity { checked_at: unix_now(), ..Default::default() };
+    let manifest = match read_manifest(resource_dir) {
+        Ok(Some(m)) => m,
+        Ok(None) => return report,
+        Err(e) => {
+            report.manifest_found = true;
+            report.error = Some(e);
+            return report;
+        }
+    };
+    report.manifest_found = true;
+    let resources = match verified_resources(&manifest) {
+        Ok(r) => r,
+        Err(e) => {
+            report.error = Some(e);
+            return report;
+        }
+    };
+    // The sidecar sits next to the executable, outside the resource dir,
+    // so only a reinstall can restore it.
+    if let Some(expected) = manifest.sidecars.get(worker_name) {
+        report.files.push(FileIntegrity {
+            name: worker_name.to_string(),
+            state: file_state(sidecar, expected),
+            repairable: false,
+        });
+    }
+    let in_archive = archive_entries(resource_dir);
+    for (name, expected) in resources {
+        report.files.push(FileIntegrity {
+            name: name.clone(),
+            state: file_state(&resource_dir.join(name), expected),
+            repairable: in_archive.contains(name),
+        });
+    }
+    report
+}
+
+/// What `repair` managed.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RepairOutcome {
+    pub repaired: Vec<String>,
+    /// Broken files that could not be restored; the app must be reinstalled.
+    pub reinstall_required: Vec<String>,
+    /// Fresh check after the repair.
+    pub integrity: InstallIntegrity,
+}
+
+/// Restore the broken repairable resources in `report` from the repair
+/// archive, verifying each against the manifest before it replaces the
+/// original. Blocking.
+pub(crate) fn repair(resource_dir: &Path, worker_name: &str, sidecar: &Path, report: &InstallIntegrity) -> Result<RepairOutcome, String> {
+    let manifest = read_manifest(resource_dir)?.ok_or("no manifest to repair against")?;
+    let resources = verified_resources(&manifest)?;
+    let wanted: HashSet<&str> = report
+        .files
+        .iter()
+        .filter(|f| f.state != FileState::Ok && f.repairable)
+        .map(|f| f.name.as_str())
+        .collect();
+
+    let mut repaired = Vec::new();
+    if !wanted.is_empty() {
+        let archive_path = resource_dir.join(REPAIR_ARCHIVE);
+        let file = File::open(&archive_path).map_err(|e| format!("{}: {}", archive_path.display(), e))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        for entry in archive.entries().map_err(|e| e.to_string())? {
+            let mut entry = entry.map_err(|e| e.to_string())?;
+            let name = entry.path().map_err(|e| e.to_string())?.to_string_lossy().replace('\\', "/");
+            let (Some(&name), Some(expected)) = (wanted.get(name.as_str()), resources.get(&name)) else { continue };
+            let dest = resource_dir.join(name);
+            let tmp = PathBuf::from(format!("{}.part", dest.display()));
+            let restored = (|| {
+                if let Some(parent) = dest.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                entry.unpack(&tmp).map_err(|e| e.to_string())?;
+                if file_state(&tmp, expected) != FileState::Ok {
+                    return Err("archived copy does not match the manifest".to_string());
+                }
+                std::fs::rename(&tmp, &dest).map_err(|e| e.to_string())
+            })();
+            match restored {
+                Ok(()) => repaired.push(name.to_string()),
+                Err(e) => {
+                    let _ = std::fs::remove_file(&tmp);
+                    eprintln!("[djbot] could not restore {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    let integrity = check_install(resource_dir, worker_name, sidecar);
+    let reinstall_required = integrity
+        .files
+        .iter()
+        .filter(|f| f.state != FileState::Ok)
+        .map(|f| f.name.clone())
+        .collect();
+    Ok(RepairOutcome { repaired, reinstall_required, integrity })
+}
@@ -0,0 +1,258 @@
+//! Every job the worker ran, for invoicing and library bookkeeping.
+//!
+//! Unlike `jobs::JobHistory`, which keeps just enough successful analyses
+//! to fit the duration model, this records each job request that went
+//! through the proxy (succeeded, failed or timed out) with its inputs,
+//! output and error code. Entries live in `{data_dir}/job_log.json`,
+//! oldest first, capped at `history.max_entries` with the oldest dropped.
+//!
+//! Exports write timestamps as RFC 3339 in local time; the stored entries
+//! and `get_job_history` keep Unix seconds like the rest of the app.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{Local, SecondsFormat, TimeZone};
+use serde::{Deserialize, Serialize};
+
+const LOG_FILE: &str = "job_log.json";
+/// Largest page `query` returns.
+const MAX_PAGE: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct JobLogEntry {
+    pub job_id: String,
+    /// Worker route, e.g. "analyze" or "render/mix".
+    pub kind: String,
+    /// Input files (or URL) the job was given.
+    pub sources: Vec<String>,
+    pub output_file: Option<String>,
+    /// Extension of `output_file`.
+    pub format: Option<String>,
+    pub duration_ms: u64,
+    /// Unix seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// "succeeded", "failed" or "timed_out", as in `job-finished`.
+    pub outcome: String,
+    /// `ErrorCode` string when the job didn't succeed.
+    pub error_code: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Which entries `get_job_history` and `export_job_history` cover. Every
+/// field left out matches everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct JobLogFilter {
+    pub kind: Option<String>,
+    pub outcome: Option<String>,
+    /// Unix seconds; entries started at or after.
+    pub since: Option<u64>,
+    /// Unix seconds; entries started before.
+    pub until: Option<u64>,
+}
+
+impl JobLogFilter {
+    fn matches(&self, entry: &JobLogEntry) -> bool {
+        self.kind.as_ref().is_none_or(|k| *k == entry.kind)
+            && self.outcome.as_ref().is_none_or(|o| *o == entry.outcome)
+            && self.since.is_none_or(|t| entry.started_at >= t)
+            && self.until.is_none_or(|t| entry.started_at < t)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct Pagination {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination { offset: 0, limit: 50 }
+    }
+}
+
+/// One page of `get_job_history`, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct JobLogPage {
+    pub entries: Vec<JobLogEntry>,
+    /// Entries matching the filter across all pages.
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// An entry as exported: local RFC 3339 timestamps instead of Unix seconds.
+#[derive(Debug, Serialize)]
+struct ExportRow<'a> {
+    job_id: &'a str,
+    kind: &'a str,
+    sources: &'a [String],
+    output_file: Option<&'a str>,
+    format: Option<&'a str>,
+    duration_ms: u64,
+    started_at: String,
+    finished_at: String,
+    outcome: &'a str,
+    error_code: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+const CSV_HEADER: &[&str] = &[
+    "job_id",
+    "kind",
+    "sources",
+    "output_file",
+    "format",
+    "duration_ms",
+    "started_at",
+    "finished_at",
+    "outcome",
+    "error_code",
+    "error",
+];
+
+impl<'a> ExportRow<'a> {
+    fn new(entry: &'a JobLogEntry) -> Self {
+        ExportRow {
+            job_id: &entry.job_id,
+            kind: &entry.kind,
+            sources: &entry.sources,
+            output_file: entry.output_file.as_deref(),
+            format: entry.format.as_deref(),
+            duration_ms: entry.duration_ms,
+            started_at: rfc3339_local(entry.started_at),
+            finished_at: rfc3339_local(entry.finished_at),
+            outcome: &entry.outcome,
+            error_code: entry.error_code.as_deref(),
+            error: entry.error.as_deref(),
+        }
+    }
+
+    /// Fields in `CSV_HEADER` order; several sources are joined with "; ".
+    fn csv_fields(&self) -> [String; 11] {
+        let opt = |v: Option<&str>| v.unwrap_or_default().to_string();
+        [
+            self.job_id.to_string(),
+            self.kind.to_string(),
+            self.sources.join("; "),
+            opt(self.output_file),
+            opt(self.format),
+            self.duration_ms.to_string(),
+            self.started_at.clone(),
+            self.finished_at.clone(),
+            self.outcome.to_string(),
+            opt(self.error_code),
+            opt(self.error),
+        ]
+    }
+}
+
+fn rfc3339_local(unix_secs: u64) -> String {
+    Local
+        .timestamp_opt(unix_secs as i64, 0)
+        .earliest()
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, false))
+        .unwrap_or_default()
+}
+
+/// RFC 4180: quoted when it holds a comma, quote or line break, with
+/// quotes doubled.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[&JobLogEntry]) -> String {
+    let mut out = CSV_HEADER.join(",");
+    out.push_str("\r\n");
+    for entry in entries {
+        let fields = ExportRow::new(entry).csv_fields();
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&line.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Managed state guarding `{data_dir}/job_log.json`.
+pub(crate) struct JobLog {
+    path: PathBuf,
+    entries: Mutex<Vec<JobLogEntry>>,
+}
+
+impl JobLog {
+    pub(crate) fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(LOG_FILE);
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("[djbot] ignoring unreadable {}: {}", LOG_FILE, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        JobLog { path, entries: Mutex::new(entries) }
+    }
+
+    fn save(&self, entries: &[JobLogEntry]) -> Result<(), String> {
+        let json = serde_json::to_vec(entries).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+
+    /// Add `entry`, dropping the oldest beyond `max_entries`.
+    pub(crate) fn record(&self, entry: JobLogEntry, max_entries: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(entry);
+        let excess = entries.len().saturating_sub(max_entries);
+        entries.drain(..excess);
+        if let Err(e) = self.save(&entries) {
+            eprintln!("[djbot] failed to save {}: {}", LOG_FILE, e);
+        }
+    }
+
+    pub(crate) fn query(&self, filter: &JobLogFilter, page: &Pagination) -> JobLogPage {
+        let entries = self.entries.lock().unwrap();
+        let matching: Vec<&JobLogEntry> = entries.iter().rev().filter(|e| filter.matches(e)).collect();
+        JobLogPage {
+            total: matching.len(),
+            entries: matching
+                .into_iter()
+                .skip(page.offset)
+                .take(page.limit.min(MAX_PAGE))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Write the entries `filter` matches to `dest`, oldest first. Returns
+    /// how many were written.
+    pub(crate) fn export(&self, filter: &JobLogFilter, format: ExportFormat, dest: &Path) -> Result<usize, String> {
+        let (text, count) = {
+            let entries = self.entries.lock().unwrap();
+            let matching: Vec<&JobLogEntry> = entries.iter().filter(|e| filter.matches(e)).collect();
+            let text = match format {
+                ExportFormat::Csv => to_csv(&matching),
+                ExportFormat::Json => {
+                    let rows: Vec<ExportRow> = matching.iter().map(|e| ExportRow::new(e)).collect();
+                    serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())?
+                }
+            };
+            (text, matching.len())
+        };
+        std::fs::write(dest, text).map_err(|e| format!("{}: {}", dest.display(), e))?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,120 @@
+//! Completed-job history and the duration model built on top of it.
+//!
+//! Processing time is modelled as `duration ≈ k × audio_seconds`, with `k`
+//! fitted by least squares through the origin over the recorded jobs.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bounded::{BoundedBuf, BufStats, ByteSize};
+
+const HISTORY_FILE: &str = "job_history.json";
+/// Oldest records are dropped beyond this many (or this many bytes).
+const MAX_RECORDS: usize = 500;
+const MAX_RECORD_BYTES: usize = 256 * 1024;
+/// Seconds of processing per second of audio before any job has completed.
+/// Deliberately pessimistic so the first ETA overestimates.
+const COLD_START_FACTOR: f64 = 0.25;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct JobRecord {
+    pub kind: String,
+    pub audio_seconds: f64,
+    pub duration_secs: f64,
+    /// Unix time in seconds.
+    pub finished_at: u64,
+    /// Aborted at the configured job timeout instead of finishing.
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Input files, kept for timed-out jobs so users know what to look at.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<String>,
+}
+
+impl ByteSize for JobRecord {
+    fn byte_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.kind.len() + self.inputs.iter().map(String::byte_size).sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct JobEstimate {
+    pub audio_seconds: f64,
+    pub estimated_secs: f64,
+    pub factor: f64,
+    /// Number of completed jobs the factor is based on (0 = cold start).
+    pub samples: usize,
+}
+
+pub(crate) struct JobHistory {
+    path: PathBuf,
+    records: Mutex<BoundedBuf<JobRecord>>,
+}
+
+impl JobHistory {
+    pub(crate) fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(HISTORY_FILE);
+        let saved: Vec<JobRecord> = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        let mut records = BoundedBuf::new(MAX_RECORDS, MAX_RECORD_BYTES);
+        records.extend(saved);
+        JobHistory { path, records: Mutex::new(records) }
+    }
+
+    pub(crate) fn record(&self, record: JobRecord) {
+        let json = {
+            let mut records = self.records.lock().unwrap();
+            records.push(record);
+            serde_json::to_vec(&*records).expect("job history serializes")
+        };
+        if let Err(e) = std::fs::write(&self.path, json) {
+            eprintln!("[djbot] failed to save job history: {}", e);
+        }
+    }
+
+    pub(crate) fn timed_out(&self) -> Vec<JobRecord> {
+        self.records.lock().unwrap().iter().filter(|r| r.timed_out).cloned().collect()
+    }
+
+    pub(crate) fn stats(&self) -> BufStats {
+        self.records.lock().unwrap().stats()
+    }
+
+    /// Current `k` and how many jobs it was fitted from.
+    pub(crate) fn factor(&self) -> (f64, usize) {
+        let records = self.records.lock().unwrap();
+        // A timed-out job's duration is the timeout, not the work it needed.
+        let (num, den, samples) = records
+            .iter()
+            .filter(|r| r.audio_seconds > 0.0 && !r.timed_out)
+            .fold((0.0, 0.0, 0), |(n, d, c), r| {
+                (n + r.duration_secs * r.audio_seconds, d + r.audio_seconds * r.audio_seconds, c + 1)
+            });
+        if den > 0.0 {
+            (num / den, samples)
+        } else {
+            (COLD_START_FACTOR, 0)
+        }
+    }
+
+    pub(crate) fn estimate(&self, audio_seconds: f64) -> JobEstimate {
+        let (factor, samples) = self.factor();
+        JobEstimate {
+            audio_seconds,
+            estimated_secs: factor * audio_seconds,
+            factor,
+            samples,
+        }
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,326 @@
+//! Local track index in `{data_dir}/library.db` (SQLite), so the search
+//! box doesn't have to ask the worker to re-list the library per keystroke.
+//!
+//! Rows are filled from tags read by `read_audio_tags`, new files in the
+//! output directory, and the worker's analysis results (duration, BPM,
+//! key). Text search goes through an FTS5 table kept in sync by triggers.
+//! Files that vanish are only flagged `missing` when a search returns them,
+//! so an unmounted drive doesn't empty the index. The schema is versioned
+//! with `PRAGMA user_version` and upgraded in place on open.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Listener};
+
+use crate::tags::{self, AudioTags};
+
+const DB_FILE: &str = "library.db";
+/// Largest page `search_tracks` returns.
+pub(crate) const MAX_SEARCH_LIMIT: u32 = 500;
+
+/// Schema steps; entry `i` upgrades `user_version` `i` to `i + 1`.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE tracks (
+        path     TEXT PRIMARY KEY,
+        title    TEXT,
+        artist   TEXT,
+        album    TEXT,
+        duration REAL,
+        bpm      REAL,
+        key      TEXT,
+        size     INTEGER,
+        mtime    INTEGER,
+        missing  INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX tracks_bpm ON tracks(bpm);
+    CREATE INDEX tracks_key ON tracks(key);
+    CREATE VIRTUAL TABLE tracks_fts USING fts5(
+        path, title, artist, album, content='tracks', content_rowid='rowid'
+    );
+    CREATE TRIGGER tracks_ai AFTER INSERT ON tracks BEGIN
+        INSERT INTO tracks_fts(rowid, path, title, artist, album)
+        VALUES (new.rowid, new.path, new.title, new.artist, new.album);
+    END;
+    CREATE TRIGGER tracks_ad AFTER DELETE ON tracks BEGIN
+        INSERT INTO tracks_fts(tracks_fts, rowid, path, title, artist, album)
+        VALUES ('delete', old.rowid, old.path, old.title, old.artist, old.album);
+    END;
+    CREATE TRIGGER tracks_au AFTER UPDATE ON tracks BEGIN
+        INSERT INTO tracks_fts(tracks_fts, rowid, path, title, artist, album)
+        VALUES ('delete', old.rowid, old.path, old.title, old.artist, old.album);
+        INSERT INTO tracks_fts(rowid, path, title, artist, album)
+        VALUES (new.rowid, new.path, new.title, new.artist, new.album);
+    END;",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct IndexedTrack {
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_secs: Option<f64>,
+    pub bpm: Option<f64>,
+    pub key: Option<String>,
+    /// The file wasn't there the last time it was looked at.
+    pub missing: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct TrackFilters {
+    pub bpm_min: Option<f64>,
+    pub bpm_max: Option<f64>,
+    pub key: Option<String>,
+    pub include_missing: bool,
+}
+
+/// Managed state for the index commands.
+#[derive(Clone)]
+pub(crate) struct TrackIndex {
+    conn: Arc<Mutex<Connection>>,
+    path: PathBuf,
+}
+
+fn db_err(e: rusqlite::Error) -> String {
+    format!("track index: {}", e)
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (i, step) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(&format!("BEGIN; {} PRAGMA user_version = {}; COMMIT;", step, i + 1))?;
+    }
+    Ok(())
+}
+
+fn open_db(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// Size and mtime (seconds) of `path`, if it exists.
+fn stat(path: &Path) -> Option<(i64, i64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    Some((meta.len() as i64, mtime))
+}
+
+/// Turn free text into an FTS5 query matching every word as a prefix.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|t| t.replace('"', ""))
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("\"{}\"*", t))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn upsert_tags(conn: &Connection, path: &str, tags: &AudioTags, stat: Option<(i64, i64)>) -> rusqlite::Result<()> {
+    let (size, mtime) = stat.unzip();
+    conn.execute(
+        "INSERT INTO tracks (path, title, artist, album, duration, size, mtime, missing)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 0)
+         ON CONFLICT(path) DO UPDATE SET
+            title = excluded.title, artist = excluded.artist, album = excluded.album,
+            duration = excluded.duration, size = excluded.size, mtime = excluded.mtime, missing = 0",
+        params![path, tags.title, tags.artist, tags.album, tags.duration_secs, size, mtime],
+    )?;
+    Ok(())
+}
+
+fn upsert_analysis(conn: &Connection, path: &str, duration: Option<f64>, bpm: Option<f64>, key: Option<&str>) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO tracks (path, duration, bpm, key) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(path) DO UPDATE SET
+            duration = COALESCE(excluded.duration, duration), bpm = excluded.bpm, key = excluded.key",
+        params![path, duration, bpm, key],
+    )?;
+    Ok(())
+}
+
+impl TrackIndex {
+    /// Open (or create) the index in `data_dir`. An unreadable database is
+    /// moved aside to `library.db.corrupt` and replaced by an empty one.
+    pub(crate) fn open(data_dir: &Path) -> Result<Self, String> {
+        let path = data_dir.join(DB_FILE);
+        let conn = match open_db(&path) {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("[djbot] track index unusable ({}); starting a new one", e);
+                let _ = std::fs::rename(&path, path.with_extension("db.corrupt"));
+                open_db(&path).map_err(db_err)?
+            }
+        };
+        Ok(TrackIndex { conn: Arc::new(Mutex::new(conn)), path })
+    }
+
+    /// Record tags read from `path`.
+    pub(crate) fn record_tags(&self, path: &str, tags: &AudioTags) {
+        let conn = self.conn.lock().unwrap();
+        if let Err(e) = upsert_tags(&conn, path, tags, stat(Path::new(path))) {
+            eprintln!("[djbot] failed to index {}: {}", path, e);
+        }
+    }
+
+    /// Record the `results` of a worker `/analyze` response.
+    pub(crate) fn record_analysis(&self, response: &serde_json::Value) {
+        let Some(results) = response.get("results").and_then(|r| r.as_array()) else { return };
+        let conn = self.conn.lock().unwrap();
+        for result in results {
+            let Some(path) = result.get("filepath").and_then(|p| p.as_str()) else { continue };
+            // The worker reports 0 / "" for values it couldn't determine.
+            let number = |field: &str| result.get(field).and_then(|v| v.as_f64()).filter(|v| *v > 0.0);
+            let key = result.get("key").and_then(|k| k.as_str()).filter(|k| !k.is_empty());
+            if let Err(e) = upsert_analysis(&conn, path, number("duration"), number("bpm"), key) {
+                eprintln!("[djbot] failed to index analysis of {}: {}", path, e);
+            }
+        }
+    }
+
+    /// Read tags of `path` and index it. Blocking.
+    pub(crate) fn index_file(&self, path: &Path) {
+        match tags::read(path) {
+            Ok(tags) => self.record_tags(&path.to_string_lossy(), &tags),
+            Err(e) => eprintln!("[djbot] not indexing {}: {}", path.display(), e),
+        }
+    }
+
+    /// Tracks whose path, title, artist or album match every word of
+    /// `query` (all tracks if empty), best matches first. Blocking.
+    pub(crate) fn search(&self, query: &str, filters: &TrackFilters, limit: u32, offset: u32) -> Result<Vec<IndexedTrack>, String> {
+        let fts = fts_query(query);
+        let sql = format!(
+            "SELECT t.path, t.title, t.artist, t.album, t.duration, t.bpm, t.key, t.missing
+             FROM tracks t {}
+             WHERE (?2 IS NULL OR t.bpm >= ?2) AND (?3 IS NULL OR t.bpm <= ?3)
+               AND (?4 IS NULL OR t.key = ?4) AND (?5 OR t.missing = 0)
+             ORDER BY {} LIMIT ?6 OFFSET ?7",
+            if fts.is_some() { "JOIN tracks_fts f ON f.rowid = t.rowid AND tracks_fts MATCH ?1" } else { "" },
+            if fts.is_some() { "f.rank" } else { "t.artist, t.title, t.path" },
+        );
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(&sql).map_err(db_err)?;
+        let rows = stmt
+            .query_map(
+                params![
+                    fts,
+                    filters.bpm_min,
+                    filters.bpm_max,
+                    filters.key,
+                    filters.include_missing,
+                    limit.min(MAX_SEARCH_LIMIT),
+                    offset
+                ],
+                |r| {
+                    Ok(IndexedTrack {
+                        path: r.get(0)?,
+                        title: r.get(1)?,
+                        artist: r.get(2)?,
+                        album: r.get(3)?,
+                        duration_secs: r.get(4)?,
+                        bpm: r.get(5)?,
+                        key: r.get(6)?,
+                        missing: r.get(7)?,
+                    })
+                },
+            )
+            .map_err(db_err)?;
+        let mut tracks = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+        drop(stmt);
+
+        // Flag (or un-flag) files on access instead of purging them.
+        for track in &mut tracks {
+            let missing = !Path::new(&track.path).exists();
+            if missing != track.missing {
+                track.missing = missing;
+                conn.execute("UPDATE tracks SET missing = ?2 WHERE path = ?1", params![track.path, missing])
+                    .map_err(db_err)?;
+            }
+        }
+        Ok(tracks)
+    }
+
+    /// Recreate the database from scratch, re-reading every previously
+    /// indexed file that is still there plus everything in `output_dir`.
+    /// Analysis results are carried over. Returns the number of tracks.
+    /// Blocking.
+    pub(crate) fn rebuild(&self, output_dir: &Path) -> Result<usize, String> {
+        let mut conn = self.conn.lock().unwrap();
+        type Analysis = (String, Option<f64>, Option<f64>, Option<String>);
+        let previous: Vec<Analysis> = conn
+            .prepare("SELECT path, duration, bpm, key FROM tracks")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?
+                    .collect()
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("[djbot] old track index unreadable, rebuilding from output only: {}", e);
+                Vec::new()
+            });
+
+        // Close the old file before deleting it.
+        *conn = Connection::open_in_memory().map_err(db_err)?;
+        for suffix in ["", "-wal", "-shm"] {
+            let file = PathBuf::from(format!("{}{}", self.path.display(), suffix));
+            if let Err(e) = std::fs::remove_file(&file) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(format!("{}: {}", file.display(), e));
+                }
+            }
+        }
+        *conn = open_db(&self.path).map_err(db_err)?;
+
+        let mut paths: Vec<PathBuf> = previous.iter().map(|p| PathBuf::from(&p.0)).collect();
+        if let Ok(entries) = std::fs::read_dir(output_dir) {
+            paths.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_file()));
+        }
+        paths.sort();
+        paths.dedup();
+
+        let tx = conn.transaction().map_err(db_err)?;
+        let mut count = 0;
+        for path in &paths {
+            if let Ok(tags) = tags::read(path) {
+                upsert_tags(&tx, &path.to_string_lossy(), &tags, stat(path)).map_err(db_err)?;
+                count += 1;
+            }
+        }
+        for (path, duration, bpm, key) in &previous {
+            let indexed: Option<i64> = tx
+                .query_row("SELECT 1 FROM tracks WHERE path = ?1", [path], |r| r.get(0))
+                .optional()
+                .map_err(db_err)?;
+            if indexed.is_some() {
+                upsert_analysis(&tx, path, *duration, *bpm, key.as_deref()).map_err(db_err)?;
+            }
+        }
+        tx.commit().map_err(db_err)?;
+        Ok(count)
+    }
+}
+
+/// Index each file the output watcher reports (`output-file-created`).
+pub(crate) fn index_new_outputs(app: &AppHandle, index: TrackIndex) {
+    app.listen("output-file-created", move |event| {
+        let Ok(paths) = serde_json::from_str::<Vec<String>>(event.payload()) else { return };
+        let index = index.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            for path in paths {
+                index.index_file(Path::new(&path));
+            }
+        });
+    });
+}
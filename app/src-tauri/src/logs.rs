@@ -0,0 +1,297 @@
+//! Coalescing of repetitive worker log lines, and the on-disk `worker.log`.
+//!
+//! ffmpeg rewrites its progress line with `\r` many times a second and
+//! workers tend to repeat the same warning in a loop. Within each event batch
+//! a run of such lines becomes a single `LogLine` carrying the latest text and
+//! a repeat count. The terminal (stderr) always sees every line; `worker.log`
+//! does too unless `coalesce_log_file` is set.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config::DjbotConfig;
+
+const LOG_FILE: &str = "worker.log";
+
+/// One entry of the worker log, as streamed by `stream_worker_log`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct LogLine {
+    pub line: String,
+    /// Number of worker lines this entry stands for (1 if not coalesced).
+    pub repeat: u64,
+}
+
+pub(crate) fn log_file_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(LOG_FILE)
+}
+
+/// `frame=… time=…` / `size=… time=…` status lines printed by ffmpeg while
+/// encoding.
+fn is_ffmpeg_progress(line: &str) -> bool {
+    let line = line.trim_start();
+    (line.starts_with("frame=") || line.starts_with("size=")) && line.contains("time=")
+}
+
+/// Level of a worker stderr line tagged `[INFO]`, `[WARN]` or `[ERROR]`,
+/// either at the start or after Go's `2006/01/02 15:04:05 ` timestamp.
+/// Untagged lines are `Debug`.
+fn worker_log_level(line: &str) -> log::Level {
+    let mut rest = line.trim_start();
+    if let Some((date, after)) = rest.split_once(' ') {
+        if let Some((time, after)) = after.split_once(' ') {
+            if date.matches('/').count() == 2 && time.matches(':').count() == 2 {
+                rest = after.trim_start();
+            }
+        }
+    }
+    if rest.starts_with("[ERROR]") {
+        log::Level::Error
+    } else if rest.starts_with("[WARN]") {
+        log::Level::Warn
+    } else if rest.starts_with("[INFO]") {
+        log::Level::Info
+    } else {
+        log::Level::Debug
+    }
+}
+
+/// Hand a worker stderr line to `tauri_plugin_log` at its tagged level, so
+/// it shows in the devtools log panel under the `goworker` target.
+pub(crate) fn worker_stderr_to_tauri_log(line: &str) {
+    log::log!(target: "goworker", worker_log_level(line), "{}", line);
+}
+
+/// `stderr_filter_patterns`, compiled once per worker run. Invalid patterns
+/// are logged and skipped.
+pub(crate) struct StderrFilter {
+    patterns: Vec<Regex>,
+}
+
+impl StderrFilter {
+    pub(crate) fn new(patterns: &[String]) -> Self {
+        let patterns = patterns
+            .iter()
+            .filter_map(|p| match Regex::new(p) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    eprintln!("[djbot] ignoring invalid stderr filter {:?}: {}", p, e);
+                    None
+                }
+            })
+            .collect();
+        StderrFilter { patterns }
+    }
+
+    pub(crate) fn matches(&self, line: &str) -> bool {
+        self.patterns.iter().any(|re| re.is_match(line))
+    }
+}
+
+/// Which runs get folded together.
+struct Coalescer {
+    ffmpeg_progress: bool,
+    identical: bool,
+}
+
+impl Coalescer {
+    /// Reduce a `\r`-separated sequence of progress updates to its last one.
+    fn collapse_carriage_returns(&self, line: String) -> (String, u64) {
+        if !self.ffmpeg_progress || !line.contains('\r') {
+            return (line, 1);
+        }
+        let updates: Vec<&str> = line.split('\r').filter(|s| !s.trim().is_empty()).collect();
+        match updates.last() {
+            Some(last) if updates.iter().all(|s| is_ffmpeg_progress(s)) => (last.to_string(), updates.len() as u64),
+            _ => (line, 1),
+        }
+    }
+
+    fn same_run(&self, prev: &str, next: &str) -> bool {
+        (self.identical && prev == next)
+            || (self.ffmpeg_progress && is_ffmpeg_progress(prev) && is_ffmpeg_progress(next))
+    }
+
+    /// Append `line` to `out`, folding it into the last entry when both
+    /// belong to the same run. Returns true if it was folded.
+    fn push(&self, out: &mut Vec<LogLine>, line: String) -> bool {
+        let (line, repeat) = self.collapse_carriage_returns(line);
+        if let Some(last) = out.last_mut() {
+            if self.same_run(&last.line, &line) {
+                last.line = line;
+                last.repeat += repeat;
+                return true;
+            }
+        }
+        out.push(LogLine { line, repeat });
+        repeat > 1
+    }
+}
+
+/// The log stage of the event bus: coalescing plus `worker.log`.
+pub(crate) struct LogPipeline {
+    coalescer: Coalescer,
+    file: Option<BufWriter<File>>,
+    /// Write `worker.log` from the coalesced entries instead of raw lines.
+    coalesce_file: bool,
+}
+
+impl LogPipeline {
+    /// Build the pipeline from `config`. Coalescing is off entirely in debug
+    /// mode. Failing to open `worker.log` is logged, not fatal.
+    pub(crate) fn new(config: &DjbotConfig, data_dir: &Path) -> Self {
+        let logs = &config.logs;
+        let coalescer = Coalescer {
+            ffmpeg_progress: logs.coalesce_ffmpeg_progress && !config.debug_mode,
+            identical: logs.coalesce_identical && !config.debug_mode,
+        };
+        let path = log_file_path(data_dir);
+        let file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(f) => Some(BufWriter::new(f)),
+            Err(e) => {
+                eprintln!("[djbot] cannot open {}: {}", path.display(), e);
+                None
+            }
+        };
+        LogPipeline {
+            coalescer,
+            file,
+            coalesce_file: logs.coalesce_log_file,
+        }
+    }
+
+    /// Add one raw worker line to `out`. Returns true if it was coalesced.
+    pub(crate) fn push(&mut self, out: &mut Vec<LogLine>, line: String) -> bool {
+        if !self.coalesce_file {
+            if let Some(file) = &mut self.file {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+        self.coalescer.push(out, line)
+    }
+
+    /// Called once per batch with the entries about to be emitted.
+    pub(crate) fn finish_batch(&mut self, lines: &[LogLine]) {
+        let Some(file) = &mut self.file else { return };
+        if self.coalesce_file {
+            for entry in lines {
+                let _ = match entry.repeat {
+                    1 => writeln!(file, "{}", entry.line),
+                    n => writeln!(file, "{} (x{})", entry.line, n),
+                };
+            }
+        }
+        if let Err(e) = file.flush() {
+            eprintln!("[djbot] failed to write {}: {}", LOG_FILE, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// stderr of `ffmpeg -i set.mp3 set.wav` (ffmpeg 6.1.1), with the
+    /// progress updates it redraws in place with `\r`.
+    const FFMPEG_STDERR: &str = concat!(
+        "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers\n",
+        "  built with gcc 13 (Ubuntu 13.2.0-23ubuntu3)\n",
+        "Input #0, mp3, from 'set.mp3':\n",
+        "  Duration: 00:03:12.48, start: 0.025057, bitrate: 320 kb/s\n",
+        "  Stream #0:0: Audio: mp3, 44100 Hz, stereo, fltp, 320 kb/s\n",
+        "Stream mapping:\n",
+        "  Stream #0:0 -> #0:0 (mp3 (mp3float) -> pcm_s16le (native))\n",
+        "Press [q] to stop, [?] for help\n",
+        "Output #0, wav, to 'set.wav':\n",
+        "  Stream #0:0: Audio: pcm_s16le ([1][0][0][0] / 0x0001), 44100 Hz, stereo, s16, 1411 kb/s\n",
+        "size=    1024kB time=00:00:05.94 bitrate=1411.2kbits/s speed=11.9x    \r",
+        "size=    4352kB time=00:00:25.25 bitrate=1411.2kbits/s speed=25.2x    \r",
+        "size=    8960kB time=00:00:52.01 bitrate=1411.2kbits/s speed=  26x    \n",
+        "[mp3float @ 0x55d0c1a3c2c0] overread, skip -5 enc-vol -4 (end of frame?)\n",
+        "[mp3float @ 0x55d0c1a3c2c0] overread, skip -5 enc-vol -4 (end of frame?)\n",
+        "[mp3float @ 0x55d0c1a3c2c0] overread, skip -5 enc-vol -4 (end of frame?)\n",
+        "size=   13568kB time=00:01:18.77 bitrate=1411.1kbits/s speed=26.2x    \r",
+        "size=   18176kB time=00:01:45.51 bitrate=1411.1kbits/s speed=26.3x    \r",
+        "size=   22784kB time=00:02:12.26 bitrate=1411.1kbits/s speed=26.4x    \r",
+        "size=   27392kB time=00:02:39.01 bitrate=1411.1kbits/s speed=26.4x    \n",
+        "size=   33164kB time=00:03:12.45 bitrate=1411.1kbits/s speed=26.5x    \n",
+        "video:0kB audio:33164kB subtitle:0kB other streams:0kB global headers:0kB muxing overhead: 0.000230%\n",
+    );
+
+    fn coalesce(coalescer: &Coalescer) -> Vec<LogLine> {
+        let mut out = Vec::new();
+        for line in FFMPEG_STDERR.lines() {
+            coalescer.push(&mut out, line.to_string());
+        }
+        out
+    }
+
+    #[test]
+    fn captured_ffmpeg_output_is_coalesced() {
+        let out = coalesce(&Coalescer { ffmpeg_progress: true, identical: true });
+        let entries: Vec<(&str, u64)> = out.iter().map(|l| (l.line.trim_end(), l.repeat)).collect();
+        assert_eq!(entries.len(), 14);
+        // The banner passes through untouched.
+        assert!(entries[..10].iter().all(|&(_, repeat)| repeat == 1));
+        assert_eq!(entries[0].0, "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers");
+        assert_eq!(entries[10], ("size=    8960kB time=00:00:52.01 bitrate=1411.2kbits/s speed=  26x", 3));
+        assert_eq!(entries[11], ("[mp3float @ 0x55d0c1a3c2c0] overread, skip -5 enc-vol -4 (end of frame?)", 3));
+        // Four redrawn updates plus the final status line.
+        assert_eq!(entries[12], ("size=   33164kB time=00:03:12.45 bitrate=1411.1kbits/s speed=26.5x", 5));
+        assert_eq!(entries[13].1, 1);
+        assert_eq!(out.iter().map(|l| l.repeat).sum::<u64>(), 22);
+    }
+
+    #[test]
+    fn each_pattern_can_be_turned_off() {
+        let progress_only = coalesce(&Coalescer { ffmpeg_progress: true, identical: false });
+        assert_eq!(progress_only.iter().filter(|l| l.line.starts_with("[mp3float")).count(), 3);
+
+        let identical_only = coalesce(&Coalescer { ffmpeg_progress: false, identical: true });
+        let warnings: Vec<_> = identical_only.iter().filter(|l| l.line.starts_with("[mp3float")).collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].repeat, 3);
+        // Redrawn progress stays one raw line per `\n`.
+        assert!(identical_only.iter().any(|l| l.line.contains('\r')));
+    }
+
+    #[test]
+    fn nothing_is_folded_in_debug_mode() {
+        let config = DjbotConfig { debug_mode: true, ..DjbotConfig::default() };
+        let dir = std::env::temp_dir().join(format!("djbot-logs-debug-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut pipeline = LogPipeline::new(&config, &dir);
+        let mut out = Vec::new();
+        for line in FFMPEG_STDERR.lines() {
+            assert!(!pipeline.push(&mut out, line.to_string()));
+        }
+        assert_eq!(out.len(), FFMPEG_STDERR.lines().count());
+    }
+
+    #[test]
+    fn worker_log_keeps_every_line_or_the_coalesced_form() {
+        for coalesce_file in [false, true] {
+            let dir = std::env::temp_dir().join(format!("djbot-logs-file-{}-{}", coalesce_file, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let _ = std::fs::remove_file(log_file_path(&dir));
+            let mut config = DjbotConfig::default();
+            config.logs.coalesce_log_file = coalesce_file;
+            let mut pipeline = LogPipeline::new(&config, &dir);
+            let mut out = Vec::new();
+            for line in FFMPEG_STDERR.lines() {
+                pipeline.push(&mut out, line.to_string());
+            }
+            pipeline.finish_batch(&out);
+            let written = std::fs::read_to_string(log_file_path(&dir)).unwrap();
+            if coalesce_file {
+                assert_eq!(written.lines().count(), 14);
+                assert!(written.contains("end of frame?) (x3)"));
+            } else {
+                assert_eq!(written.lines().count(), FFMPEG_STDERR.lines().count());
+            }
+        }
+    }
+}
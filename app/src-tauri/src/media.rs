@@ -0,0 +1,207 @@
+//! OS media-transport integration: System Media Transport Controls on
+//! Windows, MPRIS on Linux, MPRemoteCommandCenter on macOS (via souvlaki).
+//!
+//! Play/pause/next/previous arriving from the OS are forwarded to the
+//! worker's `/playback/<action>` endpoints. While the worker answers 501
+//! (preview playback lives in the webview), the action is emitted as a
+//! `media-key` event instead. On Linux the MPRIS service *is*
+//! the media-key path (desktops route the keys to it), so there is no second
+//! global-shortcut listener that could double-trigger.
+//!
+//! The same controls publish what is playing (title, artist, artwork,
+//! position) to the OS overlay. The preview player reports its state through
+//! `set_now_playing`/`clear_now_playing`; the entry is also cleared when the
+//! worker exits so a stale "playing" item doesn't linger.
+//!
+//! The controls are owned by a dedicated thread, since the platform handles
+//! aren't guaranteed to be `Send`; other code talks to it through
+//! `MediaSession`.
+
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::proxy::{self, WorkerHttp};
+use crate::plugin::WorkerState;
+
+/// What the preview player is playing, as sent by the frontend.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct NowPlaying {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Local image file for the overlay artwork.
+    pub artwork_path: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub position_ms: Option<u64>,
+    pub playing: bool,
+}
+
+enum MediaCommand {
+    /// Start or stop receiving media-key events.
+    SetEnabled(bool),
+    SetNowPlaying(NowPlaying),
+    ClearNowPlaying,
+}
+
+/// Managed handle to the media-controls thread.
+pub(crate) struct MediaSession {
+    tx: Mutex<mpsc::Sender<MediaCommand>>,
+}
+
+impl MediaSession {
+    /// Spawn the controls thread. `hwnd` (Windows only) is the main window
+    /// handle SMTC attaches to.
+    pub(crate) fn start(app: AppHandle, hwnd: Option<usize>, enabled: bool) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || run(app, hwnd, enabled, rx));
+        MediaSession { tx: Mutex::new(tx) }
+    }
+
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.send(MediaCommand::SetEnabled(enabled));
+    }
+
+    pub(crate) fn set_now_playing(&self, info: NowPlaying) {
+        self.send(MediaCommand::SetNowPlaying(info));
+    }
+
+    pub(crate) fn clear_now_playing(&self) {
+        self.send(MediaCommand::ClearNowPlaying);
+    }
+
+    fn send(&self, command: MediaCommand) {
+        let _ = self.tx.lock().unwrap().send(command);
+    }
+}
+
+/// `file://` URL for the artwork; every platform backend accepts those.
+fn artwork_url(path: &str) -> Option<String> {
+    reqwest::Url::from_file_path(path).ok().map(String::from)
+}
+
+fn publish(controls: &mut MediaControls, info: &NowPlaying) {
+    let cover_url = info.artwork_path.as_deref().and_then(artwork_url);
+    let metadata = MediaMetadata {
+        title: Some(&info.title),
+        artist: info.artist.as_deref(),
+        album: info.album.as_deref(),
+        cover_url: cover_url.as_deref(),
+        duration: info.duration_ms.map(Duration::from_millis),
+    };
+    let progress = info.position_ms.map(|ms| MediaPosition(Duration::from_millis(ms)));
+    let playback = if info.playing {
+        MediaPlayback::Playing { progress }
+    } else {
+        MediaPlayback::Paused { progress }
+    };
+    if let Err(e) = controls.set_metadata(metadata).and_then(|_| controls.set_playback(playback)) {
+        eprintln!("[djbot] failed to publish now-playing info: {:?}", e);
+    }
+}
+
+fn clear(controls: &mut MediaControls) {
+    let _ = controls.set_metadata(MediaMetadata::default());
+    let _ = controls.set_playback(MediaPlayback::Stopped);
+}
+
+fn run(app: AppHandle, hwnd: Option<usize>, enabled: bool, rx: mpsc::Receiver<MediaCommand>) {
+    let config = PlatformConfig {
+        display_name: "AutoMix DJ Bot",
+        dbus_name: "djbot",
+        hwnd: hwnd.map(|h| h as *mut std::ffi::c_void),
+    };
+    let mut controls = match MediaControls::new(config) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("[djbot] media controls unavailable: {:?}", e);
+            return;
+        }
+    };
+
+    let mut attached = enabled && attach(&mut controls, &app);
+    // Kept so it can be published once the controls are (re)attached.
+    let mut now_playing: Option<NowPlaying> = None;
+    for command in rx {
+        match command {
+            MediaCommand::SetEnabled(true) if !attached => {
+                attached = attach(&mut controls, &app);
+                if let (true, Some(info)) = (attached, &now_playing) {
+                    publish(&mut controls, info);
+                }
+            }
+            MediaCommand::SetEnabled(false) if attached => {
+                clear(&mut controls);
+                if let Err(e) = controls.detach() {
+                    eprintln!("[djbot] failed to release media keys: {:?}", e);
+                }
+                attached = false;
+            }
+            MediaCommand::SetEnabled(_) => {}
+            MediaCommand::SetNowPlaying(info) => {
+                if attached {
+                    publish(&mut controls, &info);
+                }
+                now_playing = Some(info);
+            }
+            MediaCommand::ClearNowPlaying => {
+                if attached && now_playing.is_some() {
+                    clear(&mut controls);
+                }
+                now_playing = None;
+            }
+        }
+    }
+}
+
+fn attach(controls: &mut MediaControls, app: &AppHandle) -> bool {
+    let app = app.clone();
+    match controls.attach(move |event| forward_event(&app, event)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("[djbot] failed to register for media keys: {:?}", e);
+            false
+        }
+    }
+}
+
+fn playback_action(event: &MediaControlEvent) -> Option<&'static str> {
+    match event {
+        MediaControlEvent::Play => Some("play"),
+        MediaControlEvent::Pause => Some("pause"),
+        MediaControlEvent::Toggle => Some("toggle"),
+        MediaControlEvent::Next => Some("next"),
+        MediaControlEvent::Previous => Some("previous"),
+        MediaControlEvent::Stop => Some("stop"),
+        _ => None,
+    }
+}
+
+/// POST `/playback/<action>` to the worker in the background. `on_501` runs
+/// if the worker doesn't implement playback itself. Dropped silently while
+/// the worker is down.
+pub(crate) fn worker_playback(app: &AppHandle, action: &'static str, on_501: impl FnOnce(&AppHandle) + Send + 'static) {
+    let state = app.state::<WorkerState>();
+    let Some(port) = state.port().filter(|_| state.is_accepting_commands()) else { return };
+    let Ok(client) = app.state::<WorkerHttp>().client(None) else { return };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let path = format!("playback/{}", action);
+        match proxy::forward(&client, port, "POST", &path, None).await {
+            Ok(_) => {}
+            Err(e) if e.contains("501") => on_501(&app),
+            Err(e) => eprintln!("[djbot] playback {} not handled by worker: {}", action, e),
+        }
+    });
+}
+
+fn forward_event(app: &AppHandle, event: MediaControlEvent) {
+    let Some(action) = playback_action(&event) else { return };
+    worker_playback(app, action, move |app| {
+        let _ = app.emit("media-key", action);
+    });
+}
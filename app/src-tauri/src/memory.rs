@@ -0,0 +1,43 @@
+//! Resident memory of the worker process.
+//!
+//! Sampled once shortly after the worker reports ready, as a startup
+//! footprint that catches regressions and warns users on machines where
+//! the worker barely fits.
+
+/// Resident set size of `pid` in bytes, `None` if it can't be read.
+#[cfg(target_os = "linux")]
+pub(crate) fn rss_bytes(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line["VmRSS:".len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn rss_bytes(pid: u32) -> Option<u64> {
+    let out = std::process::Command::new("ps")
+        .args(["-o", "rss=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let kib: u64 = String::from_utf8_lossy(&out.stdout).trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn rss_bytes(pid: u32) -> Option<u64> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let script = format!("(Get-Process -Id {}).WorkingSet64", pid);
+    let out = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub(crate) fn rss_bytes(_pid: u32) -> Option<u64> {
+    None
+}
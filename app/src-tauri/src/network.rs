@@ -0,0 +1,198 @@
+//! Whether the machine is online, for jobs that fetch a URL.
+//!
+//! Every `probe_interval_secs` the monitor asks the OS for a route out (a
+//! UDP "connect" to a documentation address, which sends nothing) and, if
+//! a probe host is known, opens a TCP connection to it. The probe host is
+//! `network.probe_url` or else the webhook's host; nothing third-party is
+//! contacted unless the user configured it. A change is only reported once
+//! `FLAP_PROBES` probes in a row agree, so a single dropped probe on a
+//! flaky link doesn't flip the state.
+//!
+//! While offline, URL jobs fail fast with `Offline`, or wait in the pending
+//! queue when `network.queue_when_offline` is on.
+
+use std::fmt;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::{DjbotConfig, WEBHOOK_URL_FIELD};
+use crate::jobs::unix_now;
+use crate::secrets;
+
+/// Probes in a row that must disagree with the current state to change it.
+const FLAP_PROBES: u32 = 2;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Addresses reserved for documentation (RFC 5737, RFC 3849): routable as
+/// far as the OS is concerned, owned by nobody.
+const ROUTE_CHECK_ADDRS: &[&str] = &["192.0.2.1:9", "[2001:db8::1]:9"];
+
+/// A URL job refused while offline. The `Display` form starts with
+/// "offline" for the UI to recognise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Offline {
+    pub detail: String,
+}
+
+impl fmt::Display for Offline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offline: {}", self.detail)
+    }
+}
+
+impl From<Offline> for String {
+    fn from(o: Offline) -> String {
+        o.to_string()
+    }
+}
+
+/// Returned by `get_network_status` and the payload of `network-changed`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct NetworkStatus {
+    pub online: bool,
+    /// Host the probe connects to, `None` when only the route is checked.
+    pub probe_host: Option<String>,
+    /// Unix seconds of the last change (or of startup).
+    pub since: u64,
+    /// Unix seconds of the last probe, `None` before the first one.
+    pub checked_at: Option<u64>,
+    /// Why the last probe failed.
+    pub detail: Option<String>,
+    /// URL jobs waiting for the network to come back.
+    pub pending_jobs: usize,
+}
+
+/// A URL job held while offline, replayed through `forward_worker_request`.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingUrlJob {
+    pub id: String,
+    pub method: String,
+    pub path: String,
+    pub body: Option<Value>,
+}
+
+/// Payload of `pending-url-job-finished`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PendingUrlJobFinished {
+    pub id: String,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+struct Debounce {
+    status: NetworkStatus,
+    /// Probes in a row that disagreed with `status.online`.
+    disagreeing: u32,
+}
+
+/// Managed state. Starts out online; the first probes correct it.
+pub(crate) struct NetworkMonitor {
+    state: Mutex<Debounce>,
+    pending: Mutex<Vec<PendingUrlJob>>,
+}
+
+impl Default for NetworkMonitor {
+    fn default() -> Self {
+        let status = NetworkStatus {
+            online: true,
+            probe_host: None,
+            since: unix_now(),
+            checked_at: None,
+            detail: None,
+            pending_jobs: 0,
+        };
+        NetworkMonitor { state: Mutex::new(Debounce { status, disagreeing: 0 }), pending: Mutex::default() }
+    }
+}
+
+impl NetworkMonitor {
+    pub(crate) fn status(&self) -> NetworkStatus {
+        let mut status = self.state.lock().unwrap().status.clone();
+        status.pending_jobs = self.pending.lock().unwrap().len();
+        status
+    }
+
+    pub(crate) fn check(&self) -> Result<(), Offline> {
+        let state = self.state.lock().unwrap();
+        if state.status.online {
+            return Ok(());
+        }
+        let detail = state.status.detail.clone().unwrap_or_else(|| "no network connection".to_string());
+        Err(Offline { detail })
+    }
+
+    /// Record a probe of `probe_host`. Returns the new status when this
+    /// probe settled a change.
+    pub(crate) fn observe(&self, probe_host: Option<String>, result: Result<(), String>) -> Option<NetworkStatus> {
+        let mut state = self.state.lock().unwrap();
+        let now = unix_now();
+        state.status.checked_at = Some(now);
+        state.status.probe_host = probe_host;
+        let online = result.is_ok();
+        if online == state.status.online {
+            state.disagreeing = 0;
+            state.status.detail = result.err();
+            return None;
+        }
+        state.disagreeing += 1;
+        if state.disagreeing < FLAP_PROBES {
+            return None;
+        }
+        state.disagreeing = 0;
+        state.status.online = online;
+        state.status.since = now;
+        state.status.detail = result.err();
+        drop(state);
+        Some(self.status())
+    }
+
+    /// Hold a URL job until the network is back; returns its id.
+    pub(crate) fn queue(&self, method: String, path: String, body: Option<Value>) -> Result<String, String> {
+        let mut id = [0u8; 8];
+        getrandom::getrandom(&mut id).map_err(|e| e.to_string())?;
+        let id: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+        self.pending.lock().unwrap().push(PendingUrlJob { id: id.clone(), method, path, body });
+        Ok(id)
+    }
+
+    /// The held jobs, in the order they were queued, emptying the queue.
+    pub(crate) fn take_pending(&self) -> Vec<PendingUrlJob> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+/// `host:port` to probe: `network.probe_url`, else the webhook's host
+/// while webhooks are on.
+pub(crate) fn probe_target(data_dir: &Path, config: &DjbotConfig) -> Option<(String, u16)> {
+    let url = match &config.network.probe_url {
+        Some(url) => url.clone(),
+        None if config.webhook.enabled => {
+            let sealed = config.webhook.url.as_ref()?;
+            secrets::open(data_dir, WEBHOOK_URL_FIELD, sealed).ok()?
+        }
+        None => return None,
+    };
+    let url = reqwest::Url::parse(&url).ok()?;
+    Some((url.host_str()?.to_string(), url.port_or_known_default()?))
+}
+
+/// One probe: a route out, then a TCP connection to `target` if given.
+pub(crate) async fn probe(target: Option<&(String, u16)>) -> Result<(), String> {
+    let routed = ROUTE_CHECK_ADDRS.iter().any(|addr| {
+        let bind = if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" };
+        UdpSocket::bind(bind).and_then(|s| s.connect(addr)).is_ok()
+    });
+    if !routed {
+        return Err("no network route".to_string());
+    }
+    let Some((host, port)) = target else { return Ok(()) };
+    match tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect((host.as_str(), *port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("{} unreachable: {}", host, e)),
+        Err(_) => Err(format!("{} unreachable: timed out", host)),
+    }
+}
@@ -0,0 +1,178 @@
+//! Optional detection of other programs writing into the output directory.
+//!
+//! Cloud-sync clients and antivirus scanners that touch files while the
+//! worker is rendering are a common source of corrupted output. The worker
+//! writes each file in one go, so we flag writes to a file that had been
+//! quiet for `QUIET_PERIOD`, and any activity on the temp/marker files sync
+//! clients leave behind. Each flagged write is emitted as an
+//! `output-dir-external-write` event (at most once per file per
+//! `EMIT_INTERVAL`) and recorded for `get_output_dir_activity`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::bounded::{BoundedBuf, ByteSize};
+
+const QUIET_PERIOD: Duration = Duration::from_secs(30);
+const EMIT_INTERVAL: Duration = Duration::from_secs(5);
+/// Per-file timestamps older than this are forgotten.
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+const RECENT_WRITES: usize = 50;
+const RECENT_WRITES_BYTES: usize = 64 * 1024;
+
+/// Name fragments left by sync clients, editors and scanners.
+const FOREIGN_MARKERS: &[&str] = &[
+    ".dropbox",
+    ".icloud",
+    ".sync",
+    "~$",
+    ".~",
+    "desktop.ini",
+    ".ds_store",
+    "thumbs.db",
+];
+const FOREIGN_SUFFIXES: &[&str] = &[".tmp", ".crdownload", ".partial", ".!sync", ".syncthing"];
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ExternalWrite {
+    pub path: String,
+    /// `create` or `modify`.
+    pub kind: &'static str,
+    pub reason: &'static str,
+    /// Unix time in seconds.
+    pub at: u64,
+}
+
+impl ByteSize for ExternalWrite {
+    fn byte_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.path.len()
+    }
+}
+
+/// Summary returned by `get_output_dir_activity`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct OutputActivity {
+    pub watching: bool,
+    pub events_seen: u64,
+    pub external_writes: u64,
+    pub recent: Vec<ExternalWrite>,
+}
+
+#[derive(Default)]
+struct Tracker {
+    last_write: HashMap<PathBuf, Instant>,
+    last_emit: HashMap<PathBuf, Instant>,
+    events_seen: u64,
+    external_writes: u64,
+}
+
+impl Tracker {
+    /// Record a write to `path` and say why it looks foreign, if it does.
+    fn classify(&mut self, path: &Path, created: bool, now: Instant) -> Option<&'static str> {
+        self.events_seen += 1;
+        let previous = self.last_write.insert(path.to_path_buf(), now);
+        if self.last_write.len() > 4096 {
+            self.last_write.retain(|_, t| now.duration_since(*t) < FORGET_AFTER);
+            self.last_emit.retain(|_, t| now.duration_since(*t) < FORGET_AFTER);
+        }
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if FOREIGN_MARKERS.iter().any(|m| name.contains(m)) || FOREIGN_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+            return Some("sync or scanner temp file");
+        }
+        match previous {
+            Some(t) if !created && now.duration_since(t) >= QUIET_PERIOD => Some("finished file modified"),
+            _ => None,
+        }
+    }
+
+    fn should_emit(&mut self, path: &Path, now: Instant) -> bool {
+        match self.last_emit.get(path) {
+            Some(t) if now.duration_since(*t) < EMIT_INTERVAL => false,
+            _ => {
+                self.last_emit.insert(path.to_path_buf(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Managed state: the watcher (kept alive here) and what it has seen.
+pub(crate) struct OutputDirActivity {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    tracker: Arc<Mutex<Tracker>>,
+    recent: Arc<Mutex<BoundedBuf<ExternalWrite>>>,
+}
+
+impl OutputDirActivity {
+    pub(crate) fn disabled() -> Self {
+        OutputDirActivity {
+            watcher: Mutex::new(None),
+            tracker: Arc::new(Mutex::new(Tracker::default())),
+            recent: Arc::new(Mutex::new(BoundedBuf::new(RECENT_WRITES, RECENT_WRITES_BYTES))),
+        }
+    }
+
+    /// Start watching `output_dir`. On failure the error is logged and the
+    /// returned state simply reports `watching: false`.
+    pub(crate) fn watch(app: AppHandle, output_dir: &Path) -> Self {
+        let activity = Self::disabled();
+        let tracker = Arc::clone(&activity.tracker);
+        let recent = Arc::clone(&activity.recent);
+        let handler = move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+            let (created, kind) = match event.kind {
+                EventKind::Create(_) => (true, "create"),
+                EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any | ModifyKind::Name(_)) => (false, "modify"),
+                _ => return,
+            };
+            let now = Instant::now();
+            for path in event.paths {
+                let mut t = tracker.lock().unwrap();
+                let Some(reason) = t.classify(&path, created, now) else { continue };
+                t.external_writes += 1;
+                if !t.should_emit(&path, now) {
+                    continue;
+                }
+                drop(t);
+                let write = ExternalWrite {
+                    path: path.to_string_lossy().to_string(),
+                    kind,
+                    reason,
+                    at: crate::jobs::unix_now(),
+                };
+                eprintln!("[djbot] WARNING: external write in output dir: {} ({})", write.path, reason);
+                recent.lock().unwrap().push(write.clone());
+                let _ = app.emit("output-dir-external-write", write);
+            }
+        };
+
+        let watcher = notify::recommended_watcher(handler)
+            .and_then(|mut w| w.watch(output_dir, RecursiveMode::NonRecursive).map(|_| w));
+        match watcher {
+            Ok(w) => *activity.watcher.lock().unwrap() = Some(w),
+            Err(e) => eprintln!("[djbot] cannot watch {}: {}", output_dir.display(), e),
+        }
+        activity
+    }
+
+    pub(crate) fn summary(&self) -> OutputActivity {
+        let t = self.tracker.lock().unwrap();
+        OutputActivity {
+            watching: self.watcher.lock().unwrap().is_some(),
+            events_seen: t.events_seen,
+            external_writes: t.external_writes,
+            recent: self.recent.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Polls the output directory and reports newly created files.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::events::{EventBus, WorkerEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn list_files(dir: &Path) -> HashSet<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Spawn a thread that emits an `output-file-created` event for every file
+/// that appears in `output_dir`, calling `on_created` after each poll that
+/// found any. Files present at startup are not reported.
+pub(crate) fn spawn(output_dir: PathBuf, bus: EventBus, mut on_created: impl FnMut() + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut known = list_files(&output_dir);
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = list_files(&output_dir);
+            let mut created = false;
+            for path in current.difference(&known) {
+                bus.send(WorkerEvent::OutputFile(path.to_string_lossy().to_string()));
+                created = true;
+            }
+            if created {
+                on_created();
+                // Archiving may have removed files; don't report them later.
+                known = list_files(&output_dir);
+            } else {
+                known = current;
+            }
+        }
+    });
+}
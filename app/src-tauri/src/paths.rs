@@ -0,0 +1,313 @@
+//! One place to validate file paths the frontend hands to commands.
+//!
+//! `Scope` is built at setup from the canonical data dir (which holds the
+//! output, archive and cache dirs) and the user's downloads dir; files the
+//! user picked or dropped are let in through the fs plugin scope. A path is
+//! checked lexically first (absolute, no `..`, no NUL, no Windows device
+//! names or `\\?\`/`\\.\` prefixes), then canonicalized so a symlink
+//! pointing out of the scope is caught by the containment check.
+//!
+//! Paths refused for lying outside the scope are counted (`violations`)
+//! and shown in diagnostics.
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tauri::{AppHandle, Manager};
+use tauri_plugin_fs::FsExt;
+
+use crate::audit::{AuditEntry, AuditKind, AuditLog};
+
+/// Why a path was refused. The `Display` form starts with a fixed phrase
+/// per variant ("path not found", "path outside allowed locations",
+/// "invalid path") for the UI to tell them apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PathViolation {
+    NotFound(String),
+    OutsideScope(String),
+    Invalid { path: String, reason: String },
+}
+
+impl fmt::Display for PathViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathViolation::NotFound(path) => write!(f, "path not found: {}", path),
+            PathViolation::OutsideScope(path) => write!(f, "path outside allowed locations: {}", path),
+            PathViolation::Invalid { path, reason } => write!(f, "invalid path: {} ({})", path, reason),
+        }
+    }
+}
+
+impl From<PathViolation> for String {
+    fn from(v: PathViolation) -> String {
+        v.to_string()
+    }
+}
+
+/// Reserved DOS device names, which open a device on Windows whatever the
+/// directory or extension.
+const DEVICE_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_device_name(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    DEVICE_NAMES.iter().any(|d| d.eq_ignore_ascii_case(stem))
+}
+
+/// Checks that need no filesystem access.
+fn check_lexically(input: &str) -> Result<&Path, PathViolation> {
+    let invalid = |reason: &str| PathViolation::Invalid { path: input.to_string(), reason: reason.to_string() };
+    if input.is_empty() {
+        return Err(invalid("empty"));
+    }
+    if input.contains('\0') {
+        return Err(invalid("contains a NUL byte"));
+    }
+    let path = Path::new(input);
+    if !path.is_absolute() {
+        return Err(invalid("not absolute"));
+    }
+    for component in path.components() {
+        match component {
+            Component::ParentDir => return Err(invalid("contains '..'")),
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Verbatim(_) | Prefix::VerbatimUNC(..) | Prefix::VerbatimDisk(_) | Prefix::DeviceNS(_) => {
+                    return Err(invalid("device or verbatim prefix"))
+                }
+                Prefix::UNC(..) | Prefix::Disk(_) => {}
+            },
+            Component::Normal(name) if cfg!(windows) && is_device_name(name) => {
+                return Err(invalid("reserved device name"))
+            }
+            _ => {}
+        }
+    }
+    Ok(path)
+}
+
+fn canonicalize(path: &Path, input: &str) -> Result<PathBuf, PathViolation> {
+    path.canonicalize().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => PathViolation::NotFound(input.to_string()),
+        _ => PathViolation::Invalid { path: input.to_string(), reason: e.to_string() },
+    })
+}
+
+/// `path` is `root` or below it. Windows and macOS filesystems are case
+/// insensitive by default, so components are compared ignoring case there.
+fn within(path: &Path, root: &Path) -> bool {
+    if !cfg!(any(windows, target_os = "macos")) {
+        return path.starts_with(root);
+    }
+    let mut path = path.components();
+    root.components().all(|r| {
+        path.next()
+            .is_some_and(|p| p.as_os_str().to_string_lossy().to_lowercase() == r.as_os_str().to_string_lossy().to_lowercase())
+    })
+}
+
+/// Managed state: the roots commands may touch.
+pub(crate) struct Scope {
+    app: AppHandle,
+    roots: Vec<PathBuf>,
+    output_dir: PathBuf,
+    violations: AtomicU64,
+}
+
+impl Scope {
+    /// `roots` that don't exist (yet) are skipped; `output_dir` must exist.
+    pub(crate) fn new(app: AppHandle, roots: &[PathBuf], output_dir: &Path) -> Result<Self, String> {
+        let roots = roots.iter().filter_map(|r| r.canonicalize().ok()).collect();
+        let output_dir = output_dir
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", output_dir.display(), e))?;
+        Ok(Scope { app, roots, output_dir, violations: AtomicU64::new(0) })
+    }
+
+    /// Paths refused as outside the scope since launch.
+    pub(crate) fn violations(&self) -> u64 {
+        self.violations.load(Ordering::Relaxed)
+    }
+
+    fn outside(&self, input: &str) -> PathViolation {
+        self.violations.fetch_add(1, Ordering::Relaxed);
+        eprintln!("[djbot] refused path outside allowed locations: {}", input);
+        if let Some(audit) = self.app.try_state::<AuditLog>() {
+            audit.record(
+                AuditEntry::new(AuditKind::PathViolation, "scope")
+                    .subject(input)
+                    .failed("outside allowed locations"),
+            );
+        }
+        PathViolation::OutsideScope(input.to_string())
+    }
+
+    fn allowed(&self, path: &Path) -> bool {
+        self.roots.iter().any(|r| within(path, r)) || self.app.fs_scope().is_allowed(path)
+    }
+
+    /// Canonical form of an existing path inside the scope.
+    pub(crate) fn resolve(&self, input: &str) -> Result<PathBuf, PathViolation> {
+        let resolved = canonicalize(check_lexically(input)?, input)?;
+        if self.allowed(&resolved) {
+            Ok(resolved)
+        } else {
+            Err(self.outside(input))
+        }
+    }
+
+    /// Like `resolve`, but only the output directory counts.
+    pub(crate) fn resolve_output(&self, input: &str) -> Result<PathBuf, PathViolation> {
+        let resolved = canonicalize(check_lexically(input)?, input)?;
+        if within(&resolved, &self.output_dir) {
+            Ok(resolved)
+        } else {
+            Err(self.outside(input))
+        }
+    }
+
+    /// An existing `relative` path below `root`, which must itself be one of
+    /// the scope's roots (or below one). For commands that name a location
+    /// by kind instead of taking an absolute path from the frontend.
+    pub(crate) fn resolve_in(&self, root: &Path, relative: &str) -> Result<PathBuf, PathViolation> {
+        let invalid = |reason: &str| PathViolation::Invalid { path: relative.to_string(), reason: reason.to_string() };
+        let rel = Path::new(relative);
+        if relative.contains('\0') {
+            return Err(invalid("contains a NUL byte"));
+        }
+        if rel.has_root() || rel.components().any(|c| matches!(c, Component::Prefix(_))) {
+            return Err(invalid("not relative"));
+        }
+        let full = root.join(rel).to_string_lossy().into_owned();
+        let resolved = canonicalize(check_lexically(&full)?, relative)?;
+        let root = canonicalize(root, relative)?;
+        if self.roots.iter().any(|r| within(&root, r)) && within(&resolved, &root) {
+            Ok(resolved)
+        } else {
+            Err(self.outside(relative))
+        }
+    }
+
+    /// A path that is about to be created: its parent must exist inside the
+    /// scope. An existing target is resolved like `resolve`.
+    pub(crate) fn resolve_new(&self, input: &str) -> Result<PathBuf, PathViolation> {
+        let path = check_lexically(input)?;
+        if path.exists() {
+            return self.resolve(input);
+        }
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(PathViolation::Invalid { path: input.to_string(), reason: "no file name".to_string() });
+        };
+        let parent = canonicalize(parent, input)?;
+        if self.allowed(&parent) {
+            Ok(parent.join(name))
+        } else {
+            Err(self.outside(input))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(input: &str) -> String {
+        match check_lexically(input) {
+            Err(PathViolation::Invalid { reason, .. }) => reason,
+            other => panic!("{:?} was not refused as invalid: {:?}", input, other),
+        }
+    }
+
+    #[cfg(unix)]
+    const ABS: &str = "/music/set";
+    #[cfg(windows)]
+    const ABS: &str = r"C:\music\set";
+
+    #[test]
+    fn plain_absolute_paths_pass() {
+        assert_eq!(check_lexically(ABS).unwrap(), Path::new(ABS));
+        // A trailing separator names the same directory.
+        let trailing = format!("{}{}", ABS, std::path::MAIN_SEPARATOR);
+        assert!(check_lexically(&trailing).is_ok());
+    }
+
+    #[test]
+    fn empty_relative_nul_and_parent_are_invalid() {
+        assert_eq!(reason(""), "empty");
+        assert_eq!(reason("music/set"), "not absolute");
+        assert_eq!(reason(&format!("{}\0.mp3", ABS)), "contains a NUL byte");
+        let parent = Path::new(ABS).join("..").join("secrets");
+        assert_eq!(reason(&parent.to_string_lossy()), "contains '..'");
+    }
+
+    #[test]
+    fn device_names_match_whatever_the_case_or_extension() {
+        for name in ["CON", "nul", "Com1", "lpt9.txt", "aux .wav", "PRN.tar.gz"] {
+            assert!(is_device_name(OsStr::new(name)), "{}", name);
+        }
+        for name in ["CONSOLE", "com10", "nul_mix.wav", "lpt", "track.con"] {
+            assert!(!is_device_name(OsStr::new(name)), "{}", name);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_prefixes_and_device_names() {
+        assert!(check_lexically(r"\\server\share\set.mp3").is_ok(), "UNC paths are allowed");
+        assert_eq!(reason(r"\\?\C:\music\set.mp3"), "device or verbatim prefix");
+        assert_eq!(reason(r"\\?\UNC\server\share\set.mp3"), "device or verbatim prefix");
+        assert_eq!(reason(r"\\.\PhysicalDrive0"), "device or verbatim prefix");
+        assert_eq!(reason(r"C:\music\CON.mp3"), "reserved device name");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn windows_syntax_is_just_a_name_on_unix() {
+        // No prefixes here: these are relative paths, or names that happen
+        // to contain backslashes.
+        assert_eq!(reason(r"\\server\share\set.mp3"), "not absolute");
+        assert_eq!(reason(r"\\?\C:\music\set.mp3"), "not absolute");
+        assert!(check_lexically("/music/CON.mp3").is_ok());
+    }
+
+    #[test]
+    fn within_compares_whole_components() {
+        let root = Path::new(ABS);
+        assert!(within(root, root));
+        assert!(within(&root.join("mix.mp3"), root));
+        let sibling = format!("{}-old", ABS);
+        assert!(!within(Path::new(&sibling), root));
+        assert!(!within(root.parent().unwrap(), root));
+    }
+
+    #[test]
+    fn within_follows_the_platform_case_rules() {
+        let root = Path::new(ABS);
+        let shouting = PathBuf::from(ABS.to_uppercase()).join("mix.mp3");
+        assert_eq!(within(&shouting, root), cfg!(any(windows, target_os = "macos")));
+    }
+
+    #[test]
+    fn canonicalize_reports_missing_paths_as_not_found() {
+        let missing = std::env::temp_dir().join(format!("djbot-paths-missing-{}", std::process::id()));
+        let input = missing.to_string_lossy().into_owned();
+        assert_eq!(canonicalize(&missing, &input), Err(PathViolation::NotFound(input.clone())));
+        assert!(canonicalize(&std::env::temp_dir(), &input).is_ok());
+    }
+
+    #[test]
+    fn each_violation_displays_its_fixed_phrase() {
+        let cases = [
+            (PathViolation::NotFound("a".into()), "path not found: a"),
+            (PathViolation::OutsideScope("b".into()), "path outside allowed locations: b"),
+            (PathViolation::Invalid { path: "c".into(), reason: "empty".into() }, "invalid path: c (empty)"),
+        ];
+        for (violation, text) in cases {
+            assert_eq!(String::from(violation), text);
+        }
+    }
+}
@@ -0,0 +1,4396 @@
+//! The Go worker integration as a Tauri plugin (`djbot-worker`).
+//!
+//! Everything that manages the worker process — its state, the commands the
+//! webview calls and the startup/shutdown hooks — lives here so the app
+//! entry point only has to register the plugin.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::process::{Child, Command, Stdio};
+use std::io::BufReader;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tauri::plugin::{Builder, TauriPlugin};
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, Manager, RunEvent, State, Wry};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tokio::sync::{broadcast, watch};
+
+use crate::archive::ArchiveInfo;
+use crate::audio::{AudioDevice, AudioEngineOptions, AudioWatch, EffectiveAudio};
+use crate::audit::{AuditEntry, AuditKind, AuditLog};
+use crate::artwork::{AlbumArt, ArtworkCache};
+use crate::bounded::{BoundedBuf, BufStats};
+use crate::checkpoint::{QueueCheckpoint, QueueItem};
+use crate::cli::{CliCommand, ExportArgs};
+use crate::cache::CacheBudget;
+use crate::clock::ClockSkew;
+use crate::confirm::{ConfirmationRequest, Confirmations};
+use crate::config::{AudioEngineConfig, CrashPolicy, DeviceChangeBehavior, DjbotConfig, RestartPolicy, WorkerConfig, WorkerConfigOverride};
+use crate::discovery::{DiscoveryReport, SidecarDiscovery};
+use crate::disk::DiskPerformance;
+use crate::errors::{AppError, ErrorCode};
+use crate::events::{EventBus, EventMetrics, JobProgress, WorkerEvent};
+use crate::handles::HandleUsage;
+use crate::import::{ImportRequest, PendingImport};
+use crate::integrity::InstallCheck;
+use crate::job_log::{ExportFormat, JobLog, JobLogEntry, JobLogFilter, JobLogPage, Pagination};
+use crate::jobs::{JobEstimate, JobHistory, JobRecord};
+use crate::library::{IndexedTrack, TrackFilters, TrackIndex};
+use crate::logs::StderrFilter;
+use crate::media::{MediaSession, NowPlaying};
+use crate::network::{NetworkMonitor, NetworkStatus, PendingUrlJobFinished};
+use crate::presence::{DiscordPresence, PresenceStatus, SessionInfo};
+use crate::output_activity::{OutputActivity, OutputDirActivity};
+use crate::paths::Scope;
+use crate::protocol::{TypedEvent, WorkerMessage};
+use crate::proxy::WorkerHttp;
+use crate::ratelimit::{BucketState, RateLimiter};
+use crate::runtime::MissingRuntime;
+use crate::schedule::{Schedule, ScheduleDeferred, ScheduleRun, ScheduleView, ScheduledJob, Scheduler, Timing};
+use crate::secrets::CredentialStatus;
+use crate::signature::{SignatureStatus, WorkerSignature};
+use crate::tags::{TagCache, TagResult};
+use crate::transfer::{DownloadInfo, Downloads, TransferLimiter};
+use crate::waveforms::WaveformCache;
+use crate::webhooks::{JobFinished, WebhookDelivery, Webhooks};
+use crate::{archive, audio, cli, clock, config, data_dir, discovery, disk, ffmpeg, formats, fs_scope, handles, import, intake, integrity, jobs, library, logs, media, memory, network, output_watch, protocol, proxy, runtime, sanitize, secrets, share, signature, transfer, url_guard, worker, worker_cache, worker_update};
+
+include!("commands.rs");
+
+/// How often the background job persists `WorkerState` to disk.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const SNAPSHOT_FILE: &str = "state_snapshot.json";
+/// Log lines a `stream_worker_log` subscriber may fall behind by before it
+/// skips ahead.
+const LOG_BROADCAST_CAPACITY: usize = 1024;
+/// Byte cap on the worker stderr lines kept for diagnostics; the line cap
+/// is `WorkerConfig::log_buffer_capacity`.
+const STDERR_TAIL_BYTES: usize = 256 * 1024;
+/// Total time allowed for checking where the worker binary lives.
+const DISCOVERY_BUDGET: Duration = Duration::from_secs(2);
+
+/// Lifecycle of the worker process as seen from the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WorkerStatus {
+    /// `auto_start_worker` is off and the worker hasn't been started yet.
+    NotStarted,
+    Starting,
+    Ready,
+    /// The process is alive but we can no longer read its output, so port
+    /// updates and exit detection are unreliable.
+    Degraded,
+    Stopped,
+    Failed,
+    /// Exited on startup because Windows couldn't load a runtime library;
+    /// see `WorkerState::missing_runtime`. Not restarted automatically.
+    MissingRuntime,
+}
+
+/// How the ffmpeg binary in use was found at startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FfmpegSource {
+    /// `worker.ffmpeg_path_override` from config.toml.
+    UserOverride,
+    /// Shipped next to the worker sidecar in the resource directory.
+    Bundled,
+    /// Bare `ffmpeg` resolved through PATH.
+    Path,
+    /// One of the per-platform install locations in `find_ffmpeg`, named
+    /// by what usually puts it there (`homebrew`, `scoop`, …).
+    PlatformSpecific(String),
+    /// Reused from the previous session's state snapshot file.
+    CacheFile,
+}
+
+/// Callback registered with `WorkerState::register_on_ready`.
+type OnReady = Box<dyn Fn(u16) + Send + 'static>;
+
+#[derive(Clone)]
+pub(crate) struct WorkerState {
+    status: Arc<Mutex<WorkerStatus>>,
+    /// Port the worker is listening on. A watch channel so readers never
+    /// block the stdout reader.
+    port: Arc<watch::Sender<Option<u16>>>,
+    /// Worker log lines, filtered, sanitized and coalesced, for
+    /// `stream_worker_log`.
+    log_broadcast: broadcast::Sender<String>,
+    /// Run with the port each time a worker becomes ready.
+    on_ready_callbacks: Arc<Mutex<Vec<OnReady>>>,
+    /// Woken when the worker reports its port, so async callers can await
+    /// readiness instead of polling.
+    ready_notify: Arc<tokio::sync::Notify>,
+    /// `WorkerConfig::health_check_interval_secs`; the health loop wakes
+    /// up when it changes.
+    health_interval: Arc<watch::Sender<u64>>,
+    /// Absolute path to the app data directory used by the Go worker.
+    /// Stored here so `get_output_dir` stays consistent with what we passed
+    /// to the worker via `--data-dir`.
+    data_dir: Arc<Mutex<Option<std::path::PathBuf>>>,
+    /// Worker binary selected at startup.
+    sidecar_path: Arc<Mutex<Option<std::path::PathBuf>>>,
+    /// ffmpeg binary handed to the worker via `--ffmpeg`, if one was found.
+    ffmpeg_path: Arc<Mutex<Option<String>>>,
+    /// Version reported by `ffmpeg -version`, e.g. `6.1.1`.
+    ffmpeg_version: Arc<Mutex<Option<String>>>,
+    /// `None` if no ffmpeg was found.
+    ffmpeg_source: Arc<Mutex<Option<FfmpegSource>>>,
+    /// Number of times the worker had to be started again after a previous
+    /// run ended without a clean shutdown. Carried across crashes by the
+    /// state snapshot.
+    restart_count: Arc<Mutex<u32>>,
+    /// Set when the worker was deliberately not started (e.g. it failed
+    /// integrity verification), so callers get the reason instead of
+    /// waiting forever.
+    startup_error: Arc<Mutex<Option<String>>>,
+    /// The running worker process, if any.
+    child: Arc<Mutex<Option<Child>>>,
+    /// Bumped every time a worker is launched or killed, so the output
+    /// reader of a replaced process knows to leave the state alone.
+    generation: Arc<AtomicU64>,
+    /// Last lines the worker wrote to stderr.
+    stderr_tail: Arc<Mutex<BoundedBuf<String>>>,
+    /// `OutputConfig::dir_mode` in effect for directories we create.
+    dir_mode: Arc<Mutex<u32>>,
+    /// Sidecar binary as it was when the running worker was spawned.
+    spawned_binary: Arc<Mutex<Option<integrity::BinaryFingerprint>>>,
+    /// `WorkerConfig::job_timeout_secs` currently enforced by the watchdog.
+    job_timeout_secs: Arc<Mutex<Option<u64>>>,
+    /// `DjbotConfig::on_worker_crash`, consulted when the worker exits.
+    crash_policy: Arc<Mutex<CrashPolicy>>,
+    last_crash: Arc<Mutex<Option<WorkerCrash>>>,
+    /// `WorkerConfig::output_bit_depth` applied to mix exports.
+    output_bit_depth: Arc<Mutex<Option<u8>>>,
+    /// Redacted copy of the worker environment, taken once at launch.
+    env_snapshot: Arc<Mutex<std::collections::BTreeMap<String, String>>>,
+    audio: Arc<Mutex<AudioState>>,
+    /// Working directory the running worker was started in.
+    worker_cwd: Arc<Mutex<Option<std::path::PathBuf>>>,
+    /// Resident memory of the running worker shortly after it got ready.
+    startup_rss_bytes: Arc<Mutex<Option<u64>>>,
+    /// Health checks failed in a row; reset by a passing one.
+    heartbeat_missed: Arc<AtomicU32>,
+    /// `WorkerConfig::max_missed_heartbeats`.
+    max_missed_heartbeats: Arc<AtomicU32>,
+    /// Automatic restarts since a worker last got ready; compared with
+    /// `WorkerConfig::max_start_retries`.
+    start_retries: Arc<AtomicU32>,
+    /// From the running worker's `VERSION:` line.
+    worker_version: Arc<Mutex<Option<String>>>,
+    /// Downloaded worker version that was spawned and hasn't completed its
+    /// first handshake yet.
+    unconfirmed_update: Arc<Mutex<Option<String>>>,
+    /// `WorkerConfig::config_hash` of the config the running worker was
+    /// spawned with.
+    spawn_config_hash: Arc<Mutex<Option<u64>>>,
+    /// Threads and tasks of the latest spawn.
+    session: Arc<Mutex<Option<WorkerSession>>>,
+    /// Why the last worker couldn't start, with status `MissingRuntime`.
+    missing_runtime: Arc<Mutex<Option<MissingRuntime>>>,
+}
+
+/// Audio engine settings of the running worker.
+#[derive(Debug, Clone, Default)]
+struct AudioState {
+    /// What the worker was launched (or live-reconfigured) with.
+    requested: AudioEngineConfig,
+    /// What it reported back; `None` until its `AUDIO:` line arrives.
+    effective: Option<EffectiveAudio>,
+    /// Saved settings differ from `requested` and need a restart.
+    restart_required: bool,
+}
+
+/// Serializable copy of `WorkerState`, written to `{data_dir}/state_snapshot.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct WorkerSnapshot {
+    port: Option<u16>,
+    data_dir: Option<std::path::PathBuf>,
+    ffmpeg_path: Option<String>,
+    ffmpeg_version: Option<String>,
+    restart_count: u32,
+}
+
+/// djbot helpers on the app handle, for code holding an `AppHandle` rather
+/// than the managed state behind them.
+pub(crate) trait DjbotAppExt {
+    /// Queue `event` on the `EventBus` for the webview.
+    fn emit_worker_event(&self, event: WorkerEvent);
+    /// The worker's port if it is taking commands.
+    fn worker_port(&self) -> Result<u16, AppError>;
+    /// `{data_dir}/output` (under the current directory before setup has
+    /// set the data dir). Not created here.
+    fn output_dir(&self) -> std::path::PathBuf;
+}
+
+impl DjbotAppExt for AppHandle {
+    fn emit_worker_event(&self, event: WorkerEvent) {
+        self.state::<EventBus>().send(event);
+    }
+
+    fn worker_port(&self) -> Result<u16, AppError> {
+        self.state::<WorkerState>().command_port()
+    }
+
+    fn output_dir(&self) -> std::path::PathBuf {
+        self.state::<WorkerState>().data_dir_path().join("output")
+    }
+}
+
+// Accessors copy values out and release the lock immediately, so callers
+// never hold state locks across I/O or logging.
+impl WorkerState {
+    fn new() -> Self {
+        WorkerState {
+            status:        Arc::new(Mutex::new(WorkerStatus::Starting)),
+            port:          Arc::new(watch::Sender::new(None)),
+            ready_notify:  Arc::new(tokio::sync::Notify::new()),
+            on_ready_callbacks: Arc::default(),
+            log_broadcast: broadcast::Sender::new(LOG_BROADCAST_CAPACITY),
+            health_interval: Arc::new(watch::Sender::new(WorkerConfig::default().health_check_interval_secs)),
+            data_dir:      Arc::new(Mutex::new(None)),
+            sidecar_path:  Arc::new(Mutex::new(None)),
+            ffmpeg_path:   Arc::new(Mutex::new(None)),
+            ffmpeg_version: Arc::new(Mutex::new(None)),
+            ffmpeg_source: Arc::new(Mutex::new(None)),
+            restart_count: Arc::new(Mutex::new(0)),
+            startup_error: Arc::new(Mutex::new(None)),
+            child:         Arc::new(Mutex::new(None)),
+            generation:    Arc::new(AtomicU64::new(0)),
+            stderr_tail:   Arc::new(Mutex::new(BoundedBuf::new(WorkerConfig::default().log_buffer_capacity, STDERR_TAIL_BYTES))),
+            dir_mode:      Arc::new(Mutex::new(config::OutputConfig::default().dir_mode)),
+            spawned_binary: Arc::new(Mutex::new(None)),
+            job_timeout_secs: Arc::new(Mutex::new(None)),
+            output_bit_depth: Arc::new(Mutex::new(None)),
+            crash_policy: Arc::new(Mutex::new(CrashPolicy::default())),
+            last_crash: Arc::new(Mutex::new(None)),
+            env_snapshot:  Arc::new(Mutex::new(Default::default())),
+            audio:         Arc::new(Mutex::new(AudioState::default())),
+            worker_cwd:    Arc::new(Mutex::new(None)),
+            startup_rss_bytes: Arc::new(Mutex::new(None)),
+            heartbeat_missed: Arc::new(AtomicU32::new(0)),
+            max_missed_heartbeats: Arc::new(AtomicU32::new(WorkerConfig::default().max_missed_heartbeats)),
+            start_retries: Arc::new(AtomicU32::new(0)),
+            worker_version: Arc::new(Mutex::new(None)),
+            unconfirmed_update: Arc::new(Mutex::new(None)),
+            spawn_config_hash: Arc::new(Mutex::new(None)),
+            session: Arc::new(Mutex::new(None)),
+            missing_runtime: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn worker_version(&self) -> Option<String> {
+        self.worker_version.lock().unwrap().clone()
+    }
+
+    fn set_worker_version(&self, version: Option<String>) {
+        *self.worker_version.lock().unwrap() = version;
+    }
+
+    fn set_unconfirmed_update(&self, version: Option<String>) {
+        *self.unconfirmed_update.lock().unwrap() = version;
+    }
+
+    fn take_unconfirmed_update(&self) -> Option<String> {
+        self.unconfirmed_update.lock().unwrap().take()
+    }
+
+    fn status(&self) -> WorkerStatus {
+        *self.status.lock().unwrap()
+    }
+
+    fn set_status(&self, status: WorkerStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    /// Set the status to `to` only if it is still `from`.
+    fn replace_status(&self, from: WorkerStatus, to: WorkerStatus) -> bool {
+        let mut status = self.status.lock().unwrap();
+        let replaced = *status == from;
+        if replaced {
+            *status = to;
+        }
+        replaced
+    }
+
+    pub(crate) fn port(&self) -> Option<u16> {
+        *self.port.borrow()
+    }
+
+    /// True only once the worker has reported its port and is `Ready`;
+    /// requests sent while it is still starting could reach it before it
+    /// has finished initialising.
+    pub(crate) fn is_accepting_commands(&self) -> bool {
+        self.status() == WorkerStatus::Ready && self.port().is_some()
+    }
+
+    /// Port to send a request to, or an error if the worker can't take one.
+    fn command_port(&self) -> Result<u16, AppError> {
+        match self.port() {
+            Some(port) if self.is_accepting_commands() => Ok(port),
+            _ => Err(AppError::new(ErrorCode::WorkerNotReady, format!("Worker not ready yet ({:?})", self.status()))
+                .param("status", self.status())),
+        }
+    }
+
+    fn require_ffmpeg(&self) -> Result<String, AppError> {
+        self.ffmpeg_path().ok_or_else(|| AppError::new(ErrorCode::ToolsFfmpegMissing, "ffmpeg not found"))
+    }
+
+    fn require_data_dir(&self) -> Result<std::path::PathBuf, AppError> {
+        self.data_dir().ok_or_else(|| AppError::new(ErrorCode::AppDataDirMissing, "data dir not initialised"))
+    }
+
+    fn set_port(&self, port: Option<u16>) {
+        self.port.send_replace(port);
+        if port.is_some() {
+            self.ready_notify.notify_waiters();
+        }
+    }
+
+    /// Call `callback` with the port every time a worker (including a
+    /// restarted one) reports ready. Callbacks run on the stdout reader
+    /// thread, so anything slow should be spawned; they must not register
+    /// further callbacks.
+    fn register_on_ready(&self, callback: impl Fn(u16) + Send + 'static) {
+        self.on_ready_callbacks.lock().unwrap().push(Box::new(callback));
+    }
+
+    fn run_on_ready_callbacks(&self, port: u16) {
+        for callback in self.on_ready_callbacks.lock().unwrap().iter() {
+            callback(port);
+        }
+    }
+
+    /// Resolve once the worker has reported its port, or fail after `timeout`.
+    async fn wait_for_port(&self, timeout: Duration) -> Result<u16, String> {
+        // Registered before the check, so a port reported in between still
+        // wakes us.
+        let mut notified = std::pin::pin!(self.ready_notify.notified());
+        notified.as_mut().enable();
+        if let Some(port) = self.port() {
+            return Ok(port);
+        }
+        tokio::time::timeout(timeout, notified)
+            .await
+            .map_err(|_| "Timed out waiting for worker".to_string())?;
+        self.port().ok_or_else(|| "Worker stopped before it was ready".to_string())
+    }
+
+    fn data_dir(&self) -> Option<std::path::PathBuf> {
+        self.data_dir.lock().unwrap().clone()
+    }
+
+    /// The data dir, or the current directory before setup has set it.
+    fn data_dir_path(&self) -> std::path::PathBuf {
+        self.data_dir().unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+    }
+
+    fn set_data_dir(&self, dir: std::path::PathBuf) {
+        *self.data_dir.lock().unwrap() = Some(dir);
+    }
+
+    fn sidecar_path(&self) -> Option<std::path::PathBuf> {
+        self.sidecar_path.lock().unwrap().clone()
+    }
+
+    fn set_sidecar_path(&self, path: std::path::PathBuf) {
+        *self.sidecar_path.lock().unwrap() = Some(path);
+    }
+
+    fn ffmpeg_path(&self) -> Option<String> {
+        self.ffmpeg_path.lock().unwrap().clone()
+    }
+
+    fn set_ffmpeg_path(&self, path: Option<String>) {
+        *self.ffmpeg_path.lock().unwrap() = path;
+    }
+
+    fn ffmpeg_version(&self) -> Option<String> {
+        self.ffmpeg_version.lock().unwrap().clone()
+    }
+
+    fn set_ffmpeg_version(&self, version: Option<String>) {
+        *self.ffmpeg_version.lock().unwrap() = version;
+    }
+
+    fn ffmpeg_source(&self) -> Option<FfmpegSource> {
+        self.ffmpeg_source.lock().unwrap().clone()
+    }
+
+    fn set_ffmpeg_source(&self, source: Option<FfmpegSource>) {
+        *self.ffmpeg_source.lock().unwrap() = source;
+    }
+
+    fn restart_count(&self) -> u32 {
+        *self.restart_count.lock().unwrap()
+    }
+
+    fn set_restart_count(&self, count: u32) {
+        *self.restart_count.lock().unwrap() = count;
+    }
+
+    fn startup_error(&self) -> Option<String> {
+        self.startup_error.lock().unwrap().clone()
+    }
+
+    fn set_startup_error(&self, err: String) {
+        *self.startup_error.lock().unwrap() = Some(err);
+    }
+
+    fn clear_startup_error(&self) {
+        *self.startup_error.lock().unwrap() = None;
+    }
+
+    fn dir_mode(&self) -> u32 {
+        *self.dir_mode.lock().unwrap()
+    }
+
+    fn set_dir_mode(&self, mode: u32) {
+        *self.dir_mode.lock().unwrap() = mode;
+    }
+
+    fn spawned_binary(&self) -> Option<integrity::BinaryFingerprint> {
+        self.spawned_binary.lock().unwrap().clone()
+    }
+
+    fn set_spawned_binary(&self, fingerprint: Option<integrity::BinaryFingerprint>) {
+        *self.spawned_binary.lock().unwrap() = fingerprint;
+    }
+
+    /// Make `session` the current one, shutting down the one before.
+    fn start_session(&self, session: WorkerSession) {
+        let previous = self.session.lock().unwrap().replace(session);
+        if let Some(previous) = previous {
+            previous.shut_down();
+        }
+    }
+
+    fn missing_runtime(&self) -> Option<MissingRuntime> {
+        self.missing_runtime.lock().unwrap().clone()
+    }
+
+    fn set_missing_runtime(&self, missing: Option<MissingRuntime>) {
+        *self.missing_runtime.lock().unwrap() = missing;
+    }
+
+    fn set_spawn_config_hash(&self, hash: u64) {
+        *self.spawn_config_hash.lock().unwrap() = Some(hash);
+    }
+
+    /// Whether `current` differs from the config the running worker was
+    /// spawned with, audio settings included. `false` without a worker.
+    fn config_changed_since_spawn(&self, current: &WorkerConfig) -> bool {
+        if !self.worker_running() {
+            return false;
+        }
+        let spawned = *self.spawn_config_hash.lock().unwrap();
+        spawned.is_some_and(|hash| hash != current.config_hash()) || self.audio().restart_required
+    }
+
+    fn job_timeout_secs(&self) -> Option<u64> {
+        *self.job_timeout_secs.lock().unwrap()
+    }
+
+    fn set_job_timeout_secs(&self, secs: Option<u64>) {
+        *self.job_timeout_secs.lock().unwrap() = secs;
+    }
+
+    fn crash_policy(&self) -> CrashPolicy {
+        *self.crash_policy.lock().unwrap()
+    }
+
+    fn set_crash_policy(&self, policy: CrashPolicy) {
+        *self.crash_policy.lock().unwrap() = policy;
+    }
+
+    fn last_crash(&self) -> Option<WorkerCrash> {
+        self.last_crash.lock().unwrap().clone()
+    }
+
+    fn record_crash(&self, crash: WorkerCrash) {
+        *self.last_crash.lock().unwrap() = Some(crash);
+    }
+
+    fn output_bit_depth(&self) -> Option<u8> {
+        *self.output_bit_depth.lock().unwrap()
+    }
+
+    fn set_output_bit_depth(&self, bits: Option<u8>) {
+        *self.output_bit_depth.lock().unwrap() = bits;
+    }
+
+    fn env_snapshot(&self) -> std::collections::BTreeMap<String, String> {
+        self.env_snapshot.lock().unwrap().clone()
+    }
+
+    fn set_env_snapshot(&self, env: std::collections::BTreeMap<String, String>) {
+        *self.env_snapshot.lock().unwrap() = env;
+    }
+
+    fn worker_cwd(&self) -> Option<std::path::PathBuf> {
+        self.worker_cwd.lock().unwrap().clone()
+    }
+
+    fn set_worker_cwd(&self, cwd: std::path::PathBuf) {
+        *self.worker_cwd.lock().unwrap() = Some(cwd);
+    }
+
+    fn startup_rss_bytes(&self) -> Option<u64> {
+        *self.startup_rss_bytes.lock().unwrap()
+    }
+
+    fn set_startup_rss_bytes(&self, bytes: Option<u64>) {
+        *self.startup_rss_bytes.lock().unwrap() = bytes;
+    }
+
+    fn audio(&self) -> AudioState {
+        self.audio.lock().unwrap().clone()
+    }
+
+    /// A worker was launched with `requested`.
+    fn set_spawned_audio(&self, requested: AudioEngineConfig) {
+        *self.audio.lock().unwrap() = AudioState { requested, ..Default::default() };
+    }
+
+    fn set_effective_audio(&self, effective: EffectiveAudio) {
+        self.audio.lock().unwrap().effective = Some(effective);
+    }
+
+    /// New settings were saved: either applied live, or pending a restart.
+    fn update_requested_audio(&self, requested: AudioEngineConfig, applied: bool) {
+        let mut audio = self.audio.lock().unwrap();
+        if applied {
+            audio.requested = requested;
+            audio.restart_required = false;
+        } else {
+            audio.restart_required = requested != audio.requested;
+        }
+    }
+
+    fn push_stderr(&self, line: String) {
+        self.stderr_tail.lock().unwrap().push(line);
+    }
+
+    fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn set_stderr_tail_capacity(&self, lines: usize) {
+        self.stderr_tail.lock().unwrap().set_max_entries(lines);
+    }
+
+    fn stderr_tail_stats(&self) -> BufStats {
+        self.stderr_tail.lock().unwrap().stats()
+    }
+
+    fn worker_running(&self) -> bool {
+        self.child.lock().unwrap().is_some()
+    }
+
+    /// Record `child` as the current worker and return its generation.
+    fn set_child(&self, child: Child) -> u64 {
+        let mut slot = self.child.lock().unwrap();
+        *slot = Some(child);
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Kill the current worker, if any, and wait for it to exit.
+    fn kill_worker(&self) {
+        let child = {
+            let mut slot = self.child.lock().unwrap();
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            slot.take()
+        };
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+            self.set_port(None);
+            self.set_status(WorkerStatus::Stopped);
+        }
+    }
+
+    /// Kill the worker of `generation` but leave its exit to the exit
+    /// watcher, which then applies the restart policy as for a crash.
+    fn kill_generation(&self, generation: u64) {
+        let mut slot = self.child.lock().unwrap();
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if let Some(child) = slot.as_mut() {
+            let _ = child.kill();
+        }
+    }
+
+    /// Ask the worker to shut down gracefully (SIGTERM). Needed on Linux,
+    /// where it runs in its own session and gets no SIGHUP from us.
+    #[cfg(target_os = "linux")]
+    fn terminate_worker(&self) {
+        let slot = self.child.lock().unwrap();
+        if let Some(child) = slot.as_ref() {
+            // SAFETY: plain kill(2) on the pid of a child we haven't reaped.
+            unsafe {
+                libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+
+    /// Process id of the running worker.
+    fn worker_pid(&self) -> Option<u32> {
+        self.child.lock().unwrap().as_ref().map(|c| c.id())
+    }
+
+    /// Poll the worker of `generation` for exit. `None` once that process
+    /// has been replaced or killed through `kill_worker`.
+    fn poll_worker(&self, generation: u64) -> Option<std::io::Result<Option<std::process::ExitStatus>>> {
+        let mut slot = self.child.lock().unwrap();
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return None;
+        }
+        let result = slot.as_mut()?.try_wait();
+        if !matches!(result, Ok(None)) {
+            *slot = None;
+        }
+        Some(result)
+    }
+
+    fn snapshot(&self) -> WorkerSnapshot {
+        WorkerSnapshot {
+            port: self.port(),
+            data_dir: self.data_dir(),
+            ffmpeg_path: self.ffmpeg_path(),
+            ffmpeg_version: self.ffmpeg_version(),
+            restart_count: self.restart_count(),
+        }
+    }
+}
+
+fn snapshot_path(data_dir: &std::path::Path) -> std::path::PathBuf {
+    data_dir.join(SNAPSHOT_FILE)
+}
+
+/// Write the current state to `{data_dir}/state_snapshot.json`.
+///
+/// The file is written to a temporary sibling first and renamed into place so
+/// a crash mid-write never leaves a truncated snapshot behind.
+fn save_state_snapshot(state: &WorkerState) -> Result<(), String> {
+    let snapshot = state.snapshot();
+    let data_dir = snapshot
+        .data_dir
+        .clone()
+        .ok_or_else(|| "data dir not initialised".to_string())?;
+    let json = serde_json::to_vec_pretty(&snapshot).map_err(|e| e.to_string())?;
+    let path = snapshot_path(&data_dir);
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+/// Read a snapshot left behind by a previous session, if any.
+fn load_state_snapshot(data_dir: &std::path::Path) -> Option<WorkerSnapshot> {
+    let bytes = std::fs::read(snapshot_path(data_dir)).ok()?;
+    match serde_json::from_slice(&bytes) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            eprintln!("[djbot] ignoring unreadable state snapshot: {}", e);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+fn get_worker_port(state: State<WorkerState>) -> Result<u16, AppError> {
+    if let Some(err) = state.startup_error() {
+        return Err(AppError::new(ErrorCode::WorkerStartupFailed, err.clone()).param("detail", err));
+    }
+    if state.status() == WorkerStatus::NotStarted {
+        return Err(AppError::new(ErrorCode::WorkerNotStarted, "Worker has not been started (auto-start is off)"));
+    }
+    state
+        .port()
+        .ok_or_else(|| AppError::new(ErrorCode::WorkerNotReady, "Worker not ready yet").param("status", state.status()))
+}
+
+#[tauri::command]
+fn get_worker_status(state: State<WorkerState>) -> WorkerStatusReport {
+    let audio = state.audio();
+    WorkerStatusReport {
+        status: state.status(),
+        audio_mismatches: audio.effective.as_ref().map(|e| e.mismatches(&audio.requested)).unwrap_or_default(),
+        audio: audio.effective,
+        restart_required: audio.restart_required,
+        missing_runtime: state.missing_runtime(),
+    }
+}
+
+#[derive(Serialize)]
+struct FfmpegPath {
+    path: Option<String>,
+    source: Option<FfmpegSource>,
+}
+
+#[tauri::command]
+fn get_ffmpeg_path(state: State<WorkerState>) -> FfmpegPath {
+    FfmpegPath { path: state.ffmpeg_path(), source: state.ffmpeg_source() }
+}
+
+#[tauri::command]
+fn get_ffmpeg_version(state: State<WorkerState>) -> Option<String> {
+    state.ffmpeg_version()
+}
+
+/// Hardware decoding methods (`ffmpeg -hwaccels`) the located ffmpeg
+/// supports, for `set_preferred_hwaccel`.
+#[tauri::command]
+async fn list_hwaccels(state: State<'_, WorkerState>) -> Result<Vec<String>, String> {
+    let ffmpeg = state.ffmpeg_path().ok_or_else(|| "ffmpeg not found".to_string())?;
+    tauri::async_runtime::spawn_blocking(move || ffmpeg::hwaccels(&ffmpeg))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "could not run ffmpeg -hwaccels".to_string())
+}
+
+/// Persist the hardware decoding method ffmpeg tries first (`None` for
+/// software only). Fades and streaming copies use it straight away, the
+/// worker from its next start; a method that fails to initialise falls
+/// back to software decoding.
+#[tauri::command]
+async fn set_preferred_hwaccel(state: State<'_, WorkerState>, hwaccel: Option<String>) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let hwaccel = hwaccel.filter(|h| !h.is_empty());
+    if let Some(name) = &hwaccel {
+        let ffmpeg = state.ffmpeg_path().ok_or_else(|| "ffmpeg not found".to_string())?;
+        let supported = tauri::async_runtime::spawn_blocking(move || ffmpeg::hwaccels(&ffmpeg))
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default();
+        if !supported.contains(name) {
+            return Err(format!("this ffmpeg doesn't support -hwaccel {}", name));
+        }
+    }
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.preferred_hwaccel = hwaccel;
+    config.save(&data_dir)?;
+    Ok(())
+}
+
+/// `worker.preferred_hwaccel` from config.toml.
+fn preferred_hwaccel(state: &WorkerState) -> Option<String> {
+    DjbotConfig::load(&state.data_dir()?).worker.preferred_hwaccel
+}
+
+/// Audio capture backends the located ffmpeg supports (e.g. CoreAudio via
+/// avfoundation, DirectShow, ALSA, PulseAudio/PipeWire), best first, with
+/// the arguments that list each one's input devices.
+#[tauri::command]
+async fn get_audio_backend(state: State<'_, WorkerState>) -> Result<Vec<ffmpeg::CaptureBackend>, AppError> {
+    let ffmpeg = state.require_ffmpeg()?;
+    tauri::async_runtime::spawn_blocking(move || ffmpeg::capture_backends(&ffmpeg))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "could not run ffmpeg -devices".to_string().into())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct WorkerStatusReport {
+    status: WorkerStatus,
+    /// Audio engine settings in effect, as reported by the worker.
+    audio: Option<EffectiveAudio>,
+    /// Requested audio settings the worker didn't honour.
+    audio_mismatches: Vec<String>,
+    /// Saved settings only take effect after a worker restart.
+    restart_required: bool,
+    /// Set with status `missing_runtime`.
+    missing_runtime: Option<MissingRuntime>,
+}
+
+/// Host and tool information for the about/diagnostics screens.
+#[derive(Debug, Clone, Serialize)]
+struct SystemInfo {
+    app_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    debug_build: bool,
+    ffmpeg_path: Option<String>,
+    ffmpeg_version: Option<String>,
+    ffmpeg_source: Option<FfmpegSource>,
+    /// As reported by the running worker; "dev" for unstamped builds.
+    worker_version: Option<String>,
+    /// Elevated processes resolve user paths differently and can make the
+    /// worker write into system-protected directories. Always false off
+    /// Windows.
+    running_as_admin: bool,
+    /// Where sensitive settings live (`secrets::STORAGE`).
+    secret_storage: &'static str,
+}
+
+/// True if the app is running as Administrator.
+#[cfg(target_os = "windows")]
+fn is_windows_admin() -> bool {
+    is_elevated::is_elevated()
+}
+
+fn system_info(state: &WorkerState) -> SystemInfo {
+    SystemInfo {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        debug_build: cfg!(debug_assertions),
+        ffmpeg_path: state.ffmpeg_path(),
+        ffmpeg_version: state.ffmpeg_version(),
+        ffmpeg_source: state.ffmpeg_source(),
+        worker_version: state.worker_version(),
+        #[cfg(target_os = "windows")]
+        running_as_admin: is_windows_admin(),
+        #[cfg(not(target_os = "windows"))]
+        running_as_admin: false,
+        secret_storage: secrets::STORAGE,
+    }
+}
+
+#[tauri::command]
+fn get_system_info(state: State<WorkerState>) -> SystemInfo {
+    system_info(&state)
+}
+
+/// Benchmark the data dir's volume: sequential write and read of a 10 MB
+/// temp file, and small-write latency.
+#[tauri::command]
+async fn test_data_dir_performance(state: State<'_, WorkerState>) -> Result<DiskPerformance, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    tauri::async_runtime::spawn_blocking(move || disk::measure_performance(&data_dir))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("disk benchmark failed: {}", e))
+}
+
+/// Wait (up to `timeout_ms`, default 30s) for the worker to report its port.
+#[tauri::command]
+async fn wait_for_worker(state: State<'_, WorkerState>, timeout_ms: Option<u64>) -> Result<u16, String> {
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(30_000));
+    state.wait_for_port(timeout).await
+}
+
+#[tauri::command]
+fn get_output_dir(state: State<WorkerState>) -> String {
+    let out = state.data_dir_path().join("output");
+    config::create_dir_with_mode(&out, state.dir_mode()).ok();
+    out.to_string_lossy().to_string()
+}
+
+/// Worker endpoints that run a job and are subject to `job_timeout_secs`.
+const JOB_ROUTES: &[&str] = &["analyze", "plan", "render/preview", "render/mix", "download/youtube"];
+/// Worker endpoints whose body `url` the worker fetches; checked by
+/// `url_guard` before forwarding.
+const URL_ROUTES: &[&str] = &["download/youtube"];
+/// Body of the worker's 503 when `--job-timeout` fires; also our own error
+/// when the watchdog below gives up first.
+const JOB_TIMED_OUT: &str = "job timed out";
+/// Extra time the Rust-side watchdog allows beyond the worker's own timeout.
+const JOB_TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// Proxy an HTTP request to the worker. `read_timeout_ms` overrides the
+/// configured read timeout for this request only (e.g. a long analysis).
+///
+/// Job requests are abandoned after `job_timeout_secs` (plus a grace period
+/// for the worker's own timeout to answer first) and recorded as timed out.
+#[tauri::command]
+async fn forward_worker_request(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+    http: State<'_, WorkerHttp>,
+    method: String,
+    path: String,
+    mut body: Option<serde_json::Value>,
+    read_timeout_ms: Option<u64>,
+) -> Result<serde_json::Value, String> {
+    let port = state.command_port()?;
+    let client = http.client(read_timeout_ms)?;
+    let history = app.state::<JobHistory>();
+
+    let route = path.trim_matches('/').to_string();
+    if route == "render/mix" {
+        if let Some(output_path) = body.as_ref().and_then(|b| b.get("output_path")).and_then(|v| v.as_str()) {
+            formats::check_output_path(output_path, state.output_bit_depth())?;
+        }
+    }
+    if URL_ROUTES.contains(&route.as_str()) {
+        let config = state.data_dir().map(|dir| DjbotConfig::load(&dir)).unwrap_or_default();
+        let network = app.state::<NetworkMonitor>();
+        if let Err(offline) = network.check() {
+            if !config.network.queue_when_offline {
+                return Err(offline.into());
+            }
+            let id = network.queue(method, path, body)?;
+            eprintln!("[djbot] {} held until the network is back ({})", route, id);
+            return Ok(serde_json::json!({ "queued": true, "pending_id": id }));
+        }
+        if let Some(url) = body.as_mut().and_then(|b| b.get_mut("url")) {
+            let allow_private = config.worker.allow_private_urls;
+            let checked = url_guard::validate(url.as_str().unwrap_or_default(), allow_private).await?;
+            *url = serde_json::Value::String(checked.to_string());
+        }
+    }
+    let is_job = JOB_ROUTES.contains(&route.as_str());
+    let inputs: Vec<String> = body
+        .as_ref()
+        .and_then(|b| b.get("filepaths"))
+        .and_then(|v| v.as_array())
+        .map(|paths| paths.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+
+    let timeout = state.job_timeout_secs().filter(|_| is_job);
+    let started = std::time::Instant::now();
+    let started_at = jobs::unix_now();
+    let output_file = body
+        .as_ref()
+        .and_then(|b| b.get("output_path"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    let sources = if is_job { job_sources(body.as_ref(), &inputs) } else { Vec::new() };
+    let request = proxy::forward(&client, port, &method, &path, body);
+    let result = match timeout {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs) + JOB_TIMEOUT_GRACE, request)
+            .await
+            .unwrap_or_else(|_| Err(JOB_TIMED_OUT.to_string())),
+        None => request.await,
+    };
+    if is_job {
+        let finished = JobFinished {
+            job_id: format!("{}:{}", route, started_at),
+            kind: route.clone(),
+            status: match &result {
+                Ok(_) => "succeeded",
+                Err(e) if e.contains(JOB_TIMED_OUT) => "timed_out",
+                Err(_) => "failed",
+            },
+            output_file: output_file.or_else(|| {
+                let value = result.as_ref().ok()?;
+                value.get("output_path").and_then(|v| v.as_str()).map(str::to_string)
+            }),
+            duration_ms: started.elapsed().as_millis() as u64,
+            started_at,
+            finished_at: jobs::unix_now(),
+        };
+        let _ = app.emit("job-finished", &finished);
+        if let Some(log) = app.try_state::<JobLog>() {
+            let max_entries = state.data_dir().map(|dir| DjbotConfig::load(&dir)).unwrap_or_default().history.max_entries;
+            log.record(job_log_entry(&finished, sources, &result, timeout), max_entries);
+        }
+        if let Some(hooks) = app.try_state::<Webhooks>() {
+            hooks.dispatch(finished);
+        }
+    }
+
+    match result {
+        Err(e) if is_job && e.contains(JOB_TIMED_OUT) => {
+            let secs = timeout.unwrap_or_default();
+            eprintln!("[djbot] {} timed out after {}s: {:?}", route, started.elapsed().as_secs(), inputs);
+            let record = JobRecord {
+                kind: route,
+                audio_seconds: probe_total_duration(&state, inputs.clone()).await,
+                duration_secs: started.elapsed().as_secs_f64(),
+                finished_at: jobs::unix_now(),
+                timed_out: true,
+                inputs: inputs.iter().map(|i| sanitize::field(i)).collect(),
+            };
+            let _ = app.emit("job-timed-out", &record);
+            history.record(record);
+            Err(format!("{} after {}s", JOB_TIMED_OUT, secs))
+        }
+        Err(e) => Err(sanitize::field(&e)),
+        Ok(mut value) => {
+            if route == "analyze" {
+                if let Some(index) = app.try_state::<TrackIndex>() {
+                    index.record_analysis(&value);
+                }
+                app.state::<QueueCheckpoint>().record_analysis(&value);
+            }
+            // Analysis requests feed the job-duration model.
+            if route == "analyze" && !inputs.is_empty() {
+                record_completed_job(&state, &history, "analyze", inputs, started.elapsed()).await;
+            }
+            sanitize::json(&mut value);
+            Ok(value)
+        }
+    }
+}
+
+/// What a job request works on, for the job log: its `filepaths`, the
+/// `url` it downloads, or the files in a mix `playlist`.
+fn job_sources(body: Option<&serde_json::Value>, inputs: &[String]) -> Vec<String> {
+    let sources: Vec<String> = if !inputs.is_empty() {
+        inputs.to_vec()
+    } else if let Some(url) = body.and_then(|b| b.get("url")).and_then(|v| v.as_str()) {
+        vec![url.to_string()]
+    } else {
+        body.and_then(|b| b.get("playlist"))
+            .and_then(|v| v.as_array())
+            .map(|tracks| tracks.iter().filter_map(|t| t.get("filepath")?.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    };
+    sources.iter().map(|s| sanitize::field(s)).collect()
+}
+
+fn job_log_entry(
+    finished: &JobFinished,
+    sources: Vec<String>,
+    result: &Result<serde_json::Value, String>,
+    timeout: Option<u64>,
+) -> JobLogEntry {
+    let error = result.as_ref().err().map(|e| {
+        let app_error = if finished.status == "timed_out" {
+            AppError::new(ErrorCode::JobTimedOut, sanitize::field(e)).param("secs", timeout)
+        } else {
+            AppError::new(ErrorCode::JobFailed, sanitize::field(e))
+        };
+        app_error.param("kind", &finished.kind)
+    });
+    let format = finished
+        .output_file
+        .as_deref()
+        .and_then(|f| std::path::Path::new(f).extension())
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    JobLogEntry {
+        job_id: finished.job_id.clone(),
+        kind: finished.kind.clone(),
+        sources,
+        output_file: finished.output_file.clone(),
+        format,
+        duration_ms: finished.duration_ms,
+        started_at: finished.started_at,
+        finished_at: finished.finished_at,
+        outcome: finished.status.to_string(),
+        error_code: error.as_ref().map(|e| e.code.as_str().to_string()),
+        error: error.map(String::from),
+    }
+}
+
+/// Total audio length of `paths` in seconds (0 without ffmpeg).
+async fn probe_total_duration(state: &WorkerState, paths: Vec<String>) -> f64 {
+    let Some(ffmpeg) = state.ffmpeg_path() else { return 0.0 };
+    tauri::async_runtime::spawn_blocking(move || {
+        paths.iter().filter_map(|p| ffmpeg::probe_duration(&ffmpeg, p)).sum::<f64>()
+    })
+    .await
+    .unwrap_or(0.0)
+}
+
+/// Probe the total audio length of `paths` and add a job record.
+async fn record_completed_job(
+    state: &WorkerState,
+    history: &JobHistory,
+    kind: &str,
+    paths: Vec<String>,
+    elapsed: Duration,
+) {
+    let audio_seconds = probe_total_duration(state, paths).await;
+    if audio_seconds > 0.0 {
+        history.record(JobRecord {
+            kind: kind.to_string(),
+            audio_seconds,
+            duration_secs: elapsed.as_secs_f64(),
+            finished_at: jobs::unix_now(),
+            timed_out: false,
+            inputs: Vec::new(),
+        });
+    }
+}
+
+/// Persist the per-job wall-clock limit (`None` = unlimited). The Rust-side
+/// watchdog applies it immediately; the worker's `--job-timeout` from the
+/// next worker start.
+#[tauri::command]
+fn set_job_timeout(state: State<WorkerState>, seconds: Option<u64>) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let seconds = seconds.filter(|&s| s > 0);
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.job_timeout_secs = seconds;
+    config.save(&data_dir)?;
+    state.set_job_timeout_secs(seconds);
+    Ok(())
+}
+
+/// Persist the job-finished webhook. `url` and `secret` are stored sealed;
+/// `None` or empty clears them. `events` filters on job status (empty
+/// for all).
+#[tauri::command]
+async fn set_webhook(
+    state: State<'_, WorkerState>,
+    enabled: bool,
+    url: Option<String>,
+    secret: Option<String>,
+    events: Vec<String>,
+) -> Result<(), String> {
+    if let Some(bad) = events.iter().find(|e| !config::WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(format!("unknown webhook event {:?} (expected one of {})", bad, config::WEBHOOK_EVENTS.join(", ")));
+    }
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    let url = match url.filter(|u| !u.is_empty()) {
+        Some(url) => {
+            let checked = url_guard::validate(&url, config.worker.allow_private_urls).await?;
+            Some(secrets::seal(&data_dir, config::WEBHOOK_URL_FIELD, checked.as_str())?)
+        }
+        None => None,
+    };
+    let secret = match secret.filter(|s| !s.is_empty()) {
+        Some(secret) => Some(secrets::seal(&data_dir, config::WEBHOOK_SECRET_FIELD, &secret)?),
+        None => None,
+    };
+    if enabled && url.is_none() {
+        return Err("an enabled webhook needs a url".to_string());
+    }
+    config.webhook = config::WebhookConfig { enabled, url, secret, events };
+    config.save(&data_dir)
+}
+
+/// Outcome of recent webhook deliveries, oldest first.
+#[tauri::command]
+fn get_webhook_deliveries(hooks: State<Webhooks>) -> Vec<WebhookDelivery> {
+    hooks.deliveries()
+}
+
+/// Persist the CA bundle the worker trusts for downloads (`None` goes back
+/// to auto-detection). Takes effect at the next worker start.
+#[tauri::command]
+fn set_ca_bundle(state: State<WorkerState>, path: Option<String>) -> Result<(), String> {
+    let path = path.filter(|p| !p.is_empty()).map(std::path::PathBuf::from);
+    if let Some(path) = &path {
+        if !path.is_absolute() {
+            return Err(format!("{} is not an absolute path", path.display()));
+        }
+        worker::validate_ca_bundle(path)?;
+    }
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.ca_bundle = path;
+    config.save(&data_dir)
+}
+
+/// Persist the worker's download proxy, encrypted (`None` or empty removes
+/// it). Takes effect at the next worker start.
+#[tauri::command]
+fn set_download_proxy(state: State<WorkerState>, url: Option<String>) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let sealed = match url.filter(|u| !u.is_empty()) {
+        Some(url) => {
+            config::validate_download_proxy(&url)?;
+            Some(secrets::seal(&data_dir, config::DOWNLOAD_PROXY_FIELD, &url)?)
+        }
+        None => None,
+    };
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.download_proxy = sealed;
+    config.save(&data_dir)
+}
+
+/// Whether a download proxy is stored, and whether it still decrypts. The
+/// URL itself is never sent back.
+#[tauri::command]
+fn get_download_proxy_status(state: State<WorkerState>) -> Result<CredentialStatus, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let stored = DjbotConfig::load(&data_dir).worker.download_proxy;
+    Ok(secrets::status(&data_dir, config::DOWNLOAD_PROXY_FIELD, stored.as_deref()))
+}
+
+#[derive(Serialize)]
+struct OutputFormats {
+    formats: &'static [formats::OutputFormat],
+    bit_depth: Option<u8>,
+}
+
+/// Mix export formats and the configured bit depth.
+#[tauri::command]
+fn list_output_formats(state: State<WorkerState>) -> OutputFormats {
+    OutputFormats {
+        formats: formats::OUTPUT_FORMATS,
+        bit_depth: state.output_bit_depth(),
+    }
+}
+
+/// Save the sample depth for lossless mix exports (`None` = encoder
+/// default) and push it to a running worker; it applies from the next job.
+#[tauri::command]
+async fn set_output_bit_depth(
+    state: State<'_, WorkerState>,
+    http: State<'_, WorkerHttp>,
+    bits: Option<u8>,
+) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.output_bit_depth = bits;
+    config.save(&data_dir)?;
+    state.set_output_bit_depth(bits);
+
+    if let Ok(port) = state.command_port() {
+        let body = serde_json::json!({ "bits": bits.unwrap_or(0) });
+        proxy::forward(&http.client(None)?, port, "POST", "output/bit-depth", Some(body)).await?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct CrashPolicyStatus {
+    policy: CrashPolicy,
+    last_crash: Option<WorkerCrash>,
+}
+
+/// Active crash policy and the last crash it handled.
+#[tauri::command]
+fn get_crash_policy(state: State<WorkerState>) -> CrashPolicyStatus {
+    CrashPolicyStatus {
+        policy: state.crash_policy(),
+        last_crash: state.last_crash(),
+    }
+}
+
+/// Persist how worker crashes are handled; applies to the next exit.
+#[tauri::command]
+fn set_crash_policy(state: State<WorkerState>, policy: CrashPolicy) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.on_worker_crash = policy;
+    config.save(&data_dir)?;
+    state.set_crash_policy(policy);
+    Ok(())
+}
+
+/// Persist the worker health check interval (5–300s) and apply it to the
+/// running health loop right away.
+#[tauri::command]
+fn set_health_check_interval(state: State<WorkerState>, secs: u64) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.health_check_interval_secs = secs;
+    config.save(&data_dir)?;
+    state.health_interval.send_replace(secs);
+    Ok(())
+}
+
+/// Persist the release channel the updater follows.
+#[tauri::command]
+fn set_update_channel(state: State<WorkerState>, channel: String) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.update_channel = channel;
+    config.save(&data_dir)
+}
+
+/// Phase one of `reset_settings`: the settings that would go back to their
+/// defaults, and the token to do it.
+#[tauri::command]
+fn request_reset_settings(state: State<WorkerState>, confirmations: State<Confirmations>) -> Result<ConfirmationRequest, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let changed = DjbotConfig::load(&data_dir).differences(&DjbotConfig::default());
+    let summary = if changed.is_empty() {
+        "All settings are already at their defaults.".to_string()
+    } else {
+        format!("Reset {} setting(s) to their defaults: {}.", changed.len(), changed.join(", "))
+    };
+    confirmations.issue("reset_settings", &serde_json::json!({}), summary)
+}
+
+/// Overwrite config.toml with the defaults, given a token from
+/// `request_reset_settings`. The crash policy and health check interval
+/// apply right away; everything else on the next launch.
+#[tauri::command]
+fn reset_settings(
+    state: State<WorkerState>,
+    confirmations: State<Confirmations>,
+    audit: State<AuditLog>,
+    token: Option<String>,
+) -> Result<(), String> {
+    let result = confirmations.redeem(token.as_deref(), "reset_settings", &serde_json::json!({})).and_then(|()| {
+        let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+        let config = DjbotConfig::default();
+        config.save(&data_dir)?;
+        state.set_crash_policy(config.on_worker_crash);
+        state.health_interval.send_replace(config.worker.health_check_interval_secs);
+        eprintln!("[djbot] settings reset to defaults");
+        Ok(())
+    });
+    audit.record(AuditEntry::new(AuditKind::SettingsReset, "reset_settings").subject("config.toml").outcome(&result));
+    result
+}
+
+/// The data dir in use and the validated absolute `target` for
+/// `migrate_data_dir`.
+fn migration_dirs(state: &WorkerState, target: &str) -> Result<(std::path::PathBuf, std::path::PathBuf), String> {
+    // In debug builds the data dir is the project root, next to the sources.
+    if cfg!(debug_assertions) {
+        return Err("the data directory can't be migrated in debug builds".to_string());
+    }
+    let current = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let target = std::path::PathBuf::from(target);
+    data_dir::check_target(&current, &target)?;
+    Ok((current, target))
+}
+
+/// Phase one of `migrate_data_dir` with `move_files`: what would be moved
+/// and the token it needs. Copying is single-phase.
+#[tauri::command]
+fn request_migrate_data_dir(
+    state: State<WorkerState>,
+    confirmations: State<Confirmations>,
+    target: String,
+) -> Result<ConfirmationRequest, String> {
+    let (current, target) = migration_dirs(&state, &target)?;
+    let size = data_dir::tree_size(&current)?;
+    let summary = format!(
+        "Move {} file(s) ({} bytes) from {} to {} and delete the originals. The app restarts afterwards.",
+        size.files,
+        size.bytes,
+        current.display(),
+        target.display()
+    );
+    confirmations.issue("migrate_data_dir", &serde_json::json!({ "target": target, "move": true }), summary)
+}
+
+/// Copy the data directory to `target` and use it from now on; the app
+/// restarts to pick it up. With `move_files` the old copy is emptied,
+/// which needs the token from `request_migrate_data_dir` for the same
+/// target.
+#[tauri::command]
+fn migrate_data_dir(
+    app: AppHandle,
+    state: State<WorkerState>,
+    confirmations: State<Confirmations>,
+    audit: State<AuditLog>,
+    target: String,
+    move_files: bool,
+    token: Option<String>,
+) -> Result<(), String> {
+    let result = migration_dirs(&state, &target).and_then(|(current, target)| {
+        if move_files {
+            confirmations.redeem(token.as_deref(), "migrate_data_dir", &serde_json::json!({ "target": target, "move": true }))?;
+        }
+        let default_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+        let size = data_dir::tree_size(&current)?;
+        if let Some(free) = disk::free_space(target.ancestors().find(|a| a.exists()).unwrap_or(&target)) {
+            if free < size.bytes {
+                return Err(format!("{} needs {} bytes, only {} free", target.display(), size.bytes, free));
+            }
+        }
+        // Nothing may write to the old directory while it is copied.
+        state.kill_worker();
+        data_dir::copy_tree(&current, &target)?;
+        data_dir::set_location(&default_dir, &target)?;
+        if move_files {
+            data_dir::clear(&current)?;
+        }
+        eprintln!("[djbot] data dir {} {} to {}", current.display(), if move_files { "moved" } else { "copied" }, target.display());
+        Ok(target)
+    });
+    let entry = AuditEntry::new(AuditKind::DataDirMigration, "migrate_data_dir")
+        .subject(&target)
+        .detail(if move_files { "move" } else { "copy" });
+    match result {
+        Ok(target) => {
+            AuditLog::new(&target).record(entry);
+            app.restart()
+        }
+        Err(e) => {
+            audit.record(entry.failed(&e));
+            // A worker stopped for a failed copy comes back.
+            if !state.worker_running() {
+                if let Err(e) = spawn_worker(&app) {
+                    eprintln!("[djbot] worker restart failed: {}", e);
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// A newer release on the configured channel.
+#[derive(Serialize)]
+struct AvailableUpdate {
+    version: String,
+    notes: Option<String>,
+}
+
+/// Time allowed for fetching the app's update manifest.
+const UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The fields of an update manifest `check_for_update` reads.
+#[derive(Deserialize)]
+struct UpdateManifest {
+    version: String,
+    notes: Option<String>,
+}
+
+/// `candidate` is a later `X.Y.Z` than `current` (a leading `v` and any
+/// `-pre` suffix are ignored). Versions that don't parse only count as
+/// newer when they differ.
+fn is_newer_version(candidate: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Option<Vec<u64>> {
+        let core = v.trim().trim_start_matches('v').split(['-', '+']).next()?;
+        core.split('.').map(|n| n.parse().ok()).collect()
+    };
+    match (parse(candidate), parse(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => candidate != current,
+    }
+}
+
+/// Check the configured channel's manifest for a newer release. Only reports the update:
+/// installing needs the release signing key, which the app doesn't ship
+/// yet, so the updater plugin isn't registered.
+#[tauri::command]
+async fn check_for_update(state: State<'_, WorkerState>) -> Result<Option<AvailableUpdate>, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let channel = DjbotConfig::load(&data_dir).update_channel;
+    let url = config::update_manifest_url(&channel);
+    let client = reqwest::Client::builder().timeout(UPDATE_CHECK_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let resp = client.get(&url).send().await.map_err(|e| format!("update check failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("update check failed: {} returned {}", url, resp.status()));
+    }
+    let manifest: UpdateManifest = resp.json().await.map_err(|e| format!("invalid update manifest: {}", e))?;
+    if !is_newer_version(&manifest.version, env!("CARGO_PKG_VERSION")) {
+        return Ok(None);
+    }
+    Ok(Some(AvailableUpdate { version: manifest.version, notes: manifest.notes }))
+}
+
+/// Persist the release channel worker binaries are updated from.
+#[tauri::command]
+fn set_worker_update_channel(state: State<WorkerState>, channel: String) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker_update_channel = channel;
+    config.save(&data_dir)
+}
+
+/// Time allowed for a worker manifest or binary download.
+const WORKER_UPDATE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The worker update channel's manifest.
+async fn fetch_worker_manifest(data_dir: &std::path::Path) -> Result<(reqwest::Client, worker_update::Manifest), String> {
+    let channel = DjbotConfig::load(data_dir).worker_update_channel;
+    let client = reqwest::Client::builder().timeout(WORKER_UPDATE_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let manifest = worker_update::fetch_manifest(&client, &channel).await?;
+    Ok((client, manifest))
+}
+
+/// A worker build on the worker channel other than the running one.
+#[derive(Serialize)]
+struct AvailableWorkerUpdate {
+    version: String,
+    notes: Option<String>,
+    /// Version the running worker reported; `None` if it isn't running.
+    current: Option<String>,
+}
+
+/// Check the worker channel's manifest against the running worker's
+/// handshake version. `None` when it is current or the channel has no
+/// build for this platform.
+#[tauri::command]
+async fn check_worker_update(state: State<'_, WorkerState>) -> Result<Option<AvailableWorkerUpdate>, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let (_, manifest) = fetch_worker_manifest(&data_dir).await?;
+    let current = state.worker_version();
+    if current.as_deref() == Some(manifest.version.as_str())
+        || !manifest.platforms.contains_key(&worker_update::platform_key())
+    {
+        return Ok(None);
+    }
+    Ok(Some(AvailableWorkerUpdate { version: manifest.version, notes: manifest.notes, current }))
+}
+
+/// Download and verify the worker channel's build for this platform and
+/// run it from the next worker start (`restart_worker` to switch now).
+/// Returns the installed version.
+#[tauri::command]
+async fn download_worker_update(state: State<'_, WorkerState>, audit: State<'_, AuditLog>) -> Result<String, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let result = async {
+        let (client, manifest) = fetch_worker_manifest(&data_dir).await?;
+        worker_update::install(&client, &data_dir, goworker_name(), &manifest).await
+    }
+    .await;
+    let entry = AuditEntry::new(AuditKind::WorkerUpdate, "download_worker_update");
+    let entry = match &result {
+        Ok(version) => entry.subject(version.clone()),
+        Err(_) => entry,
+    };
+    audit.record(entry.outcome(&result));
+    result
+}
+
+/// Switch back to the worker version before the current download, or the
+/// bundled worker if there was none, from the next worker start. Returns
+/// the version now selected, `None` for the bundled worker.
+#[tauri::command]
+fn rollback_worker(state: State<WorkerState>, audit: State<AuditLog>) -> Result<Option<String>, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let result = worker_update::rollback(&data_dir);
+    let subject = match &result {
+        Ok(Some(version)) => version.clone(),
+        _ => "bundled".to_string(),
+    };
+    audit.record(AuditEntry::new(AuditKind::WorkerUpdate, "rollback_worker").subject(subject).outcome(&result));
+    result
+}
+
+/// Re-read `log_buffer_capacity` from the config and shrink (or grow) the
+/// in-memory stderr buffer to it, dropping the oldest lines.
+#[tauri::command]
+fn trim_log_buffer(state: State<WorkerState>) -> Result<BufStats, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    state.set_stderr_tail_capacity(DjbotConfig::load(&data_dir).worker.log_buffer_capacity);
+    Ok(state.stderr_tail_stats())
+}
+
+/// Logged jobs matching `filter`, newest first.
+#[tauri::command]
+fn get_job_history(log: State<JobLog>, filter: Option<JobLogFilter>, pagination: Option<Pagination>) -> JobLogPage {
+    log.query(&filter.unwrap_or_default(), &pagination.unwrap_or_default())
+}
+
+/// Write the logged jobs started within `date_range` (all of them if
+/// omitted) to `dest_path` as "csv" or "json". Returns how many were
+/// written.
+#[tauri::command]
+fn export_job_history(
+    log: State<JobLog>,
+    scope: State<Scope>,
+    format: ExportFormat,
+    date_range: Option<JobLogFilter>,
+    dest_path: String,
+) -> Result<usize, String> {
+    let dest = scope.resolve_new(&dest_path)?;
+    log.export(&date_range.unwrap_or_default(), format, &dest)
+}
+
+/// Jobs that hit the timeout, oldest first, with their input files.
+#[tauri::command]
+fn get_timed_out_jobs(history: State<JobHistory>) -> Vec<JobRecord> {
+    history.timed_out()
+}
+
+/// Record a job the frontend ran against the worker directly, so the
+/// duration model also learns from requests that bypass the proxy.
+#[tauri::command]
+async fn record_job(
+    state: State<'_, WorkerState>,
+    history: State<'_, JobHistory>,
+    kind: String,
+    input_paths: Vec<String>,
+    duration_ms: u64,
+) -> Result<(), String> {
+    record_completed_job(&state, &history, &kind, input_paths, Duration::from_millis(duration_ms)).await;
+    Ok(())
+}
+
+/// Predict how long processing `input_path` will take from past runs.
+#[tauri::command]
+async fn estimate_job_duration(
+    state: State<'_, WorkerState>,
+    history: State<'_, JobHistory>,
+    scope: State<'_, Scope>,
+    input_path: String,
+) -> Result<JobEstimate, String> {
+    let ffmpeg = state.require_ffmpeg()?;
+    let probe_path = scope.resolve(&input_path)?.to_string_lossy().to_string();
+    let audio_seconds = tauri::async_runtime::spawn_blocking(move || ffmpeg::probe_duration(&ffmpeg, &probe_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("could not read the duration of {}", input_path))?;
+    Ok(history.estimate(audio_seconds))
+}
+
+/// Stream a local file to the worker's `/upload` endpoint. Returns the
+/// worker's JSON response (the stored paths).
+#[tauri::command]
+async fn upload_file_to_worker(
+    state: State<'_, WorkerState>,
+    http: State<'_, WorkerHttp>,
+    limiter: State<'_, Arc<TransferLimiter>>,
+    bus: State<'_, EventBus>,
+    scope: State<'_, Scope>,
+    path: String,
+) -> Result<serde_json::Value, String> {
+    let resolved = scope.resolve(&path)?;
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let config = DjbotConfig::load(&data_dir).import;
+    let admitted = intake::check_file(&path, &resolved, &config).map_err(|r| r.detail)?;
+    let port = state.command_port()?;
+    let client = http.client(None)?;
+    transfer::upload(&client, port, &admitted.path, Arc::clone(&limiter), bus.inner().clone()).await
+}
+
+/// Outcome of `import_files`: the worker's response for each uploaded file
+/// and why each other file was refused.
+#[derive(Serialize)]
+struct ImportReport {
+    uploaded: Vec<serde_json::Value>,
+    rejected: Vec<intake::Rejection>,
+}
+
+/// Validate `paths` (`intake::check_file`) and upload the files that pass.
+/// Refused files don't stop the rest; a batch too large for the limit or
+/// the free space fails as a whole before anything is uploaded.
+#[tauri::command]
+async fn import_files(
+    state: State<'_, WorkerState>,
+    http: State<'_, WorkerHttp>,
+    limiter: State<'_, Arc<TransferLimiter>>,
+    bus: State<'_, EventBus>,
+    scope: State<'_, Scope>,
+    paths: Vec<String>,
+) -> Result<ImportReport, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let config = DjbotConfig::load(&data_dir).import;
+    let mut rejected = Vec::new();
+    let mut admitted = Vec::new();
+    for input in &paths {
+        let checked = scope
+            .resolve(input)
+            .map_err(|e| intake::Rejection::new(input, intake::RejectReason::NotAllowed, e.to_string()))
+            .and_then(|path| intake::check_file(input, &path, &config));
+        match checked {
+            Ok(a) => admitted.push((input, a)),
+            Err(r) => rejected.push(r),
+        }
+    }
+    intake::check_batch(admitted.iter().map(|(_, a)| a.size).sum(), &config, &data_dir)?;
+
+    let port = state.command_port()?;
+    let client = http.client(None)?;
+    let mut uploaded = Vec::new();
+    for (input, file) in admitted {
+        match transfer::upload(&client, port, &file.path, Arc::clone(&limiter), bus.inner().clone()).await {
+            Ok(response) => uploaded.push(response),
+            Err(e) => rejected.push(intake::Rejection::new(input, intake::RejectReason::UploadFailed, e)),
+        }
+    }
+    for r in &rejected {
+        eprintln!("[djbot] import refused {} ({:?}): {}", r.path, r.reason, r.detail);
+    }
+    Ok(ImportReport { uploaded, rejected })
+}
+
+/// How a `download_from_worker` or `resume_download` call ended.
+#[derive(Debug, Clone, Serialize)]
+struct DownloadOutcome {
+    id: String,
+    /// "completed", "paused" or "cancelled".
+    state: &'static str,
+    /// Bytes in `dest` (completed) or in its `.part` file (paused).
+    bytes: u64,
+}
+
+/// Run (or resume) the registered download `id` until it completes, is
+/// paused or cancelled, or fails. A broken connection leaves it paused.
+async fn run_download(
+    app: &AppHandle,
+    id: String,
+    source: &str,
+    dest: &std::path::Path,
+    resume: Option<transfer::Resume>,
+    control: watch::Receiver<transfer::Control>,
+) -> Result<DownloadOutcome, String> {
+    let downloads = app.state::<Downloads>();
+    let result = async {
+        let port = app.worker_port()?;
+        let client = app.state::<WorkerHttp>().client(None)?;
+        let limiter = Arc::clone(&app.state::<Arc<TransferLimiter>>());
+        let bus = app.state::<EventBus>().inner().clone();
+        transfer::download(&client, port, source, dest, resume.clone(), control, limiter, bus).await
+    }
+    .await;
+    // Failing before a byte moved (e.g. the worker is down) keeps a paused
+    // download resumable.
+    let result = match (result, resume) {
+        (Err(error), Some(resume)) => Ok(transfer::Stopped::Interrupted { error, resume }),
+        (result, _) => result,
+    };
+    downloads.finish(&id, &result);
+    match result? {
+        transfer::Stopped::Completed(bytes) => Ok(DownloadOutcome { id, state: "completed", bytes }),
+        transfer::Stopped::Paused(resume) => Ok(DownloadOutcome { id, state: "paused", bytes: resume.offset }),
+        transfer::Stopped::Cancelled => Ok(DownloadOutcome { id, state: "cancelled", bytes: 0 }),
+        transfer::Stopped::Interrupted { error, resume } => Err(format!(
+            "download {} interrupted after {} bytes ({}); resume_download continues it",
+            id, resume.offset, error
+        )),
+    }
+}
+
+/// Stream a worker-side file (e.g. a rendered mix) to `dest`. The download
+/// is registered under `id` (one is generated if omitted) for
+/// `pause_download`, `resume_download` and `cancel_download`; the call
+/// returns when it completes, pauses or is cancelled.
+#[tauri::command]
+async fn download_from_worker(
+    app: AppHandle,
+    scope: State<'_, Scope>,
+    downloads: State<'_, Downloads>,
+    source: String,
+    dest: String,
+    id: Option<String>,
+) -> Result<DownloadOutcome, String> {
+    let dest = scope.resolve_new(&dest)?;
+    let id = match id {
+        Some(id) => id,
+        None => {
+            let mut bytes = [0u8; 8];
+            getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+            bytes.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    };
+    let control = downloads.start(&id, &source, &dest)?;
+    run_download(&app, id, &source, &dest, None, control).await
+}
+
+/// Stop reading download `id`, keeping what arrived for `resume_download`.
+/// Its `download_from_worker` call returns with state "paused".
+#[tauri::command]
+fn pause_download(downloads: State<Downloads>, id: String) -> Result<(), String> {
+    downloads.pause(&id)
+}
+
+/// Continue a paused or interrupted download from where it stopped,
+/// against whichever worker is running now.
+#[tauri::command]
+async fn resume_download(app: AppHandle, downloads: State<'_, Downloads>, id: String) -> Result<DownloadOutcome, String> {
+    let (source, dest, resume, control) = downloads.resume(&id)?;
+    run_download(&app, id, &source, &dest, Some(resume), control).await
+}
+
+/// Stop download `id` and delete its partial file.
+#[tauri::command]
+async fn cancel_download(downloads: State<'_, Downloads>, id: String) -> Result<(), String> {
+    if let Some(dest) = downloads.cancel(&id)? {
+        transfer::discard_part(&dest).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn list_downloads(downloads: State<Downloads>) -> Vec<DownloadInfo> {
+    downloads.list()
+}
+
+/// Title, artist, album and stream properties for each of `paths`, read
+/// without the worker. Files that can't be read or aren't inside the
+/// allowed input locations get an `error` instead of failing the batch.
+#[tauri::command]
+async fn read_audio_tags(
+    app: AppHandle,
+    cache: State<'_, TagCache>,
+    scope: State<'_, Scope>,
+    paths: Vec<String>,
+) -> Result<Vec<TagResult>, String> {
+    let results = cache
+        .read_all(paths, |path| scope.resolve(path).map_err(String::from))
+        .await;
+    if let Some(index) = app.try_state::<TrackIndex>() {
+        for result in &results {
+            if let Some(tags) = &result.tags {
+                index.record_tags(&result.path, tags);
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Search the local track index: every word of `query` must prefix-match
+/// the path, title, artist or album. Results are paged by `limit` (at most
+/// 500) and `offset`; files found missing are flagged, not removed.
+#[tauri::command]
+async fn search_tracks(
+    index: State<'_, TrackIndex>,
+    query: String,
+    filters: Option<TrackFilters>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<IndexedTrack>, String> {
+    let index = index.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        index.search(&query, &filters.unwrap_or_default(), limit.unwrap_or(100), offset.unwrap_or(0))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Persist the batch queue (`checkpoint.rs`). Call on every change; a queue
+/// with every item done clears the checkpoint.
+#[tauri::command]
+fn save_queue_checkpoint(checkpoint: State<QueueCheckpoint>, items: Vec<QueueItem>) -> Result<(), String> {
+    checkpoint.save(items)
+}
+
+/// Files of the saved batch that never finished, for the frontend to queue
+/// again. Files that no longer exist or are now out of scope are skipped.
+/// Empty when there is nothing to resume.
+#[tauri::command]
+fn resume_from_checkpoint(checkpoint: State<QueueCheckpoint>, scope: State<Scope>) -> Vec<String> {
+    let Some(items) = checkpoint.load() else { return Vec::new() };
+    items
+        .into_iter()
+        .filter(|item| !item.done)
+        .filter_map(|item| match scope.resolve(&item.path) {
+            Ok(_) => Some(item.path),
+            Err(e) => {
+                eprintln!("[djbot] not resuming {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Recreate the track index from the files it knew about and the output
+/// directory. Returns the number of tracks indexed.
+#[tauri::command]
+async fn rebuild_index(
+    state: State<'_, WorkerState>,
+    index: State<'_, TrackIndex>,
+) -> Result<usize, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let index = index.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || index.rebuild(&data_dir.join("output")))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Embedded cover art of `path`, scaled to fit `max_px` and cached as a
+/// JPEG; `no_artwork` when the file has none.
+#[tauri::command]
+async fn get_album_art(
+    artwork: State<'_, ArtworkCache>,
+    scope: State<'_, Scope>,
+    path: String,
+    max_px: u32,
+) -> Result<AlbumArt, String> {
+    let track = scope.resolve(&path)?;
+    artwork.get(track, max_px).await
+}
+
+/// Remux a file in the output directory so it starts and seeks quickly
+/// over HTTP (`-movflags +faststart`). Progress is reported as
+/// `job-progress` for job `optimize:<name>`. Returns the new file's path.
+#[tauri::command]
+async fn optimize_for_streaming(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+    scope: State<'_, Scope>,
+    file: String,
+) -> Result<String, AppError> {
+    let ffmpeg = state.require_ffmpeg()?;
+    let input = scope.resolve_output(&file)?;
+    let hwaccel = preferred_hwaccel(&state);
+    let job = format!("optimize:{}", input.file_name().unwrap_or_default().to_string_lossy());
+    tauri::async_runtime::spawn_blocking(move || {
+        ffmpeg::remux_for_streaming(&ffmpeg, &input, hwaccel.as_deref(), |done, total| {
+            app.emit_worker_event(WorkerEvent::Progress(JobProgress { job: job.clone(), done, total, state: None }));
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map(|path| path.to_string_lossy().to_string())
+    .map_err(AppError::from)
+}
+
+/// Faded copy of `file` in the output dir (`ffmpeg::apply_fade`), with
+/// progress on the event bus. Returns the new file's path.
+#[tauri::command]
+async fn apply_fade(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+    scope: State<'_, Scope>,
+    file: String,
+    fade_in_secs: f64,
+    fade_out_secs: f64,
+) -> Result<String, AppError> {
+    let ffmpeg = state.require_ffmpeg()?;
+    let output_dir = state.require_data_dir()?.join("output");
+    let input = scope.resolve(&file)?;
+    let hwaccel = preferred_hwaccel(&state);
+    let job = format!("fade:{}", input.file_name().unwrap_or_default().to_string_lossy());
+    tauri::async_runtime::spawn_blocking(move || {
+        ffmpeg::apply_fade(&ffmpeg, &input, &output_dir, fade_in_secs, fade_out_secs, hwaccel.as_deref(), |done, total| {
+            app.emit_worker_event(WorkerEvent::Progress(JobProgress { job: job.clone(), done, total, state: None }));
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map(|path| path.to_string_lossy().to_string())
+    .map_err(AppError::from)
+}
+
+/// Phase one of a permanent delete: what `delete_output_file` would remove
+/// and the token it needs. Only asked for when `permanent` is true; moving
+/// to the trash is single-phase.
+#[tauri::command]
+fn request_delete_output_file(
+    confirmations: State<Confirmations>,
+    scope: State<Scope>,
+    file: String,
+) -> Result<ConfirmationRequest, String> {
+    let path = scope.resolve_output(&file)?;
+    let size = std::fs::metadata(&path).map_err(|e| e.to_string())?.len();
+    let summary = format!(
+        "Permanently delete {} ({} bytes) from the output folder. This can't be undone.",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        size
+    );
+    confirmations.issue("delete_output_file", &serde_json::json!({ "file": path, "permanent": true }), summary)
+}
+
+/// Delete a file in the output directory. By default it goes to
+/// `{data_dir}/trash`; with `permanent` it is removed, which needs the
+/// token from `request_delete_output_file` for the same file.
+#[tauri::command]
+fn delete_output_file(
+    state: State<WorkerState>,
+    confirmations: State<Confirmations>,
+    scope: State<Scope>,
+    audit: State<AuditLog>,
+    file: String,
+    permanent: bool,
+    token: Option<String>,
+) -> Result<(), String> {
+    let path = scope.resolve_output(&file)?;
+    let result = if permanent {
+        let params = serde_json::json!({ "file": path, "permanent": true });
+        confirmations.redeem(token.as_deref(), "delete_output_file", &params).and_then(|()| {
+            std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+            eprintln!("[djbot] deleted {}", path.display());
+            Ok(())
+        })
+    } else {
+        state
+            .data_dir()
+            .ok_or_else(|| "data dir not initialised".to_string())
+            .and_then(|dir| move_to_trash(&dir.join("trash"), &path))
+    };
+    audit.record(
+        AuditEntry::new(AuditKind::Delete, "delete_output_file")
+            .subject(path.to_string_lossy())
+            .detail(if permanent { "permanent" } else { "trash" })
+            .outcome(&result),
+    );
+    result
+}
+
+/// Open the platform share sheet for `rel_path` in the output dir,
+/// anchored to the main window. Fails with "share not supported" where
+/// there is no share sheet.
+#[tauri::command]
+async fn share_output(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+    scope: State<'_, Scope>,
+    audit: State<'_, AuditLog>,
+    rel_path: String,
+) -> Result<(), String> {
+    let path = scope.resolve_in(&state.data_dir_path().join("output"), &rel_path)?;
+    let result = present_share_sheet(&app, &path).await;
+    audit.record(AuditEntry::new(AuditKind::Share, "share_output").subject(path.to_string_lossy()).outcome(&result));
+    result
+}
+
+async fn present_share_sheet(app: &AppHandle, path: &std::path::Path) -> Result<(), String> {
+    share::check_shareable(path)?;
+    let window = app.get_webview_window("main").ok_or_else(|| "main window not found".to_string())?;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let (anchor, target) = (window.clone(), path.to_path_buf());
+    window
+        .run_on_main_thread(move || {
+            let _ = tx.send(share::present(&anchor, &target));
+        })
+        .map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?.map_err(String::from)
+}
+
+fn move_to_trash(trash: &std::path::Path, path: &std::path::Path) -> Result<(), String> {
+    std::fs::create_dir_all(trash).map_err(|e| e.to_string())?;
+    let name = format!("{}-{}", jobs::unix_now(), path.file_name().unwrap_or_default().to_string_lossy());
+    std::fs::rename(path, trash.join(&name)).map_err(|e| e.to_string())?;
+    eprintln!("[djbot] moved {} to trash as {}", path.display(), name);
+    Ok(())
+}
+
+/// Audit log entries at or after `since` (Unix seconds) of the given
+/// `kinds` (all if omitted), oldest first.
+#[tauri::command]
+fn get_audit_log(audit: State<AuditLog>, since: Option<u64>, kinds: Option<Vec<AuditKind>>) -> Vec<AuditEntry> {
+    audit.read(since, kinds.as_deref())
+}
+
+/// Waveform thumbnail for the track at `track_id` (its path), served from
+/// the on-disk cache when possible. Returns the PNG's path.
+#[tauri::command]
+async fn get_waveform(
+    state: State<'_, WorkerState>,
+    http: State<'_, WorkerHttp>,
+    waveforms: State<'_, WaveformCache>,
+    scope: State<'_, Scope>,
+    track_id: String,
+    width: u32,
+) -> Result<String, String> {
+    let track = scope.resolve(&track_id)?.to_string_lossy().to_string();
+    let path = waveforms
+        .get(track, width, || Ok((http.client(None)?, state.command_port()?)))
+        .await?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Debug view of what the frontend may read through the fs plugin.
+#[tauri::command]
+fn get_fs_allowed_paths(app: AppHandle) -> Vec<String> {
+    fs_scope::allowed_paths(&app)
+}
+
+/// What the output-dir watcher has seen (`watch_external_writes`).
+#[tauri::command]
+fn get_output_dir_activity(activity: State<OutputDirActivity>) -> OutputActivity {
+    activity.summary()
+}
+
+fn archive_dir(state: &WorkerState) -> Result<std::path::PathBuf, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    Ok(data_dir.join("archive"))
+}
+
+/// Archives of old output files, oldest first.
+#[tauri::command]
+fn list_archives(state: State<WorkerState>) -> Result<Vec<ArchiveInfo>, String> {
+    Ok(archive::list_archives(&archive_dir(&state)?))
+}
+
+/// Unpack the archive `name` into the directory `dest`.
+#[tauri::command]
+async fn extract_archive(
+    state: State<'_, WorkerState>,
+    scope: State<'_, Scope>,
+    name: String,
+    dest: String,
+) -> Result<(), String> {
+    let dir = archive_dir(&state)?;
+    let dest = scope.resolve_new(&dest)?;
+    tauri::async_runtime::spawn_blocking(move || archive::extract_archive(&dir, &name, &dest))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Validate and download the preset behind a `djbot://import` link, check
+/// the config it would produce, then ask the UI to confirm it.
+async fn handle_import_link(app: AppHandle, link: reqwest::Url) {
+    let result = async {
+        let url = import::parse_import_link(&link)?;
+        if let Some(network) = app.try_state::<NetworkMonitor>() {
+            network.check()?;
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .redirect(import::redirect_policy())
+            .build()
+            .map_err(|e| e.to_string())?;
+        let preset = import::fetch_preset(&client, url.clone()).await?;
+        let data_dir = app.state::<WorkerState>().data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+        let changes = import::preview(&preset, &DjbotConfig::load(&data_dir))?;
+        Ok::<_, String>(ImportRequest { source: url.to_string(), preset, changes })
+    }
+    .await;
+
+    match result {
+        Ok(request) => {
+            *app.state::<PendingImport>().0.lock().unwrap() = Some(request.clone());
+            let _ = app.emit("config-import-requested", request);
+        }
+        Err(e) => {
+            eprintln!("[djbot] config import rejected: {}", e);
+            let _ = app.emit("config-import-failed", e);
+        }
+    }
+}
+
+/// Lay the preset announced by `config-import-requested` over the saved
+/// config, or discard it. Worker settings take effect the next time the
+/// worker starts.
+#[tauri::command]
+fn confirm_config_import(
+    state: State<WorkerState>,
+    pending: State<PendingImport>,
+    accept: bool,
+) -> Result<(), String> {
+    let request = pending
+        .0
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "no config import is pending".to_string())?;
+    if !accept {
+        return Ok(());
+    }
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let config = request.preset.apply_to(DjbotConfig::load(&data_dir));
+    config.save(&data_dir)?;
+    state.set_output_bit_depth(config.worker.output_bit_depth);
+    eprintln!("[djbot] applied config preset from {}", request.source);
+    Ok(())
+}
+
+/// Report whether the resolved worker binary is code-signed, and by whom.
+#[tauri::command]
+async fn check_worker_signature(state: State<'_, WorkerState>) -> Result<WorkerSignature, String> {
+    let path = state.sidecar_path().ok_or_else(|| "worker binary not resolved yet".to_string())?;
+    tauri::async_runtime::spawn_blocking(move || signature::check(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Payload of `worker-unsigned`.
+#[derive(Debug, Clone, Serialize)]
+struct WorkerUnsigned {
+    path: String,
+    reason: Option<String>,
+    /// "warning" in debug builds, where the worker is rarely signed, and
+    /// "error" in release builds.
+    severity: &'static str,
+}
+
+/// Verify the resolved worker binary's code signature with the platform
+/// signing APIs, emitting `worker-unsigned` if it isn't valid. Always
+/// invalid (without the event) on platforms where it can't be checked.
+#[tauri::command]
+async fn validate_worker_binary_signature(app: AppHandle, state: State<'_, WorkerState>) -> Result<SignatureStatus, String> {
+    let path = state.sidecar_path().ok_or_else(|| "worker binary not resolved yet".to_string())?;
+    let checked = path.clone();
+    let status = tauri::async_runtime::spawn_blocking(move || signature::validate(&checked))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !status.valid && signature::SUPPORTED {
+        let severity = if cfg!(debug_assertions) { "warning" } else { "error" };
+        eprintln!(
+            "[djbot] {}: worker binary {} has no valid signature: {}",
+            severity.to_ascii_uppercase(),
+            path.display(),
+            status.reason.as_deref().unwrap_or("unknown reason")
+        );
+        let payload = WorkerUnsigned { path: path.to_string_lossy().to_string(), reason: status.reason.clone(), severity };
+        let _ = app.emit("worker-unsigned", &payload);
+    }
+    Ok(status)
+}
+
+/// Store an install check and warn the frontend if anything is off.
+fn report_install_integrity(app: &AppHandle, check: &InstallCheck, report: integrity::InstallIntegrity) {
+    if !report.healthy() {
+        for file in report.files.iter().filter(|f| f.state != integrity::FileState::Ok) {
+            eprintln!("[djbot] WARNING: installed file {} is {:?}", file.name, file.state);
+        }
+        if let Some(e) = &report.error {
+            eprintln!("[djbot] WARNING: install manifest unusable: {}", e);
+        }
+        let _ = app.emit("install-integrity-warning", &report);
+        if let Some(audit) = app.try_state::<AuditLog>() {
+            let bad: Vec<_> = report
+                .files
+                .iter()
+                .filter(|f| f.state != integrity::FileState::Ok)
+                .map(|f| format!("{} {:?}", f.name, f.state))
+                .collect();
+            let mut entry = AuditEntry::new(AuditKind::IntegrityViolation, "install_check").detail(bad.join(", "));
+            if let Some(e) = &report.error {
+                entry = entry.failed(e.clone());
+            }
+            audit.record(entry);
+        }
+    }
+    check.set(report);
+}
+
+/// Result of the startup check of installed files against the bundled
+/// manifest; `None` while it is still running.
+#[tauri::command]
+fn get_install_integrity(check: State<InstallCheck>) -> Option<integrity::InstallIntegrity> {
+    check.report()
+}
+
+/// Restore broken bundled resources from the repair archive and re-check.
+/// Whatever is still broken afterwards (always the worker binary) needs a
+/// reinstall.
+#[tauri::command]
+async fn attempt_repair(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+    check: State<'_, InstallCheck>,
+) -> Result<integrity::RepairOutcome, String> {
+    let resource_dir = app.path().resource_dir().map_err(|e| e.to_string())?;
+    let sidecar = state.sidecar_path().ok_or_else(|| "worker binary not resolved yet".to_string())?;
+    let check = check.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = check
+            .report()
+            .unwrap_or_else(|| integrity::check_install(&resource_dir, goworker_name(), &sidecar));
+        let outcome = integrity::repair(&resource_dir, goworker_name(), &sidecar, &report)?;
+        report_install_integrity(&app, &check, outcome.integrity.clone());
+        Ok(outcome)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Open file handles of the running worker and how close they are to its
+/// soft limit. `None` if no worker is running or the count can't be read.
+#[tauri::command]
+async fn get_worker_fd_count(state: State<'_, WorkerState>) -> Result<Option<HandleUsage>, String> {
+    let Some(pid) = state.worker_pid() else { return Ok(None) };
+    tauri::async_runtime::spawn_blocking(move || handles::usage(pid))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Fill of the per-command rate limit buckets (`ratelimit.rs`).
+#[tauri::command]
+fn get_rate_limits(limiter: State<RateLimiter>) -> Vec<BucketState> {
+    limiter.states()
+}
+
+/// Worker resident memory, at startup and now.
+#[derive(Serialize)]
+struct WorkerMemory {
+    startup_rss_bytes: Option<u64>,
+    current_rss_bytes: Option<u64>,
+}
+
+async fn worker_memory(state: &WorkerState) -> WorkerMemory {
+    let current_rss_bytes = match state.worker_pid() {
+        Some(pid) => tauri::async_runtime::spawn_blocking(move || memory::rss_bytes(pid)).await.ok().flatten(),
+        None => None,
+    };
+    WorkerMemory { startup_rss_bytes: state.startup_rss_bytes(), current_rss_bytes }
+}
+
+#[tauri::command]
+async fn get_worker_memory(state: State<'_, WorkerState>) -> Result<WorkerMemory, String> {
+    Ok(worker_memory(&state).await)
+}
+
+/// Main window geometry and state, for UI bug reports.
+#[derive(Serialize)]
+struct WindowInfo {
+    /// Physical pixels.
+    width: u32,
+    height: u32,
+    is_visible: bool,
+    is_focused: bool,
+    is_maximized: bool,
+    scale_factor: f64,
+}
+
+fn window_info(app: &AppHandle) -> Result<WindowInfo, String> {
+    let window = app.get_webview_window("main").ok_or_else(|| "main window not found".to_string())?;
+    let size = window.inner_size().map_err(|e| e.to_string())?;
+    Ok(WindowInfo {
+        width: size.width,
+        height: size.height,
+        is_visible: window.is_visible().map_err(|e| e.to_string())?,
+        is_focused: window.is_focused().map_err(|e| e.to_string())?,
+        is_maximized: window.is_maximized().map_err(|e| e.to_string())?,
+        scale_factor: window.scale_factor().map_err(|e| e.to_string())?,
+    })
+}
+
+#[tauri::command]
+fn get_window_info(app: AppHandle) -> Result<WindowInfo, String> {
+    window_info(&app)
+}
+
+/// Audit entries included in diagnostics.
+const DIAGNOSTICS_AUDIT_ENTRIES: usize = 200;
+
+/// How the worker binary was found: every candidate checked, the one
+/// discovery chose and what is actually run (possibly the cached copy).
+#[derive(Serialize)]
+struct BinaryResolutionReport {
+    #[serde(flatten)]
+    discovery: DiscoveryReport,
+    running_from: Option<std::path::PathBuf>,
+    from_cache: bool,
+}
+
+/// The one call to make for "worker not found" reports.
+#[tauri::command]
+fn get_binary_resolution_report(state: State<WorkerState>, discovery: State<SidecarDiscovery>) -> BinaryResolutionReport {
+    let running_from = state.sidecar_path();
+    let from_cache = match (&running_from, state.data_dir()) {
+        (Some(path), Some(dir)) => path.starts_with(worker_cache::cache_dir(&dir)),
+        _ => false,
+    };
+    BinaryResolutionReport { discovery: discovery.report(), running_from, from_cache }
+}
+
+/// Everything a maintainer needs to triage a bug report in one call.
+#[derive(Serialize)]
+struct Diagnostics {
+    status: WorkerStatus,
+    port: Option<u16>,
+    startup_error: Option<String>,
+    restart_count: u32,
+    data_dir: Option<std::path::PathBuf>,
+    /// Octal mode applied to created data/output dirs (Unix only).
+    dir_mode: Option<String>,
+    sidecar_path: Option<std::path::PathBuf>,
+    worker_cwd: Option<std::path::PathBuf>,
+    output_bit_depth: Option<u8>,
+    worker_discovery: DiscoveryReport,
+    worker_signature: Option<WorkerSignature>,
+    ffmpeg_path: Option<String>,
+    events: EventMetrics,
+    stderr_tail: Vec<String>,
+    clock_skew: Option<ClockSkew>,
+    /// Paths refused as outside the allowed locations since launch.
+    path_violations: u64,
+    worker_handles: Option<HandleUsage>,
+    window: Option<WindowInfo>,
+    worker_memory: WorkerMemory,
+    /// Most recent audit entries, home directory shown as `~`.
+    audit_log: Vec<AuditEntry>,
+}
+
+#[tauri::command]
+async fn collect_diagnostics(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+    bus: State<'_, EventBus>,
+    discovery: State<'_, SidecarDiscovery>,
+    scope: State<'_, Scope>,
+    audit: State<'_, AuditLog>,
+) -> Result<Diagnostics, String> {
+    let home = app.path().home_dir().ok();
+    let entries = audit.read(None, None);
+    let audit_log = entries[entries.len().saturating_sub(DIAGNOSTICS_AUDIT_ENTRIES)..]
+        .iter()
+        .map(|e| e.redacted(home.as_deref()))
+        .collect();
+    let worker_handles = match state.worker_pid() {
+        Some(pid) => tauri::async_runtime::spawn_blocking(move || handles::usage(pid)).await.ok().flatten(),
+        None => None,
+    };
+    let sidecar_path = state.sidecar_path();
+    let worker_signature = match sidecar_path.clone() {
+        Some(path) => tauri::async_runtime::spawn_blocking(move || signature::check(&path))
+            .await
+            .ok(),
+        None => None,
+    };
+    Ok(Diagnostics {
+        status: state.status(),
+        port: state.port(),
+        startup_error: state.startup_error(),
+        restart_count: state.restart_count(),
+        data_dir: state.data_dir(),
+        dir_mode: cfg!(unix).then(|| format!("{:04o}", state.dir_mode())),
+        sidecar_path,
+        worker_cwd: state.worker_cwd(),
+        output_bit_depth: state.output_bit_depth(),
+        worker_discovery: discovery.report(),
+        worker_signature,
+        ffmpeg_path: state.ffmpeg_path(),
+        events: bus.metrics(),
+        stderr_tail: state.stderr_tail(),
+        clock_skew: state.data_dir().and_then(|dir| clock::check(&dir.join("output"))),
+        path_violations: scope.violations(),
+        worker_handles,
+        window: window_info(&app).ok(),
+        worker_memory: worker_memory(&state).await,
+        audit_log,
+    })
+}
+
+/// Look for output files dated in the future and emit `clock-skew` if
+/// one is found.
+#[tauri::command]
+fn check_clock_skew(app: AppHandle, state: State<WorkerState>) -> Option<ClockSkew> {
+    report_clock_skew(&app, &state.data_dir()?.join("output"))
+}
+
+fn report_clock_skew(app: &AppHandle, output_dir: &std::path::Path) -> Option<ClockSkew> {
+    let skew = clock::check(output_dir)?;
+    eprintln!(
+        "[djbot] WARNING: {} is dated {}s in the future; the system clock may be wrong",
+        skew.newest_file, skew.ahead_secs
+    );
+    let _ = app.emit("clock-skew", &skew);
+    Some(skew)
+}
+
+/// Everything about this install in one place — the first thing to ask for
+/// in a bug report.
+#[derive(Debug, Clone, Serialize)]
+struct EnvironmentReport {
+    system: SystemInfo,
+    ffmpeg_source: Option<FfmpegSource>,
+    sidecar_path: Option<std::path::PathBuf>,
+    /// Hash of the binary the running worker was spawned from, or of the
+    /// binary currently on disk if no worker has been started.
+    sidecar_sha256: Option<String>,
+    data_dir: Option<std::path::PathBuf>,
+    data_dir_free_bytes: Option<u64>,
+    worker_status: WorkerStatus,
+    worker_port: Option<u16>,
+    restart_count: u32,
+    settings: DjbotConfig,
+}
+
+async fn environment_report(state: &WorkerState) -> EnvironmentReport {
+    let sidecar_path = state.sidecar_path();
+    let data_dir = state.data_dir();
+    let spawned_sha = state.spawned_binary().map(|f| f.sha256);
+    let (sidecar_path, sidecar_sha256, data_dir, data_dir_free_bytes, settings) =
+        tauri::async_runtime::spawn_blocking(move || {
+            let sha = spawned_sha.or_else(|| {
+                sidecar_path.as_deref().and_then(|p| integrity::sha256_file(p).ok())
+            });
+            let free = data_dir.as_deref().and_then(disk::free_space);
+            let settings = data_dir.as_deref().map(DjbotConfig::load).unwrap_or_default();
+            (sidecar_path, sha, data_dir, free, settings)
+        })
+        .await
+        .unwrap_or_default();
+    EnvironmentReport {
+        system: system_info(state),
+        ffmpeg_source: state.ffmpeg_source(),
+        sidecar_path,
+        sidecar_sha256,
+        data_dir,
+        data_dir_free_bytes,
+        worker_status: state.status(),
+        worker_port: state.port(),
+        restart_count: state.restart_count(),
+        settings,
+    }
+}
+
+#[tauri::command]
+async fn get_environment_report(state: State<'_, WorkerState>) -> Result<EnvironmentReport, String> {
+    Ok(environment_report(&state).await)
+}
+
+/// `get_environment_report` as indented JSON, for pasting into an issue.
+#[tauri::command]
+async fn get_environment_report_text(state: State<'_, WorkerState>) -> Result<String, String> {
+    serde_json::to_string_pretty(&environment_report(&state).await).map_err(|e| e.to_string())
+}
+
+/// Start the worker if it isn't running, e.g. after `auto_start_worker` was
+/// off or a previous start failed.
+#[tauri::command]
+async fn retry_worker_start(
+    app: AppHandle,
+    state: State<'_, WorkerState>,
+) -> Result<(), String> {
+    if state.worker_running() {
+        return Err("Worker is already running".to_string());
+    }
+    acknowledge_worker_failure(app.state());
+    state.start_retries.store(0, Ordering::SeqCst);
+    tauri::async_runtime::spawn_blocking(move || spawn_worker(&app))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// The UI has shown the last `worker-failed`; it won't force an exit.
+#[tauri::command]
+fn acknowledge_worker_failure(failures: State<WorkerFailures>) {
+    failures.answered.store(failures.reported.load(Ordering::SeqCst), Ordering::SeqCst);
+}
+
+/// Quit the app, stopping the worker as a normal exit does. The
+/// `worker-failed` "Exit App" action.
+#[tauri::command]
+fn exit_app(app: AppHandle, code: Option<i32>) {
+    acknowledge_worker_failure(app.state());
+    app.exit(code.unwrap_or(0));
+}
+
+/// Re-check the resolved worker binary for an empty, truncated or foreign
+/// file. `Ok(false)` if it isn't on disk (e.g. the PATH fallback).
+#[tauri::command]
+fn check_worker_binary(state: State<WorkerState>) -> Result<bool, String> {
+    let path = state.sidecar_path().ok_or_else(|| "worker binary not resolved yet".to_string())?;
+    discovery::check_binary(&path).map_err(|reason| format!("{}: {}", path.display(), reason))
+}
+
+/// Whether the saved worker settings changed since the running worker was
+/// started, i.e. a restart is needed to apply them.
+#[tauri::command]
+fn config_changed_since_spawn(app: AppHandle, state: State<WorkerState>) -> Result<bool, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    Ok(state.config_changed_since_spawn(&configured_worker(&app, &data_dir)))
+}
+
+/// Stop the worker (if running) and start it again with the saved config.
+#[tauri::command]
+async fn restart_worker(app: AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<WorkerState>().kill_worker();
+        spawn_worker(&app)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Serialize)]
+struct HeartbeatStatus {
+    missed: u32,
+    max: u32,
+    is_healthy: bool,
+}
+
+/// Health checks failed in a row, and how many are tolerated before the
+/// worker is restarted.
+#[tauri::command]
+fn get_heartbeat_status(state: State<WorkerState>) -> HeartbeatStatus {
+    let missed = state.heartbeat_missed.load(Ordering::SeqCst);
+    HeartbeatStatus { missed, max: state.max_missed_heartbeats.load(Ordering::SeqCst), is_healthy: missed == 0 }
+}
+
+/// Persist whether the worker is launched together with the app.
+#[tauri::command]
+fn set_auto_start_worker(state: State<WorkerState>, enabled: bool) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.auto_start_worker = enabled;
+    config.save(&data_dir)
+}
+
+/// Fill levels of the in-memory buffers holding worker-derived data.
+#[derive(Serialize)]
+struct BufferStats {
+    stderr_tail: BufStats,
+    job_history: BufStats,
+    event_queue: BufStats,
+}
+
+#[tauri::command]
+fn get_buffer_stats(
+    state: State<WorkerState>,
+    history: State<JobHistory>,
+    bus: State<EventBus>,
+) -> BufferStats {
+    BufferStats {
+        stderr_tail: state.stderr_tail_stats(),
+        job_history: history.stats(),
+        event_queue: bus.queue_stats(),
+    }
+}
+
+/// Result of `check_worker_updated`.
+#[derive(Serialize)]
+struct WorkerUpdateCheck {
+    /// The binary on disk differs from the one the worker was spawned from;
+    /// `restart_worker` picks up the new one.
+    updated: bool,
+    spawned: Option<integrity::BinaryFingerprint>,
+    current: Option<integrity::BinaryFingerprint>,
+}
+
+/// Compare the sidecar on disk with the one the running worker came from,
+/// e.g. after an in-place app update. Emits `worker-update-available` when
+/// they differ.
+#[tauri::command]
+async fn check_worker_updated(app: AppHandle, state: State<'_, WorkerState>) -> Result<WorkerUpdateCheck, String> {
+    let path = state.sidecar_path().ok_or_else(|| "worker binary not resolved yet".to_string())?;
+    let spawned = state.spawned_binary();
+    let current = tauri::async_runtime::spawn_blocking(move || integrity::fingerprint(&path))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok();
+    let updated = match (&spawned, &current) {
+        (Some(spawned), Some(current)) => spawned.sha256 != current.sha256,
+        _ => false,
+    };
+    let check = WorkerUpdateCheck { updated, spawned, current };
+    if check.updated {
+        eprintln!("[djbot] worker binary changed on disk since it was started");
+        let _ = app.emit("worker-update-available", &check.current);
+    }
+    Ok(check)
+}
+
+/// Take or release the OS media keys; persisted for the next launch.
+#[tauri::command]
+fn set_media_keys_enabled(
+    state: State<WorkerState>,
+    media: State<MediaSession>,
+    enabled: bool,
+) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.media_keys = enabled;
+    config.save(&data_dir)?;
+    media.set_enabled(enabled);
+    Ok(())
+}
+
+/// Publish the preview player's current track and position to the OS
+/// now-playing overlay.
+#[tauri::command]
+fn set_now_playing(
+    media: State<MediaSession>,
+    presence: State<DiscordPresence>,
+    watch: State<AudioWatch>,
+    info: NowPlaying,
+) {
+    watch.playing.store(info.playing, Ordering::Relaxed);
+    presence.set_now_playing(info.clone());
+    media.set_now_playing(info);
+}
+
+/// Remove the now-playing entry (playback stopped).
+#[tauri::command]
+fn clear_now_playing(media: State<MediaSession>, presence: State<DiscordPresence>, watch: State<AudioWatch>) {
+    watch.playing.store(false, Ordering::Relaxed);
+    media.clear_now_playing();
+    presence.clear_now_playing();
+}
+
+/// Turn Discord Rich Presence on or off and persist the choice.
+#[tauri::command]
+fn set_discord_presence_enabled(
+    state: State<WorkerState>,
+    presence: State<DiscordPresence>,
+    enabled: bool,
+) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.presence.enabled = enabled;
+    config.save(&data_dir)?;
+    presence.set_enabled(enabled);
+    Ok(())
+}
+
+/// Update the session summary (BPM, tracks analyzed) shown in the presence.
+#[tauri::command]
+fn set_presence_session(presence: State<DiscordPresence>, info: SessionInfo) {
+    presence.set_session(info);
+}
+
+#[tauri::command]
+fn get_presence_status(presence: State<DiscordPresence>) -> PresenceStatus {
+    presence.status()
+}
+
+#[tauri::command]
+async fn list_audio_devices() -> Result<Vec<AudioDevice>, String> {
+    tauri::async_runtime::spawn_blocking(audio::list_output_devices)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Persist the output device handed to the worker (`None` = system
+/// default). Takes effect the next time the worker starts.
+#[tauri::command]
+fn set_preferred_output_device(
+    state: State<WorkerState>,
+    watch: State<AudioWatch>,
+    device: Option<String>,
+) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.preferred_output_device = device.clone();
+    config.save(&data_dir)?;
+    *watch.preferred.lock().unwrap() = device;
+    Ok(())
+}
+
+/// Choose what preview playback does when the default output device
+/// changes: `pause`, `continue` or `follow_new_device`.
+#[tauri::command]
+fn set_device_change_behavior(
+    state: State<WorkerState>,
+    watch: State<AudioWatch>,
+    behavior: DeviceChangeBehavior,
+) -> Result<(), String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.on_default_device_change = behavior;
+    config.save(&data_dir)?;
+    *watch.on_default_change.lock().unwrap() = behavior;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AudioSettings {
+    settings: AudioEngineConfig,
+    options: AudioEngineOptions,
+}
+
+/// Saved audio engine settings and the values valid on this platform.
+#[tauri::command]
+fn get_audio_settings(state: State<WorkerState>) -> Result<AudioSettings, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    Ok(AudioSettings {
+        settings: DjbotConfig::load(&data_dir).worker.audio,
+        options: audio::engine_options(),
+    })
+}
+
+/// Validate and save audio engine settings. They are pushed to a running
+/// worker that supports live reconfiguration; otherwise they apply from the
+/// next start. Returns whether a restart is required.
+#[tauri::command]
+async fn set_audio_settings(
+    state: State<'_, WorkerState>,
+    http: State<'_, WorkerHttp>,
+    settings: AudioEngineConfig,
+) -> Result<bool, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let mut config = DjbotConfig::load(&data_dir);
+    config.worker.audio = settings.clone();
+    config.save(&data_dir)?;
+
+    let live = state.audio().effective.is_some_and(|e| e.live_reconfig);
+    let applied = match state.command_port() {
+        Ok(port) if live => {
+            let body = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+            match proxy::forward(&http.client(None)?, port, "POST", "audio/config", Some(body)).await {
+                Ok(_) => true,
+                Err(e) => {
+                    eprintln!("[djbot] live audio reconfiguration failed: {}", e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    state.update_requested_audio(settings, applied);
+    Ok(state.audio().restart_required)
+}
+
+/// The worker's own log file, if `worker_log_file` is on.
+#[tauri::command]
+fn get_worker_log_file_path(state: State<WorkerState>) -> Option<String> {
+    let data_dir = state.data_dir()?;
+    DjbotConfig::load(&data_dir)
+        .worker
+        .worker_log_file
+        .then(|| worker::worker_log_file(&data_dir).to_string_lossy().to_string())
+}
+
+/// Where `open_path` may open things. The frontend names one of these and
+/// a path relative to it, never an absolute path.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OpenKind {
+    Output,
+    Logs,
+    Data,
+}
+
+/// Resolve `relative` below the directory for `kind` through the path scope.
+fn open_target(state: &WorkerState, scope: &Scope, kind: OpenKind, relative: &str) -> Result<std::path::PathBuf, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let root = match kind {
+        OpenKind::Output => data_dir.join("output"),
+        OpenKind::Logs => data_dir.join("logs"),
+        OpenKind::Data => data_dir,
+    };
+    Ok(scope.resolve_in(&root, relative)?)
+}
+
+/// Open a file or folder below the output, logs or data dir with the OS
+/// default handler. Only these locations can be opened from the frontend;
+/// anything else is refused and counted in diagnostics.
+#[tauri::command]
+fn open_path(state: State<WorkerState>, scope: State<Scope>, kind: OpenKind, relative_path: String) -> Result<(), String> {
+    let path = open_target(&state, &scope, kind, &relative_path)?;
+    tauri_plugin_opener::open_path(&path, None::<&str>).map_err(|e| format!("failed to open {}: {}", path.display(), e))
+}
+
+/// Send worker log lines over `channel` as they arrive, until the webview
+/// goes away. Each call is its own subscriber: one that reads too slowly
+/// skips ahead past the lines it missed, with a note of how many, and never
+/// holds up the worker or other subscribers.
+#[tauri::command]
+async fn stream_worker_log(channel: Channel<String>, state: State<'_, WorkerState>) -> Result<(), String> {
+    let mut rx = state.log_broadcast.subscribe();
+    loop {
+        let line = match rx.recv().await {
+            Ok(line) => line,
+            Err(broadcast::error::RecvError::Lagged(n)) => format!("[djbot] {} log lines skipped", n),
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        if channel.send(line).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// The worker's own log if it keeps one, else the `worker.log` we write
+/// from its stderr. Errors if the file doesn't exist yet.
+fn existing_worker_log(state: &WorkerState, scope: &Scope) -> Result<std::path::PathBuf, String> {
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let own = worker::worker_log_file(&data_dir);
+    let path = if DjbotConfig::load(&data_dir).worker.worker_log_file && own.is_file() {
+        own
+    } else {
+        logs::log_file_path(&data_dir)
+    };
+    if !path.is_file() {
+        return Err(format!("{} does not exist yet", path.display()));
+    }
+    let relative = path.strip_prefix(&data_dir).map_err(|e| e.to_string())?;
+    open_target(state, scope, OpenKind::Data, &relative.to_string_lossy())
+}
+
+/// Open the worker log in the system's default handler for `.log` files.
+#[tauri::command]
+fn open_worker_log(state: State<WorkerState>, scope: State<Scope>) -> Result<(), String> {
+    let path = existing_worker_log(&state, &scope)?;
+    tauri_plugin_opener::open_path(&path, None::<&str>).map_err(|e| format!("failed to open {}: {}", path.display(), e))
+}
+
+/// Show the worker log selected in the OS file manager without opening it.
+#[tauri::command]
+fn reveal_worker_log(state: State<WorkerState>, scope: State<Scope>) -> Result<(), String> {
+    let path = existing_worker_log(&state, &scope)?;
+    tauri_plugin_opener::reveal_item_in_dir(&path).map_err(|e| format!("failed to reveal {}: {}", path.display(), e))
+}
+
+/// The environment passed to the worker (as filtered by `sanitize_env`),
+/// with credential-looking values redacted. Captured once at launch.
+#[tauri::command]
+fn get_worker_env_snapshot(state: State<WorkerState>) -> std::collections::HashMap<String, String> {
+    state.env_snapshot().into_iter().collect()
+}
+
+/// Event forwarding counters, used to verify batching/back-pressure.
+#[tauri::command]
+fn get_worker_request_metrics(bus: State<EventBus>) -> EventMetrics {
+    bus.metrics()
+}
+
+/// Return the compile-time platform+arch specific filename for the Go worker.
+///
+/// This must match exactly what the CI build step produces; see release.yml.
+fn goworker_name() -> &'static str {
+    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+    return "goworker-x86_64-pc-windows-msvc.exe";
+
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    return "goworker-aarch64-apple-darwin";
+
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    return "goworker-x86_64-apple-darwin";
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    return "goworker-x86_64-unknown-linux-gnu";
+
+    // Fallback: bare name, rely on PATH
+    #[cfg(not(any(
+        all(target_os = "windows", target_arch = "x86_64"),
+        all(target_os = "macos",   target_arch = "aarch64"),
+        all(target_os = "macos",   target_arch = "x86_64"),
+        all(target_os = "linux",   target_arch = "x86_64"),
+    )))]
+    return "goworker";
+}
+
+/// Free space in the output dir below which a ready worker triggers
+/// `disk-space-low`.
+const LOW_DISK_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Clone, Serialize)]
+struct DiskSpaceLow {
+    path: String,
+    free_bytes: u64,
+}
+
+/// What happens each time a worker becomes ready: `worker-ready` for the
+/// frontend, a free-space check of the output dir, and fs scope access to
+/// output files left by earlier sessions.
+fn register_ready_handlers(app: &AppHandle, state: &WorkerState, output_dir: &std::path::Path) {
+    let handle = app.clone();
+    state.register_on_ready(move |port| {
+        let _ = handle.emit("worker-ready", port);
+    });
+
+    let (handle, dir) = (app.clone(), output_dir.to_path_buf());
+    state.register_on_ready(move |_| {
+        let Some(free_bytes) = disk::free_space(&dir) else { return };
+        if free_bytes < LOW_DISK_SPACE_BYTES {
+            eprintln!("[djbot] WARNING: only {} MiB free in {}", free_bytes / (1024 * 1024), dir.display());
+            let _ = handle.emit("disk-space-low", DiskSpaceLow { path: dir.to_string_lossy().to_string(), free_bytes });
+        }
+    });
+
+    let (handle, dir) = (app.clone(), output_dir.to_path_buf());
+    state.register_on_ready(move |_| fs_scope::allow_existing_outputs(&handle, &dir));
+}
+
+/// How long a headless job waits for the worker to come up.
+const HEADLESS_WORKER_WAIT: Duration = Duration::from_secs(60);
+
+/// Run `djbot export` against the worker: analyze the inputs, plan a mix
+/// and render it, the same three requests the UI's smart mix makes.
+/// Progress messages go to `report`. Returns the path of the rendered mix.
+async fn run_export(app: &AppHandle, args: &ExportArgs, report: fn(&str)) -> Result<String, String> {
+    let state = app.state::<WorkerState>();
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let inputs = args.validate(&DjbotConfig::load(&data_dir).import)?;
+    state.wait_for_port(HEADLESS_WORKER_WAIT).await?;
+    let request = |path: &'static str, body: serde_json::Value| {
+        forward_worker_request(app.clone(), app.state(), app.state(), "POST".to_string(), path.to_string(), Some(body), None)
+    };
+
+    report(&format!("analyzing {} file(s)...", inputs.len()));
+    let analysis = request("/analyze", serde_json::json!({ "filepaths": inputs })).await?;
+    let tracks: Vec<serde_json::Value> = analysis
+        .get("results")
+        .and_then(|r| r.as_array())
+        .map(|results| results.iter().filter(|r| r.get("duration").and_then(|d| d.as_f64()).unwrap_or(0.0) > 0.0).cloned().collect())
+        .unwrap_or_default();
+    if tracks.len() < inputs.len() {
+        report(&format!("{} of {} file(s) could not be analyzed", inputs.len() - tracks.len(), inputs.len()));
+    }
+    if tracks.len() < 2 {
+        return Err("fewer than 2 files could be analyzed".to_string());
+    }
+
+    report(&format!("planning mix of {} tracks...", tracks.len()));
+    let plan = request("/plan", serde_json::json!({ "tracks": tracks, "scenarios": 5 })).await?;
+    let list = |key: &str| plan["plan"][key].as_array().cloned().unwrap_or_default();
+    let (sorted, selections) = (list("sorted_tracks"), list("selections"));
+
+    let output_path = app.output_dir().join(args.file_name());
+    report(&format!("rendering {}...", output_path.display()));
+    let rendered = request(
+        "/render/mix",
+        serde_json::json!({
+            "playlist": cli::playlist(&sorted, &selections),
+            "transitions": selections,
+            "output_path": output_path.to_string_lossy(),
+        }),
+    )
+    .await?;
+    if let Some(error) = rendered.get("error").and_then(|e| e.as_str()).filter(|e| !e.is_empty()) {
+        return Err(error.to_string());
+    }
+    Ok(rendered
+        .get("mp3_path")
+        .and_then(|p| p.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| output_path.to_string_lossy().to_string()))
+}
+
+/// Run a command-line job in the background and exit with its outcome.
+fn spawn_headless_job(app: AppHandle, command: CliCommand) {
+    tauri::async_runtime::spawn(async move {
+        let CliCommand::Export(args) = command;
+        let code = match run_export(&app, &args, |m| println!("{}", m)).await {
+            Ok(path) => {
+                println!("wrote {}", path);
+                0
+            }
+            Err(e) => {
+                eprintln!("djbot export failed: {}", e);
+                1
+            }
+        };
+        app.exit(code);
+    });
+}
+
+/// How often the scheduler looks for due entries.
+const SCHEDULE_TICK: Duration = Duration::from_secs(30);
+
+/// Analyze every file directly inside `folder` that would be admitted as
+/// an import. Returns a summary for the run record.
+async fn run_analyze_folder(app: &AppHandle, folder: &std::path::Path) -> Result<String, String> {
+    // Checked again here: the scope's roots may have changed since the
+    // entry was added, or config.toml may have been edited by hand.
+    let folder = &app.state::<Scope>().resolve(&folder.to_string_lossy())?;
+    let state = app.state::<WorkerState>();
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let import = DjbotConfig::load(&data_dir).import;
+    let inputs: Vec<String> = std::fs::read_dir(folder)
+        .map_err(|e| format!("{}: {}", folder.display(), e))?
+        .flatten()
+        .filter_map(|entry| intake::check_file(&entry.path().to_string_lossy(), &entry.path(), &import).ok())
+        .map(|admitted| admitted.path.to_string_lossy().to_string())
+        .collect();
+    if inputs.is_empty() {
+        return Ok(format!("nothing to analyze in {}", folder.display()));
+    }
+    let analysis = forward_worker_request(
+        app.clone(),
+        app.state(),
+        app.state(),
+        "POST".to_string(),
+        "/analyze".to_string(),
+        Some(serde_json::json!({ "filepaths": inputs })),
+        None,
+    )
+    .await?;
+    let analyzed = analysis.get("results").and_then(|r| r.as_array()).map_or(0, |r| r.len());
+    Ok(format!("analyzed {} of {} file(s) in {}", analyzed, inputs.len(), folder.display()))
+}
+
+/// Run `schedule` now and record the outcome. One scheduled job runs at a
+/// time; a second caller waits for the first.
+async fn run_schedule(app: &AppHandle, schedule: &Schedule) -> ScheduleRun {
+    let scheduler = app.state::<Scheduler>();
+    let _running = scheduler.running.lock().await;
+    eprintln!("[djbot] running schedule {:?} ({})", schedule.name, schedule.job.kind());
+    let started_at = jobs::unix_now();
+    let result = match &schedule.job {
+        ScheduledJob::Analyze { folder } => run_analyze_folder(app, folder).await,
+        ScheduledJob::Export(args) => run_export(app, args, |m| eprintln!("[djbot] schedule: {}", m)).await,
+    };
+    if let Err(e) = &result {
+        eprintln!("[djbot] schedule {:?} failed: {}", schedule.name, e);
+    }
+    let run = ScheduleRun {
+        started_at,
+        finished_at: jobs::unix_now(),
+        ok: result.is_ok(),
+        detail: result.unwrap_or_else(|e| e),
+    };
+    scheduler.record_run(&schedule.id, run.clone());
+    run
+}
+
+/// Fire due schedule entries every `SCHEDULE_TICK`. An entry due while the
+/// worker can't take requests is reported once with `schedule-deferred`
+/// and runs on the first tick after it can.
+fn spawn_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_TICK);
+        loop {
+            interval.tick().await;
+            let scheduler = app.state::<Scheduler>();
+            for schedule in scheduler.due(jobs::unix_now()) {
+                let state = app.state::<WorkerState>();
+                if !state.is_accepting_commands() {
+                    if scheduler.mark_deferred(&schedule.id) {
+                        let reason = format!("worker not ready ({:?})", state.status());
+                        eprintln!("[djbot] schedule {:?} deferred: {}", schedule.name, reason);
+                        let _ = app.emit("schedule-deferred", ScheduleDeferred { id: schedule.id, name: schedule.name, reason });
+                    }
+                    continue;
+                }
+                run_schedule(&app, &schedule).await;
+            }
+        }
+    });
+}
+
+/// Add a schedule entry. The folder or export inputs it names must be
+/// inside the `Scope` and are stored resolved.
+#[tauri::command]
+fn add_schedule(
+    scheduler: State<Scheduler>,
+    scope: State<Scope>,
+    name: String,
+    timing: Timing,
+    job: ScheduledJob,
+    enabled: Option<bool>,
+    catch_up: Option<bool>,
+) -> Result<Schedule, String> {
+    let resolve = |path: &std::path::Path| scope.resolve(&path.to_string_lossy());
+    let job = match job {
+        ScheduledJob::Analyze { folder } => ScheduledJob::Analyze { folder: resolve(&folder)? },
+        ScheduledJob::Export(mut args) => {
+            args.inputs = args.inputs.iter().map(|p| resolve(p)).collect::<Result<_, _>>()?;
+            ScheduledJob::Export(args)
+        }
+    };
+    scheduler.add(name, timing, job, enabled.unwrap_or(true), catch_up.unwrap_or(false))
+}
+
+#[tauri::command]
+fn list_schedules(scheduler: State<Scheduler>) -> Vec<ScheduleView> {
+    scheduler.list()
+}
+
+#[tauri::command]
+fn remove_schedule(scheduler: State<Scheduler>, id: String) -> Result<(), String> {
+    scheduler.remove(&id)
+}
+
+/// Run a schedule entry immediately, whatever its timing, and return the
+/// outcome. Counts as its last run.
+#[tauri::command]
+async fn run_schedule_now(app: AppHandle, state: State<'_, WorkerState>, id: String) -> Result<ScheduleRun, String> {
+    let schedule = app.state::<Scheduler>().get(&id).ok_or_else(|| format!("no schedule {}", id))?;
+    state.command_port()?;
+    Ok(run_schedule(&app, &schedule).await)
+}
+
+/// Probe connectivity every `network.probe_interval_secs` and emit
+/// `network-changed` when it settles on a new state. Coming back online
+/// sends the held URL jobs, each reporting `pending-url-job-finished`.
+fn spawn_network_monitor(app: AppHandle, data_dir: std::path::PathBuf) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = DjbotConfig::load(&data_dir);
+            let target = network::probe_target(&data_dir, &config);
+            let result = network::probe(target.as_ref()).await;
+            let monitor = app.state::<NetworkMonitor>();
+            if let Some(status) = monitor.observe(target.map(|(host, _)| host), result) {
+                eprintln!(
+                    "[djbot] network {}{}",
+                    if status.online { "back online" } else { "offline" },
+                    status.detail.as_deref().map(|d| format!(": {}", d)).unwrap_or_default()
+                );
+                let _ = app.emit("network-changed", &status);
+                if status.online {
+                    for job in monitor.take_pending() {
+                        let app = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            let result = forward_worker_request(
+                                app.clone(),
+                                app.state(),
+                                app.state(),
+                                job.method,
+                                job.path,
+                                job.body,
+                                None,
+                            )
+                            .await;
+                            let (result, error) = match result {
+                                Ok(value) => (Some(value), None),
+                                Err(e) => (None, Some(e)),
+                            };
+                            let _ = app.emit("pending-url-job-finished", PendingUrlJobFinished { id: job.id, result, error });
+                        });
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(config.network.probe_interval_secs.max(5))).await;
+        }
+    });
+}
+
+/// Current connectivity as last settled by the monitor.
+#[tauri::command]
+fn get_network_status(network: State<NetworkMonitor>) -> NetworkStatus {
+    network.status()
+}
+
+/// The worker plugin: managed state, commands, startup and shutdown.
+/// With a `command`, the app runs it headless and exits (see `cli`).
+/// `env` and `cli` are layered over config.toml, in that order, every
+/// time the worker starts.
+pub(crate) fn init(command: Option<CliCommand>, worker_overrides: WorkerConfigOverride) -> TauriPlugin<Wry> {
+    let dispatch: fn(tauri::ipc::Invoke<Wry>) -> bool = djbot_commands!(tauri::generate_handler);
+    Builder::new("djbot-worker")
+        // Every call goes through the rate limiter first; see `ratelimit`.
+        .invoke_handler(move |invoke| {
+            let limited = invoke
+                .message
+                .webview_ref()
+                .try_state::<RateLimiter>()
+                .and_then(|limiter| limiter.check(invoke.message.command()).err());
+            match limited {
+                Some(refused) => {
+                    invoke.resolver.reject(refused.to_string());
+                    true
+                }
+                None => dispatch(invoke),
+            }
+        })
+        .setup(move |app, _api| {
+            let setup_state = WorkerState::new();
+            app.manage(setup_state.clone());
+            app.manage(WorkerOverrides(worker_overrides.clone()));
+            app.manage(PendingImport::default());
+            app.manage(TagCache::default());
+
+            let resource_path = app
+                .path()
+                .resource_dir()
+                .expect("resource dir not found");
+
+            let worker_name = goworker_name();
+
+            // Look for the worker binary in several locations (most → least specific):
+            //   1. <resource>/binaries/<name>   – Tauri-bundled sidecar
+            //   2. <resource>/<name>             – alternative bundle layout
+            //   3. <cwd>/backend/<name>          – dev mode (cargo run)
+            //   4. goworker in PATH              – system-wide install
+            let bare_name = if cfg!(target_os = "windows") { "goworker.exe" } else { "goworker" };
+            let mut candidates = vec![
+                resource_path.join("binaries").join(worker_name),
+                resource_path.join(worker_name),
+                std::env::current_dir()
+                    .unwrap_or_default()
+                    .join("backend")
+                    .join(worker_name),
+            ];
+            candidates.extend(discovery::find_in_path(bare_name));
+
+            let discovery = SidecarDiscovery::default();
+            app.manage(discovery.clone());
+            let sidecar_path = discovery::find_first_usable(&candidates, DISCOVERY_BUDGET, &discovery)
+                .or_else(|| {
+                    // Only broken copies found: keep one so spawning fails
+                    // with the reason instead of trying PATH.
+                    discovery.report().unusable.first().map(|u| u.path.clone())
+                })
+                // Nothing anywhere: the bare name, so the spawn error says what was missing.
+                .unwrap_or_else(|| std::path::PathBuf::from(bare_name));
+
+            eprintln!("[djbot] using worker: {}", sidecar_path.display());
+            setup_state.set_sidecar_path(sidecar_path.clone());
+
+            // Data directory:
+            //   debug  → project root (avoids triggering tauri dev hot-reload)
+            //   release → OS app-data dir (writable, persists across sessions)
+            let data_dir = if cfg!(debug_assertions) {
+                let mut p = std::env::current_dir().unwrap_or_default();
+                while p.ends_with("src-tauri") || p.ends_with("app") {
+                    p.pop();
+                }
+                p
+            } else {
+                let default_dir = app
+                    .path()
+                    .app_data_dir()
+                    .unwrap_or_else(|_| std::env::current_dir().unwrap_or_default());
+                // Moved elsewhere by migrate_data_dir?
+                data_dir::relocated(&default_dir).unwrap_or(default_dir)
+            };
+            // A missing config just means defaults, so it can be read before
+            // the directory is created with the configured mode.
+            let config = DjbotConfig::load(&data_dir);
+            if let Err(e) = config.output.create_dir(&data_dir) {
+                eprintln!("[djbot] failed to create {}: {}", data_dir.display(), e);
+            }
+
+            // Persist data_dir in state for get_output_dir
+            setup_state.set_data_dir(data_dir.clone());
+            setup_state.set_dir_mode(config.output.dir_mode);
+            setup_state.set_job_timeout_secs(config.worker.job_timeout_secs);
+            setup_state.max_missed_heartbeats.store(config.worker.max_missed_heartbeats, Ordering::SeqCst);
+            setup_state.set_output_bit_depth(config.worker.output_bit_depth);
+            setup_state.set_crash_policy(config.on_worker_crash);
+            setup_state.set_stderr_tail_capacity(config.worker.log_buffer_capacity);
+            setup_state.health_interval.send_replace(config.worker.health_check_interval_secs);
+            setup_state.set_env_snapshot(worker::redact_env(worker::worker_env()));
+
+            app.manage(AuditLog::new(&data_dir));
+
+            let sidecar_path = match worker_cache::resolve(&data_dir, worker_name, &sidecar_path) {
+                Some(cached) => {
+                    setup_state.set_sidecar_path(cached.clone());
+                    cached
+                }
+                None => sidecar_path,
+            };
+
+            // Hashing everything takes a while; spawn_worker verifies the
+            // worker binary itself before running it either way.
+            let install_check = InstallCheck::default();
+            app.manage(install_check.clone());
+            if let Ok(resource_dir) = app.path().resource_dir() {
+                let check_app = app.clone();
+                let check_sidecar = sidecar_path.clone();
+                std::thread::spawn(move || {
+                    let report = integrity::check_install(&resource_dir, goworker_name(), &check_sidecar);
+                    report_install_integrity(&check_app, &install_check, report);
+                });
+            }
+
+            // A snapshot left on disk means the previous session did not shut
+            // down cleanly. Restore what is still valid and count the restart.
+            let restored = load_state_snapshot(&data_dir);
+            if let Some(snap) = &restored {
+                eprintln!("[djbot] restoring state snapshot (restart #{})", snap.restart_count + 1);
+                setup_state.set_restart_count(snap.restart_count + 1);
+            }
+
+            // A configured override always wins over the cached path, so
+            // changing it takes effect on the next launch.
+            let user_override = config.worker.ffmpeg_path_override.as_deref();
+            let (ffmpeg, ffmpeg_source) = match restored
+                .and_then(|snap| snap.ffmpeg_path)
+                .filter(|p| user_override.is_none() && ffmpeg_still_usable(p))
+                .map(|path| (path, FfmpegSource::CacheFile))
+                .or_else(|| find_ffmpeg(user_override, &resource_path))
+            {
+                Some((path, source)) => (Some(path), Some(source)),
+                None => (None, None),
+            };
+            setup_state.set_ffmpeg_path(ffmpeg.clone());
+            setup_state.set_ffmpeg_source(ffmpeg_source);
+            setup_state.set_ffmpeg_version(ffmpeg.as_deref().and_then(ffmpeg::version));
+
+            #[cfg(target_os = "windows")]
+            if is_windows_admin() {
+                eprintln!("[djbot] WARNING: running as Administrator; output paths may resolve to protected locations");
+                let _ = app.emit("running-as-admin", ());
+            }
+
+            // One machine-readable line summarising the startup environment.
+            eprintln!(
+                "[djbot] startup {}",
+                serde_json::json!({
+                    "system": system_info(&setup_state),
+                    "data_dir": data_dir,
+                    "sidecar_path": sidecar_path,
+                    "restart_count": setup_state.restart_count(),
+                })
+            );
+
+            app.manage(WorkerHttp::new(&config.worker)?);
+            app.manage(Arc::new(TransferLimiter::new(config.worker.max_transfer_buffer_bytes)));
+            app.manage(Downloads::default());
+            app.manage(WorkerFailures::default());
+            app.manage(JobHistory::load(&data_dir));
+            app.manage(JobLog::load(&data_dir));
+            app.manage(Webhooks::new(&data_dir)?);
+            app.manage(Scheduler::load(&data_dir));
+            spawn_scheduler(app.clone());
+            app.manage(NetworkMonitor::default());
+            spawn_network_monitor(app.clone(), data_dir.clone());
+
+            // Linux and Windows dev builds need the scheme registered at runtime.
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                eprintln!("[djbot] failed to register djbot:// links: {}", e);
+            }
+            let link_handle = app.clone();
+            app.deep_link().on_open_url(move |event| {
+                for link in event.urls() {
+                    tauri::async_runtime::spawn(handle_import_link(link_handle.clone(), link));
+                }
+            });
+
+            let snapshot_state = setup_state.clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = save_state_snapshot(&snapshot_state) {
+                        eprintln!("[djbot] failed to write state snapshot: {}", e);
+                    }
+                }
+            });
+
+            let audio_watch = AudioWatch::new(config.worker.preferred_output_device.clone(), config.on_default_device_change);
+            // The webview player pauses itself on `audio-device-switched`;
+            // worker-side playback is told directly.
+            audio::spawn_watcher(app.clone(), audio_watch.clone(), |app| media::worker_playback(app, "pause", |_| {}));
+            app.manage(audio_watch);
+
+            // SMTC attaches to the main window on Windows.
+            #[cfg(target_os = "windows")]
+            let hwnd = app
+                .get_webview_window("main")
+                .and_then(|w| w.hwnd().ok())
+                .map(|h| h.0 as usize);
+            #[cfg(not(target_os = "windows"))]
+            let hwnd = None;
+            app.manage(MediaSession::start(app.clone(), hwnd, config.media_keys));
+            app.manage(DiscordPresence::start(&config.presence));
+
+            app.manage(QueueCheckpoint::new(&data_dir));
+            app.manage(RateLimiter::new(config.debug_mode));
+            app.manage(Confirmations::default());
+            fs_scope::allow_new_outputs(app);
+            match TrackIndex::open(&data_dir) {
+                Ok(index) => {
+                    library::index_new_outputs(app, index.clone());
+                    app.manage(index);
+                }
+                Err(e) => eprintln!("[djbot] track index disabled: {}", e),
+            }
+            let bus = EventBus::start(app.clone(), logs::LogPipeline::new(&config, &data_dir), setup_state.log_broadcast.clone());
+            spawn_health_checks(app.clone(), setup_state.clone());
+            app.manage(bus.clone());
+
+            let output_dir = data_dir.join("output");
+            let archive_dir = data_dir.join("archive");
+            let waveform_dir = data_dir.join("cache").join("waveforms");
+            let artwork_dir = data_dir.join("cache").join("artwork");
+            for dir in [&output_dir, &archive_dir, &waveform_dir, &artwork_dir] {
+                if let Err(e) = config.output.create_dir(dir) {
+                    eprintln!("[djbot] failed to create {}: {}", dir.display(), e);
+                }
+            }
+            report_clock_skew(app, &output_dir);
+            let scope_roots: Vec<std::path::PathBuf> =
+                [Some(data_dir.clone()), app.path().download_dir().ok()].into_iter().flatten().collect();
+            app.manage(Scope::new(app.clone(), &scope_roots, &output_dir)?);
+            let image_cache = Arc::new(CacheBudget::new(
+                vec![waveform_dir.clone(), artwork_dir.clone()],
+                config.cache.max_image_cache_mb * 1024 * 1024,
+            ));
+            app.manage(WaveformCache::new(waveform_dir, Arc::clone(&image_cache)));
+            app.manage(ArtworkCache::new(artwork_dir, image_cache));
+            app.manage(if config.output.watch_external_writes {
+                OutputDirActivity::watch(app.clone(), &output_dir)
+            } else {
+                OutputDirActivity::disabled()
+            });
+
+            let max_output_dir_mb = config.output.max_output_dir_mb;
+            let output_dir_for_ready = output_dir.clone();
+            let watched_dir = output_dir.clone();
+            output_watch::spawn(output_dir, bus, move || {
+                if let Err(e) = archive::archive_old_output(&watched_dir, &archive_dir, max_output_dir_mb) {
+                    eprintln!("[djbot] failed to archive old output: {}", e);
+                }
+            });
+
+            // A command-line job needs the worker whatever auto_start_worker says.
+            register_ready_handlers(app, &setup_state, &output_dir_for_ready);
+
+            if !config.auto_start_worker && command.is_none() {
+                eprintln!("[djbot] auto_start_worker is off; waiting for the user to start the worker");
+                setup_state.set_status(WorkerStatus::NotStarted);
+            } else if let Err(e) = spawn_worker(app) {
+                eprintln!("[djbot] worker not started: {}", e);
+                report_worker_failed(app, e);
+            }
+
+            if let Some(command) = command {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.hide();
+                }
+                spawn_headless_job(app.clone(), command);
+            }
+
+            Ok(())
+        })
+        .on_page_load(|webview, _payload| {
+            // Tokens belong to the page that asked for them.
+            if let Some(confirmations) = webview.try_state::<Confirmations>() {
+                confirmations.clear();
+            }
+        })
+        .on_event(|app, event| {
+            if let RunEvent::WindowEvent { event: tauri::WindowEvent::Destroyed, .. } = event {
+                // Clean shutdown: the snapshot only exists to survive crashes.
+                if let Some(dir) = app.state::<WorkerState>().data_dir() {
+                    let _ = std::fs::remove_file(snapshot_path(&dir));
+                }
+
+                app.state::<DiscordPresence>().shutdown();
+                app.state::<AudioWatch>().stop();
+
+                // On Windows, kill the worker by name so it doesn't linger.
+                #[cfg(target_os = "windows")]
+                {
+                    let _ = Command::new("taskkill")
+                        .args(["/F", "/IM", goworker_name(), "/T"])
+                        .output();
+                }
+                // On Linux the worker has its own session (see
+                // worker::isolate_session), so nothing else will stop it.
+                #[cfg(target_os = "linux")]
+                app.state::<WorkerState>().terminate_worker();
+                // On macOS the child process inherits the session and will
+                // receive SIGHUP / be reaped when the parent exits.
+            }
+        })
+        .build()
+}
+
+/// How long the stdout reader waits after EOF for the process to exit before
+/// treating the worker as degraded.
+const EXIT_GRACE: Duration = Duration::from_secs(2);
+
+/// Managed state: the worker settings given outside config.toml, the
+/// `DJBOT_*` variables with the command line options over them, combined
+/// once in `run`.
+struct WorkerOverrides(WorkerConfigOverride);
+
+/// Payload of `worker-update-failed`: a downloaded worker didn't start,
+/// and the previous (or bundled) one runs instead.
+#[derive(Debug, Clone, Serialize)]
+struct WorkerUpdateFailed {
+    version: String,
+    reason: String,
+}
+
+/// Drop the downloaded worker `version` and start the one before it.
+fn fall_back_from_update(app: &AppHandle, data_dir: &std::path::Path, version: String, reason: String) -> Result<(), String> {
+    eprintln!("[djbot] downloaded worker {} failed ({}); falling back", version, reason);
+    worker_update::reject(data_dir, &version);
+    let _ = app.emit("worker-update-failed", WorkerUpdateFailed { version, reason });
+    spawn_worker(app)
+}
+
+/// The worker settings a start uses: config.toml, which settings commands
+/// may have changed since the last start, with `WorkerOverrides` over it.
+fn configured_worker(app: &AppHandle, data_dir: &std::path::Path) -> WorkerConfig {
+    let overrides = app.state::<WorkerOverrides>();
+    WorkerConfig::merge(DjbotConfig::load(data_dir).worker, overrides.0.clone())
+}
+
+/// Verify the sidecar and launch it with the config currently on disk,
+/// handing its output to a background reader thread. A downloaded worker
+/// update, when one is selected, runs in place of the bundled sidecar.
+fn spawn_worker(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<WorkerState>().inner().clone();
+    let bus = app.state::<EventBus>().inner().clone();
+    let data_dir = state.data_dir().ok_or_else(|| "data dir not initialised".to_string())?;
+    let sidecar_path = state.sidecar_path().ok_or_else(|| "worker binary not resolved yet".to_string())?;
+    let resource_path = app.path().resource_dir().map_err(|e| e.to_string())?;
+    let update = worker_update::active_binary(&data_dir, goworker_name());
+    let sidecar_path = update.as_ref().map_or(sidecar_path, |u| u.path.clone());
+
+    state.clear_startup_error();
+    state.set_missing_runtime(None);
+    state.set_status(WorkerStatus::Starting);
+    state.set_worker_version(None);
+
+    // A truncated or empty file would only fail with an opaque spawn error.
+    if let Err(reason) = discovery::check_binary(&sidecar_path) {
+        if let Some(update) = update {
+            return fall_back_from_update(app, &data_dir, update.version, reason);
+        }
+        let msg = format!("Worker binary {} is unusable ({}); reinstall the app", sidecar_path.display(), reason);
+        eprintln!("[djbot] ERROR: {}", msg);
+        state.set_startup_error(msg.clone());
+        state.set_status(WorkerStatus::Failed);
+        return Err(msg);
+    }
+
+    // Fail closed: a sidecar that doesn't match the bundled manifest is
+    // never executed. A downloaded worker was checked against the release
+    // manifest's checksum by `active_binary` instead.
+    match &update {
+        Some(update) => eprintln!("[djbot] running downloaded worker {}", update.version),
+        None => match integrity::verify_sidecar(&resource_path, goworker_name(), &sidecar_path) {
+            Ok(true) => eprintln!("[djbot] worker checksum verified against manifest"),
+            Ok(false) => eprintln!("[djbot] no sidecar manifest found; skipping checksum verification"),
+            Err(e) => {
+                let msg = format!("Worker binary failed integrity check: {}", e);
+                eprintln!("[djbot] ERROR: {}", msg);
+                state.set_startup_error(msg.clone());
+                state.set_status(WorkerStatus::Failed);
+                return Err(msg);
+            }
+        },
+    }
+
+    let mut worker_config = configured_worker(app, &data_dir);
+    worker_config.sidecar_path = sidecar_path.clone();
+    worker_config.ffmpeg_path  = state.ffmpeg_path();
+    worker_config.data_dir     = data_dir.clone();
+
+    // Taken before spawning so a concurrent in-place update is detected.
+    let fingerprint = match integrity::fingerprint(&sidecar_path) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            eprintln!("[djbot] could not fingerprint worker binary: {}", e);
+            None
+        }
+    };
+    let mut child = match worker::build_worker_command(&worker_config).spawn() {
+        Ok(child) => child,
+        Err(e) if update.is_some() => {
+            let version = update.map(|u| u.version).unwrap_or_default();
+            return fall_back_from_update(app, &data_dir, version, e.to_string());
+        }
+        Err(e) => {
+            let msg = format!("Failed to start Go worker ({}): {}", sidecar_path.display(), e);
+            eprintln!("[djbot] {}", msg);
+            state.set_status(WorkerStatus::Failed);
+            return Err(msg);
+        }
+    };
+    state.set_unconfirmed_update(update.filter(|u| !u.confirmed).map(|u| u.version));
+    let stderr = child.stderr.take();
+    let stdout = child.stdout.take();
+    let generation = state.set_child(child);
+    let mut session = WorkerSession::new(generation);
+    if let Some(stderr) = stderr {
+        let log_bus = bus.clone();
+        let log_state = state.clone();
+        let live = session.live(&state);
+        // With the worker keeping its own log file, release builds don't
+        // need its output on our stderr as well.
+        let echo = cfg!(debug_assertions) || !worker_config.worker_log_file;
+        let filter = StderrFilter::new(&worker_config.stderr_filter_patterns);
+        session.thread("stderr", move || forward_worker_stderr(stderr, &log_state, log_bus, &filter, echo, live));
+    }
+    state.set_spawned_audio(worker_config.audio.clone());
+    state.set_worker_cwd(worker_config.data_dir.clone());
+    state.set_spawned_binary(fingerprint);
+    state.set_spawn_config_hash(worker_config.config_hash());
+    state.set_startup_rss_bytes(None);
+    state.heartbeat_missed.store(0, Ordering::SeqCst);
+    session.task(sample_startup_memory(app.clone(), state.clone(), generation, worker_config.startup_memory_warn_mb));
+    session.task(enforce_startup_timeout(state.clone(), generation, Duration::from_secs(worker_config.startup_timeout_secs)));
+
+    let restart_policy = worker_config.restart_policy;
+    let spawned_at = std::time::Instant::now();
+    let app_handle = app.clone();
+    let live = session.live(&state);
+    let reader_state = state.clone();
+    session.thread("stdout", move || {
+        let state = reader_state;
+        if let Some(stdout) = stdout {
+            let result = worker::for_each_line(BufReader::new(stdout), |line| {
+                // A replaced worker's last words must not touch the new one's state.
+                if !live.is_current() {
+                    return;
+                }
+                // One bad line must not take the reader down with it.
+                let handled = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    handle_stdout_line(&line, generation, &app_handle, &state)
+                }));
+                if handled.is_err() {
+                    eprintln!("[djbot] skipped worker output line after a panic: {:?}", line);
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("[djbot] worker stdout read failed (session {}): {}", generation, e);
+            }
+        }
+        watch_worker_exit(&app_handle, &state, generation, restart_policy, spawned_at, &sidecar_path);
+    });
+    state.start_session(session);
+    Ok(())
+}
+
+/// How long a new session waits for the previous one's threads to stop.
+const SESSION_JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Threads and tasks serving one spawned worker, replaced by the next
+/// spawn. Each is handed a `SessionLive` and ignores the worker's output
+/// once a newer session (or a kill) has taken over.
+struct WorkerSession {
+    generation: u64,
+    stop: Arc<AtomicBool>,
+    threads: Vec<(&'static str, std::thread::JoinHandle<()>)>,
+    tasks: Vec<tauri::async_runtime::JoinHandle<()>>,
+}
+
+/// A session thread's check that its worker is still the current one.
+#[derive(Clone)]
+struct SessionLive {
+    generation: u64,
+    stop: Arc<AtomicBool>,
+    current: Arc<AtomicU64>,
+}
+
+impl SessionLive {
+    fn is_current(&self) -> bool {
+        !self.stop.load(Ordering::SeqCst) && self.current.load(Ordering::SeqCst) == self.generation
+    }
+}
+
+impl WorkerSession {
+    fn new(generation: u64) -> Self {
+        WorkerSession { generation, stop: Arc::default(), threads: Vec::new(), tasks: Vec::new() }
+    }
+
+    fn live(&self, state: &WorkerState) -> SessionLive {
+        SessionLive { generation: self.generation, stop: Arc::clone(&self.stop), current: Arc::clone(&state.generation) }
+    }
+
+    fn thread(&mut self, name: &'static str, f: impl FnOnce() + Send + 'static) {
+        let spawned = std::thread::Builder::new()
+            .name(format!("djbot-worker-{}-{}", name, self.generation))
+            .spawn(f);
+        match spawned {
+            Ok(handle) => self.threads.push((name, handle)),
+            Err(e) => eprintln!("[djbot] could not start worker {} thread (session {}): {}", name, self.generation, e),
+        }
+    }
+
+    fn task(&mut self, task: tauri::async_runtime::JoinHandle<()>) {
+        self.tasks.push(task);
+    }
+
+    /// Tell the threads to stop, abort the tasks and wait up to
+    /// `SESSION_JOIN_TIMEOUT` for the threads to finish. Readers end when
+    /// the old worker's pipes close; one still blocked (e.g. a grandchild
+    /// holding stderr open) is logged and left detached, ignoring its
+    /// input. The calling thread is skipped when it is one of them, as
+    /// when the exit watcher restarts the worker.
+    fn shut_down(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for task in self.tasks {
+            task.abort();
+        }
+        let deadline = std::time::Instant::now() + SESSION_JOIN_TIMEOUT;
+        let current = std::thread::current().id();
+        for (name, handle) in self.threads {
+            if handle.thread().id() == current {
+                continue;
+            }
+            while !handle.is_finished() && std::time::Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            if !handle.is_finished() {
+                eprintln!(
+                    "[djbot] worker {} thread of session {} did not stop within {:?}; leaving it detached",
+                    name, self.generation, SESSION_JOIN_TIMEOUT
+                );
+            } else if handle.join().is_err() {
+                eprintln!("[djbot] worker {} thread of session {} panicked", name, self.generation);
+            }
+        }
+    }
+}
+
+/// Wait for the worker of `generation` (spawned from `binary` at
+/// `spawned_at`) to exit after its stdout closed, and start a new one if
+/// `policy` says so.
+fn watch_worker_exit(
+    app: &AppHandle,
+    state: &WorkerState,
+    generation: u64,
+    policy: RestartPolicy,
+    spawned_at: std::time::Instant,
+    binary: &std::path::Path,
+) {
+    let closed_at = std::time::Instant::now();
+    let mut degraded = false;
+    loop {
+        match state.poll_worker(generation) {
+            // Replaced or killed deliberately; the new owner manages state.
+            None => return,
+            Some(Ok(None)) => {
+                // stdout closed but the process is still running: we've lost
+                // our view of it rather than it having exited.
+                if !degraded && closed_at.elapsed() >= EXIT_GRACE {
+                    degraded = true;
+                    eprintln!("[djbot] worker stdout closed while the process is still running");
+                    state.set_status(WorkerStatus::Degraded);
+                    let _ = app.emit("worker-degraded", "stdout reader stopped");
+                }
+                std::thread::sleep(Duration::from_millis(200));
+            }
+            Some(exit) => {
+                // Worker exited — log for diagnostics
+                state.set_port(None);
+                state.set_status(WorkerStatus::Stopped);
+                app.state::<MediaSession>().clear_now_playing();
+                app.state::<DiscordPresence>().worker_stopped();
+                // A downloaded worker that never got ready is dropped, and
+                // the previous one started whatever the restart policy.
+                if let (Some(version), Some(data_dir)) = (state.take_unconfirmed_update(), state.data_dir()) {
+                    if let Err(e) = fall_back_from_update(app, &data_dir, version, format!("exited before its handshake: {:?}", exit)) {
+                        eprintln!("[djbot] worker restart failed: {}", e);
+                    }
+                    return;
+                }
+                let (success, exit_code) = match exit {
+                    Ok(Some(status)) => {
+                        eprintln!("[djbot] Go worker exited: {}", status);
+                        (status.success(), status.code())
+                    }
+                    Ok(None) => (false, None),
+                    Err(e) => {
+                        eprintln!("[djbot] lost track of the Go worker: {}", e);
+                        (false, None)
+                    }
+                };
+                if success {
+                    if policy.should_restart(true) {
+                        restart_exited_worker(app, state, generation);
+                    }
+                    return;
+                }
+                // Restarting can't bring back a missing DLL.
+                if let Some(missing) = runtime::diagnose(exit_code, spawned_at.elapsed(), binary) {
+                    let msg = format!("Worker could not start ({}): {} See {}", missing.code, missing.explanation, missing.remediation_url);
+                    eprintln!("[djbot] ERROR: {}", msg);
+                    state.set_startup_error(msg.clone());
+                    state.set_missing_runtime(Some(missing));
+                    state.set_status(WorkerStatus::MissingRuntime);
+                    report_worker_failed(app, msg);
+                    return;
+                }
+                let crash = WorkerCrash {
+                    exit_code,
+                    crashed_at: jobs::unix_now(),
+                    policy: state.crash_policy(),
+                };
+                state.record_crash(crash.clone());
+                match crash.policy {
+                    CrashPolicy::AutoRestart if policy.should_restart(false) => {
+                        restart_exited_worker(app, state, generation)
+                    }
+                    CrashPolicy::AutoRestart | CrashPolicy::Silent => {}
+                    CrashPolicy::NotifyOnly => {
+                        let _ = app.emit("worker-crashed", &crash);
+                    }
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// The most recent unexpected worker exit.
+#[derive(Debug, Clone, Serialize)]
+struct WorkerCrash {
+    /// `None` if it was killed by a signal or we lost track of it.
+    exit_code: Option<i32>,
+    crashed_at: u64,
+    /// Crash policy that was applied.
+    policy: CrashPolicy,
+}
+
+/// Time a `GET /health` may take before the check counts as failed.
+const HEALTH_CHECK_TIMEOUT_MS: u64 = 5_000;
+
+/// Poll `GET /health` on the running worker every `health_interval`. A
+/// `Ready` worker that stops answering is marked `Degraded` (and
+/// `worker-unhealthy` emitted) until it answers again; once more than
+/// `max_missed_heartbeats` checks in a row have failed it is restarted.
+fn spawn_health_checks(app: AppHandle, state: WorkerState) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = state.health_interval.subscribe();
+        let mut flagged = false;
+        let mut fd_flagged = false;
+        loop {
+            let secs = *interval.borrow_and_update();
+            if tokio::time::timeout(Duration::from_secs(secs), interval.changed()).await.is_ok() {
+                continue;
+            }
+            let Some(port) = state.port() else { continue };
+            let result = match app.state::<WorkerHttp>().client(Some(HEALTH_CHECK_TIMEOUT_MS)) {
+                Ok(client) => proxy::forward(&client, port, "GET", "health", None).await,
+                Err(e) => Err(e),
+            };
+            if result.is_ok() {
+                state.heartbeat_missed.store(0, Ordering::SeqCst);
+            }
+            match result {
+                Ok(_) if flagged => {
+                    flagged = false;
+                    if state.replace_status(WorkerStatus::Degraded, WorkerStatus::Ready) {
+                        eprintln!("[djbot] worker health check passing again");
+                        let _ = app.emit("worker-healthy", ());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    if state.replace_status(WorkerStatus::Ready, WorkerStatus::Degraded) {
+                        flagged = true;
+                        eprintln!("[djbot] worker health check failed: {}", e);
+                        let _ = app.emit("worker-unhealthy", &e);
+                    }
+                    let missed = state.heartbeat_missed.fetch_add(1, Ordering::SeqCst) + 1;
+                    let max = state.max_missed_heartbeats.load(Ordering::SeqCst);
+                    if missed > max {
+                        eprintln!("[djbot] worker missed {} health checks in a row; restarting it", missed);
+                        flagged = false;
+                        let restart_app = app.clone();
+                        let restarted = tauri::async_runtime::spawn_blocking(move || {
+                            restart_app.state::<WorkerState>().kill_worker();
+                            spawn_worker(&restart_app)
+                        })
+                        .await;
+                        if let Ok(Err(e)) = restarted {
+                            eprintln!("[djbot] worker restart failed: {}", e);
+                            report_worker_failed(&app, e);
+                        }
+                        continue;
+                    }
+                }
+            }
+            // Warn once per approach to the limit, not on every check.
+            let Some(pid) = state.worker_pid() else { continue };
+            if let Ok(Some(usage)) = tauri::async_runtime::spawn_blocking(move || handles::usage(pid)).await {
+                if usage.near_limit && !fd_flagged {
+                    eprintln!(
+                        "[djbot] WARNING: worker has {} open handles (soft limit {:?})",
+                        usage.open, usage.soft_limit
+                    );
+                    let _ = app.emit("worker-fd-warning", &usage);
+                }
+                fd_flagged = usage.near_limit;
+            }
+        }
+    });
+}
+
+/// How long after reporting ready the worker's memory is sampled, so
+/// startup allocations have settled.
+const STARTUP_MEMORY_DELAY: Duration = Duration::from_secs(5);
+/// A worker not ready by then has failed to start; nothing to sample.
+const STARTUP_MEMORY_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Warning payload when a fresh worker uses more than the threshold.
+#[derive(Clone, Serialize)]
+struct MemoryWarning {
+    rss_bytes: u64,
+    threshold_mb: u64,
+    message: String,
+}
+
+/// Record the startup memory of the worker of `generation` and warn if it is
+/// above `warn_mb`.
+fn sample_startup_memory(
+    app: AppHandle,
+    state: WorkerState,
+    generation: u64,
+    warn_mb: Option<u64>,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        if state.wait_for_port(STARTUP_MEMORY_READY_TIMEOUT).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(STARTUP_MEMORY_DELAY).await;
+        if state.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let Some(pid) = state.worker_pid() else { return };
+        let Ok(Some(rss)) = tauri::async_runtime::spawn_blocking(move || memory::rss_bytes(pid)).await else { return };
+        state.set_startup_rss_bytes(Some(rss));
+        eprintln!("[djbot] worker startup memory: {} MiB", rss / (1024 * 1024));
+        if let Some(threshold_mb) = warn_mb.filter(|&mb| rss > mb * 1024 * 1024) {
+            let message = format!(
+                "The worker uses {} MiB right after starting, more than the {} MiB limit. \
+                 Close other memory-hungry apps or analyse fewer tracks at once.",
+                rss / (1024 * 1024),
+                threshold_mb
+            );
+            eprintln!("[djbot] WARNING: {}", message);
+            let _ = app.emit("worker-memory-warning", MemoryWarning { rss_bytes: rss, threshold_mb, message });
+        }
+    })
+}
+
+/// Kill the worker of `generation` if it hasn't reported its port within
+/// `timeout`, so a hung start is retried like one that exited.
+fn enforce_startup_timeout(state: WorkerState, generation: u64, timeout: Duration) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        if state.wait_for_port(timeout).await.is_ok() || state.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if !state.worker_running() {
+            return;
+        }
+        let msg = format!("Worker did not report ready within {}s", timeout.as_secs());
+        eprintln!("[djbot] {}; killing it", msg);
+        state.set_startup_error(msg);
+        state.kill_generation(generation);
+    })
+}
+
+/// Pause before an automatic restart so a worker that dies on startup
+/// doesn't spin.
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+/// Start a replacement for the exited worker of `generation`, unless it was
+/// replaced in the meantime (e.g. by `restart_worker`).
+fn restart_exited_worker(app: &AppHandle, state: &WorkerState, generation: u64) {
+    std::thread::sleep(RESTART_DELAY);
+    if state.generation.load(Ordering::SeqCst) != generation || state.worker_running() {
+        return;
+    }
+    let retries = state.start_retries.fetch_add(1, Ordering::SeqCst);
+    let max_retries = state.data_dir().and_then(|dir| configured_worker(app, &dir).max_start_retries);
+    if max_retries.is_some_and(|max| retries >= max) {
+        let msg = format!("Worker failed to get ready after {} restart(s); not retrying", retries);
+        eprintln!("[djbot] ERROR: {}", msg);
+        state.set_startup_error(msg.clone());
+        state.set_status(WorkerStatus::Failed);
+        report_worker_failed(app, msg);
+        return;
+    }
+    state.set_restart_count(state.restart_count() + 1);
+    eprintln!("[djbot] restarting Go worker (restart #{})", state.restart_count());
+    if let Err(e) = spawn_worker(app) {
+        eprintln!("[djbot] worker restart failed: {}", e);
+        report_worker_failed(app, e);
+    }
+}
+
+/// Time the UI has to answer `worker-failed` before
+/// `exit_on_unrecoverable_failure` exits the app.
+const FAILURE_EXIT_GRACE: Duration = Duration::from_secs(30);
+
+/// What the UI can offer in answer to `worker-failed`; each is a command.
+const WORKER_FAILED_ACTIONS: &[&str] = &["retry_worker_start", "exit_app"];
+
+/// Payload of `worker-failed`: the worker couldn't be (re)started and
+/// nothing will try again on its own.
+#[derive(Debug, Clone, Serialize)]
+struct WorkerFailed {
+    error: String,
+    actions: &'static [&'static str],
+    /// Seconds until the app exits unless the UI answers; `None` when
+    /// `exit_on_unrecoverable_failure` is off.
+    exit_in_secs: Option<u64>,
+    /// When the worker couldn't load a runtime library.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    missing_runtime: Option<MissingRuntime>,
+}
+
+/// Managed state: failures reported through `worker-failed` and the last
+/// one the UI answered (`acknowledge_worker_failure`, `exit_app` or a
+/// successful retry).
+#[derive(Default)]
+struct WorkerFailures {
+    reported: AtomicU64,
+    answered: AtomicU64,
+}
+
+/// Emit `worker-failed` and, with `exit_on_unrecoverable_failure` on, exit
+/// if neither the UI nor a retry deals with it in `FAILURE_EXIT_GRACE`.
+fn report_worker_failed(app: &AppHandle, error: String) {
+    let exit = app
+        .state::<WorkerState>()
+        .data_dir()
+        .is_some_and(|dir| DjbotConfig::load(&dir).worker.exit_on_unrecoverable_failure);
+    let failure = app.state::<WorkerFailures>().reported.fetch_add(1, Ordering::SeqCst) + 1;
+    let payload = WorkerFailed {
+        error: error.clone(),
+        actions: WORKER_FAILED_ACTIONS,
+        exit_in_secs: exit.then_some(FAILURE_EXIT_GRACE.as_secs()),
+        missing_runtime: app.state::<WorkerState>().missing_runtime(),
+    };
+    let _ = app.emit("worker-failed", &payload);
+    if !exit {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(FAILURE_EXIT_GRACE);
+        let answered = app.state::<WorkerFailures>().answered.load(Ordering::SeqCst) >= failure;
+        if answered || app.state::<WorkerState>().worker_running() {
+            return;
+        }
+        eprintln!(
+            "[djbot] ERROR: worker-failed went unanswered for {}s; exiting. Last worker error: {}",
+            FAILURE_EXIT_GRACE.as_secs(),
+            error
+        );
+        app.exit(1);
+    });
+}
+
+/// Act on one line of worker stdout (see `protocol`). Events of types we
+/// don't know go to the webview as `worker-event`.
+fn handle_stdout_line(line: &str, generation: u64, app: &AppHandle, state: &WorkerState) {
+    let Some(message) = protocol::parse_line(line) else { return };
+    let event = match message {
+        WorkerMessage::Typed(event) => event,
+        WorkerMessage::Unknown(raw) => {
+            let _ = app.emit("worker-event", &raw);
+            return;
+        }
+        WorkerMessage::Oversized(raw) => {
+            eprintln!("[djbot] worker event over {} bytes; forwarding it truncated", protocol::MAX_EVENT_BYTES);
+            let _ = app.emit("worker-event", &raw);
+            return;
+        }
+        WorkerMessage::Malformed { line, error } => {
+            eprintln!("[djbot] ignoring malformed worker message {:?}: {}", line, error);
+            return;
+        }
+    };
+    match event {
+        TypedEvent::Version { version } => state.set_worker_version(Some(version)),
+        TypedEvent::Ready { port } => {
+            state.set_port(Some(port));
+            state.set_status(WorkerStatus::Ready);
+            state.start_retries.store(0, Ordering::SeqCst);
+            eprintln!("[djbot] Go worker listening on port {} (session {})", port, generation);
+            if let (Some(version), Some(data_dir)) = (state.take_unconfirmed_update(), state.data_dir()) {
+                eprintln!("[djbot] downloaded worker {} completed its first handshake", version);
+                worker_update::confirm(&data_dir, &version);
+            }
+            state.run_on_ready_callbacks(port);
+        }
+        TypedEvent::Progress { job, done, total } => {
+            app.emit_worker_event(WorkerEvent::Progress(JobProgress { job, done, total, state: None }))
+        }
+        TypedEvent::Log { message } => app.emit_worker_event(WorkerEvent::Log(message)),
+        TypedEvent::Heartbeat => state.heartbeat_missed.store(0, Ordering::SeqCst),
+        TypedEvent::ConfigAck { audio } => state.set_effective_audio(audio),
+    }
+}
+
+/// Echo worker stderr to our own stderr if `echo` (keeps terminal
+/// visibility in dev), keep the tail for diagnostics and forward each line
+/// not matched by `filter` to `stream_worker_log` subscribers.
+fn forward_worker_stderr(
+    stderr: std::process::ChildStderr,
+    state: &WorkerState,
+    bus: EventBus,
+    filter: &StderrFilter,
+    echo: bool,
+    live: SessionLive,
+) {
+    let result = worker::for_each_line(BufReader::new(stderr), |line| {
+        if !live.is_current() {
+            return;
+        }
+        if echo {
+            eprintln!("{}", line);
+        }
+        // The bus cleans what it emits; the tail and the log plugin need
+        // their own clean copy.
+        let clean = sanitize::line(&line);
+        if filter.matches(&line) {
+            bus.count_filtered_log();
+        } else {
+            logs::worker_stderr_to_tauri_log(&clean);
+            bus.send(WorkerEvent::Log(line));
+        }
+        state.push_stderr(clean);
+    });
+    if let Err(e) = result {
+        eprintln!("[djbot] worker stderr read failed (session {}): {}", live.generation, e);
+    }
+}
+
+/// Find a usable ffmpeg binary. Checks the configured override first, then a
+/// copy bundled with the app, then PATH, then well-known install locations
+/// for each platform. Returns Some(path) or None.
+fn find_ffmpeg(user_override: Option<&str>, resource_dir: &std::path::Path) -> Option<(String, FfmpegSource)> {
+    let platform = |path: &str, label: &str| Some((path.to_string(), FfmpegSource::PlatformSpecific(label.to_string())));
+
+    // 1. worker.ffmpeg_path_override from config.toml
+    if let Some(path) = user_override {
+        if ffmpeg_still_usable(path) {
+            eprintln!("[djbot] ffmpeg override: {}", path);
+            return Some((path.to_string(), FfmpegSource::UserOverride));
+        }
+        eprintln!("[djbot] WARNING: configured ffmpeg {} not found; searching instead", path);
+    }
+
+    // 2. Bundled next to the worker sidecar (same layouts as goworker)
+    let bare_name = if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" };
+    for c in [resource_dir.join("binaries").join(bare_name), resource_dir.join(bare_name)] {
+        if c.is_file() {
+            eprintln!("[djbot] ffmpeg found (bundled): {}", c.display());
+            return Some((c.to_string_lossy().to_string(), FfmpegSource::Bundled));
+        }
+    }
+
+    // 3. Check PATH (works on all platforms after a normal install / brew install)
+    if which_in_path("ffmpeg") {
+        eprintln!("[djbot] ffmpeg found in PATH");
+        return Some(("ffmpeg".to_string(), FfmpegSource::Path));
+    }
+
+    // 4. Platform-specific locations ----------------------------------------
+
+    // ── Windows ────────────────────────────────────────────────────────────
+    #[cfg(target_os = "windows")]
+    {
+        let home        = std::env::var("USERPROFILE").unwrap_or_default();
+        let local_app   = std::env::var("LOCALAPPDATA").unwrap_or_default();
+        let program_files     = std::env::var("ProgramFiles").unwrap_or_default();
+        let program_files_x86 = std::env::var("ProgramFiles(x86)").unwrap_or_default();
+
+        let fixed: &[(&str, &str)] = &[
+            // Package managers
+            (&format!("{}/scoop/apps/ffmpeg/current/bin/ffmpeg.exe", home), "scoop"),
+            (&format!("{}/scoop/shims/ffmpeg.exe", home), "scoop"),
+            ("C:/ProgramData/chocolatey/bin/ffmpeg.exe", "chocolatey"),
+            (&format!("{}/AppData/Local/Microsoft/WindowsApps/ffmpeg.exe", home), "winget"),
+            // Manual installs
+            (&format!("{}/ffmpeg/bin/ffmpeg.exe", program_files), "program_files"),
+            (&format!("{}/ffmpeg-essentials/bin/ffmpeg.exe", program_files), "program_files"),
+            (&format!("{}/ffmpeg/bin/ffmpeg.exe", program_files_x86), "program_files"),
+            // imageio_ffmpeg (installed by pip)
+            (&format!("{}/Python/Python312/site-packages/imageio_ffmpeg/binaries/ffmpeg.exe", local_app), "imageio"),
+            (&format!("{}/Python/Python311/site-packages/imageio_ffmpeg/binaries/ffmpeg.exe", local_app), "imageio"),
+            (&format!("{}/Python/Python310/site-packages/imageio_ffmpeg/binaries/ffmpeg.exe", local_app), "imageio"),
+            (&format!("{}/Python/Python39/site-packages/imageio_ffmpeg/binaries/ffmpeg.exe", local_app), "imageio"),
+        ];
+        for (c, label) in fixed {
+            if std::path::Path::new(c).exists() {
+                eprintln!("[djbot] ffmpeg found: {}", c);
+                return platform(c, label);
+            }
+        }
+
+        // Scan Roaming imageio_ffmpeg for any Python version / renamed binary
+        let roaming = format!("{}/AppData/Roaming", home);
+        for py_ver in &["Python314","Python313","Python312","Python311","Python310","Python39"] {
+            let bin_dir = format!("{}/Python/{}/site-packages/imageio_ffmpeg/binaries", roaming, py_ver);
+            if let Ok(entries) = std::fs::read_dir(&bin_dir) {
+                for entry in entries.flatten() {
+                    let n = entry.file_name();
+                    let ns = n.to_string_lossy();
+                    if ns.starts_with("ffmpeg") && ns.ends_with(".exe") {
+                        let full = entry.path().to_string_lossy().to_string();
+                        eprintln!("[djbot] ffmpeg found (imageio): {}", full);
+                        return platform(&full, "imageio");
+                    }
+                }
+            }
+        }
+    }
+
+    // ── macOS ──────────────────────────────────────────────────────────────
+    #[cfg(target_os = "macos")]
+    {
+        let candidates: &[(&str, &str)] = &[
+            ("/opt/homebrew/bin/ffmpeg", "homebrew"),  // Apple Silicon Homebrew
+            ("/usr/local/bin/ffmpeg", "homebrew"),     // Intel Homebrew
+            ("/opt/local/bin/ffmpeg", "macports"),     // MacPorts
+            ("/usr/bin/ffmpeg", "system"),             // (rare) system install
+        ];
+        for (c, label) in candidates {
+            if std::path::Path::new(c).exists() {
+                eprintln!("[djbot] ffmpeg found: {}", c);
+                return platform(c, label);
+            }
+        }
+    }
+
+    // ── Linux ──────────────────────────────────────────────────────────────
+    #[cfg(target_os = "linux")]
+    {
+        let candidates: &[(&str, &str)] = &[
+            ("/usr/bin/ffmpeg", "system"),
+            ("/usr/local/bin/ffmpeg", "local"),
+            ("/snap/bin/ffmpeg", "snap"),
+            ("/var/lib/flatpak/exports/bin/ffmpeg", "flatpak"),
+            ("/usr/games/ffmpeg", "system"),
+        ];
+        for (c, label) in candidates {
+            if std::path::Path::new(c).exists() {
+                eprintln!("[djbot] ffmpeg found: {}", c);
+                return platform(c, label);
+            }
+        }
+    }
+
+    eprintln!("[djbot] WARNING: ffmpeg not found. Audio analysis will fail.");
+    eprintln!("[djbot] Install ffmpeg: https://ffmpeg.org/download.html");
+    None
+}
+
+/// Returns true if an ffmpeg path restored from a snapshot can still be used.
+fn ffmpeg_still_usable(path: &str) -> bool {
+    if std::path::Path::new(path).is_absolute() {
+        std::path::Path::new(path).exists()
+    } else {
+        which_in_path(path)
+    }
+}
+
+/// Returns true if `name` can be invoked from PATH.
+fn which_in_path(name: &str) -> bool {
+    Command::new(name)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_readers_never_see_it_go_backwards() {
+        const UPDATES: u16 = 20_000;
+        let state = WorkerState::new();
+        let waiter_state = state.clone();
+        let waiter = std::thread::spawn(move || {
+            tauri::async_runtime::block_on(waiter_state.wait_for_port(Duration::from_secs(10)))
+        });
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    let mut last = 0;
+                    while last < UPDATES {
+                        let port = state.port().unwrap_or(0);
+                        assert!(port >= last, "port went from {} back to {}", last, port);
+                        last = port;
+                    }
+                })
+            })
+            .collect();
+        for port in 1..=UPDATES {
+            state.set_port(Some(port));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert!(waiter.join().unwrap().unwrap() >= 1);
+        assert_eq!(state.port(), Some(UPDATES));
+    }
+
+    #[test]
+    fn wait_for_port_wakes_on_report_and_times_out_without_one() {
+        let state = WorkerState::new();
+        assert!(tauri::async_runtime::block_on(state.wait_for_port(Duration::from_millis(50))).is_err());
+
+        let reporter = state.clone();
+        let report = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            reporter.set_port(Some(4242));
+        });
+        assert_eq!(tauri::async_runtime::block_on(state.wait_for_port(Duration::from_secs(10))), Ok(4242));
+        report.join().unwrap();
+        // Already reported: returns straight away.
+        assert_eq!(tauri::async_runtime::block_on(state.wait_for_port(Duration::ZERO)), Ok(4242));
+    }
+
+    /// Each restart kills a worker that keeps printing its port and starts
+    /// one that prints another, the way `spawn_worker` does.
+    #[cfg(unix)]
+    #[test]
+    fn fifty_restarts_join_every_reader_and_keep_the_newest_port() {
+        use std::io::BufRead;
+        use std::sync::atomic::AtomicUsize;
+
+        const RESTARTS: u16 = 50;
+        let state = WorkerState::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        for n in 1..=RESTARTS {
+            state.kill_worker();
+            let port = 20_000 + n;
+            let mut child = Command::new("sh")
+                .args(["-c", &format!("while :; do echo {}; sleep 0.001; done", port)])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            let stdout = child.stdout.take().unwrap();
+            let generation = state.set_child(child);
+            let mut session = WorkerSession::new(generation);
+            let live = session.live(&state);
+            let (reader_state, reader_running) = (state.clone(), Arc::clone(&running));
+            session.thread("stdout", move || {
+                reader_running.fetch_add(1, Ordering::SeqCst);
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else { break };
+                    if live.is_current() {
+                        reader_state.set_port(line.parse().ok());
+                    }
+                }
+                reader_running.fetch_sub(1, Ordering::SeqCst);
+            });
+            state.start_session(session);
+            // The previous reader has been joined; at most this one is left.
+            assert!(running.load(Ordering::SeqCst) <= 1, "restart {}: {} readers", n, running.load(Ordering::SeqCst));
+        }
+
+        let latest = Some(20_000 + RESTARTS);
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while state.port() != latest && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        for _ in 0..20 {
+            assert_eq!(state.port(), latest);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        state.kill_worker();
+        state.start_session(WorkerSession::new(0));
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+    }
+}
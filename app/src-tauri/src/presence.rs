@@ -0,0 +1,286 @@
+//! Optional Discord Rich Presence ("Mixing in djbot — 124 BPM, 34 tracks
+//! analyzed").
+//!
+//! Entirely inert unless `presence.enabled` is on: no socket is opened and
+//! nothing is sent. When on, a dedicated thread owns the IPC connection to
+//! the local Discord client, publishes the latest session/now-playing state
+//! at most once per `MIN_UPDATE_INTERVAL` (Discord drops updates beyond
+//! five per 20 s), and retries the connection every `RETRY_INTERVAL` while
+//! Discord isn't running. Presence is cleared when the feature is turned
+//! off, when the worker stops and when the app exits.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
+use serde::{Deserialize, Serialize};
+
+use crate::config::PresenceConfig;
+use crate::media::NowPlaying;
+
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(4);
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// How long app exit waits for the presence to be cleared.
+const SHUTDOWN_WAIT: Duration = Duration::from_millis(500);
+
+/// Session summary reported by the frontend for the presence line.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct SessionInfo {
+    pub bpm: Option<f64>,
+    pub tracks_analyzed: Option<u32>,
+}
+
+/// Returned by `get_presence_status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct PresenceStatus {
+    pub enabled: bool,
+    pub connected: bool,
+    /// Why the last connection attempt or update failed.
+    pub last_error: Option<String>,
+}
+
+enum PresenceCommand {
+    SetEnabled(bool),
+    SetSession(SessionInfo),
+    SetNowPlaying(NowPlaying),
+    ClearNowPlaying,
+    /// The worker exited; the session it belonged to is over.
+    WorkerStopped,
+    Shutdown(mpsc::Sender<()>),
+}
+
+/// Managed handle to the presence thread.
+pub(crate) struct DiscordPresence {
+    tx: Mutex<mpsc::Sender<PresenceCommand>>,
+    status: Arc<Mutex<PresenceStatus>>,
+}
+
+impl DiscordPresence {
+    pub(crate) fn start(config: &PresenceConfig) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let status = Arc::new(Mutex::new(PresenceStatus {
+            enabled: config.enabled,
+            ..Default::default()
+        }));
+        let thread_status = Arc::clone(&status);
+        let client_id = config.client_id.clone();
+        let enabled = config.enabled;
+        std::thread::spawn(move || run(client_id, enabled, rx, thread_status));
+        DiscordPresence { tx: Mutex::new(tx), status }
+    }
+
+    pub(crate) fn status(&self) -> PresenceStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.send(PresenceCommand::SetEnabled(enabled));
+    }
+
+    pub(crate) fn set_session(&self, info: SessionInfo) {
+        self.send(PresenceCommand::SetSession(info));
+    }
+
+    pub(crate) fn set_now_playing(&self, info: NowPlaying) {
+        self.send(PresenceCommand::SetNowPlaying(info));
+    }
+
+    pub(crate) fn clear_now_playing(&self) {
+        self.send(PresenceCommand::ClearNowPlaying);
+    }
+
+    pub(crate) fn worker_stopped(&self) {
+        self.send(PresenceCommand::WorkerStopped);
+    }
+
+    /// Clear the presence and close the connection, waiting briefly so it
+    /// happens before the process exits.
+    pub(crate) fn shutdown(&self) {
+        let (done_tx, done_rx) = mpsc::channel();
+        self.send(PresenceCommand::Shutdown(done_tx));
+        let _ = done_rx.recv_timeout(SHUTDOWN_WAIT);
+    }
+
+    fn send(&self, command: PresenceCommand) {
+        let _ = self.tx.lock().unwrap().send(command);
+    }
+}
+
+/// `(details, state)` lines shown under the app name.
+type Lines = (String, Option<String>);
+
+fn lines(session: &Option<SessionInfo>, now_playing: &Option<NowPlaying>) -> Option<Lines> {
+    if session.is_none() && now_playing.is_none() {
+        return None;
+    }
+    let details = match now_playing {
+        Some(np) if np.playing => match &np.artist {
+            Some(artist) => format!("Previewing {} — {}", artist, np.title),
+            None => format!("Previewing {}", np.title),
+        },
+        _ => "Mixing in djbot".to_string(),
+    };
+    let session = session.clone().unwrap_or_default();
+    let parts: Vec<String> = [
+        session.bpm.map(|bpm| format!("{:.0} BPM", bpm)),
+        session.tracks_analyzed.map(|n| format!("{} tracks analyzed", n)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Some((details, (!parts.is_empty()).then(|| parts.join(", "))))
+}
+
+/// The IPC connection and what it is currently showing.
+struct Connection {
+    client_id: String,
+    client: Option<DiscordIpcClient>,
+    /// What Discord displays for us; always `None` while disconnected.
+    shown: Option<Lines>,
+    last_sent: Option<Instant>,
+    next_attempt: Instant,
+    status: Arc<Mutex<PresenceStatus>>,
+}
+
+impl Connection {
+    /// How long until `wanted` can be sent, or `None` if there is nothing
+    /// to send.
+    fn wait_for(&self, wanted: &Option<Lines>) -> Option<Duration> {
+        (*wanted != self.shown).then(|| self.due().saturating_duration_since(Instant::now()))
+    }
+
+    fn due(&self) -> Instant {
+        let now = Instant::now();
+        let rate_limit = self.last_sent.map_or(now, |t| t + MIN_UPDATE_INTERVAL);
+        if self.client.is_none() {
+            rate_limit.max(self.next_attempt)
+        } else {
+            rate_limit
+        }
+    }
+
+    /// Bring Discord in line with `wanted` if an update is due now.
+    fn sync(&mut self, wanted: &Option<Lines>) {
+        if *wanted == self.shown || Instant::now() < self.due() {
+            return;
+        }
+        if self.client.is_none() && !self.connect() {
+            return;
+        }
+        let Some(client) = self.client.as_mut() else { return };
+        let result = match wanted {
+            Some((details, state)) => {
+                let mut a = activity::Activity::new().details(details.as_str());
+                if let Some(state) = state {
+                    a = a.state(state.as_str());
+                }
+                client.set_activity(a)
+            }
+            None => client.clear_activity(),
+        };
+        self.last_sent = Some(Instant::now());
+        match result {
+            Ok(()) => self.shown = wanted.clone(),
+            Err(e) => {
+                eprintln!("[djbot] Discord presence update failed: {}", e);
+                self.fail(e.to_string());
+            }
+        }
+    }
+
+    fn connect(&mut self) -> bool {
+        if self.client_id.is_empty() {
+            self.fail("no Discord application id configured".to_string());
+            return false;
+        }
+        let mut client = DiscordIpcClient::new(&self.client_id);
+        match client.connect() {
+            Ok(()) => {
+                eprintln!("[djbot] connected to Discord");
+                self.client = Some(client);
+                let mut status = self.status.lock().unwrap();
+                status.connected = true;
+                status.last_error = None;
+                true
+            }
+            Err(e) => {
+                self.fail(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Drop the connection and schedule a reconnect.
+    fn fail(&mut self, error: String) {
+        if let Some(mut client) = self.client.take() {
+            let _ = client.close();
+        }
+        self.shown = None;
+        self.next_attempt = Instant::now() + RETRY_INTERVAL;
+        let mut status = self.status.lock().unwrap();
+        status.connected = false;
+        status.last_error = Some(error);
+    }
+
+    /// Clear the presence and close the connection.
+    fn disconnect(&mut self) {
+        if let Some(mut client) = self.client.take() {
+            let _ = client.clear_activity();
+            let _ = client.close();
+        }
+        self.shown = None;
+        self.next_attempt = Instant::now();
+        self.status.lock().unwrap().connected = false;
+    }
+}
+
+fn run(client_id: String, mut enabled: bool, rx: mpsc::Receiver<PresenceCommand>, status: Arc<Mutex<PresenceStatus>>) {
+    let mut conn = Connection {
+        client_id,
+        client: None,
+        shown: None,
+        last_sent: None,
+        next_attempt: Instant::now(),
+        status,
+    };
+    let mut session: Option<SessionInfo> = None;
+    let mut now_playing: Option<NowPlaying> = None;
+
+    loop {
+        let wait = if enabled { conn.wait_for(&lines(&session, &now_playing)) } else { None };
+        let command = match wait {
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(d) => rx.recv_timeout(d),
+        };
+        match command {
+            Ok(PresenceCommand::SetEnabled(on)) => {
+                if !on {
+                    conn.disconnect();
+                }
+                enabled = on;
+                conn.status.lock().unwrap().enabled = on;
+            }
+            Ok(PresenceCommand::SetSession(info)) => session = Some(info),
+            Ok(PresenceCommand::SetNowPlaying(info)) => now_playing = Some(info),
+            Ok(PresenceCommand::ClearNowPlaying) => now_playing = None,
+            Ok(PresenceCommand::WorkerStopped) => {
+                session = None;
+                now_playing = None;
+            }
+            Ok(PresenceCommand::Shutdown(done)) => {
+                conn.disconnect();
+                let _ = done.send(());
+                return;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                conn.disconnect();
+                return;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if enabled {
+            conn.sync(&lines(&session, &now_playing));
+        }
+    }
+}
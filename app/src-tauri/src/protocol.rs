@@ -0,0 +1,224 @@
+//! Messages the worker writes to stdout.
+//!
+//! The structured form is one `EVENT:` line per message, carrying a JSON
+//! object whose `type` picks a `WorkerMessage` variant:
+//!
+//! ```text
+//! EVENT:{"type":"ready","port":52011}
+//! EVENT:{"type":"progress","job":"analyze","done":3,"total":10}
+//! ```
+//!
+//! Types this app doesn't know are passed on as `Unknown` (and forwarded to
+//! the webview as `worker-event`) rather than dropped, so a newer worker can
+//! still reach an older app's UI. The bare prefixes older workers print
+//! (`VERSION:`, `PORT:`, `AUDIO:`, `PROGRESS:`) are adapted to the same
+//! variants, so everything after `parse_line` sees one shape.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::audio::EffectiveAudio;
+use crate::events::JobProgress;
+
+const EVENT_PREFIX: &str = "EVENT:";
+/// Largest `EVENT:` payload parsed. Longer ones are cut to this and passed
+/// on unparsed as `Oversized`.
+pub(crate) const MAX_EVENT_BYTES: usize = 16 * 1024;
+
+/// A known message. `type` is the kebab-case variant name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub(crate) enum TypedEvent {
+    /// The worker's build version, sent before `ready`.
+    Version { version: String },
+    /// Listening on `port` and ready for requests.
+    Ready { port: u16 },
+    Progress { job: String, done: u64, total: u64 },
+    /// A line for the log viewer, like one on stderr.
+    Log { message: String },
+    /// The worker is alive; counts as a passed health check.
+    Heartbeat,
+    /// Audio engine settings in effect after a start or reconfiguration.
+    ConfigAck { audio: EffectiveAudio },
+}
+
+/// Payload of `worker-event`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RawWorkerEvent {
+    /// `type` of the event, `None` when it couldn't be read.
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// The JSON object as sent, or for an oversized event the first
+    /// `MAX_EVENT_BYTES` of it as a string.
+    pub payload: Value,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum WorkerMessage {
+    Typed(TypedEvent),
+    /// A well-formed event of a type not in `TypedEvent`.
+    Unknown(RawWorkerEvent),
+    /// An `EVENT:` payload over `MAX_EVENT_BYTES`.
+    Oversized(RawWorkerEvent),
+    /// A line that looked like a message but couldn't be parsed.
+    Malformed { line: String, error: String },
+}
+
+/// The message on one stdout line, `None` for anything else (plain output).
+pub(crate) fn parse_line(line: &str) -> Option<WorkerMessage> {
+    if let Some(payload) = line.strip_prefix(EVENT_PREFIX) {
+        return Some(parse_event(payload.trim()));
+    }
+    let malformed = |error: String| WorkerMessage::Malformed { line: line.to_string(), error };
+    let typed = if let Some(version) = line.strip_prefix("VERSION:") {
+        TypedEvent::Version { version: version.trim().to_string() }
+    } else if let Some(port) = line.strip_prefix("PORT:") {
+        match port.trim().parse() {
+            Ok(port) => TypedEvent::Ready { port },
+            Err(e) => return Some(malformed(e.to_string())),
+        }
+    } else if let Some(payload) = line.strip_prefix("AUDIO:") {
+        match serde_json::from_str(payload) {
+            Ok(audio) => TypedEvent::ConfigAck { audio },
+            Err(e) => return Some(malformed(e.to_string())),
+        }
+    } else if let Some(payload) = line.strip_prefix("PROGRESS:") {
+        match JobProgress::parse(payload) {
+            Some(p) => TypedEvent::Progress { job: p.job, done: p.done, total: p.total },
+            None => return Some(malformed("expected <job>:<done>/<total>".to_string())),
+        }
+    } else {
+        return None;
+    };
+    Some(WorkerMessage::Typed(typed))
+}
+
+fn parse_event(payload: &str) -> WorkerMessage {
+    if payload.len() > MAX_EVENT_BYTES {
+        let mut end = MAX_EVENT_BYTES;
+        while !payload.is_char_boundary(end) {
+            end -= 1;
+        }
+        return WorkerMessage::Oversized(RawWorkerEvent {
+            kind: None,
+            payload: Value::String(payload[..end].to_string()),
+            truncated: true,
+        });
+    }
+    let malformed = |error: String| WorkerMessage::Malformed { line: format!("{}{}", EVENT_PREFIX, payload), error };
+    let value: Value = match serde_json::from_str(payload) {
+        Ok(value) => value,
+        Err(e) => return malformed(e.to_string()),
+    };
+    let Some(kind) = value.get("type").and_then(Value::as_str).map(str::to_string) else {
+        return malformed("no \"type\" field".to_string());
+    };
+    if !KNOWN_TYPES.contains(&kind.as_str()) {
+        return WorkerMessage::Unknown(RawWorkerEvent { kind: Some(kind), payload: value, truncated: false });
+    }
+    match serde_json::from_value(value) {
+        Ok(typed) => WorkerMessage::Typed(typed),
+        Err(e) => malformed(format!("bad {} event: {}", kind, e)),
+    }
+}
+
+/// `type` values of `TypedEvent`'s variants. A known type with bad fields
+/// is malformed, not unknown.
+const KNOWN_TYPES: &[&str] = &["version", "ready", "progress", "log", "heartbeat", "config-ack"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn malformed(line: &str) -> String {
+        match parse_line(line) {
+            Some(WorkerMessage::Malformed { line: echoed, error }) => {
+                assert_eq!(echoed, line);
+                error
+            }
+            other => panic!("{:?} was not malformed: {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn plain_output_is_not_a_message() {
+        assert!(parse_line("").is_none());
+        assert!(parse_line("analyzing track 3").is_none());
+        assert!(parse_line(" EVENT:{\"type\":\"heartbeat\"}").is_none());
+    }
+
+    #[test]
+    fn known_events_parse() {
+        assert!(matches!(
+            parse_line(r#"EVENT:{"type":"ready","port":52011}"#),
+            Some(WorkerMessage::Typed(TypedEvent::Ready { port: 52011 }))
+        ));
+        assert!(matches!(
+            parse_line(r#"EVENT: {"type":"progress","job":"analyze","done":3,"total":10} "#),
+            Some(WorkerMessage::Typed(TypedEvent::Progress { ref job, done: 3, total: 10 })) if job == "analyze"
+        ));
+        assert!(matches!(parse_line(r#"EVENT:{"type":"heartbeat"}"#), Some(WorkerMessage::Typed(TypedEvent::Heartbeat))));
+    }
+
+    #[test]
+    fn legacy_prefixes_map_to_the_same_events() {
+        assert!(matches!(
+            parse_line("VERSION: 1.4.0"),
+            Some(WorkerMessage::Typed(TypedEvent::Version { ref version })) if version == "1.4.0"
+        ));
+        assert!(matches!(parse_line("PORT:9000"), Some(WorkerMessage::Typed(TypedEvent::Ready { port: 9000 }))));
+        assert!(matches!(
+            parse_line(r#"AUDIO:{"sample_rate":48000,"live_reconfig":true}"#),
+            Some(WorkerMessage::Typed(TypedEvent::ConfigAck { audio })) if audio.sample_rate == Some(48000) && audio.live_reconfig
+        ));
+        assert!(matches!(
+            parse_line("PROGRESS:render:5/8"),
+            Some(WorkerMessage::Typed(TypedEvent::Progress { done: 5, total: 8, .. }))
+        ));
+    }
+
+    #[test]
+    fn malformed_json_is_reported_not_dropped() {
+        assert!(malformed("EVENT:{\"type\":\"ready\",").contains("EOF"));
+        malformed("EVENT:not json");
+        assert_eq!(malformed("EVENT:[1,2,3]"), "no \"type\" field");
+        assert_eq!(malformed(r#"EVENT:{"port":1}"#), "no \"type\" field");
+        assert_eq!(malformed(r#"EVENT:{"type":7}"#), "no \"type\" field");
+    }
+
+    #[test]
+    fn known_types_with_bad_fields_are_malformed() {
+        assert!(malformed(r#"EVENT:{"type":"ready"}"#).starts_with("bad ready event"));
+        assert!(malformed(r#"EVENT:{"type":"ready","port":70000}"#).starts_with("bad ready event"));
+        malformed("PORT:abc");
+        malformed("AUDIO:{");
+        assert_eq!(malformed("PROGRESS:render"), "expected <job>:<done>/<total>");
+    }
+
+    #[test]
+    fn unknown_types_are_passed_on_whole() {
+        let line = r#"EVENT:{"type":"waveform-done","track":"a.mp3","peaks":[0.1,0.9]}"#;
+        let Some(WorkerMessage::Unknown(raw)) = parse_line(line) else { panic!("not unknown") };
+        assert_eq!(raw.kind.as_deref(), Some("waveform-done"));
+        assert_eq!(raw.payload["peaks"][1], 0.9);
+        assert!(!raw.truncated);
+    }
+
+    #[test]
+    fn oversized_events_are_cut_at_a_char_boundary() {
+        let log = |message: &str| format!(r#"{{"type":"log","message":"{}"}}"#, message);
+        let exact = log(&"x".repeat(MAX_EVENT_BYTES - log("").len()));
+        assert_eq!(exact.len(), MAX_EVENT_BYTES);
+        assert!(matches!(parse_line(&format!("EVENT:{}", exact)), Some(WorkerMessage::Typed(TypedEvent::Log { .. }))));
+
+        // Multi-byte characters straddling the limit.
+        let big = log(&"é".repeat(MAX_EVENT_BYTES));
+        let Some(WorkerMessage::Oversized(raw)) = parse_line(&format!("EVENT:{}", big)) else { panic!("not oversized") };
+        let Value::String(kept) = &raw.payload else { panic!("payload not a string") };
+        assert!(kept.len() <= MAX_EVENT_BYTES && kept.len() > MAX_EVENT_BYTES - 4);
+        assert!(big.starts_with(kept.as_str()));
+        assert!(raw.truncated);
+        assert!(raw.kind.is_none());
+    }
+}
@@ -0,0 +1,84 @@
+//! HTTP access to the Go worker from the Rust side.
+
+use std::time::Duration;
+
+use crate::config::WorkerConfig;
+
+/// Shared reqwest client configured from `WorkerConfig`.
+pub(crate) struct WorkerHttp {
+    client: reqwest::Client,
+    connect_timeout_ms: u64,
+}
+
+fn build_client(connect_timeout_ms: u64, read_timeout_ms: u64) -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(connect_timeout_ms))
+        .read_timeout(Duration::from_millis(read_timeout_ms))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+impl WorkerHttp {
+    pub(crate) fn new(config: &WorkerConfig) -> Result<Self, String> {
+        Ok(WorkerHttp {
+            client: build_client(config.connect_timeout_ms, config.read_timeout_ms)?,
+            connect_timeout_ms: config.connect_timeout_ms,
+        })
+    }
+
+    /// The shared client, or a one-off client when the caller overrides the
+    /// read timeout (reqwest only supports read timeouts per client).
+    pub(crate) fn client(&self, read_timeout_ms: Option<u64>) -> Result<reqwest::Client, String> {
+        match read_timeout_ms {
+            Some(ms) => build_client(self.connect_timeout_ms, ms),
+            None => Ok(self.client.clone()),
+        }
+    }
+}
+
+/// `GET path?query` from the worker and return the raw body, for binary
+/// responses such as images.
+pub(crate) async fn fetch_bytes(
+    client: &reqwest::Client,
+    port: u16,
+    path: &str,
+    query: &[(&str, &str)],
+) -> Result<bytes::Bytes, String> {
+    let url = format!("http://127.0.0.1:{}/{}", port, path.trim_start_matches('/'));
+    let resp = client.get(url).query(query).send().await.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("worker returned {}: {}", status, text.trim()));
+    }
+    resp.bytes().await.map_err(|e| e.to_string())
+}
+
+/// Send `method path` to the worker on `port` and return the decoded body.
+///
+/// Non-JSON bodies are returned as a JSON string; non-2xx statuses become an
+/// error carrying the worker's response text.
+pub(crate) async fn forward(
+    client: &reqwest::Client,
+    port: u16,
+    method: &str,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> Result<serde_json::Value, String> {
+    let method = reqwest::Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+        .map_err(|e| e.to_string())?;
+    let path = path.trim_start_matches('/');
+    let url = format!("http://127.0.0.1:{}/{}", port, path);
+
+    let mut req = client.request(method, url);
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    let resp = req.send().await.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("worker returned {}: {}", status, text.trim()));
+    }
+    Ok(serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)))
+}
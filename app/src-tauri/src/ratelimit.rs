@@ -0,0 +1,201 @@
+//! Token buckets for frontend commands that are expensive to run, so a
+//! runaway loop in the webview can't keep the machine busy with them.
+//!
+//! Each listed command has its own bucket, full at launch; a call takes one
+//! token and tokens refill at a steady rate. Everything is in memory, so
+//! limits reset when the app restarts. With `debug_mode` on nothing is
+//! limited, for stress testing.
+//!
+//! The plugin's invoke handler checks every call against `LIMITS` before
+//! dispatching it, so the commands themselves don't take part.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// (command, burst, seconds per token)
+const LIMITS: &[(&str, u32, f64)] = &[
+    ("collect_diagnostics", 5, 2.0),
+    ("rebuild_index", 2, 30.0),
+    ("restart_worker", 3, 10.0),
+    ("retry_worker_start", 3, 10.0),
+    ("attempt_repair", 2, 60.0),
+];
+
+/// A call refused by the limiter. The `Display` form starts with "rate
+/// limited" for the UI to recognise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RateLimited {
+    pub command: &'static str,
+    pub retry_after_ms: u64,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rate limited: {} (retry after {} ms)", self.command, self.retry_after_ms)
+    }
+}
+
+impl From<RateLimited> for String {
+    fn from(r: RateLimited) -> String {
+        r.to_string()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    burst: u32,
+    secs_per_token: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let earned = now.duration_since(self.updated).as_secs_f64() / self.secs_per_token;
+        self.tokens = (self.tokens + earned).min(self.burst as f64);
+        self.updated = now;
+    }
+}
+
+/// Current fill of one bucket, for `get_rate_limits`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct BucketState {
+    pub command: &'static str,
+    pub tokens: f64,
+    pub burst: u32,
+    pub secs_per_token: f64,
+}
+
+/// Managed state.
+pub(crate) struct RateLimiter {
+    buckets: Mutex<HashMap<&'static str, Bucket>>,
+    exempt: AtomicBool,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(exempt: bool) -> Self {
+        Self::starting_at(exempt, Instant::now())
+    }
+
+    /// A limiter whose buckets were full at `now`; with `check_at` and
+    /// `states_at` tests drive it from their own clock.
+    fn starting_at(exempt: bool, now: Instant) -> Self {
+        let buckets = LIMITS
+            .iter()
+            .map(|&(command, burst, secs_per_token)| {
+                (command, Bucket { tokens: burst as f64, burst, secs_per_token, updated: now })
+            })
+            .collect();
+        RateLimiter { buckets: Mutex::new(buckets), exempt: AtomicBool::new(exempt) }
+    }
+
+    /// Take a token for `command`. Commands without a bucket always pass.
+    pub(crate) fn check(&self, command: &str) -> Result<(), RateLimited> {
+        self.check_at(command, Instant::now())
+    }
+
+    fn check_at(&self, command: &str, now: Instant) -> Result<(), RateLimited> {
+        if self.exempt.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        let Some((&command, bucket)) = buckets.iter_mut().find(|(name, _)| **name == command) else { return Ok(()) };
+        bucket.refill(now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let retry_after_ms = ((1.0 - bucket.tokens) * bucket.secs_per_token * 1000.0).ceil() as u64;
+        eprintln!("[djbot] rate limited {} (retry after {} ms)", command, retry_after_ms);
+        Err(RateLimited { command, retry_after_ms })
+    }
+
+    pub(crate) fn states(&self) -> Vec<BucketState> {
+        self.states_at(Instant::now())
+    }
+
+    fn states_at(&self, now: Instant) -> Vec<BucketState> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut states: Vec<_> = buckets
+            .iter_mut()
+            .map(|(&command, bucket)| {
+                bucket.refill(now);
+                BucketState { command, tokens: bucket.tokens, burst: bucket.burst, secs_per_token: bucket.secs_per_token }
+            })
+            .collect();
+        states.sort_by_key(|s| s.command);
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn burst_is_spent_then_refused() {
+        let start = Instant::now();
+        let limiter = RateLimiter::starting_at(false, start);
+        // rebuild_index: burst 2, one token per 30 s.
+        assert!(limiter.check_at("rebuild_index", start).is_ok());
+        assert!(limiter.check_at("rebuild_index", start).is_ok());
+        let refused = limiter.check_at("rebuild_index", start).unwrap_err();
+        assert_eq!(refused, RateLimited { command: "rebuild_index", retry_after_ms: 30_000 });
+        assert!(refused.to_string().starts_with("rate limited"));
+    }
+
+    #[test]
+    fn tokens_refill_at_the_configured_rate() {
+        let start = Instant::now();
+        let limiter = RateLimiter::starting_at(false, start);
+        for _ in 0..2 {
+            limiter.check_at("rebuild_index", start).unwrap();
+        }
+        let halfway = start + Duration::from_secs(15);
+        assert_eq!(limiter.check_at("rebuild_index", halfway).unwrap_err().retry_after_ms, 15_000);
+        assert!(limiter.check_at("rebuild_index", start + Duration::from_secs(30)).is_ok());
+        assert!(limiter.check_at("rebuild_index", start + Duration::from_secs(30)).is_err());
+    }
+
+    #[test]
+    fn refill_stops_at_the_burst() {
+        let start = Instant::now();
+        let limiter = RateLimiter::starting_at(false, start);
+        let later = start + Duration::from_secs(3600);
+        let state = limiter.states_at(later).into_iter().find(|s| s.command == "restart_worker").unwrap();
+        assert_eq!(state.tokens, 3.0);
+        for _ in 0..3 {
+            limiter.check_at("restart_worker", later).unwrap();
+        }
+        assert!(limiter.check_at("restart_worker", later).is_err());
+    }
+
+    #[test]
+    fn buckets_are_per_command() {
+        let start = Instant::now();
+        let limiter = RateLimiter::starting_at(false, start);
+        for _ in 0..2 {
+            limiter.check_at("attempt_repair", start).unwrap();
+        }
+        assert!(limiter.check_at("attempt_repair", start).is_err());
+        assert!(limiter.check_at("collect_diagnostics", start).is_ok());
+    }
+
+    #[test]
+    fn unlisted_commands_and_debug_mode_are_not_limited() {
+        let start = Instant::now();
+        let limiter = RateLimiter::starting_at(false, start);
+        for _ in 0..100 {
+            limiter.check_at("get_worker_port", start).unwrap();
+        }
+        let exempt = RateLimiter::starting_at(true, start);
+        for _ in 0..100 {
+            exempt.check_at("attempt_repair", start).unwrap();
+        }
+    }
+}
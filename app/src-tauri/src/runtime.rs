@@ -0,0 +1,103 @@
+//! Recognising a worker that Windows couldn't load.
+//!
+//! A DLL that can't be found (`STATUS_DLL_NOT_FOUND`) or fails to
+//! initialise (`STATUS_DLL_INIT_FAILED`) kills the process before `main`,
+//! so the worker exits within moments with that NTSTATUS as its exit code.
+//! `diagnose` turns such an exit into a `MissingRuntime` with a plain
+//! explanation and, where it can, the DLL at fault: the usual suspects the
+//! binary references are looked for next to it and in the system
+//! directory. Elsewhere `diagnose` always returns `None`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// An exit this soon after spawning counts as failing to start.
+#[cfg(target_os = "windows")]
+const IMMEDIATE_EXIT: Duration = Duration::from_secs(2);
+
+#[cfg(target_os = "windows")]
+const REMEDIATION_URL: &str = "https://learn.microsoft.com/cpp/windows/latest-supported-vc-redist";
+
+/// The worker couldn't start because a runtime library is missing or
+/// broken. Part of the status report and of `worker-failed`.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub(crate) struct MissingRuntime {
+    /// Exit status in hex, e.g. "0xC0000135".
+    pub code: String,
+    pub explanation: String,
+    pub remediation_url: &'static str,
+    /// The DLL found missing, when the probe could tell.
+    pub missing_dll: Option<String>,
+}
+
+#[cfg(target_os = "windows")]
+const STATUS_DLL_NOT_FOUND: u32 = 0xC000_0135;
+#[cfg(target_os = "windows")]
+const STATUS_DLL_INIT_FAILED: u32 = 0xC000_0142;
+
+/// Runtime DLLs most often missing on fresh or stripped-down installs: the
+/// Visual C++ redistributable and the Universal CRT forwarders.
+#[cfg(target_os = "windows")]
+const COMMON_DLLS: &[&str] = &[
+    "vcruntime140.dll",
+    "vcruntime140_1.dll",
+    "msvcp140.dll",
+    "ucrtbase.dll",
+    "api-ms-win-crt-runtime-l1-1-0.dll",
+];
+
+/// `exit_code` after `ran_for` explained as a missing runtime, if it is one.
+#[cfg(target_os = "windows")]
+pub(crate) fn diagnose(exit_code: Option<i32>, ran_for: Duration, binary: &Path) -> Option<MissingRuntime> {
+    let status = exit_code? as u32;
+    let immediate = ran_for < IMMEDIATE_EXIT;
+    let missing_dll = if immediate || matches!(status, STATUS_DLL_NOT_FOUND | STATUS_DLL_INIT_FAILED) {
+        find_missing_dll(binary)
+    } else {
+        None
+    };
+    let explanation = match status {
+        STATUS_DLL_NOT_FOUND => match &missing_dll {
+            Some(dll) => format!("The worker needs {}, which isn't installed.", dll),
+            None => "The worker needs a system library (DLL) that isn't installed.".to_string(),
+        },
+        STATUS_DLL_INIT_FAILED => "A system library (DLL) the worker needs failed to start; \
+                                   it may be damaged or blocked by security software."
+            .to_string(),
+        // Any other immediate failure only counts when a library is missing.
+        _ if immediate && status != 0 => format!("The worker exited on startup and {} isn't installed.", missing_dll.as_ref()?),
+        _ => return None,
+    };
+    Some(MissingRuntime {
+        code: format!("0x{:08X}", status),
+        explanation: format!("{} Installing the latest Microsoft Visual C++ Redistributable usually fixes this.", explanation),
+        remediation_url: REMEDIATION_URL,
+        missing_dll,
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn diagnose(_exit_code: Option<i32>, _ran_for: Duration, _binary: &Path) -> Option<MissingRuntime> {
+    None
+}
+
+/// The first of `COMMON_DLLS` the binary names that is neither next to it
+/// nor in the system directory. A name in the file is taken as an import,
+/// which is close enough to pick the likely culprit.
+#[cfg(target_os = "windows")]
+fn find_missing_dll(binary: &Path) -> Option<String> {
+    let contents = std::fs::read(binary).ok()?.to_ascii_lowercase();
+    let system_dir = std::env::var_os("SystemRoot").map(|root| Path::new(&root).join("System32"));
+    let beside = binary.parent();
+    COMMON_DLLS
+        .iter()
+        .filter(|dll| contents.windows(dll.len()).any(|w| w == dll.as_bytes()))
+        .find(|dll| {
+            let present = |dir: Option<&Path>| dir.is_some_and(|d| d.join(dll).is_file());
+            !present(beside) && !present(system_dir.as_deref())
+        })
+        .map(|dll| dll.to_string())
+}
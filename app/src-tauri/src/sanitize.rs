@@ -0,0 +1,186 @@
+//! Cleaning of text that comes from the worker, from file names or from
+//! tags before it reaches the webview, in events or command results.
+//!
+//! ANSI escape sequences are removed; other control characters are shown
+//! as their Unicode control pictures (NUL becomes `␀`) so binary garbage
+//! stays visible in diagnostics instead of vanishing. Overlong text is cut
+//! with a marker saying how much was dropped. Input is already valid UTF-8:
+//! bytes are decoded lossily where they are read (`worker::for_each_line`).
+//!
+//! This does not HTML-escape; the frontend must still insert these strings
+//! as text, not markup.
+
+use serde_json::Value;
+
+/// Longest log line passed on, in chars.
+pub(crate) const MAX_LINE_CHARS: usize = 4000;
+/// Longest other string (paths, tag values, JSON fields), in chars.
+pub(crate) const MAX_FIELD_CHARS: usize = 16 * 1024;
+
+/// A single log line. Of a line redrawn with `\r` (ffmpeg progress) only
+/// the last state is kept, as a terminal would show it.
+pub(crate) fn line(text: &str) -> String {
+    let last = text.split('\r').rfind(|s| !s.is_empty()).unwrap_or_default();
+    clean(last, false, MAX_LINE_CHARS)
+}
+
+/// Any other string. Newlines and tabs are kept.
+pub(crate) fn field(text: &str) -> String {
+    clean(text, true, MAX_FIELD_CHARS)
+}
+
+/// Every string in `value` (keys included) through `field`.
+pub(crate) fn json(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = field(s),
+        Value::Array(items) => items.iter_mut().for_each(json),
+        Value::Object(map) => {
+            let dirty = map.keys().any(|k| field(k) != *k);
+            if dirty {
+                let entries = std::mem::take(map);
+                for (k, mut v) in entries {
+                    json(&mut v);
+                    map.insert(field(&k), v);
+                }
+            } else {
+                map.values_mut().for_each(json);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The control picture for C0 controls and DEL, U+FFFD for C1 controls.
+fn picture(c: char) -> char {
+    match c {
+        '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or('\u{fffd}'),
+        '\u{7f}' => '\u{2421}',
+        _ => '\u{fffd}',
+    }
+}
+
+fn clean(text: &str, multiline: bool, max_chars: usize) -> String {
+    let mut out = String::with_capacity(text.len().min(max_chars));
+    let mut kept = 0;
+    let mut dropped = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            // CSI: ESC [ params… final byte in @..~ (also the one-char C1 form).
+            '\u{1b}' if chars.peek() == Some(&'[') => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '\u{9b}' => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+                continue;
+            }
+            // OSC: ESC ] … terminated by BEL or ESC \.
+            '\u{1b}' if chars.peek() == Some(&']') => {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+                continue;
+            }
+            // Any other escape: ESC, intermediate bytes (as in the charset
+            // switch ESC ( B that `tput sgr0` emits), then one final char.
+            '\u{1b}' => {
+                while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                chars.next();
+                continue;
+            }
+            '\t' => '\t',
+            '\n' if multiline => '\n',
+            c if c.is_control() => picture(c),
+            c => c,
+        };
+        if kept < max_chars {
+            out.push(c);
+            kept += 1;
+        } else {
+            dropped += 1;
+        }
+    }
+    if dropped > 0 {
+        out.push_str(&format!(" …[truncated {} chars]", dropped));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ansi_color_codes_are_removed() {
+        assert_eq!(line("\u{1b}[31merror\u{1b}[0m: no such file"), "error: no such file");
+        assert_eq!(line("\u{1b}[1;38;5;208mbold orange\u{1b}[m"), "bold orange");
+        assert_eq!(line("\u{9b}32mgreen"), "green");
+        // OSC title and hyperlink sequences, BEL- and ST-terminated.
+        assert_eq!(line("\u{1b}]0;title\u{7}done"), "done");
+        assert_eq!(line("\u{1b}]8;;https://x.test\u{1b}\\link\u{1b}]8;;\u{1b}\\"), "link");
+        assert_eq!(line("\u{1b}(Bplain"), "plain");
+    }
+
+    #[test]
+    fn cr_overwritten_progress_keeps_the_last_state() {
+        let ffmpeg = "size=    256kB time=00:00:01.00\rsize=    512kB time=00:00:02.00\rsize=   1024kB time=00:00:04.00\r";
+        assert_eq!(line(ffmpeg), "size=   1024kB time=00:00:04.00");
+        assert_eq!(line("\r\r"), "");
+        assert_eq!(line("no carriage return"), "no carriage return");
+    }
+
+    #[test]
+    fn embedded_nulls_and_controls_become_visible() {
+        assert_eq!(line("a\0b"), "a\u{2400}b");
+        assert_eq!(line("bell\u{7}\u{7f}"), "bell\u{2407}\u{2421}");
+        assert_eq!(line("c1\u{85}"), "c1\u{fffd}");
+        // Newlines end a log line but are part of a field.
+        assert_eq!(line("one\ntwo"), "one\u{240a}two");
+        assert_eq!(field("one\ntwo\tthree"), "one\ntwo\tthree");
+    }
+
+    #[test]
+    fn script_looking_text_is_left_for_the_frontend() {
+        // Not HTML-escaped here; see the module docs.
+        assert_eq!(field("<img src=x onerror=alert(1)>.mp3"), "<img src=x onerror=alert(1)>.mp3");
+    }
+
+    #[test]
+    fn overlong_text_is_cut_with_a_marker() {
+        let long = "é".repeat(MAX_LINE_CHARS + 10);
+        let cut = line(&long);
+        assert!(cut.starts_with(&"é".repeat(MAX_LINE_CHARS)));
+        assert!(cut.ends_with(" …[truncated 10 chars]"));
+        assert_eq!(field(&"x".repeat(MAX_FIELD_CHARS)), "x".repeat(MAX_FIELD_CHARS));
+        // Escape sequences don't count towards the limit.
+        let coloured = format!("\u{1b}[31m{}\u{1b}[0m", "y".repeat(MAX_LINE_CHARS));
+        assert_eq!(line(&coloured), "y".repeat(MAX_LINE_CHARS));
+    }
+
+    #[test]
+    fn json_cleans_nested_strings_and_keys() {
+        let mut value = serde_json::json!({
+            "title": "\u{1b}[1mTrack\u{1b}[0m",
+            "tags": ["ok", "null\0byte"],
+            "bad\u{1}key": { "n": 1 },
+        });
+        json(&mut value);
+        assert_eq!(value["title"], "Track");
+        assert_eq!(value["tags"][1], "null\u{2400}byte");
+        assert_eq!(value["bad\u{2401}key"]["n"], 1);
+        assert!(value.get("bad\u{1}key").is_none());
+    }
+}
@@ -0,0 +1,280 @@
+//! Jobs run on a timetable by the app itself: re-analyzing a library
+//! folder every night, rendering the same export every week.
+//!
+//! Entries live in `{data_dir}/schedules.json` with the outcome of their
+//! last run. The tick loop in `plugin` asks `due` which entries should
+//! fire; a due time that passed while the app was closed fires once at the
+//! next launch for entries with `catch_up`, and is skipped otherwise.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{Datelike, Days, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::ExportArgs;
+use crate::jobs::unix_now;
+
+const SCHEDULE_FILE: &str = "schedules.json";
+/// Shortest `Timing::Interval` accepted.
+const MIN_INTERVAL_SECS: u64 = 60;
+
+/// When an entry fires. Times of day are local.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Timing {
+    Interval { secs: u64 },
+    Daily { hour: u32, minute: u32 },
+    /// `weekday` counts from 0 = Monday.
+    Weekly { weekday: u32, hour: u32, minute: u32 },
+}
+
+impl Timing {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let time_ok = |hour: &u32, minute: &u32| *hour < 24 && *minute < 60;
+        match self {
+            Timing::Interval { secs } if *secs < MIN_INTERVAL_SECS => {
+                Err(format!("interval must be at least {} seconds", MIN_INTERVAL_SECS))
+            }
+            Timing::Daily { hour, minute } if !time_ok(hour, minute) => Err(format!("invalid time {}:{:02}", hour, minute)),
+            Timing::Weekly { weekday, .. } if *weekday > 6 => Err(format!("invalid weekday {}; expected 0-6", weekday)),
+            Timing::Weekly { hour, minute, .. } if !time_ok(hour, minute) => {
+                Err(format!("invalid time {}:{:02}", hour, minute))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// First time this fires strictly after `after` (Unix seconds).
+    pub(crate) fn next_after(&self, after: u64) -> u64 {
+        let (hour, minute, weekday) = match *self {
+            Timing::Interval { secs } => return after + secs,
+            Timing::Daily { hour, minute } => (hour, minute, None),
+            Timing::Weekly { weekday, hour, minute } => (hour, minute, Some(weekday)),
+        };
+        let now = Local.timestamp_opt(after as i64, 0).earliest().unwrap_or_else(Local::now);
+        let mut date = now.date_naive();
+        if let Some(weekday) = weekday {
+            let ahead = (weekday + 7 - now.weekday().num_days_from_monday()) % 7;
+            date = date + Days::new(ahead as u64);
+        }
+        let step = Days::new(if weekday.is_some() { 7 } else { 1 });
+        loop {
+            let at = at_local(date, hour, minute);
+            if at > after {
+                return at;
+            }
+            date = date + step;
+        }
+    }
+}
+
+/// `hour:minute` on `date` in local time, as Unix seconds. A time that
+/// doesn't exist (skipped by a DST change) moves to the hour after.
+fn at_local(date: NaiveDate, hour: u32, minute: u32) -> u64 {
+    let naive = date.and_hms_opt(hour, minute, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&(naive + chrono::Duration::hours(1))).earliest())
+        .map(|t| t.timestamp().max(0) as u64)
+        .unwrap_or_default()
+}
+
+/// What an entry runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum ScheduledJob {
+    /// Analyze every importable file directly inside `folder`.
+    Analyze { folder: PathBuf },
+    /// The same job as `djbot export`.
+    Export(ExportArgs),
+}
+
+impl ScheduledJob {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self {
+            ScheduledJob::Analyze { folder } if !folder.is_dir() => Err(format!("{} is not a folder", folder.display())),
+            ScheduledJob::Export(args) if args.inputs.len() < 2 => Err("a mix needs at least 2 inputs".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            ScheduledJob::Analyze { .. } => "analyze",
+            ScheduledJob::Export(_) => "export",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ScheduleRun {
+    /// Unix seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub ok: bool,
+    /// What it produced, or why it failed.
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Schedule {
+    pub id: String,
+    pub name: String,
+    pub timing: Timing,
+    pub job: ScheduledJob,
+    pub enabled: bool,
+    /// Run once at launch if a due time passed while the app was closed.
+    pub catch_up: bool,
+    pub created_at: u64,
+    pub last_run: Option<ScheduleRun>,
+}
+
+impl Schedule {
+    /// Next time this entry is due, given that the app was launched at
+    /// `launched_at`.
+    fn next_due(&self, launched_at: u64) -> u64 {
+        let last = self.last_run.as_ref().map_or(self.created_at, |r| r.started_at);
+        let baseline = if self.catch_up { last } else { last.max(launched_at) };
+        self.timing.next_after(baseline)
+    }
+}
+
+/// A `Schedule` with when it runs next, for `list_schedules`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ScheduleView {
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    /// `None` while disabled.
+    pub next_run: Option<u64>,
+}
+
+/// Managed state guarding `{data_dir}/schedules.json`.
+pub(crate) struct Scheduler {
+    path: PathBuf,
+    entries: Mutex<Vec<Schedule>>,
+    launched_at: u64,
+    /// Entries whose current due time was already reported as deferred.
+    deferred: Mutex<HashSet<String>>,
+    /// Held while a scheduled job runs; they run one at a time.
+    pub running: tokio::sync::Mutex<()>,
+}
+
+impl Scheduler {
+    pub(crate) fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(SCHEDULE_FILE);
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("[djbot] ignoring unreadable {}: {}", SCHEDULE_FILE, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Scheduler {
+            path,
+            entries: Mutex::new(entries),
+            launched_at: unix_now(),
+            deferred: Mutex::default(),
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn save(&self, entries: &[Schedule]) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(entries).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+
+    pub(crate) fn list(&self) -> Vec<ScheduleView> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|s| ScheduleView {
+                next_run: s.enabled.then(|| s.next_due(self.launched_at)),
+                schedule: s.clone(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<Schedule> {
+        self.entries.lock().unwrap().iter().find(|s| s.id == id).cloned()
+    }
+
+    pub(crate) fn add(
+        &self,
+        name: String,
+        timing: Timing,
+        job: ScheduledJob,
+        enabled: bool,
+        catch_up: bool,
+    ) -> Result<Schedule, String> {
+        timing.validate()?;
+        job.validate()?;
+        let mut id = [0u8; 8];
+        getrandom::getrandom(&mut id).map_err(|e| e.to_string())?;
+        let schedule = Schedule {
+            id: id.iter().map(|b| format!("{:02x}", b)).collect(),
+            name,
+            timing,
+            job,
+            enabled,
+            catch_up,
+            created_at: unix_now(),
+            last_run: None,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(schedule.clone());
+        self.save(&entries)?;
+        Ok(schedule)
+    }
+
+    pub(crate) fn remove(&self, id: &str) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|s| s.id != id);
+        if entries.len() == before {
+            return Err(format!("no schedule {}", id));
+        }
+        self.save(&entries)
+    }
+
+    /// Enabled entries due at `now`.
+    pub(crate) fn due(&self, now: u64) -> Vec<Schedule> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.enabled && s.next_due(self.launched_at) <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// True the first time `id` is deferred for its current due time.
+    pub(crate) fn mark_deferred(&self, id: &str) -> bool {
+        self.deferred.lock().unwrap().insert(id.to_string())
+    }
+
+    pub(crate) fn record_run(&self, id: &str, run: ScheduleRun) {
+        self.deferred.lock().unwrap().remove(id);
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.iter_mut().find(|s| s.id == id) {
+            entry.last_run = Some(run);
+        }
+        if let Err(e) = self.save(&entries) {
+            eprintln!("[djbot] failed to save {}: {}", SCHEDULE_FILE, e);
+        }
+    }
+}
+
+/// Payload of `schedule-deferred`: an entry was due but the worker
+/// couldn't take it. It runs as soon as the worker can.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ScheduleDeferred {
+    pub id: String,
+    pub name: String,
+    pub reason: String,
+}
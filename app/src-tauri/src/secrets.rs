@@ -0,0 +1,167 @@
+//! Encryption of sensitive config.toml fields.
+//!
+//! djbot has no keychain integration, so secrets are sealed in place: the
+//! key is derived from the machine's identifier plus a random per-install
+//! salt kept next to (not inside) config.toml, and each value is encrypted
+//! with ChaCha20-Poly1305, bound to the field it belongs to. Stored values
+//! look like `enc:v1:<base64 nonce‖ciphertext>`.
+//!
+//! This keeps secrets out of plain sight in config backups and bug reports;
+//! it does not protect them from someone with the user's account on the
+//! same machine. A value that no longer decrypts (config copied to another
+//! machine, salt deleted, file edited) is reported as needing re-entry.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+const PREFIX: &str = "enc:v1:";
+const SALT_FILE: &str = "secrets.salt";
+const NONCE_LEN: usize = 12;
+
+/// How sensitive settings are stored, for `get_system_info`.
+pub(crate) const STORAGE: &str = "encrypted config (no keychain)";
+
+/// A stored secret that can't be decrypted any more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NeedsReentry {
+    pub field: &'static str,
+}
+
+impl fmt::Display for NeedsReentry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "credential needs re-entry: {}", self.field)
+    }
+}
+
+/// State of one sensitive setting, as shown in the settings screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CredentialStatus {
+    NotSet,
+    Stored,
+    NeedsReentry,
+}
+
+pub(crate) fn status(data_dir: &Path, field: &'static str, stored: Option<&str>) -> CredentialStatus {
+    match stored {
+        None => CredentialStatus::NotSet,
+        Some(s) if open(data_dir, field, s).is_ok() => CredentialStatus::Stored,
+        Some(_) => CredentialStatus::NeedsReentry,
+    }
+}
+
+/// Encrypt `plaintext` for `field`, creating the install's salt if needed.
+pub(crate) fn seal(data_dir: &Path, field: &'static str, plaintext: &str) -> Result<String, String> {
+    let salt = match read_salt(data_dir) {
+        Some(salt) => salt,
+        None => create_salt(data_dir)?,
+    };
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut nonce).map_err(|e| format!("no randomness for a nonce: {}", e))?;
+    let sealed = cipher(&salt)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext.as_bytes(), aad: field.as_bytes() })
+        .map_err(|_| format!("could not encrypt {}", field))?;
+    let mut bytes = nonce.to_vec();
+    bytes.extend_from_slice(&sealed);
+    Ok(format!("{}{}", PREFIX, base64::engine::general_purpose::STANDARD.encode(bytes)))
+}
+
+/// Decrypt a value written by `seal` for the same `field`.
+pub(crate) fn open(data_dir: &Path, field: &'static str, stored: &str) -> Result<String, NeedsReentry> {
+    let reentry = NeedsReentry { field };
+    let encoded = stored.strip_prefix(PREFIX).ok_or_else(|| reentry.clone())?;
+    let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).map_err(|_| reentry.clone())?;
+    if bytes.len() < NONCE_LEN {
+        return Err(reentry);
+    }
+    let salt = read_salt(data_dir).ok_or_else(|| reentry.clone())?;
+    let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+    let plain = cipher(&salt)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad: field.as_bytes() })
+        .map_err(|_| reentry.clone())?;
+    String::from_utf8(plain).map_err(|_| reentry)
+}
+
+fn cipher(salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut hasher = Sha256::new();
+    hasher.update(b"djbot secrets v1\0");
+    hasher.update(machine_id().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(salt);
+    ChaCha20Poly1305::new(Key::from_slice(&hasher.finalize()))
+}
+
+fn read_salt(data_dir: &Path) -> Option<Vec<u8>> {
+    std::fs::read(data_dir.join(SALT_FILE)).ok().filter(|s| s.len() == 32)
+}
+
+fn create_salt(data_dir: &Path) -> Result<Vec<u8>, String> {
+    let mut salt = vec![0u8; 32];
+    getrandom::getrandom(&mut salt).map_err(|e| format!("no randomness for a salt: {}", e))?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let path = data_dir.join(SALT_FILE);
+    let mut file = options.open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    file.write_all(&salt).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(salt)
+}
+
+/// A stable identifier for this machine, empty if there is none (the key
+/// then depends on the salt alone).
+#[cfg(target_os = "linux")]
+fn machine_id() -> String {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|p| std::fs::read_to_string(p).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+fn machine_id() -> String {
+    let out = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output();
+    let Ok(out) = out else { return String::new() };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find(|l| l.contains("IOPlatformUUID"))
+        .and_then(|l| l.split('"').nth(3))
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(target_os = "windows")]
+fn machine_id() -> String {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let out = std::process::Command::new("reg")
+        .args(["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    let Ok(out) = out else { return String::new() };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find(|l| l.contains("MachineGuid"))
+        .and_then(|l| l.split_whitespace().last())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn machine_id() -> String {
+    String::new()
+}
@@ -0,0 +1,116 @@
+//! The platform share sheet for a finished export: NSSharingServicePicker
+//! on macOS (AirDrop, Mail, Messages, …) and the Windows share UI through
+//! DataTransferManager. Both are anchored to the main window and driven on
+//! the main thread. Elsewhere, and on Windows builds without the share UI,
+//! sharing fails with `ShareError::NotSupported` so the UI can hide it.
+
+use std::fmt;
+use std::path::Path;
+
+use tauri::WebviewWindow;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ShareError {
+    NotSupported,
+    /// The file is missing or empty.
+    Unavailable(String),
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    Failed(String),
+}
+
+/// The `Display` form of `NotSupported` is exactly "share not supported",
+/// for the UI to recognise.
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::NotSupported => write!(f, "share not supported"),
+            ShareError::Unavailable(detail) => write!(f, "cannot share: {}", detail),
+            ShareError::Failed(detail) => write!(f, "share failed: {}", detail),
+        }
+    }
+}
+
+impl From<ShareError> for String {
+    fn from(e: ShareError) -> String {
+        e.to_string()
+    }
+}
+
+/// The file must exist and hold something at the moment it is shared.
+pub(crate) fn check_shareable(path: &Path) -> Result<(), ShareError> {
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_file() && meta.len() > 0 => Ok(()),
+        Ok(meta) if meta.is_file() => Err(ShareError::Unavailable(format!("{} is empty", path.display()))),
+        Ok(_) => Err(ShareError::Unavailable(format!("{} is not a file", path.display()))),
+        Err(e) => Err(ShareError::Unavailable(format!("{}: {}", path.display(), e))),
+    }
+}
+
+/// Open the share sheet for `path` over `window`. Must be called on the
+/// main thread.
+#[cfg(target_os = "macos")]
+pub(crate) fn present(window: &WebviewWindow, path: &Path) -> Result<(), ShareError> {
+    use objc2::rc::Retained;
+    use objc2::AnyThread;
+    use objc2_app_kit::{NSSharingServicePicker, NSWindow};
+    use objc2_foundation::{NSArray, NSRectEdge, NSString, NSURL};
+
+    let ns_window = window.ns_window().map_err(|e| ShareError::Failed(e.to_string()))? as *const NSWindow;
+    // SAFETY: tauri hands out the live NSWindow of this webview window.
+    let ns_window = unsafe { ns_window.as_ref() }.ok_or_else(|| ShareError::Failed("no native window".to_string()))?;
+    let view = ns_window.contentView().ok_or_else(|| ShareError::Failed("window has no content view".to_string()))?;
+    let url = NSURL::fileURLWithPath(&NSString::from_str(&path.to_string_lossy()));
+    let items = NSArray::from_retained_slice(&[Retained::into_super(Retained::into_super(url))]);
+    // SAFETY: NSURL conforms to NSPasteboardWriting, as the items must.
+    let picker = unsafe { NSSharingServicePicker::initWithItems(NSSharingServicePicker::alloc(), &items) };
+    picker.showRelativeToRect_ofView_preferredEdge(view.bounds(), &view, NSRectEdge::MinY);
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn present(window: &WebviewWindow, path: &Path) -> Result<(), ShareError> {
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::Arc;
+
+    use windows::core::{factory, Interface, Ref, HSTRING};
+    use windows::ApplicationModel::DataTransfer::{DataRequestedEventArgs, DataTransferManager};
+    use windows::Foundation::TypedEventHandler;
+    use windows::Storage::{IStorageItem, StorageFile};
+    use windows::Win32::UI::Shell::IDataTransferManagerInterop;
+    use windows_collections::IIterable;
+
+    // Missing before Windows 10 2004, where the call itself fails.
+    if !DataTransferManager::IsSupported().unwrap_or(false) {
+        return Err(ShareError::NotSupported);
+    }
+    let failed = |e: windows::core::Error| ShareError::Failed(e.message());
+    let hwnd = window.hwnd().map_err(|e| ShareError::Failed(e.to_string()))?;
+    let interop = factory::<DataTransferManager, IDataTransferManagerInterop>().map_err(failed)?;
+    // SAFETY: `hwnd` is the live top-level window of this app.
+    let manager: DataTransferManager = unsafe { interop.GetForWindow(hwnd) }.map_err(failed)?;
+
+    let file_path = HSTRING::from(path);
+    let title = HSTRING::from(path.file_name().unwrap_or_default());
+    // The handler removes itself after supplying the file once, so shares
+    // don't accumulate handlers on the window's manager.
+    let token = Arc::new(AtomicI64::new(0));
+    let handler_token = Arc::clone(&token);
+    let handler = TypedEventHandler::new(
+        move |sender: Ref<DataTransferManager>, args: Ref<DataRequestedEventArgs>| {
+            let request = args.ok()?.Request()?;
+            let data = request.Data()?;
+            data.Properties()?.SetTitle(&title)?;
+            let file = StorageFile::GetFileFromPathAsync(&file_path)?.join()?;
+            data.SetStorageItemsReadOnly(&IIterable::<IStorageItem>::from(vec![Some(file.cast::<IStorageItem>()?)]))?;
+            sender.ok()?.RemoveDataRequested(handler_token.load(Ordering::SeqCst))
+        },
+    );
+    token.store(manager.DataRequested(&handler).map_err(failed)?, Ordering::SeqCst);
+    // SAFETY: as for GetForWindow.
+    unsafe { interop.ShowShareUIForWindow(hwnd) }.map_err(failed)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) fn present(_window: &WebviewWindow, _path: &Path) -> Result<(), ShareError> {
+    Err(ShareError::NotSupported)
+}
@@ -0,0 +1,231 @@
+//! Code-signature inspection of the resolved worker binary.
+//!
+//! `check` uses the platform tools rather than linking the signing APIs
+//! directly: `codesign`/`spctl` on macOS and `Get-AuthenticodeSignature` on
+//! Windows. `validate` is the stricter pass/fail check behind
+//! `validate_worker_binary_signature`: `codesign --verify --deep --strict`
+//! on macOS and `WinVerifyTrust` on Windows, without spawning PowerShell.
+
+use std::path::Path;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::process::Command;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct WorkerSignature {
+    pub signed: bool,
+    /// Signing identity (macOS authority / Windows certificate subject).
+    pub signer: Option<String>,
+    /// Gatekeeper/notarization acceptance on macOS; `None` elsewhere.
+    pub notarized: Option<bool>,
+    /// Tool output or the reason the check could not run.
+    pub detail: String,
+}
+
+/// Result of `validate`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SignatureStatus {
+    pub valid: bool,
+    pub signer: Option<String>,
+    /// Why the signature isn't valid.
+    pub reason: Option<String>,
+}
+
+/// Whether `validate` can check signatures on this platform.
+pub(crate) const SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+/// Leaf signing identity: `codesign -dv` prints its details to stderr and
+/// the first Authority line is the leaf.
+#[cfg(target_os = "macos")]
+fn codesign_signer(path: &Path) -> Option<String> {
+    Command::new("codesign")
+        .args(["-dv", "--verbose=2"])
+        .arg(path)
+        .output()
+        .ok()
+        .and_then(|out| {
+            String::from_utf8_lossy(&out.stderr)
+                .lines()
+                .find_map(|l| l.strip_prefix("Authority=").map(str::to_string))
+        })
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn check(path: &Path) -> WorkerSignature {
+    let verify = Command::new("codesign")
+        .args(["--verify", "--deep", "--strict"])
+        .arg(path)
+        .output();
+    let verify = match verify {
+        Ok(out) => out,
+        Err(e) => {
+            return WorkerSignature {
+                signed: false,
+                signer: None,
+                notarized: None,
+                detail: format!("could not run codesign: {}", e),
+            }
+        }
+    };
+    let signed = verify.status.success();
+    let signer = codesign_signer(path);
+
+    let notarized = Command::new("spctl")
+        .args(["--assess", "--type", "execute"])
+        .arg(path)
+        .output()
+        .ok()
+        .map(|out| out.status.success());
+
+    WorkerSignature {
+        signed,
+        signer,
+        notarized,
+        detail: String::from_utf8_lossy(&verify.stderr).trim().to_string(),
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn check(path: &Path) -> WorkerSignature {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    // Single-quoted PowerShell literal; embedded quotes are doubled.
+    let literal = path.to_string_lossy().replace('\'', "''");
+    let script = format!(
+        "$s = Get-AuthenticodeSignature -LiteralPath '{}'; \
+         Write-Output $s.Status; Write-Output $s.SignerCertificate.Subject",
+        literal
+    );
+    let out = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    match out {
+        Ok(out) => {
+            let text = String::from_utf8_lossy(&out.stdout);
+            let mut lines = text.lines().map(str::trim);
+            let status = lines.next().unwrap_or("").to_string();
+            let signer = lines.next().filter(|s| !s.is_empty()).map(str::to_string);
+            WorkerSignature {
+                signed: status == "Valid",
+                signer,
+                notarized: None,
+                detail: status,
+            }
+        }
+        Err(e) => WorkerSignature {
+            signed: false,
+            signer: None,
+            notarized: None,
+            detail: format!("could not run powershell: {}", e),
+        },
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) fn check(_path: &Path) -> WorkerSignature {
+    WorkerSignature {
+        signed: false,
+        signer: None,
+        notarized: None,
+        detail: "code signatures are not checked on this platform".to_string(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn validate(path: &Path) -> SignatureStatus {
+    let verify = Command::new("codesign")
+        .args(["--verify", "--deep", "--strict"])
+        .arg(path)
+        .output();
+    match verify {
+        Ok(out) if out.status.success() => SignatureStatus { valid: true, signer: codesign_signer(path), reason: None },
+        Ok(out) => {
+            let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+            let reason = if stderr.is_empty() { format!("codesign exited with {}", out.status) } else { stderr };
+            SignatureStatus { valid: false, signer: None, reason: Some(reason) }
+        }
+        Err(e) => SignatureStatus { valid: false, signer: None, reason: Some(format!("could not run codesign: {}", e)) },
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn validate(path: &Path) -> SignatureStatus {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::{HRESULT, PCWSTR};
+    use windows::Win32::Foundation::{HWND, TRUST_E_NOSIGNATURE};
+    use windows::Win32::Security::WinTrust::{
+        WinVerifyTrust, WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA, WINTRUST_DATA_0, WINTRUST_FILE_INFO,
+        WTD_CHOICE_FILE, WTD_REVOKE_NONE, WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut file = WINTRUST_FILE_INFO {
+        cbStruct: std::mem::size_of::<WINTRUST_FILE_INFO>() as u32,
+        pcwszFilePath: PCWSTR(wide.as_ptr()),
+        ..Default::default()
+    };
+    let mut data = WINTRUST_DATA {
+        cbStruct: std::mem::size_of::<WINTRUST_DATA>() as u32,
+        dwUIChoice: WTD_UI_NONE,
+        fdwRevocationChecks: WTD_REVOKE_NONE,
+        dwUnionChoice: WTD_CHOICE_FILE,
+        Anonymous: WINTRUST_DATA_0 { pFile: &mut file },
+        dwStateAction: WTD_STATEACTION_VERIFY,
+        ..Default::default()
+    };
+    let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+    // SAFETY: `data` points at `file`, whose path buffer `wide` outlives
+    // both calls; the state the first call opens is closed by the second.
+    let status = unsafe { WinVerifyTrust(HWND::default(), &mut action, &mut data as *mut WINTRUST_DATA as *mut _) };
+    let signer = if status == 0 { unsafe { windows_signer(data.hWVTStateData) } } else { None };
+    data.dwStateAction = WTD_STATEACTION_CLOSE;
+    unsafe { WinVerifyTrust(HWND::default(), &mut action, &mut data as *mut WINTRUST_DATA as *mut _) };
+
+    let reason = match HRESULT(status) {
+        HRESULT(0) => None,
+        TRUST_E_NOSIGNATURE => Some("not signed".to_string()),
+        other => Some(format!("{} (0x{:08X})", other.message(), other.0 as u32)),
+    };
+    SignatureStatus { valid: status == 0, signer, reason }
+}
+
+/// Subject of the leaf certificate behind a successful `WinVerifyTrust`.
+///
+/// # Safety
+/// `state` must be the open state data of a successful verification.
+#[cfg(target_os = "windows")]
+unsafe fn windows_signer(state: windows::Win32::Foundation::HANDLE) -> Option<String> {
+    use windows::Win32::Security::Cryptography::{CertGetNameStringW, CERT_NAME_SIMPLE_DISPLAY_TYPE};
+    use windows::Win32::Security::WinTrust::{WTHelperGetProvSignerFromChain, WTHelperProvDataFromStateData};
+
+    unsafe {
+        let provider = WTHelperProvDataFromStateData(state);
+        if provider.is_null() {
+            return None;
+        }
+        let signer = WTHelperGetProvSignerFromChain(provider, 0, false, 0);
+        if signer.is_null() || (*signer).csCertChain == 0 {
+            return None;
+        }
+        let cert = (*(*signer).pasCertChain).pCert;
+        let len = CertGetNameStringW(cert, CERT_NAME_SIMPLE_DISPLAY_TYPE, 0, None, None) as usize;
+        if len <= 1 {
+            return None;
+        }
+        let mut name = vec![0u16; len];
+        CertGetNameStringW(cert, CERT_NAME_SIMPLE_DISPLAY_TYPE, 0, None, Some(&mut name));
+        Some(String::from_utf16_lossy(&name[..len - 1]))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) fn validate(_path: &Path) -> SignatureStatus {
+    SignatureStatus {
+        valid: false,
+        signer: None,
+        reason: Some("code signatures are not checked on this platform".to_string()),
+    }
+}
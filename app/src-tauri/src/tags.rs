@@ -0,0 +1,149 @@
+//! Tag and stream metadata read directly from audio files, so the library
+//! can show freshly dropped tracks without waiting on the worker's analysis
+//! queue.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use futures_util::stream::{self, StreamExt};
+use lofty::file::FileType;
+use lofty::prelude::*;
+use serde::Serialize;
+
+use crate::sanitize;
+
+/// Files read concurrently per `read_audio_tags` call.
+const MAX_PARALLEL_READS: usize = 8;
+/// Cached entries kept before the cache is cleared and refilled.
+const MAX_CACHED: usize = 20_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_secs: f64,
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    /// Audio bitrate in kbps.
+    pub bitrate_kbps: Option<u32>,
+}
+
+/// Outcome for one requested path; exactly one of `tags`/`error` is set.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct TagResult {
+    pub path: String,
+    pub tags: Option<AudioTags>,
+    pub error: Option<String>,
+}
+
+/// Size and mtime a cached entry was read at.
+type Stamp = (u64, u128);
+
+/// Managed state for `read_audio_tags`.
+#[derive(Clone, Default)]
+pub(crate) struct TagCache {
+    entries: Arc<Mutex<HashMap<PathBuf, (Stamp, AudioTags)>>>,
+}
+
+fn codec_name(file_type: FileType) -> String {
+    match file_type {
+        FileType::Mpeg => "mp3".to_string(),
+        FileType::Aiff | FileType::Wav => "pcm".to_string(),
+        FileType::Custom(name) => name.to_string(),
+        other => format!("{:?}", other).to_lowercase(),
+    }
+}
+
+fn stamp(path: &Path) -> Result<Stamp, String> {
+    let meta = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok((meta.len(), mtime))
+}
+
+pub(crate) fn read(path: &Path) -> Result<AudioTags, String> {
+    let file = lofty::read_from_path(path).map_err(|e| e.to_string())?;
+    let props = file.properties();
+    let tag = file.primary_tag().or_else(|| file.first_tag());
+    let text = |f: fn(&lofty::tag::Tag) -> Option<std::borrow::Cow<'_, str>>| {
+        tag.and_then(f).map(|s| sanitize::field(&s))
+    };
+    Ok(AudioTags {
+        title: text(|t| t.title()),
+        artist: text(|t| t.artist()),
+        album: text(|t| t.album()),
+        duration_secs: props.duration().as_secs_f64(),
+        codec: codec_name(file.file_type()),
+        sample_rate: props.sample_rate(),
+        bitrate_kbps: props.audio_bitrate().or(props.overall_bitrate()),
+    })
+}
+
+impl TagCache {
+    fn lookup(&self, path: &Path, stamp: Stamp) -> Option<AudioTags> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(path).filter(|(s, _)| *s == stamp).map(|(_, tags)| tags.clone())
+    }
+
+    fn store(&self, path: PathBuf, stamp: Stamp, tags: AudioTags) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_CACHED && !entries.contains_key(&path) {
+            entries.clear();
+        }
+        entries.insert(path, (stamp, tags));
+    }
+
+    /// Tags for one already-validated path, from the cache if the file's
+    /// size and mtime are unchanged.
+    async fn get(&self, path: PathBuf) -> Result<AudioTags, String> {
+        let cache = self.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let stamp = stamp(&path)?;
+            if let Some(tags) = cache.lookup(&path, stamp) {
+                return Ok(tags);
+            }
+            let tags = read(&path)?;
+            cache.store(path, stamp, tags.clone());
+            Ok(tags)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Read every path, in order. `validate` resolves a requested path or
+    /// rejects it; a failure only affects that path's result.
+    pub(crate) async fn read_all(
+        &self,
+        paths: Vec<String>,
+        validate: impl Fn(&str) -> Result<PathBuf, String>,
+    ) -> Vec<TagResult> {
+        let jobs: Vec<(String, Result<PathBuf, String>)> = paths
+            .into_iter()
+            .map(|path| {
+                let resolved = validate(&path);
+                (path, resolved)
+            })
+            .collect();
+        stream::iter(jobs)
+            .map(|(path, resolved)| async move {
+                let result = match resolved {
+                    Ok(resolved) => self.get(resolved).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(tags) => TagResult { path, tags: Some(tags), error: None },
+                    Err(e) => TagResult { path, tags: None, error: Some(e) },
+                }
+            })
+            .buffered(MAX_PARALLEL_READS)
+            .collect()
+            .await
+    }
+}
@@ -0,0 +1,677 @@
+//! Streaming file transfers between the app and the worker.
+//!
+//! Files are moved in `CHUNK_SIZE` pieces and never buffered whole. Before a
+//! chunk is read (from disk or from the socket) it reserves buffer space from
+//! a shared `TransferLimiter`, so the total across concurrent transfers stays
+//! under `WorkerConfig::max_transfer_buffer_bytes`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::Stream;
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::events::{EventBus, JobProgress, WorkerEvent};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Progress is reported at most once per this many bytes (plus at the end).
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Shared cap on in-flight transfer buffers, counted in KiB.
+pub(crate) struct TransferLimiter {
+    permits: Arc<Semaphore>,
+    max_kib: u32,
+}
+
+impl TransferLimiter {
+    pub(crate) fn new(max_bytes: u64) -> Self {
+        let max_kib = (max_bytes / 1024).max((CHUNK_SIZE / 1024) as u64).min(u32::MAX as u64) as u32;
+        TransferLimiter {
+            permits: Arc::new(Semaphore::new(max_kib as usize)),
+            max_kib,
+        }
+    }
+
+    /// Wait until `bytes` of buffer space is available.
+    async fn reserve(&self, bytes: usize) -> OwnedSemaphorePermit {
+        let kib = (bytes.div_ceil(1024) as u32).clamp(1, self.max_kib);
+        Arc::clone(&self.permits)
+            .acquire_many_owned(kib)
+            .await
+            .expect("transfer semaphore is never closed")
+    }
+}
+
+/// Byte-based progress for one transfer, throttled to `PROGRESS_STEP`.
+struct Progress {
+    job: String,
+    total: Option<u64>,
+    done: u64,
+    reported: u64,
+    bus: EventBus,
+}
+
+impl Progress {
+    fn new(job: String, total: Option<u64>, bus: EventBus) -> Self {
+        Progress { job, total, done: 0, reported: 0, bus }
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.done += n as u64;
+        if self.done - self.reported >= PROGRESS_STEP {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.send(None);
+    }
+
+    /// An update marking a pause or resume.
+    fn report_state(&mut self, state: &'static str) {
+        self.send(Some(state));
+    }
+
+    fn send(&mut self, state: Option<&'static str>) {
+        self.reported = self.done;
+        self.bus.send(WorkerEvent::Progress(JobProgress {
+            job: self.job.clone(),
+            done: self.done,
+            total: self.total.unwrap_or(self.done),
+            state,
+        }));
+    }
+}
+
+struct UploadState {
+    file: tokio::fs::File,
+    limiter: Arc<TransferLimiter>,
+    permit: Option<OwnedSemaphorePermit>,
+    progress: Progress,
+    finished: bool,
+}
+
+/// Stream `file` in fixed-size chunks. Each chunk's reservation is held
+/// until the HTTP body asks for the next one, i.e. until it has been sent.
+fn upload_stream(state: UploadState) -> impl Stream<Item = std::io::Result<Bytes>> {
+    futures_util::stream::unfold(state, |mut st| async move {
+        st.permit = None;
+        if st.finished {
+            return None;
+        }
+        st.permit = Some(st.limiter.reserve(CHUNK_SIZE).await);
+        let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+        match st.file.read_buf(&mut buf).await {
+            Ok(0) => {
+                st.progress.report();
+                None
+            }
+            Ok(n) => {
+                st.progress.advance(n);
+                Some((Ok(buf.freeze()), st))
+            }
+            Err(e) => {
+                st.finished = true;
+                Some((Err(e), st))
+            }
+        }
+    })
+}
+
+/// Upload `path` to the worker's `/upload` endpoint as multipart form data.
+pub(crate) async fn upload(
+    client: &reqwest::Client,
+    port: u16,
+    path: &Path,
+    limiter: Arc<TransferLimiter>,
+    bus: EventBus,
+) -> Result<serde_json::Value, String> {
+    let file = tokio::fs::File::open(path).await.map_err(|e| format!("{}: {}", path.display(), e))?;
+    let len = file.metadata().await.map_err(|e| e.to_string())?.len();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "upload".to_string());
+
+    let state = UploadState {
+        file,
+        limiter,
+        permit: None,
+        progress: Progress::new(format!("upload:{}", name), Some(len), bus),
+        finished: false,
+    };
+    let body = reqwest::Body::wrap_stream(upload_stream(state));
+    let part = reqwest::multipart::Part::stream_with_length(body, len).file_name(name);
+    let form = reqwest::multipart::Form::new().part("file", part);
+
+    let resp = client
+        .post(format!("http://127.0.0.1:{}/upload", port))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("worker returned {}: {}", status, text.trim()));
+    }
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// Where a paused or interrupted download picks up.
+#[derive(Debug, Clone)]
+pub(crate) struct Resume {
+    /// Bytes already in the `.part` file.
+    pub offset: u64,
+    pub total: Option<u64>,
+    /// `ETag` (or `Last-Modified`) of the first response, sent as `If-Range`
+    /// so a file changed in between is fetched again from the start.
+    pub validator: Option<String>,
+}
+
+/// Asked of a running download; checked between chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// How a download call ended, other than failing outright.
+#[derive(Debug, Clone)]
+pub(crate) enum Stopped {
+    /// Bytes in the finished file.
+    Completed(u64),
+    Paused(Resume),
+    Cancelled,
+    /// The connection broke (e.g. the worker restarted); the `.part` file
+    /// is kept for `resume_download`.
+    Interrupted { error: String, resume: Resume },
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut part_name = dest.as_os_str().to_owned();
+    part_name.push(".part");
+    PathBuf::from(part_name)
+}
+
+fn header(resp: &reqwest::Response, name: reqwest::header::HeaderName) -> Option<String> {
+    resp.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+}
+
+/// The `Content-Range` of a 206 answers `resume`: it starts at the offset
+/// and the file is still the same length.
+fn range_matches(resp: &reqwest::Response, resume: &Resume) -> bool {
+    let Some(range) = header(resp, reqwest::header::CONTENT_RANGE) else { return false };
+    let Some((span, total)) = range.strip_prefix("bytes ").and_then(|r| r.split_once('/')) else { return false };
+    let start = span.split_once('-').and_then(|(start, _)| start.parse::<u64>().ok());
+    start == Some(resume.offset) && (resume.total.is_none() || total.parse::<u64>().ok() == resume.total)
+}
+
+async fn get(client: &reqwest::Client, url: &reqwest::Url, resume: Option<&Resume>) -> Result<reqwest::Response, String> {
+    let mut request = client.get(url.clone());
+    if let Some(resume) = resume {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume.offset));
+        if let Some(validator) = &resume.validator {
+            request = request.header(reqwest::header::IF_RANGE, validator);
+        }
+    }
+    let resp = request.send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("worker returned {}: {}", status, text.trim()));
+    }
+    Ok(resp)
+}
+
+/// Download `source` (a path on the worker side, served by `/files/serve`)
+/// into `dest`. Data goes to `<dest>.part` and is renamed on completion.
+///
+/// With `resume`, only the rest is requested (`Range`). If the worker
+/// answers with anything but a matching 206 (same validator, same start
+/// and length) the file is fetched again from zero. `control` is checked
+/// between chunks; pausing keeps the `.part` file, cancelling removes it.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn download(
+    client: &reqwest::Client,
+    port: u16,
+    source: &str,
+    dest: &Path,
+    resume: Option<Resume>,
+    control: tokio::sync::watch::Receiver<Control>,
+    limiter: Arc<TransferLimiter>,
+    bus: EventBus,
+) -> Result<Stopped, String> {
+    let mut url = reqwest::Url::parse(&format!("http://127.0.0.1:{}/files/serve", port))
+        .expect("static worker URL is valid");
+    url.query_pairs_mut().append_pair("path", source);
+    let part = part_path(dest);
+
+    let mut resp = get(client, &url, resume.as_ref()).await?;
+    let validator = header(&resp, reqwest::header::ETAG).or_else(|| header(&resp, reqwest::header::LAST_MODIFIED));
+    let resumed = match &resume {
+        Some(r) if resp.status() == reqwest::StatusCode::PARTIAL_CONTENT && validator == r.validator && range_matches(&resp, r) => {
+            Some(r.clone())
+        }
+        Some(_) => {
+            eprintln!("[djbot] worker did not resume {}; downloading it again from the start", source);
+            if resp.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+                resp = get(client, &url, None).await?;
+            }
+            None
+        }
+        None => None,
+    };
+
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (out, total) = match &resumed {
+        Some(r) => {
+            let out = tokio::fs::OpenOptions::new().append(true).open(&part).await;
+            (out, r.total)
+        }
+        None => (tokio::fs::File::create(&part).await, resp.content_length()),
+    };
+    let mut out = out.map_err(|e| format!("{}: {}", part.display(), e))?;
+    let mut progress = Progress::new(format!("download:{}", name), total, bus);
+    if let Some(r) = &resumed {
+        progress.done = r.offset;
+        progress.report_state("resumed");
+    }
+
+    enum End {
+        Done,
+        Asked(Control),
+        /// Reading from the worker failed; writing failures are `Err`.
+        Broken(String),
+    }
+    let result = async {
+        loop {
+            let asked = *control.borrow();
+            if asked != Control::Run {
+                return Ok(End::Asked(asked));
+            }
+            // Reserve before pulling so back-pressure reaches the socket.
+            let _permit = limiter.reserve(CHUNK_SIZE).await;
+            let chunk = match resp.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => return Ok(End::Done),
+                Err(e) => return Ok(End::Broken(e.to_string())),
+            };
+            out.write_all(&chunk).await.map_err(|e| e.to_string())?;
+            progress.advance(chunk.len());
+        }
+    }
+    .await;
+    let flushed = out.flush().await.map_err(|e| e.to_string());
+    drop(out);
+
+    let here = Resume { offset: progress.done, total, validator };
+    match result.and_then(|end| flushed.map(|()| end)) {
+        Ok(End::Done) => {
+            tokio::fs::rename(&part, dest).await.map_err(|e| e.to_string())?;
+            progress.report();
+            Ok(Stopped::Completed(progress.done))
+        }
+        Ok(End::Asked(Control::Pause)) => {
+            progress.report_state("paused");
+            Ok(Stopped::Paused(here))
+        }
+        Ok(End::Broken(error)) if here.validator.is_some() => Ok(Stopped::Interrupted { error, resume: here }),
+        Ok(End::Asked(_)) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            Ok(Stopped::Cancelled)
+        }
+        Ok(End::Broken(e)) | Err(e) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            Err(e)
+        }
+    }
+}
+
+/// Remove what a paused download left behind.
+pub(crate) async fn discard_part(dest: &Path) {
+    let _ = tokio::fs::remove_file(part_path(dest)).await;
+}
+
+/// Snapshot of a registered download, for `list_downloads`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DownloadInfo {
+    pub id: String,
+    pub source: String,
+    pub dest: PathBuf,
+    /// "running" or "paused".
+    pub state: &'static str,
+    /// Bytes received so far; only known while paused.
+    pub done: Option<u64>,
+    pub total: Option<u64>,
+}
+
+struct DownloadOp {
+    source: String,
+    dest: PathBuf,
+    control: tokio::sync::watch::Sender<Control>,
+    /// `Some` while paused.
+    resume: Option<Resume>,
+}
+
+/// Managed state: downloads from the worker that can be paused, resumed
+/// and cancelled by id. Kept in the app, so a paused download survives a
+/// worker restart and resumes against the new worker as long as the file
+/// it serves is unchanged.
+#[derive(Default)]
+pub(crate) struct Downloads {
+    ops: Mutex<HashMap<String, DownloadOp>>,
+}
+
+impl Downloads {
+    /// Register a new running download.
+    pub(crate) fn start(
+        &self,
+        id: &str,
+        source: &str,
+        dest: &Path,
+    ) -> Result<tokio::sync::watch::Receiver<Control>, String> {
+        let mut ops = self.ops.lock().unwrap();
+        if ops.contains_key(id) {
+            return Err(format!("download {} already exists", id));
+        }
+        let (control, rx) = tokio::sync::watch::channel(Control::Run);
+        ops.insert(id.to_string(), DownloadOp { source: source.to_string(), dest: dest.to_path_buf(), control, resume: None });
+        Ok(rx)
+    }
+
+    pub(crate) fn pause(&self, id: &str) -> Result<(), String> {
+        match self.ops.lock().unwrap().get(id) {
+            Some(op) if op.resume.is_none() => {
+                op.control.send_replace(Control::Pause);
+                Ok(())
+            }
+            Some(_) => Err(format!("download {} is already paused", id)),
+            None => Err(format!("no download {}", id)),
+        }
+    }
+
+    /// Mark a paused download running again: its source, destination,
+    /// where to resume from and the control channel for the new run.
+    pub(crate) fn resume(
+        &self,
+        id: &str,
+    ) -> Result<(String, PathBuf, Resume, tokio::sync::watch::Receiver<Control>), String> {
+        let mut ops = self.ops.lock().unwrap();
+        let op = ops.get_mut(id).ok_or_else(|| format!("no download {}", id))?;
+        let resume = op.resume.take().ok_or_else(|| format!("download {} is not paused", id))?;
+        op.control.send_replace(Control::Run);
+        Ok((op.source.clone(), op.dest.clone(), resume, op.control.subscribe()))
+    }
+
+    /// Stop a running download or drop a paused one. Returns the
+    /// destination whose `.part` file the caller should remove when the
+    /// download was paused (a running one removes its own).
+    pub(crate) fn cancel(&self, id: &str) -> Result<Option<PathBuf>, String> {
+        let mut ops = self.ops.lock().unwrap();
+        let op = ops.get(id).ok_or_else(|| format!("no download {}", id))?;
+        if op.resume.is_none() {
+            op.control.send_replace(Control::Cancel);
+            return Ok(None);
+        }
+        Ok(ops.remove(id).map(|op| op.dest))
+    }
+
+    /// Record how a run of `id` ended: paused and interrupted downloads
+    /// stay registered, anything else is forgotten.
+    pub(crate) fn finish(&self, id: &str, result: &Result<Stopped, String>) {
+        let mut ops = self.ops.lock().unwrap();
+        match result {
+            Ok(Stopped::Paused(resume)) | Ok(Stopped::Interrupted { resume, .. }) => {
+                if let Some(op) = ops.get_mut(id) {
+                    op.resume = Some(resume.clone());
+                }
+            }
+            _ => {
+                ops.remove(id);
+            }
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<DownloadInfo> {
+        let mut list: Vec<DownloadInfo> = self
+            .ops
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, op)| DownloadInfo {
+                id: id.clone(),
+                source: op.source.clone(),
+                dest: op.dest.clone(),
+                state: if op.resume.is_some() { "paused" } else { "running" },
+                done: op.resume.as_ref().map(|r| r.offset),
+                total: op.resume.as_ref().and_then(|r| r.total),
+            })
+            .collect();
+        list.sort_by(|a, b| a.id.cmp(&b.id));
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    const LEN: u64 = 1024 * 1024 * 1024;
+    /// In-flight buffer cap given to the limiter.
+    const MAX_BUFFER: u64 = 1024 * 1024;
+    /// Allowed RSS growth during a transfer: far below `LEN`, with room for
+    /// tests running alongside.
+    const MAX_GROWTH_KIB: u64 = 128 * 1024;
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("djbot-transfer-{}-{}", name, std::process::id()))
+    }
+
+    /// A 1 GiB file that takes no disk space.
+    fn sparse_file(name: &str) -> PathBuf {
+        let path = temp(name);
+        std::fs::File::create(&path).unwrap().set_len(LEN).unwrap();
+        path
+    }
+
+    fn rss_kib() -> Option<u64> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+        line.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    /// Samples RSS until dropped; `peak_growth` is the most it rose above the
+    /// level at creation.
+    struct RssWatch {
+        base: u64,
+        peak: Arc<AtomicU64>,
+        stop: Arc<AtomicBool>,
+    }
+
+    impl RssWatch {
+        fn start() -> Option<Self> {
+            let base = rss_kib()?;
+            let peak = Arc::new(AtomicU64::new(base));
+            let stop = Arc::new(AtomicBool::new(false));
+            let (p, s) = (Arc::clone(&peak), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                while !s.load(Ordering::Relaxed) {
+                    p.fetch_max(rss_kib().unwrap_or(0), Ordering::Relaxed);
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+            });
+            Some(RssWatch { base, peak, stop })
+        }
+
+        fn peak_growth(&self) -> u64 {
+            self.peak.load(Ordering::Relaxed).saturating_sub(self.base)
+        }
+    }
+
+    impl Drop for RssWatch {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Accept one request on a local port and hand its head and the rest of
+    /// the stream to `respond`.
+    fn stub_server(
+        respond: impl FnOnce(&str, &mut BufReader<TcpStream>) + Send + 'static,
+    ) -> (u16, std::thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let thread = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                assert_ne!(reader.read_line(&mut head).unwrap(), 0, "request head cut short");
+            }
+            respond(&head, &mut reader);
+        });
+        (port, thread)
+    }
+
+    fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    /// Read and discard a request body, returning its length.
+    fn drain_body(head: &str, reader: &mut BufReader<TcpStream>) -> u64 {
+        if let Some(len) = header_value(head, "content-length") {
+            let len: u64 = len.parse().unwrap();
+            assert_eq!(std::io::copy(&mut reader.take(len), &mut std::io::sink()).unwrap(), len);
+            return len;
+        }
+        assert_eq!(header_value(head, "transfer-encoding"), Some("chunked"));
+        let mut total = 0;
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size).unwrap();
+            let size = u64::from_str_radix(size.trim(), 16).unwrap();
+            std::io::copy(&mut reader.take(size + 2), &mut std::io::sink()).unwrap();
+            if size == 0 {
+                return total;
+            }
+            total += size;
+        }
+    }
+
+    fn collect_progress(mut rx: tokio::sync::mpsc::Receiver<WorkerEvent>) -> std::thread::JoinHandle<Vec<JobProgress>> {
+        std::thread::spawn(move || {
+            let mut updates = Vec::new();
+            while let Some(event) = rx.blocking_recv() {
+                if let WorkerEvent::Progress(p) = event {
+                    updates.push(p);
+                }
+            }
+            updates
+        })
+    }
+
+    /// Updates come once per `PROGRESS_STEP` or a chunk more (the socket
+    /// may hand over more than `CHUNK_SIZE` at a time) and end at the full
+    /// length.
+    fn assert_progress(updates: &[JobProgress], kind: &str, path: &Path) {
+        let job = format!("{}:{}", kind, path.file_name().unwrap().to_string_lossy());
+        assert!(updates.iter().all(|p| p.job == job && p.total == LEN));
+        assert_eq!(updates.last().map(|p| p.done), Some(LEN));
+        let gaps: Vec<u64> = updates.windows(2).map(|w| w[1].done - w[0].done).collect();
+        let (last, steps) = gaps.split_last().unwrap();
+        assert!(steps.iter().all(|gap| (PROGRESS_STEP..2 * PROGRESS_STEP).contains(gap)), "{:?}", steps);
+        assert!(*last < 2 * PROGRESS_STEP);
+    }
+
+    #[test]
+    fn upload_of_a_1_gib_file_streams_in_bounded_memory() {
+        let source = sparse_file("upload.bin");
+        let (port, server) = stub_server(|head, reader| {
+            assert!(head.starts_with("POST /upload "));
+            let received = drain_body(head, reader);
+            let body = format!("{{\"received\":{}}}", received);
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        let (bus, rx) = EventBus::detached();
+        let updates = collect_progress(rx);
+        let limiter = Arc::new(TransferLimiter::new(MAX_BUFFER));
+
+        let watch = RssWatch::start();
+        let reply = tauri::async_runtime::block_on(upload(&reqwest::Client::new(), port, &source, limiter, bus)).unwrap();
+        server.join().unwrap();
+        if let Some(watch) = watch {
+            assert!(watch.peak_growth() < MAX_GROWTH_KIB, "RSS grew by {} KiB", watch.peak_growth());
+        }
+        // The multipart framing comes on top of the file itself.
+        assert!(reply["received"].as_u64().unwrap() > LEN);
+        assert_progress(&updates.join().unwrap(), "upload", &source);
+        std::fs::remove_file(source).unwrap();
+    }
+
+    #[test]
+    fn download_of_a_1_gib_file_streams_in_bounded_memory() {
+        let served = sparse_file("served.bin");
+        let dest = temp("download.bin");
+        let file = served.clone();
+        let (port, server) = stub_server(move |head, reader| {
+            assert!(head.starts_with("GET /files/serve?path="));
+            let stream = reader.get_mut();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+                LEN
+            )
+            .unwrap();
+            std::io::copy(&mut std::fs::File::open(file).unwrap(), stream).unwrap();
+            stream.flush().unwrap();
+        });
+        let (bus, rx) = EventBus::detached();
+        let updates = collect_progress(rx);
+        let limiter = Arc::new(TransferLimiter::new(MAX_BUFFER));
+        let (_control, control_rx) = tokio::sync::watch::channel(Control::Run);
+
+        let watch = RssWatch::start();
+        let stopped = tauri::async_runtime::block_on(download(
+            &reqwest::Client::new(),
+            port,
+            "served.bin",
+            &dest,
+            None,
+            control_rx,
+            limiter,
+            bus,
+        ))
+        .unwrap();
+        server.join().unwrap();
+        if let Some(watch) = watch {
+            assert!(watch.peak_growth() < MAX_GROWTH_KIB, "RSS grew by {} KiB", watch.peak_growth());
+        }
+        assert!(matches!(stopped, Stopped::Completed(LEN)));
+        assert_eq!(std::fs::metadata(&dest).unwrap().len(), LEN);
+        assert!(!part_path(&dest).exists());
+        assert_progress(&updates.join().unwrap(), "download", &dest);
+        std::fs::remove_file(served).unwrap();
+        std::fs::remove_file(dest).unwrap();
+    }
+}
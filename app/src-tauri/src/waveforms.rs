@@ -0,0 +1,129 @@
+//! On-disk cache of the waveform thumbnails the library view shows for
+//! every visible row.
+//!
+//! Thumbnails live in `{data_dir}/cache/waveforms/<key>.png`, where the key
+//! hashes the track path, its size and mtime and the requested width, so
+//! editing or replacing a track invalidates its entry. Misses are rendered
+//! by the worker (`GET /waveform`); concurrent requests for the same missing
+//! thumbnail share one upstream fetch. The directory counts towards the
+//! shared `CacheBudget`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use sha2::{Digest, Sha256};
+
+use crate::cache::CacheBudget;
+use crate::proxy;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Zero-length IEND chunk every complete PNG ends with.
+const PNG_TRAILER: &[u8] = b"\0\0\0\0IEND\xae\x42\x60\x82";
+
+type Fetch = Shared<BoxFuture<'static, Result<PathBuf, String>>>;
+
+/// Managed state for `get_waveform`.
+pub(crate) struct WaveformCache {
+    dir: PathBuf,
+    budget: Arc<CacheBudget>,
+    inflight: Arc<Mutex<HashMap<String, Fetch>>>,
+}
+
+/// Cheap completeness check; anything else is treated as corrupt.
+fn is_complete_png(data: &[u8]) -> bool {
+    data.starts_with(PNG_SIGNATURE) && data.ends_with(PNG_TRAILER)
+}
+
+fn cache_key(track: &Path, width: u32) -> Result<String, String> {
+    let meta = std::fs::metadata(track).map_err(|e| format!("{}: {}", track.display(), e))?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut hasher = Sha256::new();
+    hasher.update(track.to_string_lossy().as_bytes());
+    hasher.update(format!("\0{}\0{}\0{}", meta.len(), mtime, width).as_bytes());
+    let digest = hasher.finalize();
+    Ok(digest[..16].iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+async fn fetch(
+    client: reqwest::Client,
+    port: u16,
+    track: String,
+    width: u32,
+    dest: PathBuf,
+    budget: Arc<CacheBudget>,
+) -> Result<PathBuf, String> {
+    let width_str = width.to_string();
+    let data = proxy::fetch_bytes(&client, port, "waveform", &[("path", &track), ("width", &width_str)]).await?;
+    if !is_complete_png(&data) {
+        return Err(format!("worker returned an invalid waveform image for {}", track));
+    }
+    let tmp = dest.with_extension("part");
+    tokio::fs::write(&tmp, &data).await.map_err(|e| format!("{}: {}", tmp.display(), e))?;
+    tokio::fs::rename(&tmp, &dest).await.map_err(|e| format!("{}: {}", dest.display(), e))?;
+    let _ = tauri::async_runtime::spawn_blocking(move || budget.enforce()).await;
+    Ok(dest)
+}
+
+impl WaveformCache {
+    pub(crate) fn new(dir: PathBuf, budget: Arc<CacheBudget>) -> Self {
+        WaveformCache {
+            dir,
+            budget,
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Path of the cached thumbnail for `track` at `width` pixels. On a miss
+    /// `worker` supplies the client and port to fetch it with, so hits work
+    /// while the worker is down.
+    pub(crate) async fn get(
+        &self,
+        track: String,
+        width: u32,
+        worker: impl FnOnce() -> Result<(reqwest::Client, u16), String>,
+    ) -> Result<PathBuf, String> {
+        let key = cache_key(Path::new(&track), width)?;
+        let path = self.dir.join(format!("{}.png", key));
+
+        match tokio::fs::read(&path).await {
+            Ok(data) if is_complete_png(&data) => {
+                CacheBudget::touch(&path);
+                return Ok(path);
+            }
+            Ok(_) => {
+                eprintln!("[djbot] discarding corrupt waveform {}", path.display());
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+            Err(_) => {}
+        }
+
+        let (client, port) = worker()?;
+        let shared = {
+            let mut inflight = self.inflight.lock().unwrap();
+            inflight
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let inflight = Arc::clone(&self.inflight);
+                    let key = key.clone();
+                    let fut = fetch(client, port, track, width, path, Arc::clone(&self.budget));
+                    async move {
+                        let result = fut.await;
+                        inflight.lock().unwrap().remove(&key);
+                        result
+                    }
+                    .boxed()
+                    .shared()
+                })
+                .clone()
+        };
+        shared.await
+    }
+}
@@ -0,0 +1,199 @@
+//! Opt-in webhook notifications when a worker job finishes.
+//!
+//! Each `job-finished` event that passes the configured filter is POSTed
+//! as JSON to the webhook URL, signed with HMAC-SHA256 of the body under
+//! the configured secret (`X-Djbot-Signature: sha256=<hex>`). Failed
+//! deliveries are retried with backoff; the outcome of each delivery is
+//! kept in memory for `get_webhook_deliveries`. Everything happens on a
+//! background task, so a slow or broken endpoint never holds up a job.
+//!
+//! The URL and secret are stored sealed (`secrets`), and the URL goes
+//! through `url_guard` before every delivery, not only when it is set.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::config::{DjbotConfig, WEBHOOK_SECRET_FIELD, WEBHOOK_URL_FIELD};
+use crate::{secrets, url_guard};
+
+/// Retries after the first attempt, waiting 1s, 2s, 4s.
+const MAX_RETRIES: u32 = 3;
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries kept for `get_webhook_deliveries`.
+const HISTORY_LEN: usize = 50;
+
+/// Payload of the `job-finished` event, and the webhook body.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct JobFinished {
+    pub job_id: String,
+    pub kind: String,
+    /// "succeeded", "failed" or "timed_out".
+    pub status: &'static str,
+    pub output_file: Option<String>,
+    pub duration_ms: u64,
+    /// Unix seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct WebhookDelivery {
+    pub job_id: String,
+    pub status: &'static str,
+    /// Host the webhook was sent to; the full URL may hold a token.
+    pub host: String,
+    pub attempts: u32,
+    pub delivered: bool,
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+    /// Unix seconds of the last attempt.
+    pub at: u64,
+}
+
+/// Managed state.
+#[derive(Clone)]
+pub(crate) struct Webhooks {
+    data_dir: PathBuf,
+    client: reqwest::Client,
+    history: Arc<Mutex<VecDeque<WebhookDelivery>>>,
+}
+
+struct Target {
+    url: reqwest::Url,
+    secret: Option<String>,
+    allow_private: bool,
+}
+
+impl Webhooks {
+    pub(crate) fn new(data_dir: &Path) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            // A redirect could point anywhere; url_guard only saw the first hop.
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Webhooks { data_dir: data_dir.to_path_buf(), client, history: Arc::default() })
+    }
+
+    pub(crate) fn deliveries(&self) -> Vec<WebhookDelivery> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The configured target for `job`, `None` if webhooks are off or the
+    /// job's status is filtered out.
+    fn target(&self, job: &JobFinished) -> Result<Option<Target>, String> {
+        let config = DjbotConfig::load(&self.data_dir);
+        let hook = config.webhook;
+        if !hook.enabled || !(hook.events.is_empty() || hook.events.iter().any(|e| e == job.status)) {
+            return Ok(None);
+        }
+        let Some(sealed) = hook.url else { return Ok(None) };
+        let url = secrets::open(&self.data_dir, WEBHOOK_URL_FIELD, &sealed).map_err(|e| e.to_string())?;
+        let secret = match hook.secret {
+            Some(s) => Some(secrets::open(&self.data_dir, WEBHOOK_SECRET_FIELD, &s).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        let url = reqwest::Url::parse(&url).map_err(|e| e.to_string())?;
+        Ok(Some(Target { url, secret, allow_private: config.worker.allow_private_urls }))
+    }
+
+    /// Send `job` to the webhook in the background, if one is configured.
+    pub(crate) fn dispatch(&self, job: JobFinished) {
+        let hooks = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let target = match hooks.target(&job) {
+                Ok(Some(target)) => target,
+                Ok(None) => return,
+                Err(e) => {
+                    eprintln!("[djbot] webhook not sent for {}: {}", job.job_id, e);
+                    return;
+                }
+            };
+            let delivery = hooks.deliver(&job, &target).await;
+            if !delivery.delivered {
+                eprintln!(
+                    "[djbot] webhook for {} failed after {} attempt(s): {}",
+                    job.job_id,
+                    delivery.attempts,
+                    delivery.error.as_deref().unwrap_or("unknown error")
+                );
+            }
+            let mut history = hooks.history.lock().unwrap();
+            if history.len() == HISTORY_LEN {
+                history.pop_front();
+            }
+            history.push_back(delivery);
+        });
+    }
+
+    async fn deliver(&self, job: &JobFinished, target: &Target) -> WebhookDelivery {
+        let mut delivery = WebhookDelivery {
+            job_id: job.job_id.clone(),
+            status: job.status,
+            host: target.url.host_str().unwrap_or_default().to_string(),
+            attempts: 0,
+            delivered: false,
+            http_status: None,
+            error: None,
+            at: crate::jobs::unix_now(),
+        };
+        let body = match serde_json::to_vec(job) {
+            Ok(body) => body,
+            Err(e) => {
+                delivery.error = Some(e.to_string());
+                return delivery;
+            }
+        };
+        let mut backoff = FIRST_BACKOFF;
+        loop {
+            delivery.attempts += 1;
+            delivery.at = crate::jobs::unix_now();
+            match self.post(target, &body).await {
+                Ok(status) if status.is_success() => {
+                    delivery.delivered = true;
+                    delivery.http_status = Some(status.as_u16());
+                    delivery.error = None;
+                    return delivery;
+                }
+                Ok(status) => {
+                    delivery.http_status = Some(status.as_u16());
+                    delivery.error = Some(format!("endpoint returned {}", status));
+                }
+                Err(e) => delivery.error = Some(e),
+            }
+            if delivery.attempts > MAX_RETRIES {
+                return delivery;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    async fn post(&self, target: &Target, body: &[u8]) -> Result<reqwest::StatusCode, String> {
+        url_guard::validate(target.url.as_str(), target.allow_private).await?;
+        let mut request = self
+            .client
+            .post(target.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(secret) = &target.secret {
+            request = request.header("X-Djbot-Signature", format!("sha256={}", sign(secret, body)));
+        }
+        let resp = request.send().await.map_err(|e| e.to_string())?;
+        Ok(resp.status())
+    }
+}
+
+/// HMAC-SHA256 of `body` under `secret`, lowercase hex.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
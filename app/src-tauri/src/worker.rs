@@ -0,0 +1,392 @@
+//! Construction of the Go worker process.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::process::{Command, Stdio};
+
+use crate::config::{self, WorkerConfig, DOWNLOAD_PROXY_FIELD};
+use crate::secrets;
+
+/// Variables that let the parent environment inject code into, or change the
+/// runtime behaviour of, the worker. They are never passed through.
+const BLOCKED_ENV: &[&str] = &[
+    "LD_PRELOAD",
+    "LD_AUDIT",
+    "GODEBUG",
+    "GOTRACEBACK",
+];
+const BLOCKED_ENV_PREFIXES: &[&str] = &["DYLD_"];
+
+/// Filter `vars` down to the environment the worker is allowed to inherit.
+pub(crate) fn sanitize_env(vars: impl IntoIterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    vars.into_iter()
+        .filter(|(k, _)| {
+            let upper = k.to_ascii_uppercase();
+            !BLOCKED_ENV.contains(&upper.as_str())
+                && !BLOCKED_ENV_PREFIXES.iter().any(|p| upper.starts_with(p))
+        })
+        .collect()
+}
+
+/// The environment the worker is started with: ours, minus non-UTF-8
+/// variables (which can't be inspected) and whatever `sanitize_env` blocks.
+pub(crate) fn worker_env() -> BTreeMap<String, String> {
+    let inherited = std::env::vars_os()
+        .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)));
+    sanitize_env(inherited)
+}
+
+/// Key fragments whose values are hidden in `get_worker_env_snapshot`.
+const REDACTED_KEY_PARTS: &[&str] = &["KEY", "SECRET", "TOKEN", "PASSWORD", "PROXY"];
+
+/// `env` with the values of credential-looking variables replaced.
+pub(crate) fn redact_env(env: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    env.into_iter()
+        .map(|(k, v)| {
+            let upper = k.to_ascii_uppercase();
+            if REDACTED_KEY_PARTS.iter().any(|part| upper.contains(part)) {
+                (k, "<redacted>".to_string())
+            } else {
+                (k, v)
+            }
+        })
+        .collect()
+}
+
+/// Usual locations of the system CA bundle. Windows has none; Go reads the
+/// certificate store there directly.
+const SYSTEM_CA_BUNDLES: &[&str] = &[
+    #[cfg(target_os = "linux")]
+    "/etc/ssl/certs/ca-certificates.crt",
+    #[cfg(target_os = "linux")]
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    #[cfg(target_os = "linux")]
+    "/etc/pki/ca-trust/extracted/pem/tls-ca-bundle.pem",
+    #[cfg(target_os = "linux")]
+    "/etc/ssl/ca-bundle.pem",
+    #[cfg(target_os = "macos")]
+    "/etc/ssl/cert.pem",
+    #[cfg(target_os = "macos")]
+    "/usr/local/etc/openssl/cert.pem",
+];
+
+/// A CA bundle must be a readable file holding at least one PEM certificate.
+pub(crate) fn validate_ca_bundle(path: &std::path::Path) -> Result<(), String> {
+    let pem = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if !pem.contains("-----BEGIN CERTIFICATE-----") {
+        return Err(format!("{} contains no PEM certificates", path.display()));
+    }
+    Ok(())
+}
+
+/// The bundle to hand the worker: the configured one, else nothing if the
+/// environment already sets `SSL_CERT_FILE`, else the first usable system
+/// bundle.
+fn ca_bundle(config: &WorkerConfig, env: &BTreeMap<String, String>) -> Option<std::path::PathBuf> {
+    if let Some(path) = &config.ca_bundle {
+        return match validate_ca_bundle(path) {
+            Ok(()) => Some(path.clone()),
+            Err(e) => {
+                eprintln!("[djbot] ignoring ca_bundle: {}", e);
+                None
+            }
+        };
+    }
+    if env.contains_key("SSL_CERT_FILE") {
+        return None;
+    }
+    SYSTEM_CA_BUNDLES
+        .iter()
+        .map(std::path::PathBuf::from)
+        .find(|p| validate_ca_bundle(p).is_ok())
+}
+
+/// Where the worker writes its log when `worker_log_file` is on.
+pub(crate) fn worker_log_file(data_dir: &std::path::Path) -> std::path::PathBuf {
+    data_dir.join("logs").join("worker.log")
+}
+
+/// Build the worker `Command` from `config`: program, flags (in a fixed
+/// order: `--ffmpeg`, `--hwaccel`, `--data-dir`, `--port`, `--bind`, `--audio-device`,
+/// `--job-timeout`, then extra args), sanitized
+/// environment and piped stdio.
+pub(crate) fn build_worker_command(config: &WorkerConfig) -> Command {
+    let mut cmd = Command::new(&config.sidecar_path);
+    if let Some(ff) = &config.ffmpeg_path {
+        cmd.args(["--ffmpeg", ff]);
+    }
+    if let Some(hwaccel) = &config.preferred_hwaccel {
+        cmd.args(["--hwaccel", hwaccel]);
+    }
+    cmd.arg("--data-dir").arg(&config.data_dir);
+    if let Some(port) = config.port {
+        cmd.args(["--port", &port.to_string()]);
+    }
+    // config.toml isn't validated on load; the environment already is.
+    match config.bind_address {
+        Some(address) if config::bind_address_reachable(&address) => {
+            cmd.args(["--bind", &address.to_string()]);
+        }
+        Some(address) => eprintln!("[djbot] ignoring bind_address {}: not loopback or unspecified", address),
+        None => {}
+    }
+    if let Some(device) = &config.preferred_output_device {
+        cmd.args(["--audio-device", device]);
+    }
+    if let Some(secs) = config.job_timeout_secs {
+        cmd.args(["--job-timeout", &secs.to_string()]);
+    }
+    if config.worker_log_file {
+        cmd.arg("--log-file").arg(worker_log_file(&config.data_dir));
+    }
+    if let Some(rate) = config.audio.sample_rate {
+        cmd.args(["--sample-rate", &rate.to_string()]);
+    }
+    if let Some(size) = config.audio.buffer_size {
+        cmd.args(["--buffer-size", &size.to_string()]);
+    }
+    if let Some(backend) = config.audio.backend {
+        cmd.args(["--audio-backend", backend.as_str()]);
+    }
+    if let Some(bits) = config.output_bit_depth {
+        cmd.args(["--output-bit-depth", &bits.to_string()]);
+    }
+    let mut env = worker_env();
+    if let Some(ca) = ca_bundle(config, &env) {
+        cmd.arg("--ca-bundle").arg(&ca);
+        // For yt-dlp and anything else the worker runs.
+        env.insert("SSL_CERT_FILE".to_string(), ca.to_string_lossy().to_string());
+    }
+    // Go's default transport and yt-dlp both read these.
+    if let Some(sealed) = &config.download_proxy {
+        match secrets::open(&config.data_dir, DOWNLOAD_PROXY_FIELD, sealed) {
+            Ok(url) => {
+                env.insert("HTTPS_PROXY".to_string(), url.clone());
+                env.insert("HTTP_PROXY".to_string(), url);
+            }
+            Err(e) => eprintln!("[djbot] download proxy not used: {}", e),
+        }
+    }
+    cmd.args(&config.extra_args);
+    // A bundled app's cwd can be anything (`/` on macOS), so pin the
+    // worker's relative paths to the data dir.
+    cmd.current_dir(&config.data_dir);
+
+    cmd.env_clear().envs(env);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    isolate_session(&mut cmd);
+    cmd
+}
+
+/// Start the worker in its own session so Ctrl-C in the terminal that
+/// launched djbot (SIGINT to the foreground process group) doesn't reach it.
+///
+/// The flip side: closing that terminal no longer SIGHUPs the worker, so
+/// shutdown must stop it explicitly (see `terminate_worker` in lib.rs).
+#[cfg(target_os = "linux")]
+fn isolate_session(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    // SAFETY: setsid is async-signal-safe and touches no parent state.
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn isolate_session(_cmd: &mut Command) {}
+
+/// Lines longer than this are truncated; the remainder up to the next
+/// newline is discarded so a runaway line can't grow memory without bound.
+pub(crate) const MAX_LINE_BYTES: usize = 64 * 1024;
+
+fn decode_line(raw: &[u8], truncated: bool) -> String {
+    let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+    let mut line = String::from_utf8_lossy(raw).into_owned();
+    if truncated {
+        line.push_str(" …[truncated]");
+    }
+    line
+}
+
+/// Call `on_line` for every line read from `reader`.
+///
+/// Unlike `BufRead::lines`, invalid UTF-8 is replaced rather than dropped,
+/// `\r\n` endings are stripped, and lines are capped at `MAX_LINE_BYTES`.
+/// A final line without a trailing newline is still delivered.
+pub(crate) fn for_each_line<R: BufRead>(mut reader: R, mut on_line: impl FnMut(String)) -> std::io::Result<()> {
+    let mut line = Vec::new();
+    let mut truncated = false;
+    loop {
+        let (used, complete) = {
+            let buf = match reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                if !line.is_empty() {
+                    on_line(decode_line(&line, truncated));
+                }
+                return Ok(());
+            }
+            let newline = buf.iter().position(|&b| b == b'\n');
+            let chunk = &buf[..newline.unwrap_or(buf.len())];
+            let room = MAX_LINE_BYTES.saturating_sub(line.len());
+            if chunk.len() > room {
+                truncated = true;
+            }
+            line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            (chunk.len() + usize::from(newline.is_some()), newline.is_some())
+        };
+        reader.consume(used);
+        if complete {
+            on_line(decode_line(&line, truncated));
+            line.clear();
+            truncated = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::io::BufReader;
+    use std::path::{Path, PathBuf};
+
+    use crate::config::AudioBackend;
+
+    /// A fresh directory with a minimal CA bundle in it, so the command
+    /// doesn't depend on which system bundles this machine has.
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("djbot-worker-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ca.pem"), "-----BEGIN CERTIFICATE-----\n-----END CERTIFICATE-----\n").unwrap();
+        dir
+    }
+
+    fn base_config(dir: &Path) -> WorkerConfig {
+        WorkerConfig {
+            sidecar_path: PathBuf::from("/opt/djbot/goworker"),
+            data_dir: dir.to_path_buf(),
+            ca_bundle: Some(dir.join("ca.pem")),
+            worker_log_file: false,
+            ..WorkerConfig::default()
+        }
+    }
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn minimal_command() {
+        let dir = data_dir("minimal");
+        let cmd = build_worker_command(&base_config(&dir));
+        assert_eq!(cmd.get_program(), OsStr::new("/opt/djbot/goworker"));
+        let ca = dir.join("ca.pem").to_string_lossy().into_owned();
+        assert_eq!(args(&cmd), ["--data-dir", &dir.to_string_lossy(), "--ca-bundle", &ca]);
+        assert_eq!(cmd.get_current_dir(), Some(dir.as_path()));
+    }
+
+    #[test]
+    fn every_flag_in_order() {
+        let dir = data_dir("full");
+        let mut config = base_config(&dir);
+        config.ffmpeg_path = Some("/usr/bin/ffmpeg".to_string());
+        config.preferred_hwaccel = Some("cuda".to_string());
+        config.port = Some(9000);
+        config.bind_address = Some("127.0.0.1".parse().unwrap());
+        config.preferred_output_device = Some("speakers".to_string());
+        config.job_timeout_secs = Some(600);
+        config.worker_log_file = true;
+        config.audio.sample_rate = Some(48_000);
+        config.audio.buffer_size = Some(256);
+        config.audio.backend = Some(AudioBackend::Alsa);
+        config.output_bit_depth = Some(24);
+        config.extra_args = vec!["--verbose".to_string()];
+        let cmd = build_worker_command(&config);
+
+        let dir_arg = dir.to_string_lossy().into_owned();
+        let log = worker_log_file(&dir).to_string_lossy().into_owned();
+        let ca = dir.join("ca.pem").to_string_lossy().into_owned();
+        let expected = [
+            "--ffmpeg", "/usr/bin/ffmpeg",
+            "--hwaccel", "cuda",
+            "--data-dir", &dir_arg,
+            "--port", "9000",
+            "--bind", "127.0.0.1",
+            "--audio-device", "speakers",
+            "--job-timeout", "600",
+            "--log-file", &log,
+            "--sample-rate", "48000",
+            "--buffer-size", "256",
+            "--audio-backend", "alsa",
+            "--output-bit-depth", "24",
+            "--ca-bundle", &ca,
+            "--verbose",
+        ];
+        assert_eq!(args(&cmd), expected);
+        assert_eq!(cmd.get_program(), OsStr::new("/opt/djbot/goworker"));
+        let cert_file = cmd.get_envs().find(|(k, _)| *k == "SSL_CERT_FILE").and_then(|(_, v)| v);
+        assert_eq!(cert_file, Some(OsStr::new(&ca)));
+    }
+
+    #[test]
+    fn unreachable_bind_address_is_left_out() {
+        let dir = data_dir("bind");
+        let mut config = base_config(&dir);
+        config.bind_address = Some("10.0.0.1".parse().unwrap());
+        assert!(!args(&build_worker_command(&config)).contains(&"--bind".to_string()));
+    }
+
+    #[test]
+    fn blocked_variables_are_not_inherited() {
+        let vars = [("LD_PRELOAD", "/tmp/x.so"), ("dyld_insert_libraries", "x"), ("GODEBUG", "x"), ("HOME", "/home/dj")];
+        let env = sanitize_env(vars.map(|(k, v)| (k.to_string(), v.to_string())));
+        assert_eq!(env.keys().collect::<Vec<_>>(), ["HOME"]);
+    }
+
+    fn lines(input: &[u8], capacity: usize) -> Vec<String> {
+        let mut out = Vec::new();
+        for_each_line(BufReader::with_capacity(capacity, input), |l| out.push(l)).unwrap();
+        out
+    }
+
+    #[test]
+    fn crlf_and_a_missing_final_newline() {
+        for capacity in [1, 3, 4096] {
+            assert_eq!(lines(b"one\r\ntwo\nthree", capacity), ["one", "two", "three"], "capacity {}", capacity);
+        }
+        // Only a trailing \r is an ending; one mid-line is kept.
+        assert_eq!(lines(b"50%\r75%\r\n", 4096), ["50%\r75%"]);
+        assert_eq!(lines(b"\n\n", 4096), ["", ""]);
+        assert!(lines(b"", 4096).is_empty());
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_not_dropped() {
+        assert_eq!(lines(b"caf\xc3\xa9\nbad \xff\xfe byte\n", 4096), ["café", "bad \u{fffd}\u{fffd} byte"]);
+        // A multi-byte char split across reads still decodes.
+        assert_eq!(lines("naïve\n".as_bytes(), 3), ["naïve"]);
+    }
+
+    #[test]
+    fn over_long_lines_are_capped_and_the_rest_discarded() {
+        let mut input = vec![b'a'; MAX_LINE_BYTES + 1000];
+        input.extend_from_slice(b"\nnext\n");
+        for capacity in [8 * 1024, MAX_LINE_BYTES * 2] {
+            let out = lines(&input, capacity);
+            assert_eq!(out.len(), 2);
+            assert_eq!(out[0], format!("{} …[truncated]", "a".repeat(MAX_LINE_BYTES)));
+            assert_eq!(out[1], "next");
+        }
+        let exact = vec![b'b'; MAX_LINE_BYTES];
+        assert_eq!(lines(&exact, 4096), ["b".repeat(MAX_LINE_BYTES)]);
+    }
+}
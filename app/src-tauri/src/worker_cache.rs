@@ -0,0 +1,105 @@
+//! Local copy of the worker binary under `{data_dir}/bin`.
+//!
+//! The resolved binary may sit somewhere slow or transient (a network
+//! share, a mounted DMG). It is copied to the data dir once, and later
+//! launches run the copy while the source's size and modification time
+//! still match what was copied; a new app version refreshes it. If the
+//! source is missing altogether (share offline) the copy is still used,
+//! as long as it hashes to what was recorded when it was made.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::integrity;
+
+/// Where cached worker copies live.
+pub(crate) fn cache_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("bin")
+}
+
+/// What the cached copy was made from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Record {
+    source: PathBuf,
+    size: u64,
+    /// Modification time of the source, seconds since the Unix epoch.
+    modified: Option<u64>,
+    sha256: String,
+}
+
+struct WorkerCache {
+    binary: PathBuf,
+    record: PathBuf,
+}
+
+fn modified_secs(meta: &std::fs::Metadata) -> Option<u64> {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
+impl WorkerCache {
+    fn new(data_dir: &Path, worker_name: &str) -> Self {
+        let dir = cache_dir(data_dir);
+        WorkerCache { binary: dir.join(worker_name), record: dir.join(format!("{}.source.json", worker_name)) }
+    }
+
+    fn read_record(&self) -> Option<Record> {
+        serde_json::from_str(&std::fs::read_to_string(&self.record).ok()?).ok()
+    }
+
+    /// The copy hashes to what was recorded.
+    fn intact(&self, record: &Record) -> bool {
+        integrity::sha256_file(&self.binary).is_ok_and(|h| h == record.sha256)
+    }
+
+    fn refresh(&self, source: &Path, meta: &std::fs::Metadata) -> Result<(), String> {
+        let dir = self.binary.parent().unwrap_or(Path::new("."));
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let part = self.binary.with_extension("part");
+        // fs::copy keeps the permission bits, so the copy stays executable.
+        std::fs::copy(source, &part).map_err(|e| format!("copy {}: {}", source.display(), e))?;
+        let sha256 = integrity::sha256_file(&part).map_err(|e| e.to_string())?;
+        if let Err(e) = std::fs::rename(&part, &self.binary) {
+            let _ = std::fs::remove_file(&part);
+            return Err(format!("replace {}: {}", self.binary.display(), e));
+        }
+        let record = Record { source: source.to_path_buf(), size: meta.len(), modified: modified_secs(meta), sha256 };
+        let text = serde_json::to_string_pretty(&record).map_err(|e| e.to_string())?;
+        std::fs::write(&self.record, text).map_err(|e| e.to_string())
+    }
+}
+
+/// The path to run the worker from: the cached copy of `source`, made or
+/// refreshed as needed. `None` means use `source` itself (nothing usable
+/// cached and the copy failed, or `source` doesn't exist and was never
+/// cached).
+pub(crate) fn resolve(data_dir: &Path, worker_name: &str, source: &Path) -> Option<PathBuf> {
+    let cache = WorkerCache::new(data_dir, worker_name);
+    let record = cache.read_record();
+    let meta = match std::fs::metadata(source) {
+        Ok(meta) if meta.is_file() => meta,
+        _ => {
+            return match record {
+                Some(record) if cache.intact(&record) => {
+                    eprintln!("[djbot] {} unavailable; using cached worker {}", source.display(), cache.binary.display());
+                    Some(cache.binary)
+                }
+                _ => None,
+            };
+        }
+    };
+    let fresh = record.as_ref().is_some_and(|r| {
+        r.source == source && r.size == meta.len() && r.modified == modified_secs(&meta) && cache.intact(r)
+    });
+    if !fresh {
+        if let Err(e) = cache.refresh(source, &meta) {
+            eprintln!("[djbot] could not cache worker binary: {}", e);
+            return None;
+        }
+        eprintln!("[djbot] cached worker binary at {}", cache.binary.display());
+    }
+    Some(cache.binary)
+}
@@ -0,0 +1,246 @@
+//! Worker binaries downloaded from the worker release channel, separate
+//! from app updates.
+//!
+//! Each channel publishes a manifest (`config::worker_manifest_url`) with
+//! the latest version and, per platform, the binary's URL and SHA-256.
+//! A download lands in `{data_dir}/worker/<version>/` and is checksummed
+//! before `{data_dir}/worker/current.json` is switched to it (written to a
+//! temp file and renamed, so a crash leaves the old choice in place). The
+//! version it replaced is kept for `rollback`; older ones are deleted.
+//!
+//! A new version stays unconfirmed until its first handshake. If it exits
+//! or fails to start before reporting its port, `reject` drops back to the
+//! previous version, or to the bundled binary when there is none.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{config, integrity, url_guard};
+
+const WORKER_DIR: &str = "worker";
+const CURRENT_FILE: &str = "current.json";
+/// Next to the binary: the SHA-256 it was verified against.
+const CHECKSUM_FILE: &str = "sha256";
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Manifest {
+    pub version: String,
+    pub notes: Option<String>,
+    /// Keyed by `platform_key()`.
+    pub platforms: HashMap<String, Artifact>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Artifact {
+    pub url: String,
+    pub sha256: String,
+}
+
+/// `current.json`: which downloaded worker runs. `None` runs the bundled one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Current {
+    active: Option<String>,
+    /// Whether `active` has completed a handshake.
+    confirmed: bool,
+    previous: Option<String>,
+}
+
+/// A downloaded worker chosen to run.
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveWorker {
+    pub version: String,
+    pub path: PathBuf,
+    /// `false` until its first handshake.
+    pub confirmed: bool,
+}
+
+/// Manifest key of the platform we're running on, e.g. "macos-aarch64".
+pub(crate) fn platform_key() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn worker_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(WORKER_DIR)
+}
+
+fn version_dir(data_dir: &Path, version: &str) -> PathBuf {
+    worker_dir(data_dir).join(version)
+}
+
+fn load_current(data_dir: &Path) -> Current {
+    std::fs::read(worker_dir(data_dir).join(CURRENT_FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn save_current(data_dir: &Path, current: &Current) -> Result<(), String> {
+    let path = worker_dir(data_dir).join(CURRENT_FILE);
+    let json = serde_json::to_vec_pretty(current).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+/// Versions are used as directory names.
+fn check_version(version: &str) -> Result<(), String> {
+    let ok = !version.is_empty()
+        && !version.starts_with('.')
+        && version.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("invalid worker version {:?} in manifest", version))
+    }
+}
+
+pub(crate) async fn fetch_manifest(client: &reqwest::Client, channel: &str) -> Result<Manifest, String> {
+    let url = config::worker_manifest_url(channel);
+    let resp = client.get(&url).send().await.map_err(|e| format!("worker update check failed: {}", e))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("worker update check failed: {} returned {}", url, status));
+    }
+    let manifest: Manifest = resp.json().await.map_err(|e| format!("invalid worker manifest: {}", e))?;
+    check_version(&manifest.version)?;
+    Ok(manifest)
+}
+
+/// The downloaded worker to run, if one is selected and still hashes to
+/// what it was verified against.
+pub(crate) fn active_binary(data_dir: &Path, worker_name: &str) -> Option<ActiveWorker> {
+    let current = load_current(data_dir);
+    let version = current.active?;
+    let dir = version_dir(data_dir, &version);
+    let path = dir.join(worker_name);
+    let expected = std::fs::read_to_string(dir.join(CHECKSUM_FILE)).ok()?;
+    match integrity::sha256_file(&path) {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected.trim()) => {
+            Some(ActiveWorker { version, path, confirmed: current.confirmed })
+        }
+        Ok(_) => {
+            eprintln!("[djbot] downloaded worker {} fails its checksum; using the bundled worker", version);
+            None
+        }
+        Err(e) => {
+            eprintln!("[djbot] downloaded worker {} unreadable ({}); using the bundled worker", version, e);
+            None
+        }
+    }
+}
+
+/// Download `manifest`'s binary for this platform, verify it and make it
+/// the worker the next start runs. Returns its version.
+pub(crate) async fn install(
+    client: &reqwest::Client,
+    data_dir: &Path,
+    worker_name: &str,
+    manifest: &Manifest,
+) -> Result<String, String> {
+    let artifact = manifest
+        .platforms
+        .get(&platform_key())
+        .ok_or_else(|| format!("worker {} has no build for {}", manifest.version, platform_key()))?;
+    let url = url_guard::validate(&artifact.url, false).await?;
+    let dir = version_dir(data_dir, &manifest.version);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("create {}: {}", dir.display(), e))?;
+    let part = dir.join(format!("{}.part", worker_name));
+    let result = download(client, url, &part, &artifact.sha256).await;
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&part);
+        return Err(e);
+    }
+    std::fs::write(dir.join(CHECKSUM_FILE), artifact.sha256.to_ascii_lowercase()).map_err(|e| e.to_string())?;
+    std::fs::rename(&part, dir.join(worker_name)).map_err(|e| e.to_string())?;
+
+    let mut current = load_current(data_dir);
+    if current.active.as_deref() != Some(manifest.version.as_str()) {
+        current.previous = current.active.take();
+    }
+    current.active = Some(manifest.version.clone());
+    current.confirmed = false;
+    save_current(data_dir, &current)?;
+    prune(data_dir, &current);
+    Ok(manifest.version.clone())
+}
+
+async fn download(client: &reqwest::Client, url: reqwest::Url, part: &Path, sha256: &str) -> Result<(), String> {
+    let mut resp = client.get(url).send().await.map_err(|e| format!("worker download failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("worker download failed: server returned {}", resp.status()));
+    }
+    let mut file = std::fs::File::create(part).map_err(|e| format!("create {}: {}", part.display(), e))?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = resp.chunk().await.map_err(|e| format!("worker download failed: {}", e))? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+    }
+    file.sync_all().map_err(|e| e.to_string())?;
+    let actual: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    if !actual.eq_ignore_ascii_case(sha256.trim()) {
+        return Err(format!("worker download checksum mismatch: expected {}, got {}", sha256, actual));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(part, std::fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Delete downloaded versions that are neither active nor previous.
+fn prune(data_dir: &Path, current: &Current) {
+    let Ok(entries) = std::fs::read_dir(worker_dir(data_dir)) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let keep = current.active.as_deref() == Some(name.as_str()) || current.previous.as_deref() == Some(name.as_str());
+        if !keep && entry.path().is_dir() {
+            if let Err(e) = std::fs::remove_dir_all(entry.path()) {
+                eprintln!("[djbot] could not remove old worker {}: {}", name, e);
+            }
+        }
+    }
+}
+
+/// `version` completed a handshake.
+pub(crate) fn confirm(data_dir: &Path, version: &str) {
+    let mut current = load_current(data_dir);
+    if current.active.as_deref() == Some(version) && !current.confirmed {
+        current.confirmed = true;
+        if let Err(e) = save_current(data_dir, &current) {
+            eprintln!("[djbot] could not record worker {} as working: {}", version, e);
+        }
+    }
+}
+
+/// `version` failed before its first handshake: go back to the previous
+/// version, or the bundled worker.
+pub(crate) fn reject(data_dir: &Path, version: &str) {
+    let mut current = load_current(data_dir);
+    if current.active.as_deref() != Some(version) {
+        return;
+    }
+    current.active = current.previous.take();
+    // It has to prove itself again before there is nothing to fall back to.
+    current.confirmed = false;
+    if let Err(e) = save_current(data_dir, &current) {
+        eprintln!("[djbot] could not roll back worker {}: {}", version, e);
+    }
+}
+
+/// Switch back to the version before the active one (the bundled worker
+/// if there was none). Returns the version now selected.
+pub(crate) fn rollback(data_dir: &Path) -> Result<Option<String>, String> {
+    let mut current = load_current(data_dir);
+    if current.active.is_none() {
+        return Err("already running the bundled worker".to_string());
+    }
+    current.active = current.previous.take();
+    current.confirmed = false;
+    save_current(data_dir, &current)?;
+    Ok(current.active)
+}
@@ -56,7 +56,7 @@ async function workerFetch(path, opts = {}) {
 // ── Startup ───────────────────────────────────────────────────
 async function init() {
   for (let i = 0; i < 60; i++) {
-    try { state.workerPort = await invoke('get_worker_port'); break; }
+    try { state.workerPort = await invoke('plugin:djbot-worker|get_worker_port'); break; }
     catch { await sleep(500); }
   }
   if (!state.workerPort) { setStatus('error', 'Go 워커 연결 실패'); return; }
@@ -374,7 +374,7 @@ async function smartMix() {
 async function renderFinalMix() {
   setProgress('최종 믹스 렌더링 중...', 60, '몇 분 걸릴 수 있습니다...');
 
-  const outputDir = await invoke('get_output_dir');
+  const outputDir = await invoke('plugin:djbot-worker|get_output_dir');
   const ts = new Date().toISOString().replace(/[:.]/g, '-').slice(0, 19);
   const outputPath = outputDir + '\\' + `club_mix_${ts}.mp3`;
 
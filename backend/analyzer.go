@@ -18,6 +18,10 @@ import (
 
 var ffmpegPath = "ffmpeg"
 
+// hwaccel is the ffmpeg -hwaccel method tried first when decoding (empty =
+// software only). Set from --hwaccel.
+var hwaccel string
+
 func initFFmpeg() {
 	if p := os.Getenv("FFMPEG_PATH"); p != "" {
 		ffmpegPath = p
@@ -55,11 +59,14 @@ func fileHash(path string) (string, error) {
 	return fmt.Sprintf("%x", h.Sum(nil)), nil
 }
 
-// decodeToPCM decodes audio to mono float32 PCM at 22050Hz via ffmpeg
-func decodeToPCM(path string) ([]float32, int, error) {
-	sr := 22050
-	cmd := exec.Command(ffmpegPath,
-		"-v", "error",
+// runDecode runs ffmpeg to decode path to raw mono float32 PCM at sr Hz,
+// with -hwaccel hw unless hw is empty. Returns the PCM bytes and stderr.
+func runDecode(path string, sr int, hw string) ([]byte, string, error) {
+	args := []string{"-v", "error"}
+	if hw != "" {
+		args = append(args, "-hwaccel", hw)
+	}
+	args = append(args,
 		"-i", path,
 		"-f", "f32le",
 		"-acodec", "pcm_f32le",
@@ -67,6 +74,7 @@ func decodeToPCM(path string) ([]float32, int, error) {
 		"-ar", fmt.Sprintf("%d", sr),
 		"-",
 	)
+	cmd := exec.Command(ffmpegPath, args...)
 	hideWindow(cmd)
 
 	var stderr bytes.Buffer
@@ -74,23 +82,38 @@ func decodeToPCM(path string) ([]float32, int, error) {
 
 	stdout, err := cmd.StdoutPipe()
 	if err != nil {
-		return nil, 0, fmt.Errorf("pipe: %w", err)
+		return nil, "", fmt.Errorf("pipe: %w", err)
 	}
 	if err := cmd.Start(); err != nil {
-		return nil, 0, fmt.Errorf("start ffmpeg: %w (%s)", err, stderr.String())
+		return nil, "", fmt.Errorf("start ffmpeg: %w (%s)", err, stderr.String())
 	}
 
 	data, err := io.ReadAll(stdout)
 	if err != nil {
-		return nil, 0, fmt.Errorf("read: %w", err)
+		return nil, "", fmt.Errorf("read: %w", err)
 	}
 	if waitErr := cmd.Wait(); waitErr != nil {
 		log.Printf("[ffmpeg stderr] %s", stderr.String())
 	}
+	return data, stderr.String(), nil
+}
+
+// decodeToPCM decodes audio to mono float32 PCM at 22050Hz via ffmpeg,
+// falling back to software decoding if the -hwaccel method fails.
+func decodeToPCM(path string) ([]float32, int, error) {
+	sr := 22050
+	data, stderr, err := runDecode(path, sr, hwaccel)
+	if err == nil && len(data) < 4 && hwaccel != "" {
+		log.Printf("[ffmpeg] -hwaccel %s failed for %s, decoding in software", hwaccel, path)
+		data, stderr, err = runDecode(path, sr, "")
+	}
+	if err != nil {
+		return nil, 0, err
+	}
 
 	numSamples := len(data) / 4
 	if numSamples == 0 {
-		return nil, 0, fmt.Errorf("no audio data decoded from %s (stderr: %s)", path, stderr.String())
+		return nil, 0, fmt.Errorf("no audio data decoded from %s (stderr: %s)", path, stderr)
 	}
 
 	samples := make([]float32, numSamples)
@@ -203,10 +226,20 @@ func AnalyzeBatch(paths []string, cacheDir string) ([]TrackAnalysis, []string) {
 	}
 	sem := make(chan struct{}, concurrency)
 
+	// Progress lines on stdout are picked up by the Tauri host.
+	var doneMu sync.Mutex
+	done := 0
+
 	for i, p := range paths {
 		wg.Add(1)
 		go func(idx int, path string) {
 			defer wg.Done()
+			defer func() {
+				doneMu.Lock()
+				done++
+				fmt.Printf("PROGRESS:analyze:%d/%d\n", done, len(paths))
+				doneMu.Unlock()
+			}()
 			sem <- struct{}{}
 			defer func() { <-sem }()
 
@@ -170,5 +170,8 @@ func handleServeFile(w http.ResponseWriter, r *http.Request) {
 
 	w.Header().Set("Content-Type", "application/octet-stream")
 	w.Header().Set("Content-Disposition", fmt.Sprintf("attachment; filename=%s", filepath.Base(absPath)))
+	// Lets the app resume an interrupted download (Range + If-Range) only
+	// while the file is unchanged.
+	w.Header().Set("ETag", fmt.Sprintf("\"%x-%x\"", info.ModTime().UnixNano(), info.Size()))
 	http.ServeContent(w, r, filepath.Base(absPath), info.ModTime(), f)
 }
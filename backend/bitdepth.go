@@ -0,0 +1,64 @@
+package main
+
+import (
+	"encoding/json"
+	"fmt"
+	"net/http"
+	"path/filepath"
+	"strings"
+	"sync/atomic"
+)
+
+// outputBitDepth is the sample depth for lossless mix exports (0 = encoder
+// default). Set with --output-bit-depth and changed at runtime through
+// POST /output/bit-depth; picked up by the next render.
+var outputBitDepth atomic.Int32
+
+// codecsByDepth lists, per lossless container, the encoder arguments for
+// each supported depth. Lossy formats are absent: depth doesn't apply.
+var codecsByDepth = map[string]map[int][]string{
+	".wav":  {16: {"-c:a", "pcm_s16le"}, 24: {"-c:a", "pcm_s24le"}, 32: {"-c:a", "pcm_s32le"}},
+	".aiff": {16: {"-c:a", "pcm_s16be"}, 24: {"-c:a", "pcm_s24be"}, 32: {"-c:a", "pcm_s32be"}},
+	".flac": {16: {"-c:a", "flac", "-sample_fmt", "s16"}, 24: {"-c:a", "flac", "-sample_fmt", "s32", "-bits_per_raw_sample", "24"}},
+}
+
+// bitDepthArgs returns the encoder arguments that apply outputBitDepth to
+// outputPath, or an error if its format can't be written at that depth.
+func bitDepthArgs(outputPath string) ([]string, error) {
+	bits := int(outputBitDepth.Load())
+	if bits == 0 {
+		return nil, nil
+	}
+	ext := strings.ToLower(filepath.Ext(outputPath))
+	depths, lossless := codecsByDepth[ext]
+	if !lossless {
+		return nil, nil
+	}
+	args, ok := depths[bits]
+	if !ok {
+		return nil, fmt.Errorf("%s output does not support %d-bit samples", ext, bits)
+	}
+	return args, nil
+}
+
+func validBitDepth(bits int) bool {
+	return bits == 0 || bits == 16 || bits == 24 || bits == 32
+}
+
+// handleSetBitDepth accepts {"bits": 16|24|32|0}.
+func handleSetBitDepth(w http.ResponseWriter, r *http.Request) {
+	var req struct {
+		Bits int `json:"bits"`
+	}
+	if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+		http.Error(w, err.Error(), 400)
+		return
+	}
+	if !validBitDepth(req.Bits) {
+		http.Error(w, "bits must be 16, 24 or 32", 400)
+		return
+	}
+	outputBitDepth.Store(int32(req.Bits))
+	w.Header().Set("Content-Type", "application/json")
+	json.NewEncoder(w).Encode(map[string]int{"bits": req.Bits})
+}
@@ -11,8 +11,10 @@ import (
 	"os"
 	"os/signal"
 	"path/filepath"
+	"strconv"
 	"strings"
 	"syscall"
+	"time"
 )
 
 var cacheDir = "cache"
@@ -20,6 +22,25 @@ var uploadsDir = "cache/uploads"
 var outputDir = "output"
 var binDir = "bin" // managed directory for self-downloaded binaries (e.g. yt-dlp)
 
+// workerVersion is stamped by release builds (-ldflags "-X main.workerVersion=1.4.0")
+// and reported to the app on stdout as a VERSION: line.
+var workerVersion = "dev"
+
+// audioDevice is the output device id chosen in the app ("<host>:<name>"),
+// or "" for the system default. Reserved for preview/cue playback.
+var audioDevice = ""
+
+// audioEngine holds the preview playback settings requested by the app
+// (zero/empty = no preference). Reported back on stdout as an AUDIO: line.
+type audioEngine struct {
+	SampleRate   int    `json:"sample_rate,omitempty"`
+	BufferSize   int    `json:"buffer_size,omitempty"`
+	Backend      string `json:"backend,omitempty"`
+	LiveReconfig bool   `json:"live_reconfig"`
+}
+
+var audioSettings audioEngine
+
 func corsMiddleware(next http.Handler) http.Handler {
 	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
 		w.Header().Set("Access-Control-Allow-Origin", "*")
@@ -35,9 +56,49 @@ func corsMiddleware(next http.Handler) http.Handler {
 
 func main() {
 	ffmpegFlag := flag.String("ffmpeg", "", "Path to ffmpeg executable")
+	flag.StringVar(&hwaccel, "hwaccel", "", "ffmpeg -hwaccel method to try first when decoding (empty = software)")
 	dataDirFlag := flag.String("data-dir", ".", "Root directory for cache and output")
+	portFlag := flag.Int("port", 0, "Port to listen on (0 = pick a free port)")
+	bindFlag := flag.String("bind", "", "Address to listen on (empty = all interfaces)")
+	audioDeviceFlag := flag.String("audio-device", "", "Output device for preview playback (empty = system default)")
+	jobTimeoutFlag := flag.Int("job-timeout", 0, "Seconds a single job may run before it is aborted (0 = no limit)")
+	flag.IntVar(&audioSettings.SampleRate, "sample-rate", 0, "Preview playback sample rate in Hz (0 = device default)")
+	flag.IntVar(&audioSettings.BufferSize, "buffer-size", 0, "Preview playback buffer size in frames (0 = device default)")
+	flag.StringVar(&audioSettings.Backend, "audio-backend", "", "Preview playback host API (empty = platform default)")
+	logFileFlag := flag.String("log-file", "", "Also append log output to this file")
+	bitDepthFlag := flag.Int("output-bit-depth", 0, "Sample depth for lossless mix exports: 16, 24 or 32 (0 = encoder default)")
+	caBundleFlag := flag.String("ca-bundle", "", "Extra PEM root certificates to trust for downloads")
 	flag.Parse()
 
+	if validBitDepth(*bitDepthFlag) {
+		outputBitDepth.Store(int32(*bitDepthFlag))
+	} else {
+		log.Printf("ignoring invalid --output-bit-depth %d", *bitDepthFlag)
+	}
+
+	if *logFileFlag != "" {
+		os.MkdirAll(filepath.Dir(*logFileFlag), 0755)
+		f, err := os.OpenFile(*logFileFlag, os.O_CREATE|os.O_APPEND|os.O_WRONLY, 0644)
+		if err != nil {
+			log.Printf("cannot open log file %s: %v", *logFileFlag, err)
+		} else {
+			log.SetOutput(io.MultiWriter(os.Stderr, f))
+		}
+	}
+
+	if *caBundleFlag != "" {
+		if err := useCABundle(*caBundleFlag); err != nil {
+			log.Printf("ignoring --ca-bundle: %v", err)
+		} else {
+			log.Printf("trusting CA bundle %s", *caBundleFlag)
+		}
+	}
+
+	audioDevice = *audioDeviceFlag
+	if audioDevice != "" {
+		log.Printf("audio output device: %s", audioDevice)
+	}
+
 	if *ffmpegFlag != "" {
 		os.Setenv("FFMPEG_PATH", *ffmpegFlag)
 	}
@@ -69,27 +130,38 @@ func main() {
 		json.NewEncoder(w).Encode(map[string]string{"status": "ok"})
 	})
 
-	mux.HandleFunc("POST /analyze", handleAnalyze)
+	job := jobTimeout(time.Duration(*jobTimeoutFlag) * time.Second)
+
+	mux.Handle("POST /analyze", job(handleAnalyze))
 	mux.HandleFunc("POST /upload", handleUpload)
-	mux.HandleFunc("POST /plan", handlePlan)
-	mux.HandleFunc("POST /render/preview", handleRenderPreview)
-	mux.HandleFunc("POST /render/mix", handleRenderMix)
-	mux.HandleFunc("POST /download/youtube", handleDownloadYouTube)
+	mux.Handle("POST /plan", job(handlePlan))
+	mux.Handle("POST /render/preview", job(handleRenderPreview))
+	mux.Handle("POST /render/mix", job(handleRenderMix))
+	mux.Handle("POST /download/youtube", job(handleDownloadYouTube))
 	mux.HandleFunc("GET /weights", handleGetWeights)
 	mux.HandleFunc("POST /weights", handleSaveWeights)
 	mux.HandleFunc("POST /export/zip", handleExportZip)
 	mux.HandleFunc("POST /cache/clear", handleCacheClear)
 	mux.HandleFunc("GET /files/serve", handleServeFile)
+	mux.HandleFunc("GET /waveform", handleWaveform)
+	mux.HandleFunc("POST /playback/{action}", handlePlayback)
+	mux.HandleFunc("POST /output/bit-depth", handleSetBitDepth)
 
-	// Listen on random port
-	listener, err := net.Listen("tcp", ":0")
+	// Listen on the requested address and port, or a random port
+	listener, err := net.Listen("tcp", net.JoinHostPort(*bindFlag, strconv.Itoa(*portFlag)))
 	if err != nil {
 		log.Fatalf("listen: %v", err)
 	}
 	port := listener.Addr().(*net.TCPAddr).Port
 
-	// Print port for Python bridge / Tauri to read
+	// Print version and port for Python bridge / Tauri to read
+	fmt.Printf("VERSION:%s\n", workerVersion)
 	fmt.Printf("PORT:%d\n", port)
+	// No playback engine runs here yet, so the requested settings are the
+	// effective ones and can't be changed without a restart.
+	if audio, err := json.Marshal(audioSettings); err == nil {
+		fmt.Printf("AUDIO:%s\n", audio)
+	}
 	log.Printf("Go worker listening on :%d (ffmpeg: %s)", port, ffmpegPath)
 
 	// Graceful shutdown
@@ -106,6 +178,31 @@ func main() {
 	}
 }
 
+// jobTimeout wraps job handlers so they answer 503 "job timed out" once
+// limit has passed. The request context is cancelled at the deadline; work
+// that doesn't watch the context finishes in the background and its result
+// is discarded. A zero limit leaves handlers unwrapped.
+func jobTimeout(limit time.Duration) func(http.HandlerFunc) http.Handler {
+	return func(h http.HandlerFunc) http.Handler {
+		if limit <= 0 {
+			return h
+		}
+		return http.TimeoutHandler(h, limit, "job timed out")
+	}
+}
+
+// handlePlayback receives transport commands (play, pause, toggle, next,
+// previous, stop) forwarded from OS media keys. Preview playback currently
+// runs in the webview, so the worker only acknowledges that it can't act.
+func handlePlayback(w http.ResponseWriter, r *http.Request) {
+	switch action := r.PathValue("action"); action {
+	case "play", "pause", "toggle", "next", "previous", "stop":
+		http.Error(w, "playback is not handled by the worker", http.StatusNotImplemented)
+	default:
+		http.Error(w, "unknown playback action: "+action, http.StatusBadRequest)
+	}
+}
+
 // handleUpload accepts multipart file uploads and saves them to uploadsDir.
 // Returns JSON: {"files": [{"path": "...", "filename": "..."}]}
 func handleUpload(w http.ResponseWriter, r *http.Request) {
@@ -524,6 +524,12 @@ func RenderFinalMix(playlist []TrackEntry, transitions []TransitionSpec, outputP
 		return "", "", fmt.Errorf("failed to drop master PCM to disk: %w", err)
 	}
 
+	depthArgs, err := bitDepthArgs(outputPath)
+	if err != nil {
+		os.Remove(finalPcmPath)
+		return "", "", err
+	}
+
 	log.Printf("[ffmpeg] encoding final mp3 from master PCM overlay...")
 	encodeArgs := []string{
 		"-y",
@@ -531,8 +537,9 @@ func RenderFinalMix(playlist []TrackEntry, transitions []TransitionSpec, outputP
 		"-i", finalPcmPath,
 		"-af", "alimiter=limit=0.89:attack=5:release=50:level=false",
 		"-b:a", "320k", "-q:a", "0",
-		outputPath,
 	}
+	encodeArgs = append(encodeArgs, depthArgs...)
+	encodeArgs = append(encodeArgs, outputPath)
 
 	var encStderr bytes.Buffer
 	encCmd := exec.Command(ffmpegPath, encodeArgs...)
@@ -0,0 +1,74 @@
+package main
+
+import (
+	"bytes"
+	"fmt"
+	"image"
+	"image/color"
+	"image/png"
+	"math"
+	"net/http"
+	"strconv"
+)
+
+const (
+	waveformHeight   = 64
+	waveformMaxWidth = 4096
+)
+
+var waveformColor = color.NRGBA{R: 0x4f, G: 0xc3, B: 0xf7, A: 0xff}
+
+// renderWaveform draws the peak amplitude of each column of samples as a
+// vertically centred bar on a transparent background.
+func renderWaveform(samples []float32, width, height int) image.Image {
+	img := image.NewNRGBA(image.Rect(0, 0, width, height))
+	perCol := float64(len(samples)) / float64(width)
+	mid := height / 2
+	for x := 0; x < width; x++ {
+		start := int(float64(x) * perCol)
+		end := int(float64(x+1) * perCol)
+		if end <= start {
+			end = start + 1
+		}
+		if end > len(samples) {
+			end = len(samples)
+		}
+		peak := 0.0
+		for _, s := range samples[start:end] {
+			peak = math.Max(peak, math.Abs(float64(s)))
+		}
+		half := int(math.Min(peak, 1) * float64(mid))
+		for y := mid - half; y <= mid+half && y < height; y++ {
+			img.SetNRGBA(x, y, waveformColor)
+		}
+	}
+	return img
+}
+
+// handleWaveform returns a PNG waveform of the track at ?path=, ?width=
+// pixels wide.
+func handleWaveform(w http.ResponseWriter, r *http.Request) {
+	path := r.URL.Query().Get("path")
+	if path == "" {
+		http.Error(w, "path is required", 400)
+		return
+	}
+	width, err := strconv.Atoi(r.URL.Query().Get("width"))
+	if err != nil || width <= 0 || width > waveformMaxWidth {
+		http.Error(w, fmt.Sprintf("width must be 1-%d", waveformMaxWidth), 400)
+		return
+	}
+
+	samples, _, err := decodeToPCM(path)
+	if err != nil {
+		http.Error(w, err.Error(), 500)
+		return
+	}
+	var buf bytes.Buffer
+	if err := png.Encode(&buf, renderWaveform(samples, width, waveformHeight)); err != nil {
+		http.Error(w, err.Error(), 500)
+		return
+	}
+	w.Header().Set("Content-Type", "image/png")
+	w.Write(buf.Bytes())
+}